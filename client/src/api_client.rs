@@ -1,19 +1,30 @@
 //! This module defines the asynchronous API client for interacting with the remote server.
 //!
 //! All functions here use `reqwest` to perform HTTP requests and are intended to be
-//! called from within the Tokio runtime (e.g., using `runtime.block_on` in the
+//! called from within the Tokio runtime (e.g., using `RemoteFS::block_on` in the
 //! synchronous FUSE implementation).
 
 use reqwest::Body;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde::Deserialize;
 use bytes::Bytes;
 use serde_json::json;
+use std::any::Any;
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Seek, SeekFrom, Write};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use sha2::{Digest, Sha256};
 
 /// Represents a single file or directory entry returned by the server's `/list` endpoint.
 ///
 /// This struct is deserialized directly from the server's JSON response.
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct RemoteEntry {
     /// The name of the file or directory (e.g., "file.txt").
     pub name: String,
@@ -21,38 +32,655 @@ pub struct RemoteEntry {
     pub kind: String,
     /// The size of the file in bytes.
     pub size: u64,
+    /// The 512-byte blocks actually allocated on the server's disk, lower than
+    /// `size / 512` for sparse files (missing from older servers).
+    #[serde(default)]
+    pub blocks: Option<u64>,
     /// The modification time (mtime) as a Unix timestamp (seconds since epoch).
     pub mtime: i64,
+    /// The creation (birth) time as a Unix timestamp, if the server's filesystem
+    /// records it (missing from older servers).
+    #[serde(default)]
+    pub btime: Option<i64>,
     /// The file permissions as an octal string (e.g., "644").
     pub perm: String,
+    /// The owner's user id on the server (missing from older servers).
+    #[serde(default)]
+    pub uid: Option<u32>,
+    /// The owner's group id on the server (missing from older servers).
+    #[serde(default)]
+    pub gid: Option<u32>,
+    /// The file's inode number on the server (missing from older servers).
+    #[serde(default)]
+    pub ino: Option<u64>,
+}
+
+impl RemoteEntry {
+    /// Whether the entry is a directory (`kind` is "directory", or "dir" for older servers).
+    pub fn is_dir(&self) -> bool {
+        self.kind.eq_ignore_ascii_case("dir") || self.kind.eq_ignore_ascii_case("directory")
+    }
+}
+
+/// One entry of a `/list` or `/list-stream` response.
+///
+/// The server sends full `RemoteEntry` objects, but older or simpler servers
+/// only send the names (`["a.txt", "docs/"]`): both shapes are accepted.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ListedEntry {
+    Entry(RemoteEntry),
+    Name(String),
+}
+
+impl From<ListedEntry> for RemoteEntry {
+    /// A bare name gets default metadata: a directory if it ends with '/',
+    /// otherwise an empty file. The empty `perm` falls back to the default
+    /// permissions of its kind (see `attr_from_entry`).
+    fn from(listed: ListedEntry) -> Self {
+        match listed {
+            ListedEntry::Entry(entry) => entry,
+            ListedEntry::Name(name) => {
+                let (name, kind) = match name.strip_suffix('/') {
+                    Some(dir_name) => (dir_name.to_string(), "directory"),
+                    None => (name, "file"),
+                };
+                RemoteEntry {
+                    name,
+                    kind: kind.to_string(),
+                    size: 0,
+                    blocks: None,
+                    mtime: 0,
+                    btime: None,
+                    perm: String::new(),
+                    uid: None,
+                    gid: None,
+                    ino: None,
+                }
+            }
+        }
+    }
+}
+
+/// State of a chunked upload session, as returned by the server's `/upload` endpoints.
+#[derive(Deserialize, Debug)]
+pub struct UploadStatus {
+    /// The session id, used in every following `/upload/<id>` request.
+    pub id: String,
+    /// The number of bytes the server has committed so far.
+    pub offset: u64,
+}
+
+/// A single descendant returned by the server's `/tree` endpoint.
+#[derive(Deserialize, Debug)]
+pub struct TreeEntry {
+    /// The path relative to the server root (e.g., "dir/sub/file.txt").
+    pub path: String,
+    /// The type of the entry ("file" or "directory").
+    pub kind: String,
+}
+
+/// A match returned by the server's `/search` endpoint.
+#[derive(Deserialize, Debug)]
+pub struct SearchResult {
+    /// The path relative to the server root (e.g., "dir/sub/file.txt").
+    pub path: String,
+    /// The entry's metadata, as in `/list`.
+    #[serde(flatten)]
+    pub entry: RemoteEntry,
+}
+
+/// The response of the server's `/search` and `/changes-since` endpoints.
+#[derive(Deserialize, Debug)]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>,
+    /// True if the server stopped early (result limit or scan limit reached).
+    pub truncated: bool,
+}
+
+/// The response of `GET /checksum`: the SHA-256 of a file and the metadata it was computed for.
+#[derive(Deserialize, Debug)]
+pub struct FileChecksum {
+    /// Hex-encoded SHA-256 of the content.
+    pub sha256: String,
+    pub size: u64,
+    /// Modification time as Unix seconds.
+    pub mtime: i64,
+}
+
+/// The outcome of one path of a `/delete-batch` request.
+#[derive(Deserialize, Debug)]
+pub struct DeleteResult {
+    /// The path that was to be deleted.
+    pub path: String,
+    /// `None` on success, otherwise `"not_found"`, `"not_empty"`, `"permission_denied"` or `"io"`.
+    pub error: Option<String>,
+}
+
+/// The error of every function of this module.
+///
+/// The failures callers react to differently get their own variant, so that a
+/// FUSE handler maps them to an errno with a plain `match` instead of
+/// inspecting a boxed error. Error statuses are classified by `ApiError::from_status`.
+#[derive(Debug)]
+pub enum ApiError {
+    /// `404 Not Found`: the path does not exist on the server.
+    NotFound,
+    /// `401 Unauthorized` or `403 Forbidden`.
+    PermissionDenied,
+    /// `409 Conflict`: the path already exists (`create_directory`, `touch_file`),
+    /// or a chunk was sent at an offset the server has not committed.
+    Conflict,
+    /// `413 Payload Too Large`: the file would be over the server's size limit.
+    FileTooLarge,
+    /// `400 Bad Request`, e.g. a new path deeper than the server's `MAX_PATH_DEPTH`
+    /// or with a name longer than its `MAX_NAME_LENGTH` (reported as `ENAMETOOLONG`).
+    BadRequest,
+    /// The server cannot perform the operation (`501 Not Implemented`, or a
+    /// `fallocate` mode its filesystem does not support).
+    NotSupported,
+    /// Downloaded content does not match the server's `X-Content-SHA256`
+    /// (corrupted or truncated transfer).
+    ChecksumMismatch,
+    /// The server could not be reached, or the connection broke (refused, reset, ...).
+    Network(reqwest::Error),
+    /// The request got no answer in time.
+    Timeout(reqwest::Error),
+    /// Any other error status.
+    Server(StatusCode),
+    /// The response could not be decoded (JSON body, metadata headers).
+    Decode(String),
+    /// The local temporary file of a download failed (see `download_file_to_spill`).
+    Io(std::io::Error),
+}
+
+/// The `Result` of every function of this module.
+type ApiResult<T> = Result<T, ApiError>;
+
+impl ApiError {
+    /// Classifies an error status of the server.
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::NOT_FOUND => ApiError::NotFound,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ApiError::PermissionDenied,
+            StatusCode::CONFLICT => ApiError::Conflict,
+            StatusCode::PAYLOAD_TOO_LARGE => ApiError::FileTooLarge,
+            StatusCode::BAD_REQUEST => ApiError::BadRequest,
+            StatusCode::NOT_IMPLEMENTED => ApiError::NotSupported,
+            status => ApiError::Server(status),
+        }
+    }
+
+    /// Whether the server could not be reached at all (connection refused, reset
+    /// or timed out), as opposed to an error status or a bad response.
+    pub fn is_connection_error(&self) -> bool {
+        matches!(self, ApiError::Network(_) | ApiError::Timeout(_))
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::NotFound => write!(f, "not found on the server"),
+            ApiError::PermissionDenied => write!(f, "permission denied by the server"),
+            ApiError::Conflict => write!(f, "conflict with the server's state (e.g. the path already exists)"),
+            ApiError::FileTooLarge => write!(f, "exceeds the server's maximum file size"),
+            ApiError::BadRequest => write!(f, "refused by the server (e.g. path too deep or name too long)"),
+            ApiError::NotSupported => write!(f, "operation not supported by the server"),
+            ApiError::ChecksumMismatch => write!(f, "checksum mismatch: the content is corrupted or truncated"),
+            ApiError::Network(e) => write!(f, "server unreachable: {}", e),
+            ApiError::Timeout(e) => write!(f, "request timed out: {}", e),
+            ApiError::Server(status) => write!(f, "server error: {}", status),
+            ApiError::Decode(message) => write!(f, "invalid response: {}", message),
+            ApiError::Io(e) => write!(f, "temporary file error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApiError::Network(e) | ApiError::Timeout(e) => Some(e),
+            ApiError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A `reqwest` error: an error status (from `error_for_status`) is classified by
+/// `from_status`, a body that cannot be decoded is `Decode`, anything else is a
+/// transport failure.
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        if let Some(status) = e.status() {
+            ApiError::from_status(status)
+        } else if e.is_timeout() {
+            ApiError::Timeout(e)
+        } else if e.is_decode() {
+            ApiError::Decode(e.to_string())
+        } else {
+            ApiError::Network(e)
+        }
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        ApiError::Decode(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for ApiError {
+    fn from(e: std::io::Error) -> Self {
+        ApiError::Io(e)
+    }
+}
+
+/// Upper bound of `server_retry_window_seconds`: a FUSE request must not block for longer.
+pub const MAX_RETRY_WINDOW: Duration = Duration::from_secs(60);
+/// Pause before the first retry of a request; it doubles at every retry.
+const RETRY_INITIAL_DELAY: Duration = Duration::from_millis(250);
+/// Longest pause between two retries of a request.
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(4);
+
+/// Pause before retry number `retry` (from 0) of an operation: `RETRY_INITIAL_DELAY`,
+/// doubled at every retry up to `RETRY_MAX_DELAY`. The policy of `send`, shared by
+/// the callers that retry whole operations (e.g. the final upload of `release`).
+pub fn backoff_delay(retry: u32) -> Duration {
+    RETRY_INITIAL_DELAY.saturating_mul(1 << retry.min(16)).min(RETRY_MAX_DELAY)
+}
+
+/// The state of the server as seen by the requests of one mount, which bounds
+/// their retries (`send`). Each mount has its own, since each has its own server.
+#[derive(Debug, Default)]
+pub struct RetryState {
+    /// How long (in milliseconds) requests keep retrying while the server cannot be reached.
+    window_ms: AtomicU64,
+    /// When the server was first found unreachable, by a request or by the `/health`
+    /// poller; `None` while it is up.
+    down_since: Mutex<Option<Instant>>,
+}
+
+impl RetryState {
+    /// Sets the window during which requests that cannot connect to the server are
+    /// retried instead of failing (`server_retry_window_seconds`, capped at `MAX_RETRY_WINDOW`).
+    /// `Duration::ZERO` (the default) disables the retries.
+    pub fn set_window(&self, window: Duration) {
+        let window = window.min(MAX_RETRY_WINDOW);
+        self.window_ms.store(window.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// The current retry window.
+    fn window(&self) -> Duration {
+        Duration::from_millis(self.window_ms.load(Ordering::Relaxed))
+    }
+
+    /// Records the outcome of a `/health` poll, so that during a long outage requests
+    /// stop waiting once the retry window has elapsed from the start of the outage.
+    pub fn set_server_reachable(&self, reachable: bool) {
+        let mut down_since = self.down_since.lock().unwrap();
+        if reachable {
+            *down_since = None;
+        } else {
+            down_since.get_or_insert_with(Instant::now);
+        }
+    }
+
+    /// When the current outage started, now if it starts with this call.
+    fn outage_start(&self) -> Instant {
+        *self.down_since.lock().unwrap().get_or_insert_with(Instant::now)
+    }
+}
+
+/// The outcome of a shared request, published by the caller that sends it:
+/// `None` while in flight, then `Some(Some(value))`, or `Some(None)` if it failed.
+type Flight<T> = tokio::sync::watch::Receiver<Option<Option<T>>>;
+
+/// The metadata requests in flight of a mount, by method and URL, so that identical
+/// concurrent requests share a single server call ("single-flight").
+///
+/// The first caller sends the request; the others wait for its response instead
+/// of sending their own. Only successes are shared: errors are not `Clone`, and a
+/// caller may retry differently, so on a failure (or if the first caller is
+/// cancelled) each waiting caller sends its own request.
+#[derive(Debug, Default)]
+pub struct InFlight {
+    requests: Mutex<HashMap<String, Arc<dyn Any + Send + Sync>>>,
+}
+
+/// Removes the request of its key from `InFlight` when dropped, also if the
+/// caller that sent it is cancelled.
+struct FlightGuard<'a> {
+    in_flight: &'a InFlight,
+    key: String,
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight.requests.lock().unwrap().remove(&self.key);
+    }
+}
+
+impl InFlight {
+    /// Runs `request` under `key`, or waits for the identical request already in flight.
+    ///
+    /// `key` must identify the request and the type of its result (e.g. method and URL).
+    pub async fn share<T, F>(&self, key: String, request: F) -> ApiResult<T>
+    where
+        T: Clone + Send + Sync + 'static,
+        F: Future<Output = ApiResult<T>>,
+    {
+        let joined = {
+            let mut requests = self.requests.lock().unwrap();
+            match requests.get(&key).and_then(|flight| flight.clone().downcast::<Flight<T>>().ok()) {
+                Some(flight) => Err((*flight).clone()),
+                None => {
+                    let (sender, flight) = tokio::sync::watch::channel(None);
+                    requests.insert(key.clone(), Arc::new(flight));
+                    Ok(sender)
+                }
+            }
+        };
+        match joined {
+            Ok(sender) => {
+                let _guard = FlightGuard { in_flight: self, key };
+                let result = request.await;
+                sender.send_replace(Some(result.as_ref().ok().cloned()));
+                result
+            }
+            Err(mut flight) => {
+                let shared = flight.wait_for(Option::is_some).await.ok().and_then(|outcome| outcome.clone().flatten());
+                match shared {
+                    Some(value) => Ok(value),
+                    None => request.await,
+                }
+            }
+        }
+    }
+}
+
+/// The HTTP client of a mount: its `reqwest::Client` (connection pool, default
+/// headers), which it dereferences to for building requests, its `RetryState`
+/// and its `InFlight` requests.
+///
+/// `ApiClient::default()` is a client of its own, for the one-shot commands.
+#[derive(Debug, Clone, Default)]
+pub struct ApiClient {
+    http: Client,
+    retry: Arc<RetryState>,
+    in_flight: Arc<InFlight>,
+}
+
+impl ApiClient {
+    /// Wraps `http`, with a new `RetryState` and no requests in flight.
+    pub fn new(http: Client) -> Self {
+        ApiClient { http, retry: Arc::new(RetryState::default()), in_flight: Arc::new(InFlight::default()) }
+    }
+
+    /// The retry state shared by the clones of this client (e.g. with the `/health` poller).
+    pub fn retry_state(&self) -> &RetryState {
+        &self.retry
+    }
+
+    /// The requests in flight, shared by the clones of this client.
+    pub fn in_flight(&self) -> &InFlight {
+        &self.in_flight
+    }
+}
+
+impl Deref for ApiClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.http
+    }
+}
+
+tokio::task_local! {
+    /// The `X-Request-ID` of the FUSE operation whose requests are being sent,
+    /// set by `RemoteFS::block_on`. Requests sent outside an operation (empty id,
+    /// or the background tasks) get a new id each.
+    pub static REQUEST_ID: String;
+}
+
+/// Counter mixed into every new request id, so two ids never collide within the process.
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a new random request id (16 hex digits), sent as `X-Request-ID`.
+///
+/// The server logs it with each request: an id found in the client's logs
+/// (`[HTTP]`, `[SLOW]`, `[RETRY]`) can be looked up on the server's side.
+pub fn new_request_id() -> String {
+    // RandomState è inizializzato con chiavi casuali per processo
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed));
+    format!("{:016x}", hasher.finish())
 }
 
-/// A generic `Result` type for API client functions, using a dynamic Error.
+/// The `User-Agent` of every request of a mount: `remotefs-client/<version> (<client id>)`,
+/// after `product` (`user_agent` in the configuration) if given.
+pub fn user_agent(product: Option<&str>, client_id: &str) -> String {
+    let client = format!("remotefs-client/{} ({})", env!("CARGO_PKG_VERSION"), client_id);
+    match product {
+        Some(product) if !product.trim().is_empty() => format!("{} {}", product.trim(), client),
+        _ => client,
+    }
+}
+
+/// Sends `request`, retrying it while the server cannot be reached.
 ///
-/// This simplifies error handling by boxing any error that occurs
-/// (e.g., `reqwest::Error`, `std::io::Error`).
-type ClientResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+/// Within the retry window of the mount (`RetryState::set_window`), a request that
+/// fails to connect is sent again with exponential backoff (`RETRY_INITIAL_DELAY` up to `RETRY_MAX_DELAY`),
+/// so a server restart of a few seconds is not seen by applications. The window
+/// runs from the start of the outage, not of the request: once the server has been
+/// down for longer, requests fail at once instead of each waiting the whole window.
+///
+/// Only connection failures are retried: the request never reached the server, so
+/// sending it again cannot apply it twice. Requests whose body cannot be cloned
+/// (streams) are sent once. The outage is the one of `client`'s mount (`RetryState`).
+///
+/// Every request carries the `X-Request-ID` of the current operation (`REQUEST_ID`),
+/// also written in the `[HTTP]` log line of a request that fails or gets a `5xx`.
+async fn send(client: &ApiClient, request: RequestBuilder) -> reqwest::Result<Response> {
+    let request_id = REQUEST_ID.try_with(String::clone).ok().filter(|id| !id.is_empty()).unwrap_or_else(new_request_id);
+    let result = send_with_retries(&client.retry, request.header("X-Request-ID", &request_id), &request_id).await;
+    match &result {
+        Err(e) => eprintln!("[HTTP] Richiesta {} fallita: {}", request_id, e),
+        Ok(response) if response.status().is_server_error() => {
+            eprintln!("[HTTP] Richiesta {} a {}: {}", request_id, response.url(), response.status());
+        }
+        Ok(_) => {}
+    }
+    result
+}
+
+/// The retry loop of `send`.
+async fn send_with_retries(retry: &RetryState, request: RequestBuilder, request_id: &str) -> reqwest::Result<Response> {
+    let window = retry.window();
+    let mut next = match request.try_clone() {
+        Some(copy) if !window.is_zero() => copy,
+        _ => return request.send().await,
+    };
+    let mut request = request;
+    let mut delay = RETRY_INITIAL_DELAY;
+    loop {
+        match request.send().await {
+            Err(e) if e.is_connect() => {
+                let deadline = retry.outage_start() + window;
+                if Instant::now() + delay > deadline {
+                    return Err(e);
+                }
+                println!("[RETRY] Server irraggiungibile, nuovo tentativo della richiesta {} tra {:?}: {}", request_id, delay, e);
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(RETRY_MAX_DELAY);
+                request = next;
+                next = request.try_clone().expect("the body of a cloned request can be cloned");
+            }
+            result => {
+                if result.is_ok() {
+                    retry.set_server_reachable(true);
+                }
+                return result;
+            }
+        }
+    }
+}
 
 /// Fetches the list of directory entries from the server's `/list` endpoint.
 ///
 /// This corresponds to a `readdir` operation. It handles both the root directory
-/// (when `path` is empty) and subdirectories.
+/// (when `path` is empty) and subdirectories. The response may be an array of
+/// entry objects or of bare names (see `ListedEntry`).
 ///
 /// # Arguments
-/// * `client` - The shared `reqwest::Client` instance.
+/// * `client` - The `ApiClient` of the mount.
 /// * `path` - The relative path of the directory to list. An empty string signifies the root.
 ///
 /// # Returns
-/// A `Result` containing a `Vec<RemoteEntry>` on success, or a `reqwest::Error`.
-pub async fn get_files_from_server(client: &Client, path: &str, base_url: &str) -> Result<Vec<RemoteEntry>, reqwest::Error> {
+/// A `Result` containing a `Vec<RemoteEntry>` on success, or an `ApiError`.
+pub async fn get_files_from_server(client: &ApiClient, path: &str, base_url: &str) -> ApiResult<Vec<RemoteEntry>> {
     let url = if path.is_empty() {
         format!("{}/list", base_url)
     } else {
         format!("{}/list/{}", base_url, path)
     };
     println!("API Client: requesting file list from {}", url);
-    let response = client.get(&url).send().await?;
-    response.json::<Vec<RemoteEntry>>().await
+    let response = send(client, client.get(&url)).await?.error_for_status()?;
+    let entries = response.json::<Vec<ListedEntry>>().await?;
+    Ok(entries.into_iter().map(RemoteEntry::from).collect())
+}
+
+/// A directory listing with the directory's mtime, as returned by `get_files_if_modified`.
+#[derive(Debug, Clone)]
+pub struct DirListing {
+    pub entries: Vec<RemoteEntry>,
+    /// The `Last-Modified` of the directory, to revalidate the listing with.
+    /// `None` if the server sent none (e.g. a directory modified in the last second).
+    pub last_modified: Option<String>,
+}
+
+/// Fetches a directory listing from `/list`, unless the directory did not change
+/// since `since` (the `last_modified` of a cached `DirListing`, sent as `If-Modified-Since`).
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `path` - The relative path of the directory ("" for the root).
+/// * `since` - The date of the cached listing, if any.
+///
+/// # Returns
+/// * `Ok(Some(listing))` with the entries and the directory's `Last-Modified`.
+/// * `Ok(None)` if the server answered `304 Not Modified`: the cached listing is still valid.
+pub async fn get_files_if_modified(client: &ApiClient, path: &str, since: Option<&str>, base_url: &str) -> ApiResult<Option<DirListing>> {
+    let url = if path.is_empty() {
+        format!("{}/list", base_url)
+    } else {
+        format!("{}/list/{}", base_url, path)
+    };
+    let key = format!("GET {} since {}", url, since.unwrap_or("-"));
+    client.in_flight().share(key, async {
+        let mut request = client.get(&url);
+        if let Some(since) = since {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, since);
+        }
+        let response = send(client, request).await?.error_for_status()?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        let last_modified = response
+            .headers()
+            .get(reqwest::header::LAST_MODIFIED)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let entries = response.json::<Vec<ListedEntry>>().await?;
+        Ok(Some(DirListing { entries: entries.into_iter().map(RemoteEntry::from).collect(), last_modified }))
+    }).await
+}
+
+/// One page of a directory listing, as returned by `get_files_page`.
+#[derive(Debug)]
+pub struct ListPage {
+    /// The entries of the page, in the server's order (by name).
+    pub entries: Vec<RemoteEntry>,
+    /// The number of entries of the whole directory (`X-Total-Count`).
+    pub total: Option<u64>,
+    /// The offset of the next page, or `None` if this was the last one (`X-Next-Offset`).
+    pub next_offset: Option<u64>,
+}
+
+/// Fetches one page of a directory listing via `/list?offset=&limit=`.
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `path` - The relative path of the directory ("" for the root).
+/// * `offset` - The 0-based position of the first entry to return.
+/// * `limit` - The maximum number of entries to return.
+pub async fn get_files_page(client: &ApiClient, path: &str, offset: u64, limit: u64, base_url: &str) -> ApiResult<ListPage> {
+    let url = if path.is_empty() {
+        format!("{}/list", base_url)
+    } else {
+        format!("{}/list/{}", base_url, path)
+    };
+    let response = send(client, client.get(&url).query(&[("offset", offset), ("limit", limit)])).await?.error_for_status()?;
+
+    let header = |name: &str| response.headers().get(name)?.to_str().ok()?.parse::<u64>().ok();
+    let total = header("X-Total-Count");
+    let next_offset = header("X-Next-Offset");
+    let mut entries = Vec::with_capacity(total.map_or(0, |total| total.saturating_sub(offset).min(limit)) as usize);
+    entries.extend(response.json::<Vec<ListedEntry>>().await?.into_iter().map(RemoteEntry::from));
+    Ok(ListPage { entries, total, next_offset })
+}
+
+/// A directory listing being received from the server's `/list-stream` endpoint.
+///
+/// The server sends one JSON `RemoteEntry` (or bare name, see `ListedEntry`) per line (NDJSON); `next_entry`
+/// parses them as the bytes arrive, so the caller can use the first entries
+/// before the whole listing has been transferred.
+pub struct ListingStream {
+    response: reqwest::Response,
+    /// Received bytes not yet parsed (at most one incomplete line).
+    pending: Vec<u8>,
+}
+
+impl ListingStream {
+    /// Returns the next entry of the listing, or `None` once the server has sent them all.
+    pub async fn next_entry(&mut self) -> ApiResult<Option<RemoteEntry>> {
+        loop {
+            if let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.pending.drain(..=end).collect();
+                if line.len() > 1 {
+                    return Ok(Some(serde_json::from_slice::<ListedEntry>(&line[..line.len() - 1])?.into()));
+                }
+                continue;
+            }
+            match self.response.chunk().await? {
+                Some(chunk) => self.pending.extend_from_slice(&chunk),
+                // A last line without the trailing newline
+                None if !self.pending.is_empty() => {
+                    let line = std::mem::take(&mut self.pending);
+                    return Ok(Some(serde_json::from_slice::<ListedEntry>(&line)?.into()));
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Starts streaming the entries of a directory from the server's `/list-stream` endpoint.
+///
+/// Streaming counterpart of `get_files_from_server` for very large directories:
+/// entries are read one at a time with `ListingStream::next_entry`.
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `path` - The relative path of the directory ("" for the root).
+pub async fn stream_files_from_server(client: &ApiClient, path: &str, base_url: &str) -> ApiResult<ListingStream> {
+    let url = if path.is_empty() {
+        format!("{}/list-stream", base_url)
+    } else {
+        format!("{}/list-stream/{}", base_url, path)
+    };
+    let response = send(client, client.get(&url)).await?.error_for_status()?;
+    Ok(ListingStream { response, pending: Vec::new() })
 }
 
 /// Fetches the entire content of a file from the server's `/files` endpoint.
@@ -61,22 +689,100 @@ pub async fn get_files_from_server(client: &Client, path: &str, base_url: &str)
 /// at once. The FUSE `read` handler is responsible for slicing this `Bytes` object
 /// to satisfy the kernel's specific offset and size request.
 ///
+/// When `verify_checksum` is set and the server sends an `X-Content-SHA256` header,
+/// the downloaded bytes are hashed and compared against it.
+///
 /// # Arguments
-/// * `client` - The shared `reqwest::Client` instance.
+/// * `client` - The `ApiClient` of the mount.
 /// * `path` - The relative path of the file to read.
+/// * `verify_checksum` - Whether to verify the content against `X-Content-SHA256`.
 ///
 /// # Returns
-/// A `ClientResult` containing the file's content as `Bytes` on success.
-pub async fn get_file_content_from_server(client: &Client, path: &str, base_url: &str) -> ClientResult<Bytes> {
+/// A `ClientResult` containing the file's content as `Bytes` on success,
+/// or `ApiError::ChecksumMismatch` if the content is corrupted or truncated.
+pub async fn get_file_content_from_server(client: &ApiClient, path: &str, verify_checksum: bool, base_url: &str) -> ApiResult<Bytes> {
     let url = format!("{}/files/{}", base_url, path);
-    let response = client.get(&url).send().await?.error_for_status()?;
+    let response = send(client, client.get(&url)).await?.error_for_status()?;
+    let expected = response
+        .headers()
+        .get("X-Content-SHA256")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_ascii_lowercase());
 
     // Reads the entire response body into memory as Bytes
     let data = response.bytes().await?;
 
+    if let (true, Some(expected)) = (verify_checksum, expected) {
+        let actual = format!("{:x}", Sha256::digest(&data));
+        if actual != expected {
+            eprintln!("API Client: checksum mismatch for '{}' (expected {}, got {})", path, expected, actual);
+            return Err(ApiError::ChecksumMismatch);
+        }
+    }
+
     Ok(data)
 }
 
+/// Downloads the entire content of a file, like `get_file_content_from_server`,
+/// into an anonymous temporary file instead of memory (see `create_spill_file`).
+///
+/// The body is written to disk as it arrives, so only one network chunk at a time
+/// is held in memory, whatever the size of the file. The checksum is verified the
+/// same way, hashing the chunks on the fly.
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `path` - The relative path of the file to read.
+/// * `verify_checksum` - Whether to verify the content against `X-Content-SHA256`.
+///
+/// # Returns
+/// The temporary file, positioned at its start: callers can read it sequentially
+/// or `seek` it. `Err(ApiError::ChecksumMismatch)` if the content is corrupted or truncated.
+pub async fn download_file_to_spill(client: &ApiClient, path: &str, verify_checksum: bool, base_url: &str) -> ApiResult<std::fs::File> {
+    let url = format!("{}/files/{}", base_url, path);
+    let mut response = send(client, client.get(&url)).await?.error_for_status()?;
+    let expected = response
+        .headers()
+        .get("X-Content-SHA256")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_ascii_lowercase());
+
+    let mut file = create_spill_file()?;
+    let mut hasher = Sha256::new();
+    let mut written = 0u64;
+    while let Some(chunk) = response.chunk().await? {
+        hasher.update(&chunk);
+        file.write_all(&chunk)?;
+        written += chunk.len() as u64;
+    }
+    println!("[API] '{}' scaricato su file temporaneo ({} byte)", path, written);
+
+    if let (true, Some(expected)) = (verify_checksum, expected) {
+        let actual = format!("{:x}", hasher.finalize());
+        if actual != expected {
+            eprintln!("API Client: checksum mismatch for '{}' (expected {}, got {})", path, expected, actual);
+            return Err(ApiError::ChecksumMismatch);
+        }
+    }
+
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file)
+}
+
+/// Creates an empty temporary file in the system's temp directory, for content too
+/// large to keep in memory.
+///
+/// The file is unlinked right after creation: it has no name, and its space is
+/// released as soon as the returned handle is dropped, even if the client crashes.
+pub fn create_spill_file() -> std::io::Result<std::fs::File> {
+    static NEXT_SPILL: AtomicU64 = AtomicU64::new(0);
+    let name = format!("remotefs-spill-{}-{}", std::process::id(), NEXT_SPILL.fetch_add(1, Ordering::Relaxed));
+    let path = std::env::temp_dir().join(name);
+    let file = std::fs::OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+    std::fs::remove_file(&path)?;
+    Ok(file)
+}
+
 /// Uploads (or overwrites) the entire content of a file to the server's `/files` endpoint.
 ///
 /// This function is used by `create` (to create an empty file) and `release` (to
@@ -84,33 +790,52 @@ pub async fn get_file_content_from_server(client: &Client, path: &str, base_url:
 /// with the provided `Bytes` as the request body.
 ///
 /// # Arguments
-/// * `client` - The shared `reqwest::Client` instance.
+/// * `client` - The `ApiClient` of the mount.
 /// * `path` - The relative path of the file to write.
 /// * `data` - The complete byte content to upload.
+/// * `atime` / `mtime` - Timestamps the server should apply after writing
+///   (`X-Atime`/`X-Mtime`); `None` lets the server use the time of the write.
 ///
 /// # Returns
-/// A `ClientResult<()>` indicating success or failure.
-pub async fn put_file_content_to_server(client: &Client, path: &str, data: Bytes, base_url: &str) -> ClientResult<()> {
+/// An `ApiResult<()>` indicating success or failure, `Err(ApiError::FileTooLarge)` if the
+/// server refuses the size of the file.
+pub async fn put_file_content_to_server(client: &ApiClient, path: &str, data: Bytes, atime: Option<SystemTime>, mtime: Option<SystemTime>, base_url: &str) -> ApiResult<()> {
     let url = format!("{}/files/{}", base_url, path);
 
     // reqwest::Body can be created directly from Bytes
     let body = Body::from(data);
 
     // Send the PUT request and check for HTTP errors (4xx, 5xx)
-    client.put(&url).body(body).send().await?.error_for_status()?;
+    send(client, with_time_headers(client.put(&url), atime, mtime).body(body)).await?.error_for_status()?;
     Ok(())
 }
 
+/// Converts a `SystemTime` into Unix seconds, as expected by the server.
+fn unix_secs(t: SystemTime) -> i64 {
+    t.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+/// Adds the optional `X-Atime`/`X-Mtime` headers to an upload request.
+fn with_time_headers(mut request: RequestBuilder, atime: Option<SystemTime>, mtime: Option<SystemTime>) -> RequestBuilder {
+    if let Some(atime) = atime {
+        request = request.header("X-Atime", unix_secs(atime).to_string());
+    }
+    if let Some(mtime) = mtime {
+        request = request.header("X-Mtime", unix_secs(mtime).to_string());
+    }
+    request
+}
+
 /// Deletes a file or directory on the server via the `/files` endpoint.
 ///
 /// This corresponds to `unlink` or `rmdir` operations.
 ///
 /// # Arguments
-/// * `client` - The shared `reqwest::Client` instance.
+/// * `client` - The `ApiClient` of the mount.
 /// * `path` - The relative path of the resource to delete.
-pub async fn delete_resource(client: &Client, path: &str, base_url: &str) -> ClientResult<()> {
+pub async fn delete_resource(client: &ApiClient, path: &str, base_url: &str) -> ApiResult<()> {
     let url = format!("{}/files/{}", base_url, path);
-    client.delete(&url).send().await?.error_for_status()?;
+    send(client, client.delete(&url)).await?.error_for_status()?;
     Ok(())
 }
 
@@ -119,11 +844,98 @@ pub async fn delete_resource(client: &Client, path: &str, base_url: &str) -> Cli
 /// This corresponds to the `mkdir` operation.
 ///
 /// # Arguments
-/// * `client` - The shared `reqwest::Client` instance.
+/// * `client` - The `ApiClient` of the mount.
 /// * `path` - The relative path of the directory to create.
-pub async fn create_directory(client: &Client, path: &str, base_url: &str) -> ClientResult<()> {
+/// * `parents` - If true, missing parents are created and an existing directory
+///   is not an error (`mkdir -p`). If false, only the last component is created.
+///
+/// # Returns
+/// * `Ok(())` on success.
+/// * `Err(ApiError::Conflict)` if `parents` is false and the path already exists.
+pub async fn create_directory(client: &ApiClient, path: &str, parents: bool, base_url: &str) -> ApiResult<()> {
     let url = format!("{}/mkdir/{}", base_url, path);
-    client.post(&url).send().await?.error_for_status()?;
+    send(client, client.post(&url).query(&[("parents", parents)])).await?.error_for_status()?;
+    Ok(())
+}
+
+/// Creates an empty file on the server via the `/touch` endpoint.
+///
+/// This corresponds to the `create` operation: no content is sent, the data
+/// written afterwards is uploaded once when the file is released.
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `path` - The relative path of the file to create.
+///
+/// # Returns
+/// * `Ok(())` on success.
+/// * `Err(ApiError::Conflict)` if the path already exists on the server.
+pub async fn touch_file(client: &ApiClient, path: &str, base_url: &str) -> ApiResult<()> {
+    let url = format!("{}/touch/{}", base_url, path);
+    send(client, client.post(&url)).await?.error_for_status()?;
+    Ok(())
+}
+
+/// Resizes a file on the server without transferring its content (`POST /truncate`).
+///
+/// The server drops the tail when `size` is smaller than the file, and extends
+/// it with zeros when it is larger.
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `path` - The relative path of the file.
+/// * `size` - The new size in bytes.
+///
+/// # Returns
+/// * `Ok(())` on success.
+/// * `Err(ApiError::FileTooLarge)` if `size` is over the server's size limit.
+pub async fn truncate_on_server(client: &ApiClient, path: &str, size: u64, base_url: &str) -> ApiResult<()> {
+    let url = format!("{}/truncate/{}", base_url, path);
+    send(client, client.post(&url).query(&[("size", size)])).await?.error_for_status()?;
+    Ok(())
+}
+
+/// Writes `data` into an existing file on the server at `offset` (`POST /write`).
+///
+/// Only the given range changes: the rest of the file is kept, and writing
+/// past the end extends it (with zeros in any gap).
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `path` - The relative path of the file.
+/// * `offset` - The byte offset to write at.
+/// * `data` - The bytes to write.
+///
+/// # Returns
+/// * `Ok(())` on success.
+/// * `Err(ApiError::FileTooLarge)` if the file would grow past the server's size limit.
+pub async fn write_at_offset(client: &ApiClient, path: &str, offset: u64, data: Vec<u8>, base_url: &str) -> ApiResult<()> {
+    let url = format!("{}/write/{}", base_url, path);
+    send(client, client.post(&url).query(&[("offset", offset)]).body(data)).await?.error_for_status()?;
+    Ok(())
+}
+
+/// Applies `fallocate(2)` to a file on the server (`POST /fallocate`).
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `path` - The relative path of the file.
+/// * `offset` / `len` - The byte range to preallocate or deallocate.
+/// * `mode` - The `fallocate` flags (`0`, `FALLOC_FL_KEEP_SIZE`, or
+///   `FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE`).
+///
+/// # Returns
+/// * `Ok(())` on success.
+/// * `Err(ApiError::FileTooLarge)` if the file would grow past the server's size limit.
+/// * `Err(ApiError::NotSupported)` if the server refuses the mode.
+pub async fn fallocate_on_server(client: &ApiClient, path: &str, offset: u64, len: u64, mode: i32, base_url: &str) -> ApiResult<()> {
+    let url = format!("{}/fallocate/{}", base_url, path);
+    let response = send(client, client.post(&url).query(&[("offset", offset), ("len", len)]).query(&[("mode", mode)])).await?;
+    // The server answers `400` for a mode its filesystem refuses
+    if response.status() == StatusCode::BAD_REQUEST {
+        return Err(ApiError::NotSupported);
+    }
+    response.error_for_status()?;
     Ok(())
 }
 
@@ -133,15 +945,33 @@ pub async fn create_directory(client: &Client, path: &str, base_url: &str) -> Cl
 /// the new octal permission string (e.g., `{ "perm": "755" }`).
 ///
 /// # Arguments
-/// * `client` - The shared `reqwest::Client` instance.
+/// * `client` - The `ApiClient` of the mount.
 /// * `path` - The relative path of the file.
 /// * `mode` - The new mode (u32) from which permissions are extracted.
-pub async fn update_permissions(client: &Client, path: &str, mode: u32, base_url: &str) -> ClientResult<()> {
+pub async fn update_permissions(client: &ApiClient, path: &str, mode: u32, base_url: &str) -> ApiResult<()> {
     let perm_str = format!("{:o}", mode & 0o777);
     let url = format!("{}/files/{}", base_url, path);
     let payload = json!({ "perm": perm_str });
 
-    client.patch(&url).json(&payload).send().await?.error_for_status()?;
+    send(client, client.patch(&url).json(&payload)).await?.error_for_status()?;
+    Ok(())
+}
+
+/// Updates the access and/or modification time via a `POST` request to the `/utimes` endpoint.
+///
+/// This is used by `setattr` (utimensat). Times are sent as Unix seconds;
+/// `None` leaves the corresponding timestamp unchanged on the server.
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `path` - The relative path of the file.
+/// * `atime` - The new access time, if any.
+/// * `mtime` - The new modification time, if any.
+pub async fn update_times(client: &ApiClient, path: &str, atime: Option<SystemTime>, mtime: Option<SystemTime>, base_url: &str) -> ApiResult<()> {
+    let url = format!("{}/utimes/{}", base_url, path);
+    let payload = json!({ "atime": atime.map(unix_secs), "mtime": mtime.map(unix_secs) });
+
+    send(client, client.post(&url).json(&payload)).await?.error_for_status()?;
     Ok(())
 }
 
@@ -150,44 +980,349 @@ pub async fn update_permissions(client: &Client, path: &str, mode: u32, base_url
 /// This uses the HTTP `Range` header to request only a specific chunk of data.
 /// It is much more memory efficient than `get_file_content_from_server`.
 ///
+/// The request asks for the file's metadata too (`?metadata=true`), so the
+/// caller gets the attributes without a separate `HEAD`.
+///
 /// # Arguments
 /// * `offset` - The start byte position.
 /// * `size` - The number of bytes to read.
-pub async fn get_file_chunk_from_server(client: &Client, path: &str, offset: u64, size: u32, base_url: &str) -> ClientResult<Bytes> {
-    let url = format!("{}/files/{}", base_url, path);
+///
+/// # Returns
+/// The bytes read, and the metadata of the file if the server sent it (`None`
+/// for servers that ignore `metadata=true`).
+pub async fn get_file_chunk_from_server(client: &ApiClient, path: &str, offset: u64, size: u32, base_url: &str) -> ApiResult<(Bytes, Option<RemoteEntry>)> {
+    let url = format!("{}/files/{}?metadata=true", base_url, path);
 
-    // Calculate the end byte (inclusive)
-    let end = offset + (size as u64) - 1;
+    // Calculate the end byte (inclusive), without overflowing near `u64::MAX`
+    let end = offset.saturating_add(size as u64).saturating_sub(1);
     let range_header_val = format!("bytes={}-{}", offset, end);
 
     println!("[API] Requesting chunk: {} (Range: {})", path, range_header_val);
 
-    let response = client.get(&url)
-        .header("Range", range_header_val)
-        .send()
+    let response = send(client, client.get(&url).header("Range", range_header_val))
         .await?
         .error_for_status()?;
+    let entry = entry_from_headers(path, response.headers(), "X-Size").ok();
 
     // Check status code:
     // 206 Partial Content = Server supports ranges (Good).
     // 200 OK = Server ignored Range header and sent full file (Fallback).
     if response.status() == 206 {
         let data = response.bytes().await?;
-        Ok(data)
+        Ok((data, entry))
     } else {
         // Fallback: The server sent the whole file. We must slice it manually here.
         // This is inefficient but safe.
         println!("[API] WARN: Server returned 200 OK instead of 206. Downloading full file.");
         let full_data = response.bytes().await?;
-        let start = offset as usize;
+        // An offset that does not fit in `usize` (32-bit builds) is past any downloaded file
+        let start = match usize::try_from(offset) {
+            Ok(start) if start < full_data.len() => start,
+            _ => return Ok((Bytes::new(), entry)), // EOF
+        };
         let requested_len = size as usize;
 
-        if start >= full_data.len() {
-            return Ok(Bytes::new()); // EOF
-        }
-
         let available_len = std::cmp::min(requested_len, full_data.len() - start);
         let chunk = full_data.slice(start..(start + available_len));
-        Ok(chunk)
+        Ok((chunk, entry))
+    }
+}
+
+/// The state of a reachable server, as reported by `GET /healthz`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerHealth {
+    /// The server answers and its storage takes new data.
+    Healthy,
+    /// The server answers, but cannot store data (`503` from `/healthz`): its data
+    /// directory is not writable or, with `out_of_space`, below the free space minimum.
+    Degraded { out_of_space: bool, reason: String },
+}
+
+/// The body of a `503` from `/healthz`.
+#[derive(Deserialize, Debug)]
+struct HealthzReport {
+    #[serde(default)]
+    check: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Checks that the server answers, with its cheap health check `GET /health`.
+///
+/// # Returns
+/// * `Ok(())` if the server answered `200 OK`.
+/// * `Err` if it cannot be reached, or answered with an error status.
+pub async fn check_alive(client: &ApiClient, base_url: &str) -> ApiResult<()> {
+    client.get(format!("{}/health", base_url)).send().await?.error_for_status()?;
+    Ok(())
+}
+
+/// Checks whether the storage of a reachable server takes new data, with its
+/// deep health check `GET /healthz`.
+///
+/// A server without `/healthz` (`404`, an older version) is reported healthy.
+///
+/// # Returns
+/// * `Ok(ServerHealth)` if the server answered.
+/// * `Err` if it cannot be reached, or answered with another error status.
+pub async fn check_health(client: &ApiClient, base_url: &str) -> ApiResult<ServerHealth> {
+    let response = client.get(format!("{}/healthz", base_url)).send().await?;
+    match response.status() {
+        StatusCode::NOT_FOUND => Ok(ServerHealth::Healthy),
+        StatusCode::SERVICE_UNAVAILABLE => {
+            let report = response.json::<HealthzReport>().await?;
+            Ok(ServerHealth::Degraded {
+                out_of_space: report.check.as_deref() == Some("space"),
+                reason: report.reason.unwrap_or_default(),
+            })
+        }
+        _ => {
+            response.error_for_status()?;
+            Ok(ServerHealth::Healthy)
+        }
+    }
+}
+
+/// Fetches the metadata of a file or directory with `HEAD /files/<path>`.
+///
+/// No content is transferred: the server answers with headers only
+/// (`Content-Length`, `X-Kind`, `X-Perm`, `X-Mtime`, `X-Blocks`, `X-Uid`, `X-Gid`, `X-Ino`).
+///
+/// # Returns
+/// * `Ok(Some(RemoteEntry))` with the metadata (`name` is the last component of `path`).
+/// * `Ok(None)` if the path does not exist.
+/// * `Err` on network errors, other status codes, or a server without `HEAD`
+///   support (missing headers), so the caller can fall back to `/list`.
+pub async fn head_file_from_server(client: &ApiClient, path: &str, base_url: &str) -> ApiResult<Option<RemoteEntry>> {
+    let url = format!("{}/files/{}", base_url, path);
+    client.in_flight().share(format!("HEAD {}", url), async {
+        let response = send(client, client.head(&url)).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response.error_for_status()?;
+        Ok(Some(entry_from_headers(path, response.headers(), "Content-Length")?))
+    }).await
+}
+
+/// Builds the `RemoteEntry` of `path` from the metadata headers of `HEAD /files`
+/// or `GET /files?metadata=true`.
+///
+/// `size_header` names the header holding the file size: `Content-Length` for a
+/// `HEAD`, `X-Size` for a `GET` (whose `Content-Length` may be that of a range).
+///
+/// # Returns
+/// An error if `X-Kind`, `X-Mtime`, `X-Perm` or the size are missing or invalid;
+/// the other headers are optional.
+fn entry_from_headers(path: &str, headers: &reqwest::header::HeaderMap, size_header: &str) -> ApiResult<RemoteEntry> {
+    let header = |name: &str| -> ApiResult<String> {
+        let value = headers.get(name).ok_or_else(|| ApiError::Decode(format!("header {} mancante nella risposta", name)))?;
+        Ok(value.to_str().map_err(|e| ApiError::Decode(format!("header {} non valido: {}", name, e)))?.to_string())
+    };
+    let number = |name: &str| -> ApiResult<i64> {
+        header(name)?.parse().map_err(|e| ApiError::Decode(format!("header {} non valido: {}", name, e)))
+    };
+    Ok(RemoteEntry {
+        name: path.rsplit('/').next().unwrap_or_default().to_string(),
+        kind: header("X-Kind")?,
+        size: number(size_header)? as u64,
+        blocks: header("X-Blocks").ok().and_then(|v| v.parse().ok()),
+        mtime: number("X-Mtime")?,
+        btime: header("X-Btime").ok().and_then(|v| v.parse().ok()),
+        perm: header("X-Perm")?,
+        uid: header("X-Uid").ok().and_then(|v| v.parse().ok()),
+        gid: header("X-Gid").ok().and_then(|v| v.parse().ok()),
+        ino: header("X-Ino").ok().and_then(|v| v.parse().ok()),
+    })
+}
+
+/// Fetches the SHA-256 of a file with `GET /checksum/<path>`, without downloading it.
+///
+/// Cheaper than a `GET /files` to tell whether a copy of a large file is still
+/// current, and more reliable than comparing mtimes (changes within the same second).
+///
+/// # Returns
+/// * `Ok(Some(FileChecksum))` with the hash, size and mtime.
+/// * `Ok(None)` if the file does not exist.
+/// * `Err` on network errors or other status codes (e.g. `400` for a directory).
+pub async fn get_checksum_from_server(client: &ApiClient, path: &str, base_url: &str) -> ApiResult<Option<FileChecksum>> {
+    let url = format!("{}/checksum/{}", base_url, path);
+    let response = send(client, client.get(&url)).await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    Ok(Some(response.error_for_status()?.json().await?))
+}
+
+/// Searches a subtree by name with a single `GET /search` request.
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `query` - A substring of the name, or a glob pattern if `glob` is true.
+/// * `path` - The directory to search in ("" for the root).
+/// * `limit` - Maximum number of results (the server caps it anyway).
+pub async fn search(client: &ApiClient, query: &str, path: &str, glob: bool, limit: Option<usize>, base_url: &str) -> ApiResult<SearchResponse> {
+    let url = format!("{}/search", base_url);
+    let mut request = client.get(&url).query(&[("q", query), ("path", path), ("glob", if glob { "true" } else { "false" })]);
+    if let Some(limit) = limit {
+        request = request.query(&[("limit", limit)]);
+    }
+
+    let response = send(client, request).await?.error_for_status()?.json::<SearchResponse>().await?;
+    Ok(response)
+}
+
+/// Lists the entries of a subtree modified at or after `since` with `GET /changes-since`.
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `since` - Unix seconds, compared by the server with the mtime of each entry.
+/// * `path` - The directory to look in ("" for the root).
+///
+/// # Returns
+/// * `Ok(SearchResponse)` with the changed entries; if `truncated`, the list is incomplete.
+/// * `Err` on network errors or other status codes (`404` also from servers without the endpoint).
+pub async fn changes_since(client: &ApiClient, since: i64, path: &str, base_url: &str) -> ApiResult<SearchResponse> {
+    let url = format!("{}/changes-since", base_url);
+    let request = client.get(&url).query(&[("ts", since.to_string().as_str()), ("path", path)]);
+    let response = send(client, request).await?.error_for_status()?.json::<SearchResponse>().await?;
+    Ok(response)
+}
+
+/// Fetches the metadata of a single file or directory via the `/stat` endpoint.
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `path` - The relative path of the resource (empty for the server's data directory).
+///
+/// # Returns
+/// * `Ok(Some(RemoteEntry))` if the resource exists.
+/// * `Ok(None)` if the server replies `404 Not Found`.
+pub async fn stat_resource(client: &ApiClient, path: &str, base_url: &str) -> ApiResult<Option<RemoteEntry>> {
+    // The server's data directory itself has no path component
+    let url = if path.is_empty() { format!("{}/stat", base_url) } else { format!("{}/stat/{}", base_url, path) };
+    let response = send(client, client.get(&url)).await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
     }
-}
\ No newline at end of file
+    let entry = response.error_for_status()?.json::<RemoteEntry>().await?;
+    Ok(Some(entry))
+}
+
+/// Renames a resource on the server via the `/rename` endpoint.
+///
+/// The server performs a single atomic `rename(2)`, overwriting an existing
+/// destination file. This is used by `rename` for moves within one directory.
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `from` - The current relative path of the resource.
+/// * `to` - The new relative path.
+pub async fn rename_resource(client: &ApiClient, from: &str, to: &str, base_url: &str) -> ApiResult<()> {
+    let url = format!("{}/rename", base_url);
+    let payload = json!({ "from": from, "to": to });
+
+    send(client, client.post(&url).json(&payload)).await?.error_for_status()?;
+    Ok(())
+}
+
+/// Atomically swaps two existing resources via the `/exchange` endpoint.
+///
+/// This is used by `rename` when called with the `RENAME_EXCHANGE` flag.
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `from` - The relative path of the first resource.
+/// * `to` - The relative path of the second resource.
+pub async fn exchange_resources(client: &ApiClient, from: &str, to: &str, base_url: &str) -> ApiResult<()> {
+    let url = format!("{}/exchange", base_url);
+    let payload = json!({ "from": from, "to": to });
+
+    send(client, client.post(&url).json(&payload)).await?.error_for_status()?;
+    Ok(())
+}
+
+/// Opens a chunked upload session for `path` via `POST /upload/start`.
+///
+/// # Arguments
+/// * `client` - The `ApiClient` of the mount.
+/// * `path` - The relative path the file will be moved to on completion.
+///
+/// # Returns
+/// The new session, with offset `0`.
+pub async fn start_upload(client: &ApiClient, path: &str, base_url: &str) -> ApiResult<UploadStatus> {
+    let url = format!("{}/upload/start", base_url);
+    let payload = json!({ "path": path });
+
+    let status = send(client, client.post(&url).json(&payload)).await?.error_for_status()?.json::<UploadStatus>().await?;
+    Ok(status)
+}
+
+/// Queries how many bytes of upload `id` the server has committed (`GET /upload/<id>`).
+///
+/// Used to resume an interrupted upload from the right offset.
+pub async fn get_upload_offset(client: &ApiClient, id: &str, base_url: &str) -> ApiResult<u64> {
+    let url = format!("{}/upload/{}", base_url, id);
+
+    let status = send(client, client.get(&url)).await?.error_for_status()?.json::<UploadStatus>().await?;
+    Ok(status.offset)
+}
+
+/// Appends a chunk to upload `id` at `offset` (`PUT /upload/<id>?offset=<n>`).
+///
+/// # Returns
+/// The new committed offset. Fails with `ApiError::Conflict` if `offset` does not match
+/// what the server has committed, and with `ApiError::FileTooLarge` if the chunk would take
+/// the file over the server's size limit.
+pub async fn upload_chunk(client: &ApiClient, id: &str, offset: u64, data: Bytes, base_url: &str) -> ApiResult<u64> {
+    let url = format!("{}/upload/{}?offset={}", base_url, id, offset);
+
+    let status = send(client, client.put(&url).body(Body::from(data))).await?.error_for_status()?.json::<UploadStatus>().await?;
+    Ok(status.offset)
+}
+
+/// Finalizes upload `id`, moving the assembled file into place (`POST /upload/<id>/complete`).
+///
+/// `atime`/`mtime` work as in `put_file_content_to_server`.
+pub async fn complete_upload(client: &ApiClient, id: &str, atime: Option<SystemTime>, mtime: Option<SystemTime>, base_url: &str) -> ApiResult<()> {
+    let url = format!("{}/upload/{}/complete", base_url, id);
+
+    send(client, with_time_headers(client.post(&url), atime, mtime)).await?.error_for_status()?;
+    Ok(())
+}
+
+/// Lists every descendant of directory `path` in one request via `GET /tree`.
+///
+/// Entries come in post-order (children before their parent directory), ready
+/// to be passed to `delete_batch`.
+pub async fn get_tree(client: &ApiClient, path: &str, base_url: &str) -> ApiResult<Vec<TreeEntry>> {
+    let url = format!("{}/tree/{}", base_url, path);
+
+    let entries = send(client, client.get(&url)).await?.error_for_status()?.json::<Vec<TreeEntry>>().await?;
+    Ok(entries)
+}
+
+/// Fetches the metadata of many paths with a single `POST /stat-batch` request.
+///
+/// At most 1000 paths can be sent at once.
+///
+/// # Returns
+/// One element per path, in the same order: the entry, or `None` if the path
+/// does not exist.
+pub async fn stat_batch(client: &ApiClient, paths: &[String], base_url: &str) -> ApiResult<Vec<Option<RemoteEntry>>> {
+    let url = format!("{}/stat-batch", base_url);
+
+    let entries = send(client, client.post(&url).json(paths)).await?.error_for_status()?.json::<Vec<Option<RemoteEntry>>>().await?;
+    Ok(entries)
+}
+
+/// Deletes many paths, in order, with a single `POST /delete-batch` request.
+///
+/// # Returns
+/// One `DeleteResult` per path. A failed path does not stop the batch.
+pub async fn delete_batch(client: &ApiClient, paths: &[String], base_url: &str) -> ApiResult<Vec<DeleteResult>> {
+    let url = format!("{}/delete-batch", base_url);
+
+    let results = send(client, client.post(&url).json(paths)).await?.error_for_status()?.json::<Vec<DeleteResult>>().await?;
+    Ok(results)
+}
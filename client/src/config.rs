@@ -1,11 +1,15 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use clap::ValueEnum;
+use fuser::MountOption;
+use url::Url;
 /// Defines the available strategies for the internal attribute cache.
 ///
 /// This is read from `config.toml` and controls the behavior of `AttributeCache`.
-#[derive(Deserialize, Debug, Clone, Copy,ValueEnum)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ValueEnum)]
 #[serde(rename_all = "lowercase")]
 pub enum CacheStrategy {
     /// Time-to-Live: Entries expire after a set duration.
@@ -19,18 +23,425 @@ pub enum CacheStrategy {
 /// Holds all filesystem configuration, loaded from `config.toml`.
 ///
 /// This struct defines the behavior of both the internal application cache
-#[derive(Deserialize, Debug, Clone)]
+///
+/// On `SIGHUP` the file is read again and the hot-reloadable fields are applied
+/// without remounting (see `RemoteFS::reload_config`).
+///
+/// Serialized back to TOML by `default_config_template` (`client init-config`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Config {
-    /// The URL of the remote filesystem server.
+    /// The URL of the remote filesystem server (`http://` or `https://`, optionally
+    /// with a path). Stored without trailing `/`, see `normalize_server_url`.
     pub server_url: String,
+    /// Directory of the server mounted as the root (e.g. `projects/alice`); empty = the
+    /// whole data directory. Stored without leading/trailing `/`.
+    ///
+    /// Every path sent to the server starts with it, so the client never sees
+    /// anything outside this subtree.
+    #[serde(default)]
+    pub root_prefix: String,
     /// The strategy to use for the internal attribute cache.
     pub cache_strategy: CacheStrategy,
     /// Time-to-live in seconds for entries in the `Ttl` cache.
     pub cache_ttl_seconds: u64,
+    /// Time-to-live in seconds of the `Ttl` entries of directories, which change
+    /// far less often than file content. `None` = `cache_ttl_seconds`.
+    #[serde(default)]
+    pub dir_cache_ttl_seconds: Option<u64>,
+    /// Time-to-live in seconds of the `Ttl` entries of files. `None` = `cache_ttl_seconds`.
+    #[serde(default)]
+    pub file_cache_ttl_seconds: Option<u64>,
+    /// Random spread of each `Ttl` entry's lifetime, as a fraction of `cache_ttl_seconds`
+    /// (`0.1` = ±10%, `0` = every entry lives exactly the TTL).
+    ///
+    /// Entries cached together (e.g. by one `ls -l`) then expire at different
+    /// times instead of being revalidated all at once. Clamped to `0..=1`.
+    #[serde(default = "default_cache_ttl_jitter")]
+    pub cache_ttl_jitter: f64,
     /// The maximum number of entries for the `Lru` cache.
     pub cache_lru_capacity: usize,
     #[serde(default)] // Se manca nel TOML, usa il valore di default (false)
     pub daemon: bool,
+    /// Block size (in bytes) reported as `blksize` and used as the unit of ranged reads.
+    ///
+    /// Larger blocks reduce the number of round trips on sequential reads,
+    /// at the cost of higher latency on small, scattered reads.
+    #[serde(default = "default_transfer_block_size")]
+    pub transfer_block_size: u32,
+    /// Interval in seconds between WebSocket `Ping` frames sent to the server.
+    #[serde(default = "default_keepalive_interval_seconds")]
+    pub keepalive_interval_seconds: u64,
+    /// Seconds without any frame from the server (e.g. a `Pong`) after which
+    /// the watcher connection is considered dead and is re-established.
+    ///
+    /// Always longer than `keepalive_interval_seconds` (see `check_keepalive`):
+    /// it is checked at each ping, about one interval after the last `Pong`.
+    #[serde(default = "default_keepalive_timeout_seconds")]
+    pub keepalive_timeout_seconds: u64,
+    /// Seconds after which a WebSocket connection attempt to the watcher is
+    /// abandoned and retried like any other failed attempt.
+    ///
+    /// Without it, a host that drops the packets can stall the reconnection loop
+    /// for as long as the OS TCP timeout (minutes).
+    #[serde(default = "default_ws_connect_timeout_seconds")]
+    pub ws_connect_timeout_seconds: u64,
+    /// Interval in seconds between `GET /health` polls of the server (`0` disables them).
+    ///
+    /// When the server becomes reachable again after a failed poll, every
+    /// cached attribute is dropped, since changes may have been missed.
+    #[serde(default = "default_health_poll_seconds")]
+    pub health_poll_seconds: u64,
+    /// Number of worker threads of the Tokio runtime that runs the HTTP requests
+    /// (`0` = one per CPU core, Tokio's default).
+    #[serde(default)]
+    pub runtime_worker_threads: usize,
+    /// Maximum number of HTTP requests to the server in flight at the same time
+    /// (`0` = unlimited). Also the number of idle connections kept open for reuse.
+    ///
+    /// A low value protects the server from bursts (e.g. recursive operations),
+    /// a high one lets independent requests overlap on high-latency links.
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Whether full-file downloads are verified against the server's `X-Content-SHA256`.
+    ///
+    /// Detects corrupted or truncated transfers, at the cost of hashing every download.
+    #[serde(default = "default_verify_checksums")]
+    pub verify_checksums: bool,
+    /// Files larger than this (in bytes) are uploaded in chunks through a resumable session.
+    #[serde(default = "default_chunked_upload_threshold")]
+    pub chunked_upload_threshold: u64,
+    /// Files at least this large (in bytes) are downloaded to a temporary file instead of
+    /// memory when they must be rewritten whole (the upload of buffered writes), and are
+    /// then uploaded in chunks read from it. `0` = always in memory.
+    #[serde(default = "default_spill_threshold_bytes")]
+    pub spill_threshold_bytes: u64,
+    /// Size in bytes of each chunk of a chunked upload.
+    #[serde(default = "default_upload_chunk_size")]
+    pub upload_chunk_size: u64,
+    /// Maximum size in bytes of a file written through the mount (`0` = unlimited).
+    ///
+    /// Writes past it fail with `EFBIG` without contacting the server, which
+    /// also keeps the in-memory write buffers bounded.
+    #[serde(default)]
+    pub max_file_size_bytes: u64,
+    /// Maximum number of inodes kept in the inode <-> path maps (`0` = unlimited).
+    ///
+    /// Past this limit the least recently used inodes without open handles are
+    /// forgotten and re-resolved on the next `lookup`.
+    #[serde(default = "default_max_tracked_inodes")]
+    pub max_tracked_inodes: usize,
+    /// File where the inode <-> path mappings are saved, so inode numbers stay
+    /// stable across remounts. Disabled if unset.
+    #[serde(default)]
+    pub inode_map_file: Option<String>,
+    /// Glob patterns of the files shown by the mount (e.g. `*.md`); empty = every file.
+    /// Directories are always shown. See `PathFilter`.
+    #[serde(default)]
+    pub include_globs: Vec<String>,
+    /// Glob patterns of the files and directories hidden from the mount (e.g. `.git`).
+    ///
+    /// Hidden entries cannot be looked up (`ENOENT`) nor created (`EPERM`).
+    #[serde(default)]
+    pub exclude_globs: Vec<String>,
+    /// Whether `ls` of the mount root shows the `.remotefs` control directory.
+    /// Its files (e.g. `.remotefs/flush`) are reachable by path either way. See `fs::control`.
+    #[serde(default)]
+    pub list_control_files: bool,
+    /// Whether `read`, `getattr` and `lookup` fall back to the data cached by the
+    /// client when the server cannot be reached (connection refused or timed out),
+    /// instead of failing with `EIO`/`ENOENT`. Writes still fail. See `RemoteFS::stale_blocks`.
+    #[serde(default)]
+    pub serve_stale_on_error: bool,
+    /// Seconds during which a request that cannot connect to the server is retried
+    /// with backoff instead of failing (`0` = fail at once). Counted from the start of
+    /// the outage and capped at `api_client::MAX_RETRY_WINDOW`, see `api_client::send`.
+    #[serde(default)]
+    pub server_retry_window_seconds: u64,
+    /// Number of times the final upload of a closed file is retried, with the backoff
+    /// of `api_client::backoff_delay`, before its writes are given up (`0` = no retries).
+    #[serde(default = "default_release_upload_retries")]
+    pub release_upload_retries: u32,
+    /// Directory where the buffered writes of a file are saved when its final upload
+    /// still fails after `release_upload_retries`, so they can be recovered by hand.
+    /// See `fs::write::save_unsynced`.
+    #[serde(default = "default_unsynced_dir")]
+    pub unsynced_dir: String,
+    /// FUSE operations taking at least this long (in milliseconds) are logged as `[SLOW]`,
+    /// with the time spent waiting for the lock and for the server (`0` = never).
+    /// See `fs::timing::OpTimer`.
+    #[serde(default = "default_slow_op_threshold_ms")]
+    pub slow_op_threshold_ms: u64,
+    /// Number of recently used paths whose attributes are fetched again, with
+    /// `/stat-batch`, when the server comes back after an outage or the watcher
+    /// reconnects (`0` = no warmup). See `fs::warmup`.
+    #[serde(default = "default_warmup_recent_paths")]
+    pub warmup_recent_paths: usize,
+    /// Product name put before the client's own in the `User-Agent` of every request
+    /// (e.g. `"backup-nightly"`), to tell the mounts apart in the server's logs.
+    /// The client version and id are always included, see `api_client::user_agent`.
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Whether every `write` is sent to the server right away (`POST /write` at its
+    /// offset) instead of being buffered until the descriptor is closed.
+    ///
+    /// Slower, but other clients see the data immediately and a crash of the
+    /// client loses nothing. Buffered mode (`false`) is the default.
+    #[serde(default)]
+    pub write_through: bool,
+    /// Owner reported for every entry of the mount, the root included, instead of
+    /// the server's `uid` (unset = the server's, or the mounting user's if it has none).
+    ///
+    /// With `default_permissions` the kernel checks access against this owner:
+    /// setting it to the local user avoids `EACCES` on files whose server ids do
+    /// not exist on this machine. The server is never asked to change owners.
+    #[serde(default)]
+    pub force_uid: Option<u32>,
+    /// Group reported for every entry of the mount, like `force_uid`.
+    #[serde(default)]
+    pub force_gid: Option<u32>,
+    /// FUSE mount options, with the names used by `mount.fuse` (e.g. `allow_other`,
+    /// `default_permissions`, `fsname=remote`). See `parse_mount_option`.
+    #[serde(default = "default_mount_options")]
+    pub mount_options: Vec<String>,
+    /// Path of the PID file written in daemon mode and read by `status`/`unmount`.
+    /// If unset, it is derived from the mountpoint (see `Config::daemon_paths`).
+    #[serde(default)]
+    pub pid_file: Option<String>,
+    /// File receiving the daemon's stdout. Derived from the mountpoint if unset.
+    #[serde(default)]
+    pub daemon_stdout: Option<String>,
+    /// File receiving the daemon's stderr. Derived from the mountpoint if unset.
+    #[serde(default)]
+    pub daemon_stderr: Option<String>,
+    /// Further mounts served by the same process (`[[mount]]` tables), see `Config::mount_configs`.
+    ///
+    /// Each table needs a `mountpoint` and may set any other field (e.g. `server_url`,
+    /// `root_prefix`, the cache settings); the fields it omits are taken from the top level.
+    #[serde(default, rename = "mount", skip_serializing_if = "Vec::is_empty")]
+    pub mounts: Vec<toml::Table>,
+}
+
+/// The files used by a daemonized client, with per-mountpoint defaults applied.
+#[derive(Debug, Clone)]
+pub struct DaemonPaths {
+    pub stdout: String,
+    pub stderr: String,
+    pub pid_file: String,
+}
+
+/// Default value for `Config::transfer_block_size`.
+fn default_transfer_block_size() -> u32 {
+    5120
+}
+
+/// Default value for `Config::keepalive_interval_seconds`.
+fn default_keepalive_interval_seconds() -> u64 {
+    15
+}
+
+/// Default value for `Config::keepalive_timeout_seconds`.
+fn default_keepalive_timeout_seconds() -> u64 {
+    45
+}
+
+/// Default value for `Config::ws_connect_timeout_seconds`.
+fn default_ws_connect_timeout_seconds() -> u64 {
+    10
+}
+
+/// Default value for `Config::health_poll_seconds`.
+fn default_health_poll_seconds() -> u64 {
+    10
+}
+
+/// Default value for `Config::max_concurrent_requests`.
+fn default_max_concurrent_requests() -> usize {
+    8
+}
+
+/// Default value for `Config::cache_ttl_jitter`.
+fn default_cache_ttl_jitter() -> f64 {
+    0.1
+}
+
+/// Default value for `Config::verify_checksums`.
+fn default_verify_checksums() -> bool {
+    true
+}
+
+/// Default value for `Config::spill_threshold_bytes` (64 MiB).
+fn default_spill_threshold_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+/// Default value for `Config::chunked_upload_threshold` (16 MiB).
+fn default_chunked_upload_threshold() -> u64 {
+    16 * 1024 * 1024
+}
+
+/// Default value for `Config::upload_chunk_size` (4 MiB).
+fn default_upload_chunk_size() -> u64 {
+    4 * 1024 * 1024
+}
+
+/// Default value for `Config::release_upload_retries`.
+fn default_release_upload_retries() -> u32 {
+    3
+}
+
+/// Default value for `Config::unsynced_dir` (kept across reboots, unlike `/tmp`).
+fn default_unsynced_dir() -> String {
+    "/var/tmp/remotefs-unsynced".to_string()
+}
+
+/// Default value for `Config::slow_op_threshold_ms`.
+fn default_slow_op_threshold_ms() -> u64 {
+    1000
+}
+
+/// Default value for `Config::warmup_recent_paths`.
+fn default_warmup_recent_paths() -> usize {
+    256
+}
+
+/// Default value for `Config::max_tracked_inodes`.
+fn default_max_tracked_inodes() -> usize {
+    100_000
+}
+
+/// Default value for `Config::mount_options` (the options the client always used).
+fn default_mount_options() -> Vec<String> {
+    vec!["auto_unmount".to_string(), "rw".to_string()]
+}
+
+/// Parses a single mount option, as written in `config.toml` or passed to `--mount-option`.
+///
+/// # Returns
+/// * `Ok(MountOption)` for a known option.
+/// * `Err(String)` with a readable message for an unknown one.
+pub fn parse_mount_option(option: &str) -> Result<MountOption, String> {
+    if let Some((key, value)) = option.split_once('=') {
+        return match key {
+            "fsname" => Ok(MountOption::FSName(value.to_string())),
+            "subtype" => Ok(MountOption::Subtype(value.to_string())),
+            _ => Err(format!("opzione di mount sconosciuta: '{}'", option)),
+        };
+    }
+    match option {
+        "allow_other" => Ok(MountOption::AllowOther),
+        "allow_root" => Ok(MountOption::AllowRoot),
+        "auto_unmount" => Ok(MountOption::AutoUnmount),
+        "default_permissions" => Ok(MountOption::DefaultPermissions),
+        "dev" => Ok(MountOption::Dev),
+        "nodev" => Ok(MountOption::NoDev),
+        "suid" => Ok(MountOption::Suid),
+        "nosuid" => Ok(MountOption::NoSuid),
+        "ro" => Ok(MountOption::RO),
+        "rw" => Ok(MountOption::RW),
+        "exec" => Ok(MountOption::Exec),
+        "noexec" => Ok(MountOption::NoExec),
+        "atime" => Ok(MountOption::Atime),
+        "noatime" => Ok(MountOption::NoAtime),
+        "dirsync" => Ok(MountOption::DirSync),
+        "sync" => Ok(MountOption::Sync),
+        "async" => Ok(MountOption::Async),
+        // Passed to fusermount as is: lets the mount hide the content of the mountpoint
+        "nonempty" => Ok(MountOption::CUSTOM("nonempty".to_string())),
+        _ => Err(format!("opzione di mount sconosciuta: '{}'", option)),
+    }
+}
+
+/// Builds the default path of a daemon file for `mountpoint`.
+///
+/// The absolute mountpoint is flattened into the file name
+/// (e.g. `/tmp/mnt` -> `/tmp/fuse_client-tmp_mnt.pid`), so that two daemons
+/// mounted on different directories never share logs or PID files.
+pub fn default_daemon_path(mountpoint: &str, extension: &str) -> String {
+    let name = mountpoint.trim_matches('/').replace('/', "_");
+    format!("/tmp/fuse_client-{}.{}", name, extension)
+}
+
+impl Config {
+    /// Time-to-live of a cached entry of a directory (`is_dir`) or a file: the specific
+    /// `dir_cache_ttl_seconds`/`file_cache_ttl_seconds`, or `cache_ttl_seconds` if unset.
+    pub fn cache_ttl(&self, is_dir: bool) -> Duration {
+        let specific = if is_dir { self.dir_cache_ttl_seconds } else { self.file_cache_ttl_seconds };
+        Duration::from_secs(specific.unwrap_or(self.cache_ttl_seconds))
+    }
+
+    /// Parses `mount_options` into the values passed to `fuser::Session::new`.
+    ///
+    /// `fsname=remoteFS` is added unless an `fsname` is configured.
+    ///
+    /// # Returns
+    /// The parsed options, or the message of the first unknown option.
+    pub fn fuse_mount_options(&self) -> Result<Vec<MountOption>, String> {
+        let mut options = self.mount_options.iter()
+            .map(|option| parse_mount_option(option))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !options.iter().any(|option| matches!(option, MountOption::FSName(_))) {
+            options.push(MountOption::FSName("remoteFS".to_string()));
+        }
+        Ok(options)
+    }
+
+    /// Resolves the daemon's stdout, stderr and PID file paths for `mountpoint`,
+    /// falling back to `default_daemon_path` for every path that is not configured.
+    pub fn daemon_paths(&self, mountpoint: &str) -> DaemonPaths {
+        let resolve = |configured: &Option<String>, extension: &str| {
+            configured.clone().unwrap_or_else(|| default_daemon_path(mountpoint, extension))
+        };
+        DaemonPaths {
+            stdout: resolve(&self.daemon_stdout, "out"),
+            stderr: resolve(&self.daemon_stderr, "err"),
+            pid_file: resolve(&self.pid_file, "pid"),
+        }
+    }
+
+    /// Resolves the `[[mount]]` tables into one configuration per mount: the top-level
+    /// fields, overridden by the ones set in the table.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<(String, Config)>)` with the mountpoint and configuration of each table,
+    ///   in file order (`mounts` is empty in every configuration).
+    /// * `Err(String)` if a table has no `mountpoint`, an invalid field or an invalid `server_url`.
+    pub fn mount_configs(&self) -> Result<Vec<(String, Config)>, String> {
+        let base = Config { mounts: Vec::new(), ..self.clone() };
+        let base = toml::Table::try_from(base).map_err(|e| e.to_string())?;
+        self.mounts.iter().enumerate().map(|(index, table)| {
+            let mut table = table.clone();
+            let Some(toml::Value::String(mountpoint)) = table.remove("mountpoint") else {
+                return Err(format!("[[mount]] n. {}: manca 'mountpoint'", index + 1));
+            };
+            let mut merged = base.clone();
+            merged.extend(table);
+            let mut config: Config = toml::Value::Table(merged).try_into()
+                .map_err(|e| format!("[[mount]] {}: {}", mountpoint, e))?;
+            config.mounts = Vec::new();
+            config.root_prefix = config.root_prefix.trim_matches('/').to_string();
+            config.check_keepalive();
+            config.server_url = normalize_server_url(&config.server_url)
+                .map_err(|e| format!("[[mount]] {}: {}", mountpoint, e))?;
+            Ok((mountpoint, config))
+        }).collect()
+    }
+
+    /// Raises `keepalive_timeout_seconds` to one second more than the (at least
+    /// one second) `keepalive_interval_seconds` if it is not longer, with a warning.
+    ///
+    /// The watcher checks the timeout at each ping, when about one interval has
+    /// passed since the last `Pong`: a shorter timeout (or `0`) would drop the
+    /// connection at every ping and reconnect in a loop.
+    pub fn check_keepalive(&mut self) {
+        let minimum = self.keepalive_interval_seconds.max(1) + 1;
+        if self.keepalive_timeout_seconds < minimum {
+            eprintln!(
+                "WARNING: keepalive_timeout_seconds ({}) is not longer than keepalive_interval_seconds ({}). Using {}.",
+                self.keepalive_timeout_seconds, self.keepalive_interval_seconds, minimum
+            );
+            self.keepalive_timeout_seconds = minimum;
+        }
+    }
 }
 
 /// Provides a sane default configuration.
@@ -41,39 +452,236 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             server_url: "http://localhost:8080".to_string(),
+            root_prefix: String::new(),
             cache_strategy: CacheStrategy::Ttl,
             cache_ttl_seconds: 60,
+            dir_cache_ttl_seconds: None,
+            file_cache_ttl_seconds: None,
+            cache_ttl_jitter: default_cache_ttl_jitter(),
             cache_lru_capacity: 1000,
             daemon: false,
+            transfer_block_size: default_transfer_block_size(),
+            keepalive_interval_seconds: default_keepalive_interval_seconds(),
+            keepalive_timeout_seconds: default_keepalive_timeout_seconds(),
+            ws_connect_timeout_seconds: default_ws_connect_timeout_seconds(),
+            health_poll_seconds: default_health_poll_seconds(),
+            runtime_worker_threads: 0,
+            max_concurrent_requests: default_max_concurrent_requests(),
+            verify_checksums: default_verify_checksums(),
+            chunked_upload_threshold: default_chunked_upload_threshold(),
+            spill_threshold_bytes: default_spill_threshold_bytes(),
+            upload_chunk_size: default_upload_chunk_size(),
+            max_file_size_bytes: 0,
+            max_tracked_inodes: default_max_tracked_inodes(),
+            inode_map_file: None,
+            include_globs: Vec::new(),
+            exclude_globs: Vec::new(),
+            list_control_files: false,
+            serve_stale_on_error: false,
+            server_retry_window_seconds: 0,
+            release_upload_retries: default_release_upload_retries(),
+            unsynced_dir: default_unsynced_dir(),
+            slow_op_threshold_ms: default_slow_op_threshold_ms(),
+            warmup_recent_paths: default_warmup_recent_paths(),
+            user_agent: None,
+            write_through: false,
+            force_uid: None,
+            force_gid: None,
+            mount_options: default_mount_options(),
+            pid_file: None,
+            daemon_stdout: None,
+            daemon_stderr: None,
+            mounts: Vec::new(),
         }
     }
 }
 
-/// Loads the filesystem configuration from `config.toml` in the current directory.
+/// The comment written above each field by `default_config_template`, in file order,
+/// with the example shown (commented out) for the optional fields unset by default.
+const TEMPLATE_FIELDS: &[(&str, &str, &str)] = &[
+    ("server_url", "URL del server (http:// o https://, eventualmente con un path).", ""),
+    ("root_prefix", "Directory del server montata come radice (es. \"projects/alice\"); vuota = tutto il server.", ""),
+    ("cache_strategy", "Strategia della cache degli attributi: \"ttl\", \"lru\" o \"none\".", ""),
+    ("cache_ttl_seconds", "Durata in secondi delle voci della cache \"ttl\".", ""),
+    ("dir_cache_ttl_seconds", "Durata in secondi delle voci delle directory (default: cache_ttl_seconds).", "300"),
+    ("file_cache_ttl_seconds", "Durata in secondi delle voci dei file (default: cache_ttl_seconds).", "30"),
+    ("cache_ttl_jitter", "Variazione casuale della durata delle voci, in frazione del TTL (0.1 = ±10%).", ""),
+    ("cache_lru_capacity", "Numero massimo di voci della cache \"lru\".", ""),
+    ("daemon", "Avvia il client come demone in background (come --daemon).", ""),
+    ("transfer_block_size", "Dimensione in byte del blocco di trasferimento (blksize e letture a range).", ""),
+    ("keepalive_interval_seconds", "Secondi tra i Ping WebSocket inviati al server.", ""),
+    ("keepalive_timeout_seconds", "Secondi senza frame dal server dopo cui la connessione del watcher è considerata persa.", ""),
+    ("ws_connect_timeout_seconds", "Secondi dopo cui un tentativo di connessione del watcher viene abbandonato.", ""),
+    ("health_poll_seconds", "Secondi tra i controlli GET /health del server (0 = disattivati).", ""),
+    ("runtime_worker_threads", "Thread del runtime Tokio per le richieste HTTP (0 = uno per core).", ""),
+    ("max_concurrent_requests", "Richieste HTTP contemporanee al server (0 = nessun limite).", ""),
+    ("verify_checksums", "Verifica i download completi con l'SHA-256 inviato dal server.", ""),
+    ("chunked_upload_threshold", "File più grandi di questa soglia (byte) vengono caricati a blocchi.", ""),
+    ("spill_threshold_bytes", "File da riscrivere almeno così grandi (byte) passano da un file temporaneo invece che dalla memoria (0 = sempre in memoria).", ""),
+    ("upload_chunk_size", "Dimensione in byte di ogni blocco di un caricamento a blocchi.", ""),
+    ("max_file_size_bytes", "Dimensione massima in byte di un file scritto dal mount (0 = nessun limite).", ""),
+    ("max_tracked_inodes", "Inode tenuti in memoria al massimo (0 = nessun limite).", ""),
+    ("inode_map_file", "File in cui salvare la mappa inode <-> path, per inode stabili tra un mount e l'altro.", "\"/var/tmp/remotefs-inodes.json\""),
+    ("include_globs", "Pattern dei file mostrati dal mount (es. [\"*.md\"]); vuoto = tutti.", ""),
+    ("exclude_globs", "Pattern dei file e delle directory nascosti dal mount (es. [\".git\"]).", ""),
+    ("list_control_files", "Mostra la directory di controllo .remotefs in `ls` della radice.", ""),
+    ("serve_stale_on_error", "Con il server irraggiungibile, usa i dati in cache invece di fallire.", ""),
+    ("server_retry_window_seconds", "Secondi in cui una richiesta senza connessione al server viene ritentata (0 = fallisce subito).", ""),
+    ("release_upload_retries", "Tentativi aggiuntivi del caricamento finale di un file chiuso prima di rinunciare (0 = nessuno).", ""),
+    ("unsynced_dir", "Directory in cui salvare le scritture di un file il cui caricamento finale è fallito.", ""),
+    ("slow_op_threshold_ms", "Operazioni FUSE più lente di così (ms) vengono segnalate nel log come [SLOW] (0 = mai).", ""),
+    ("warmup_recent_paths", "Path usati di recente i cui attributi vengono riscaricati quando il server torna raggiungibile (0 = nessuno).", ""),
+    ("user_agent", "Nome messo davanti a quello del client nello User-Agent delle richieste, per riconoscere il mount nei log del server.", "\"backup-nightly\""),
+    ("write_through", "Invia ogni write subito al server invece di bufferizzarla fino alla chiusura.", ""),
+    ("force_uid", "Proprietario mostrato per ogni voce del mount (default: quello del server).", "1000"),
+    ("force_gid", "Gruppo mostrato per ogni voce del mount (default: quello del server).", "1000"),
+    ("mount_options", "Opzioni di mount FUSE (es. \"allow_other\", \"default_permissions\").", ""),
+    ("pid_file", "File PID del demone (default: derivato dal mountpoint).", "\"/tmp/remotefs.pid\""),
+    ("daemon_stdout", "File dello stdout del demone (default: derivato dal mountpoint).", "\"/tmp/remotefs.out\""),
+    ("daemon_stderr", "File dello stderr del demone (default: derivato dal mountpoint).", "\"/tmp/remotefs.err\""),
+    ("mount", "Altri mount serviti dallo stesso processo, uno per tabella [[mount]] con il suo mountpoint; i campi omessi sono quelli qui sopra.", "[{ mountpoint = \"/mnt/bob\", root_prefix = \"projects/bob\" }]"),
+];
+
+/// Builds the `config.toml` written by `client init-config`: every field of
+/// `Config::default()`, serialized with serde, under a comment describing it.
 ///
-/// If `config.toml` is not found, cannot be read, or fails to parse,
-/// this function will print an error message to `stderr` and
-/// return `Config::default()`.
-pub fn load_config() -> Config {
-    let path = Path::new("config.toml");
-    if !path.exists() {
-        println!("WARNING: 'config.toml' not found. Using default configuration.");
-        return Config::default();
+/// Optional fields unset by default (`None`, which TOML cannot represent) are
+/// written commented out with an example value, so the file parses back to the defaults.
+pub fn default_config_template() -> String {
+    let table = toml::Table::try_from(Config::default()).expect("Config è sempre serializzabile in TOML");
+    let mut template = String::from(
+        "# Configurazione del client remoteFS, generata da `client init-config`.\n\
+         # I valori indicati sono quelli di default: basta modificare quelli che servono.\n",
+    );
+    for (key, comment, example) in TEMPLATE_FIELDS {
+        let line = match table.get(*key) {
+            Some(value) => format!("{} = {}", key, value),
+            None => format!("# {} = {}", key, example),
+        };
+        let _ = writeln!(template, "\n# {}\n{}", comment, line);
+    }
+    // Un campo aggiunto a Config senza commento compare comunque nel file
+    for (key, value) in table.iter().filter(|(key, _)| !TEMPLATE_FIELDS.iter().any(|(field, _, _)| field == key)) {
+        let _ = writeln!(template, "\n{} = {}", key, value);
     }
+    template
+}
 
-    let content = match fs::read_to_string(path) {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("ERROR: Failed to read 'config.toml': {}. Using default.", e);
-            return Config::default();
-        }
-    };
+/// Where `config.toml` is looked for when `--config` is not given, in order:
+/// the working directory, the directory of the binary, `$XDG_CONFIG_HOME/remotefs/`
+/// (`~/.config/remotefs/` if unset) and `/etc/remotefs/`.
+pub fn config_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Ok(dir) = std::env::current_dir() {
+        paths.push(dir.join("config.toml"));
+    }
+    if let Some(dir) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        paths.push(dir.join("config.toml"));
+    }
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    if let Some(dir) = config_home {
+        paths.push(dir.join("remotefs").join("config.toml"));
+    }
+    paths.push(PathBuf::from("/etc/remotefs/config.toml"));
+    paths
+}
 
-    match toml::from_str(&content) {
-        Ok(config) => config,
+/// Loads the filesystem configuration at startup, and logs the file it came from.
+///
+/// With `explicit` (the `--config` flag) only that file is read, and it must be
+/// valid. Otherwise the first existing file of `config_search_paths` is read: if
+/// none exists, or it cannot be read or parsed, an error is printed to `stderr`
+/// and `Config::default()` is used.
+///
+/// It must run before daemonizing, since the daemon changes its working directory to `/`.
+///
+/// # Returns
+/// * `Ok((config, path))` with the absolute path of the file read, or `None` for the defaults.
+/// * `Err(String)` if the file given with `--config` cannot be read or parsed.
+pub fn load_config(explicit: Option<&Path>) -> Result<(Config, Option<PathBuf>), String> {
+    if let Some(path) = explicit {
+        // Absolute, so that `SIGHUP` re-reads the same file after the `chdir`
+        let path = std::path::absolute(path).map_err(|e| format!("Invalid path '{}': {}", path.display(), e))?;
+        let config = read_config(&path)?;
+        println!("INFO: Configurazione letta da {} (--config)", path.display());
+        return Ok((config, Some(path)));
+    }
+
+    let Some(path) = config_search_paths().into_iter().find(|path| path.exists()) else {
+        println!("WARNING: 'config.toml' not found. Using default configuration.");
+        return Ok((Config::default(), None));
+    };
+    match read_config(&path) {
+        Ok(config) => {
+            println!("INFO: Configurazione letta da {}", path.display());
+            Ok((config, Some(path)))
+        }
         Err(e) => {
-            eprintln!("ERROR: Failed to parse 'config.toml': {}. Using default.", e);
-            Config::default()
+            eprintln!("ERROR: {}. Using default.", e);
+            Ok((Config::default(), Some(path)))
         }
     }
-}
\ No newline at end of file
+}
+
+/// Reads and parses the configuration file at `path`, without any fallback.
+///
+/// Used on `SIGHUP` (with the absolute path resolved at startup, since the
+/// daemon changes its working directory), where a broken file must leave the
+/// running configuration untouched instead of resetting it to the defaults.
+///
+/// # Returns
+/// * `Ok(Config)` with the parsed configuration.
+/// * `Err(String)` with a readable message if the file cannot be read or parsed.
+pub fn read_config(path: &Path) -> Result<Config, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+    let mut config: Config = toml::from_str(&content)
+        .map_err(|e| format!("Failed to parse '{}': {}", path.display(), e))?;
+    config.root_prefix = config.root_prefix.trim_matches('/').to_string();
+    config.check_keepalive();
+    // An invalid URL is reported by `main` at startup (and ignored on reload, like any `server_url` change)
+    if let Ok(server_url) = normalize_server_url(&config.server_url) {
+        config.server_url = server_url;
+    }
+    Ok(config)
+}
+
+/// Validates `server_url` and returns it in the form every request URL is built from.
+///
+/// The URL must use `http` or `https` and name a host; a path is allowed (e.g. a
+/// server behind a reverse proxy), a query or a fragment is not. Trailing slashes
+/// are removed, since endpoints are appended as `/<endpoint>`.
+///
+/// # Returns
+/// * `Ok(String)` with the normalized URL (e.g. `http://host:8080/fs/` -> `http://host:8080/fs`).
+/// * `Err(String)` with a readable message if the URL is malformed.
+pub fn normalize_server_url(server_url: &str) -> Result<String, String> {
+    let url = Url::parse(server_url.trim())
+        .map_err(|e| format!("server_url '{}' non valido: {}", server_url, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("server_url '{}': schema '{}' non supportato (usare http o https)", server_url, url.scheme()));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(format!("server_url '{}' non indica un host", server_url));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(format!("server_url '{}' non può contenere query o frammenti", server_url));
+    }
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// Builds the URL of the server's WebSocket endpoint (`/ws`) from `server_url`:
+/// `http` becomes `ws` and `https` becomes `wss`, host, port and path are kept.
+///
+/// # Returns
+/// The `/ws` URL, or the error of `normalize_server_url`.
+pub fn ws_url_from(server_url: &str) -> Result<Url, String> {
+    let base = normalize_server_url(server_url)?;
+    let mut url = Url::parse(&format!("{}/ws", base)).map_err(|e| e.to_string())?;
+    let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+    url.set_scheme(scheme).map_err(|_| format!("impossibile usare lo schema {} per {}", scheme, base))?;
+    Ok(url)
+}
@@ -0,0 +1,158 @@
+//! Helpers for running the client as a background daemon and talking to it.
+//!
+//! While mounted in daemon mode, the client periodically writes a small status
+//! file next to its PID file (`<pid_file>.status`). The `status` and `unmount`
+//! subcommands use the PID file to find the process and the status file to
+//! report on it, so no extra IPC channel is needed.
+
+use crate::fs::RemoteFS;
+use std::fs;
+use std::sync::{Arc, Mutex, PoisonError, mpsc::Sender};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::signal::unix::{signal, SignalKind};
+
+/// How often the daemon refreshes its status file.
+const STATUS_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Returns the path of the status file associated with `pid_file`.
+pub fn status_file_path(pid_file: &str) -> String {
+    format!("{}.status", pid_file)
+}
+
+/// Spawns a thread that rewrites the status file every `STATUS_REFRESH_INTERVAL`.
+///
+/// The file holds one `key=value` pair per line: for each of the `mounts` the
+/// mountpoint, whether the WebSocket watcher is connected and the number of
+/// pending writes, then the time of the last update (Unix seconds).
+pub fn spawn_status_writer(mounts: Vec<(String, Arc<Mutex<RemoteFS>>)>, pid_file: &str) {
+    let status_path = status_file_path(pid_file);
+    std::thread::spawn(move || loop {
+        let mut content = String::new();
+        for (mountpoint, fs_arc) in &mounts {
+            let (ws_connected, pending_writes) = {
+                let fs = fs_arc.lock().unwrap_or_else(PoisonError::into_inner);
+                (fs.ws_connected, fs.pending_writes())
+            };
+            content += &format!(
+                "mountpoint={}\nwebsocket={}\npending_writes={}\n",
+                mountpoint,
+                if ws_connected { "connected" } else { "disconnected" },
+                pending_writes,
+            );
+        }
+        let updated = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        content += &format!("updated={}\n", updated);
+        if let Err(e) = fs::write(&status_path, content) {
+            eprintln!("[DAEMON] Impossibile scrivere il file di stato {}: {}", status_path, e);
+        }
+        std::thread::sleep(STATUS_REFRESH_INTERVAL);
+    });
+}
+
+/// Spawns a thread that waits for `SIGTERM`/`SIGINT` and shuts the mount down cleanly.
+///
+/// The buffered writes of every mount are uploaded first, then the name of the signal
+/// is sent on `shutdown`: `main` wakes up and drops the `BackgroundSession`s, which
+/// unmount the filesystems. The signals are received on `rt`, the runtime shared by the mounts.
+pub fn spawn_shutdown_handler(mounts: Vec<Arc<Mutex<RemoteFS>>>, rt: tokio::runtime::Handle, shutdown: Sender<&'static str>) {
+    std::thread::spawn(move || {
+        let (mut sigterm, mut sigint) = rt.block_on(async {
+            (
+                signal(SignalKind::terminate()).expect("Impossibile registrare SIGTERM"),
+                signal(SignalKind::interrupt()).expect("Impossibile registrare SIGINT"),
+            )
+        });
+
+        let signal_name = rt.block_on(async {
+            tokio::select! {
+                _ = sigterm.recv() => "SIGTERM",
+                _ = sigint.recv() => "SIGINT",
+            }
+        });
+        println!("[DAEMON] Ricevuto {}: scarico le scritture in sospeso...", signal_name);
+
+        // Il lock va rilasciato prima dello smontaggio: il kernel invierà ancora richieste.
+        // Un mount andato in panic non deve impedire lo scarico degli altri.
+        let failed: usize = mounts.iter()
+            .map(|fs_arc| fs_arc.lock().unwrap_or_else(PoisonError::into_inner).flush_pending_writes())
+            .sum();
+        if failed > 0 {
+            eprintln!("[DAEMON] {} file non sono stati caricati sul server.", failed);
+        }
+        let _ = shutdown.send(signal_name);
+    });
+}
+
+/// Removes the PID file and the status file when the daemon exits.
+pub fn cleanup_files(pid_file: &str) {
+    let _ = fs::remove_file(pid_file);
+    let _ = fs::remove_file(status_file_path(pid_file));
+}
+
+/// Reads the PID stored in `pid_file`, if the file exists and is valid.
+fn read_pid(pid_file: &str) -> Option<libc::pid_t> {
+    fs::read_to_string(pid_file).ok()?.trim().parse().ok()
+}
+
+/// Checks whether a process with the given PID is alive (signal 0 only probes it).
+fn is_alive(pid: libc::pid_t) -> bool {
+    unsafe { libc::kill(pid, 0) == 0 }
+}
+
+/// Implements the `status` subcommand.
+///
+/// # Returns
+/// The process exit code: `0` if the daemon is running, `1` otherwise.
+pub fn print_status(pid_file: &str) -> i32 {
+    let Some(pid) = read_pid(pid_file) else {
+        println!("Demone non in esecuzione (file PID {} assente o non valido).", pid_file);
+        return 1;
+    };
+    if !is_alive(pid) {
+        println!("Demone non in esecuzione (PID {} in {} non attivo).", pid, pid_file);
+        return 1;
+    }
+    println!("Demone in esecuzione (PID {}).", pid);
+
+    let status_path = status_file_path(pid_file);
+    match fs::read_to_string(&status_path) {
+        Ok(content) => {
+            for line in content.lines() {
+                if let Some((key, value)) = line.split_once('=') {
+                    println!("  {}: {}", key, value);
+                }
+            }
+        }
+        Err(_) => println!("  Stato non disponibile ({} assente).", status_path),
+    }
+    0
+}
+
+/// Implements the `unmount` subcommand.
+///
+/// Sends `SIGTERM` to the daemon (handled by `spawn_shutdown_handler`) and waits
+/// for it to exit.
+///
+/// # Returns
+/// The process exit code: `0` if the daemon exited, `1` otherwise.
+pub fn request_unmount(pid_file: &str) -> i32 {
+    let Some(pid) = read_pid(pid_file).filter(|&pid| is_alive(pid)) else {
+        println!("Nessun demone in esecuzione per {}.", pid_file);
+        return 1;
+    };
+    if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+        eprintln!("Impossibile inviare SIGTERM al PID {}: {}", pid, std::io::Error::last_os_error());
+        return 1;
+    }
+
+    println!("Richiesta di smontaggio inviata al PID {}, attendo...", pid);
+    for _ in 0..100 {
+        if !is_alive(pid) {
+            println!("Filesystem smontato.");
+            return 0;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    eprintln!("Il demone (PID {}) è ancora attivo dopo 10 secondi: upload delle scritture in sospeso ancora in corso?", pid);
+    1
+}
@@ -0,0 +1,331 @@
+//! End-to-end tests: the whole client stack (FUSE, HTTP, storage) in one process.
+//!
+//! Each test starts the server in-process with `server::spawn`, over a
+//! `LocalFsBackend` on a temporary data directory, and mounts a `RemoteFS`
+//! against it with `fuser::spawn_mount2`. The file operations are real syscalls
+//! on the mountpoint, checked against what lands in the data directory.
+//!
+//! Mounting needs `/dev/fuse` (and usually root), so the tests are ignored by
+//! default: `cargo test -- --ignored` runs them. The tests of the operations'
+//! logic that need no kernel use `unmounted_fs` instead, and always run.
+
+use crate::config::Config;
+use crate::fs::{FsWrapper, RemoteFS};
+use fuser::MountOption;
+use server::handlers::AppState;
+use server::storage::LocalFsBackend;
+use std::fs;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A client mounted against a server of its own, unmounted when dropped.
+struct TestMount {
+    /// Root of the server's files.
+    data_dir: PathBuf,
+    mountpoint: PathBuf,
+    session: Option<fuser::BackgroundSession>,
+    /// Runs the server and the requests of the client; dropped after the session.
+    _runtime: Arc<tokio::runtime::Runtime>,
+}
+
+impl TestMount {
+    /// Starts the server over an empty data directory and mounts the client on it.
+    fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let base = std::env::temp_dir().join(format!("remote-fs-e2e-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)));
+        let data_dir = base.join("data");
+        let mountpoint = base.join("mnt");
+        fs::create_dir_all(&data_dir).expect("Failed to create the data directory");
+        fs::create_dir_all(&mountpoint).expect("Failed to create the mountpoint");
+
+        let runtime = Arc::new(tokio::runtime::Runtime::new().expect("Failed to start the runtime"));
+        let storage = Arc::new(LocalFsBackend::new(data_dir.to_string_lossy().to_string()));
+        // Un disco di test può avere poco spazio libero: /healthz non deve fallire per questo
+        let state = AppState { min_free_bytes: 0, ..AppState::new(storage) };
+        let addr = runtime.block_on(server::spawn(state)).expect("Failed to start the server");
+
+        let config = Config {
+            server_url: format!("http://{}", addr),
+            unsynced_dir: base.join("unsynced").to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        let fs_arc = Arc::new(Mutex::new(RemoteFS::new(config, runtime.clone())));
+        let options = [MountOption::FSName("remote-fs-e2e".to_string()), MountOption::RW];
+        let session = fuser::spawn_mount2(FsWrapper(fs_arc), &mountpoint, &options).expect("Failed to mount the filesystem");
+
+        TestMount { data_dir, mountpoint, session: Some(session), _runtime: runtime }
+    }
+
+    /// The path of `name` in the mount.
+    fn path(&self, name: &str) -> PathBuf {
+        self.mountpoint.join(name)
+    }
+
+    /// The path of `name` in the server's data directory.
+    fn server_path(&self, name: &str) -> PathBuf {
+        self.data_dir.join(name)
+    }
+}
+
+impl Drop for TestMount {
+    fn drop(&mut self) {
+        // Il drop della sessione smonta il filesystem, prima di cancellarne le directory
+        drop(self.session.take());
+        if let Some(base) = self.data_dir.parent() {
+            let _ = fs::remove_dir_all(base);
+        }
+    }
+}
+
+/// A `RemoteFS` against `router`, served in-process on a free port, without a
+/// mount: the tests call the operations on it directly, and need no `/dev/fuse`.
+///
+/// The server runs on the runtime of the returned `RemoteFS`, and stops with it.
+pub(crate) fn unmounted_fs(router: axum::Router) -> RemoteFS {
+    unmounted_fs_with(router, Config::default())
+}
+
+/// `unmounted_fs` with `config`, whose `server_url` and `unsynced_dir` are replaced.
+pub(crate) fn unmounted_fs_with(router: axum::Router, config: Config) -> RemoteFS {
+    let runtime = Arc::new(tokio::runtime::Runtime::new().expect("Failed to start the runtime"));
+    let addr = runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind the server");
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, router.into_make_service_with_connect_info::<std::net::SocketAddr>()).into_future());
+        addr
+    });
+    fs_for(&format!("http://{}", addr), config, runtime)
+}
+
+/// A `RemoteFS` with `config`, against the server at `server_url`.
+fn fs_for(server_url: &str, config: Config, runtime: Arc<tokio::runtime::Runtime>) -> RemoteFS {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let unsynced_dir = std::env::temp_dir().join(format!("remote-fs-unsynced-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)));
+    let config = Config {
+        server_url: server_url.to_string(),
+        unsynced_dir: unsynced_dir.to_string_lossy().to_string(),
+        ..config
+    };
+    RemoteFS::new(config, runtime)
+}
+
+/// Reads `path` as a string, panicking with the path on failure.
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| panic!("Failed to read {:?}: {}", path, e))
+}
+
+#[test]
+#[ignore = "mounts a FUSE filesystem: needs /dev/fuse"]
+fn test_create_write_and_read() {
+    let mount = TestMount::new();
+
+    fs::write(mount.path("hello.txt"), "Hello, world!").expect("Failed to write through the mount");
+
+    // Il close ha fatto il flush: il file è già sul server
+    assert_eq!(read(&mount.server_path("hello.txt")), "Hello, world!");
+    assert_eq!(read(&mount.path("hello.txt")), "Hello, world!");
+    assert_eq!(fs::metadata(mount.path("hello.txt")).unwrap().len(), 13);
+}
+
+#[test]
+#[ignore = "mounts a FUSE filesystem: needs /dev/fuse"]
+fn test_overwrite_and_append() {
+    let mount = TestMount::new();
+
+    fs::write(mount.path("log.txt"), "first\n").unwrap();
+    let mut file = fs::OpenOptions::new().append(true).open(mount.path("log.txt")).unwrap();
+    std::io::Write::write_all(&mut file, b"second\n").unwrap();
+    drop(file);
+    assert_eq!(read(&mount.server_path("log.txt")), "first\nsecond\n");
+
+    fs::write(mount.path("log.txt"), "replaced").unwrap();
+    assert_eq!(read(&mount.server_path("log.txt")), "replaced");
+    assert_eq!(read(&mount.path("log.txt")), "replaced");
+}
+
+/// Writes `data` at `offset` of the existing `path` without truncating it, in a
+/// full open -> write -> close cycle (the buffer is merged and uploaded on close).
+fn write_at(path: &Path, offset: u64, data: &[u8]) {
+    let file = fs::OpenOptions::new().write(true).open(path).unwrap_or_else(|e| panic!("Failed to open {:?}: {}", path, e));
+    file.write_all_at(data, offset).unwrap();
+}
+
+#[test]
+#[ignore = "mounts a FUSE filesystem: needs /dev/fuse"]
+fn test_offset_writes_merged_on_release() {
+    let mount = TestMount::new();
+    let path = mount.path("offsets.bin");
+    fs::write(&path, "AAAAAAAAAA").unwrap();
+
+    // Sovrascrittura all'offset 0: il resto del file scaricato dal server resta invariato
+    write_at(&path, 0, b"BB");
+    assert_eq!(fs::read(mount.server_path("offsets.bin")).unwrap(), b"BBAAAAAAAA");
+
+    // Scrittura oltre la fine: il buco viene riempito di zeri
+    write_at(&path, 15, b"CC");
+    assert_eq!(fs::read(mount.server_path("offsets.bin")).unwrap(), b"BBAAAAAAAA\0\0\0\0\0CC");
+
+    // Scrittura a cavallo tra i dati esistenti e il buco
+    write_at(&path, 8, b"DDDD");
+    let expected = b"BBAAAAAADDDD\0\0\0CC";
+    assert_eq!(fs::read(mount.server_path("offsets.bin")).unwrap(), expected);
+
+    // Più blocchi nello stesso handle, anche sovrapposti e fuori ordine: vince l'ultimo
+    let file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+    file.write_all_at(b"EEEE", 4).unwrap();
+    file.write_all_at(b"FF", 2).unwrap();
+    file.write_all_at(b"GGG", 5).unwrap();
+    drop(file);
+    let expected = b"BBFFEGGGDDDD\0\0\0CC";
+    assert_eq!(fs::read(mount.server_path("offsets.bin")).unwrap(), expected);
+
+    // Riletto dal mount, il contenuto unito è lo stesso
+    assert_eq!(fs::read(&path).unwrap(), expected);
+    assert_eq!(fs::metadata(&path).unwrap().len(), expected.len() as u64);
+}
+
+#[test]
+#[ignore = "mounts a FUSE filesystem: needs /dev/fuse"]
+fn test_mkdir_and_list() {
+    let mount = TestMount::new();
+
+    fs::create_dir(mount.path("dir")).unwrap();
+    fs::write(mount.path("dir/a.txt"), "a").unwrap();
+    fs::write(mount.path("dir/b.txt"), "b").unwrap();
+
+    assert!(mount.server_path("dir").is_dir());
+    let mut names: Vec<String> = fs::read_dir(mount.path("dir"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["a.txt", "b.txt"]);
+}
+
+#[test]
+#[ignore = "mounts a FUSE filesystem: needs /dev/fuse"]
+fn test_rename() {
+    let mount = TestMount::new();
+
+    fs::create_dir(mount.path("dest")).unwrap();
+    fs::write(mount.path("old.txt"), "content").unwrap();
+    fs::rename(mount.path("old.txt"), mount.path("dest/new.txt")).unwrap();
+
+    assert!(!mount.server_path("old.txt").exists());
+    assert_eq!(read(&mount.server_path("dest/new.txt")), "content");
+    assert!(!mount.path("old.txt").exists());
+    assert_eq!(read(&mount.path("dest/new.txt")), "content");
+}
+
+#[test]
+#[ignore = "mounts a FUSE filesystem: needs /dev/fuse"]
+fn test_delete() {
+    let mount = TestMount::new();
+
+    fs::write(mount.path("gone.txt"), "bye").unwrap();
+    fs::create_dir(mount.path("empty")).unwrap();
+    fs::remove_file(mount.path("gone.txt")).unwrap();
+    fs::remove_dir(mount.path("empty")).unwrap();
+
+    assert!(!mount.server_path("gone.txt").exists());
+    assert!(!mount.server_path("empty").exists());
+    assert_eq!(fs::metadata(mount.path("gone.txt")).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_retry_state_per_mount() {
+    let config = Config { server_retry_window_seconds: 1, ..Config::default() };
+    let storage = Arc::new(server::storage::InMemoryBackend::new());
+    storage.insert_file("up.txt", "up").unwrap();
+    let up = unmounted_fs_with(server::app(AppState { min_free_bytes: 0, ..AppState::new(storage) }), config.clone());
+
+    // Nessun server in ascolto su questa porta: il server del secondo mount è giù
+    let down_addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let down = fs_for(&format!("http://{}", down_addr), config, up.runtime.clone());
+    let stat = |fs: &RemoteFS, path: &str| fs.block_on(crate::api_client::stat_resource(&fs.client, path, &fs.config.server_url));
+
+    // Il poller di /health del secondo mount lo trova giù, e la finestra di retry passa
+    down.client.retry_state().set_server_reachable(false);
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+
+    // Le richieste riuscite del primo mount non fanno ripartire l'interruzione del secondo
+    assert!(stat(&up, "up.txt").unwrap().is_some());
+    let started = std::time::Instant::now();
+    assert!(stat(&down, "up.txt").is_err());
+    assert!(started.elapsed() < std::time::Duration::from_millis(500), "the request waited {:?}", started.elapsed());
+
+    // Né l'interruzione del secondo mount tocca il primo
+    assert!(stat(&up, "up.txt").unwrap().is_some());
+}
+
+#[test]
+fn test_retry_window_per_mount() {
+    let runtime = Arc::new(tokio::runtime::Runtime::new().expect("Failed to start the runtime"));
+    let down_addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let server_url = format!("http://{}", down_addr);
+    let no_retries = fs_for(&server_url, Config { server_retry_window_seconds: 0, ..Config::default() }, runtime.clone());
+    // Un secondo mount, con la sua finestra, creato dopo il primo
+    let retries = fs_for(&server_url, Config { server_retry_window_seconds: 1, ..Config::default() }, runtime);
+    let stat = |fs: &RemoteFS| fs.block_on(crate::api_client::stat_resource(&fs.client, "a.txt", &fs.config.server_url));
+
+    let started = std::time::Instant::now();
+    assert!(stat(&no_retries).is_err());
+    assert!(started.elapsed() < std::time::Duration::from_millis(500), "the request waited {:?}", started.elapsed());
+
+    let started = std::time::Instant::now();
+    assert!(stat(&retries).is_err());
+    assert!(started.elapsed() >= std::time::Duration::from_millis(500), "the request did not retry");
+}
+
+/// Conta le richieste arrivate al server e le rallenta, così quelle concorrenti si sovrappongono.
+async fn slow_and_counted(request: axum::extract::Request, next: axum::middleware::Next) -> axum::response::Response {
+    SLOW_REQUESTS.fetch_add(1, Ordering::SeqCst);
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    next.run(request).await
+}
+
+static SLOW_REQUESTS: AtomicU64 = AtomicU64::new(0);
+
+#[test]
+fn test_concurrent_identical_requests_share_one_call() {
+    let storage = Arc::new(server::storage::InMemoryBackend::new());
+    storage.insert_file("hot.txt", "hot").unwrap();
+    let router = server::app(AppState { min_free_bytes: 0, ..AppState::new(storage) })
+        .layer(axum::middleware::from_fn(slow_and_counted));
+    let fs = unmounted_fs_with(router, Config::default());
+    let (client, url) = (&fs.client, fs.config.server_url.as_str());
+    use crate::api_client::{get_files_if_modified, head_file_from_server};
+
+    // Quattro miss concorrenti dello stesso file: una sola HEAD al server, lo stesso risultato per tutti
+    let heads = fs.runtime.block_on(async {
+        tokio::join!(
+            head_file_from_server(client, "hot.txt", url),
+            head_file_from_server(client, "hot.txt", url),
+            head_file_from_server(client, "hot.txt", url),
+            head_file_from_server(client, "hot.txt", url),
+        )
+    });
+    assert_eq!(SLOW_REQUESTS.swap(0, Ordering::SeqCst), 1);
+    for head in [heads.0, heads.1, heads.2, heads.3] {
+        assert_eq!(head.unwrap().unwrap().size, 3);
+    }
+
+    // Lo stesso per i listing di lookup; path diversi restano richieste distinte
+    let (a, b, other) = fs.runtime.block_on(async {
+        tokio::join!(
+            get_files_if_modified(client, "", None, url),
+            get_files_if_modified(client, "", None, url),
+            head_file_from_server(client, "missing.txt", url),
+        )
+    });
+    assert_eq!(SLOW_REQUESTS.swap(0, Ordering::SeqCst), 2);
+    assert_eq!(a.unwrap().unwrap().entries.len(), 1);
+    assert_eq!(b.unwrap().unwrap().entries.len(), 1);
+    assert!(other.unwrap().is_none());
+
+    // Finita la richiesta, la successiva va di nuovo al server
+    assert!(fs.block_on(head_file_from_server(client, "hot.txt", url)).unwrap().is_some());
+    assert_eq!(SLOW_REQUESTS.load(Ordering::SeqCst), 1);
+}
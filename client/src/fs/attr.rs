@@ -4,11 +4,24 @@ use super::prelude::*;
 ///
 /// This is the central function for attribute management. It implements a
 /// "cache-miss" strategy:
-/// 1. Check if the Inode is the ROOT (1). If so, return static root attributes.
+/// 1. Check if the Inode is the ROOT (1). If so, return its attributes (see `root_attributes`).
 /// 2. Check if the attributes are in the `attribute_cache`. If so, return them.
-/// 3. On a cache miss, fetch the parent directory's listing from the server.
-/// 4. Find the matching entry in the list to build the `FileAttr`.
-/// 5. Store the new attributes in the cache before returning them.
+/// 3. On a cache miss, ask the server for the entry's metadata with a `HEAD`
+///    request (no content and no parent listing is transferred). If the server
+///    does not support it, fall back to listing the parent directory.
+/// 4. Build the `FileAttr` from the entry.
+/// 5. Apply any timestamps set locally via `setattr` (see `TimeOverride`).
+/// 6. Store the new attributes in the cache before returning them.
+/// 7. Apply the writes buffered by open handles (see `with_buffered_writes`);
+///    the cache keeps the server's values.
+///
+/// Every inode asked for is recorded in `fs.recent_paths`, for `warmup::warm_up`.
+///
+/// With `serve_stale_on_error`, if the server cannot be reached, the last cached
+/// attributes are returned even if expired (logged as `[STALE]`).
+///
+/// The `HEAD` goes through the client's `InFlight` registry: a concurrent miss of
+/// the same path waits for the request already sent instead of sending another.
 ///
 /// # Arguments
 /// * `fs` - A mutable reference to the `RemoteFS` state.
@@ -19,57 +32,188 @@ use super::prelude::*;
 /// * `None` if the Inode's path cannot be found or the file does not exist on the server.
 pub fn fetch_and_cache_attributes(fs: &mut RemoteFS, ino: u64) -> Option<FileAttr> {
     if ino == 1 {
-        return Some(ROOT_DIR_ATTR);
+        return Some(root_attributes(fs));
     }
+    if let Some(attr) = crate::fs::control::attr(fs, ino) {
+        return Some(attr);
+    }
+
+    fs.recent_paths.record(ino);
 
     // 1. Check cache
     if let Some(attr) = fs.attribute_cache.get(&ino) {
-        return Some(attr);
+        return Some(with_buffered_writes(fs, ino, attr));
     }
 
     // 2. Cache miss, contact server
-    let path = match fs.inode_to_path.get(&ino) {
-        Some(p) => p.clone(),
-        None => return None,
+    let path = fs.inodes.path(ino)?;
+    let entry = match fs.block_on(head_file_from_server(&fs.client, &path, &fs.config.server_url)) {
+        Ok(entry) => entry,
+        Err(e) if fs.config.serve_stale_on_error && e.is_connection_error() => {
+            let attr = fs.attribute_cache.get_stale(&ino);
+            if attr.is_some() {
+                println!("[STALE] Server irraggiungibile: attributi di '{}' serviti dalla cache", path);
+            }
+            return attr.map(|attr| with_buffered_writes(fs, ino, attr));
+        }
+        Err(_) => fetch_entry_from_parent(fs, &path),
     };
 
-    // We must list the parent to get metadata for the requested file
-    let (parent_path, file_name) = match path.rsplit_once('/') {
-        Some((p, f)) => (p.to_string(), f.to_string()),
-        None => ("".to_string(), path.clone()),
+    let entry = entry?;
+    let attrs = attr_from_entry(fs, ino, &entry);
+
+    // 3. Store new attributes in cache
+    let ttl = fs.config.cache_ttl(attrs.kind == FileType::Directory);
+    fs.attribute_cache.put(ino, attrs.clone(), ttl);
+
+    Some(with_buffered_writes(fs, ino, attrs))
+}
+
+/// Returns the attributes of the mount root: `root_attr`, with the times of the
+/// server directory (`/stat` of `root_prefix`).
+///
+/// They are cached like those of any other directory, so when an entry is created,
+/// removed or renamed in the root (which drops the cache entry) the next `getattr`
+/// reports the new `mtime`, as `make` and directory watchers expect. If the server
+/// cannot be asked, the fixed times of `root_attr` are returned, uncached.
+fn root_attributes(fs: &mut RemoteFS) -> FileAttr {
+    if let Some(attr) = fs.attribute_cache.get(&1) {
+        return attr;
+    }
+    let root = fs.root_attr();
+    let entry = match fs.block_on(stat_resource(&fs.client, &fs.config.root_prefix, &fs.config.server_url)) {
+        Ok(Some(entry)) => entry,
+        _ => return root,
     };
+    let mtime = UNIX_EPOCH + Duration::from_secs(entry.mtime.max(0) as u64);
+    let crtime = entry.btime.map_or(mtime, |btime| UNIX_EPOCH + Duration::from_secs(btime.max(0) as u64));
+    let attr = FileAttr { size: entry.size, atime: mtime, mtime, ctime: mtime, crtime, ..root };
+    let ttl = fs.config.cache_ttl(true);
+    fs.attribute_cache.put(1, attr, ttl);
+    attr
+}
 
-    let entries = match fs.runtime.block_on(get_files_from_server(&fs.client, &parent_path,  &fs.config.server_url)) {
-        Ok(list) => list,
-        Err(_) => return None,
+/// Applies the writes buffered for `ino` by open handles and not uploaded yet
+/// to the server's attributes, so `fstat` after a `write` sees them.
+///
+/// If the buffered data extends past the server's size, the size (and the
+/// block count) grows to its end. While anything is buffered, `mtime` is the time
+/// of the last write, unless a timestamp was set on the open file (e.g. `cp -p`).
+fn with_buffered_writes(fs: &RemoteFS, ino: u64, mut attrs: FileAttr) -> FileAttr {
+    let Some(open_file) = fs.open_files.get(&ino).filter(|f| !f.buffer.is_empty()) else {
+        return attrs;
     };
+    let buffered_end = open_file.buffered_end();
+    if buffered_end > attrs.size {
+        attrs.size = buffered_end;
+        attrs.blocks = attrs.blocks.max(buffered_end.div_ceil(512));
+    }
+    if open_file.times.mtime.is_none()
+        && let Some(last_write) = open_file.last_write
+    {
+        attrs.mtime = last_write;
+        attrs.ctime = last_write;
+    }
+    attrs
+}
 
-    if let Some(entry) = entries.into_iter().find(|e| e.name == file_name) {
-        let kind = if entry.kind.eq_ignore_ascii_case("dir") || entry.kind.eq_ignore_ascii_case("directory") { FileType::Directory } else { FileType::RegularFile };
-        let perm = u16::from_str_radix(&entry.perm, 8).unwrap_or(if kind == FileType::Directory { 0o755 } else { 0o644 });
-
-        let attrs = FileAttr {
-            ino, size: entry.size, blocks: (entry.size + 511) / 512,
-            atime: UNIX_EPOCH + Duration::from_secs(entry.mtime as u64),
-            mtime: UNIX_EPOCH + Duration::from_secs(entry.mtime as u64),
-            ctime: UNIX_EPOCH + Duration::from_secs(entry.mtime as u64),
-            crtime: UNIX_EPOCH, kind, perm,
-            nlink: if kind == FileType::Directory { 2 } else { 1 },
-            uid: 501, // Faked UID
-            gid: 20,  // Faked GID
-            rdev: 0, flags: 0, blksize: 5120,
-        };
+/// Caches the attributes of `ino` from metadata the server sent along with
+/// another response (e.g. the content fetched by `read`), saving a `HEAD` later.
+pub fn cache_entry_attributes(fs: &mut RemoteFS, ino: u64, entry: &RemoteEntry) {
+    let attrs = attr_from_entry(fs, ino, entry);
+    let ttl = fs.config.cache_ttl(entry.is_dir());
+    fs.attribute_cache.put(ino, attrs, ttl);
+}
+
+/// Builds the `FileAttr` of inode `ino` from its server metadata.
+///
+/// The creation time (`crtime`) is the server's `btime`, or the mtime if the
+/// server does not know it.
+///
+/// Also binds the server's inode number to `ino`, and applies the timestamps
+/// set locally via `setattr` (see `TimeOverride`), which take precedence over
+/// the server's.
+pub(crate) fn attr_from_entry(fs: &mut RemoteFS, ino: u64, entry: &RemoteEntry) -> FileAttr {
+    if let Some(server_ino) = entry.ino {
+        fs.inodes.bind_server_inode(ino, server_ino);
+    }
+    let kind = if entry.is_dir() { FileType::Directory } else { FileType::RegularFile };
+    let perm = u16::from_str_radix(&entry.perm, 8).unwrap_or(if kind == FileType::Directory { 0o755 } else { 0o644 });
+
+    let mut attrs = FileAttr {
+        // Real on-disk usage, so `du` is right for sparse files and directories
+        ino, size: entry.size, blocks: entry.blocks.unwrap_or(entry.size.div_ceil(512)),
+        atime: UNIX_EPOCH + Duration::from_secs(entry.mtime as u64),
+        mtime: UNIX_EPOCH + Duration::from_secs(entry.mtime as u64),
+        ctime: UNIX_EPOCH + Duration::from_secs(entry.mtime as u64),
+        crtime: UNIX_EPOCH + Duration::from_secs(entry.btime.unwrap_or(entry.mtime).max(0) as u64),
+        kind, perm,
+        nlink: if kind == FileType::Directory { 2 } else { 1 },
+        // Real owner from the server, so `default_permissions` checks are correct,
+        // unless the configuration forces one for the whole mount
+        uid: fs.config.force_uid.or(entry.uid).unwrap_or(fs.root_attr().uid),
+        gid: fs.config.force_gid.or(entry.gid).unwrap_or(fs.root_attr().gid),
+        rdev: 0, flags: 0, blksize: fs.config.transfer_block_size,
+    };
+
+    if let Some(times) = fs.time_overrides.get(&ino) {
+        if let Some(atime) = times.atime {
+            attrs.atime = atime;
+        }
+        if let Some(mtime) = times.mtime {
+            attrs.mtime = mtime;
+        }
+    }
+    attrs
+}
 
-        // 3. Store new attributes in cache
-        let ttl = Duration::from_secs(fs.config.cache_ttl_seconds);
-        fs.attribute_cache.put(ino, attrs.clone(), ttl);
+/// Maximum number of paths sent in one `/stat-batch` request (the server's limit).
+pub(crate) const STAT_BATCH_SIZE: usize = 1000;
+
+/// Fills the attribute cache for many inodes with `/stat-batch` requests.
+///
+/// Called by `readdir` with the entries it just returned, so the `getattr`
+/// that follows for each of them (e.g. `ls -l`) is served from the cache instead
+/// of one request per entry. Inodes that are already cached are skipped. This is
+/// only an optimization: if the server does not answer, nothing is cached and
+/// `getattr` fetches the attributes as usual.
+///
+/// # Arguments
+/// * `fs` - The mutable `RemoteFS` state.
+/// * `entries` - The inodes to cache, with their server-relative paths.
+pub fn prewarm_attributes(fs: &mut RemoteFS, entries: &[(u64, String)]) {
+    let missing: Vec<&(u64, String)> = entries.iter()
+        .filter(|(ino, _)| fs.attribute_cache.get(ino).is_none())
+        .collect();
 
-        Some(attrs)
-    } else {
-        None
+    for batch in missing.chunks(STAT_BATCH_SIZE) {
+        let paths: Vec<String> = batch.iter().map(|(_, path)| path.clone()).collect();
+        let stats = match fs.block_on(stat_batch(&fs.client, &paths, &fs.config.server_url)) {
+            Ok(stats) => stats,
+            Err(e) => {
+                println!("[CACHE] /stat-batch unavailable, attributes not prefetched: {:?}", e);
+                return;
+            }
+        };
+        for (&(ino, _), entry) in batch.iter().zip(stats) {
+            if let Some(entry) = entry {
+                let attrs = attr_from_entry(fs, *ino, &entry);
+                let ttl = fs.config.cache_ttl(entry.is_dir());
+                fs.attribute_cache.put(*ino, attrs, ttl);
+            }
+        }
     }
 }
 
+/// Finds the metadata of `path` in the listing of its parent directory.
+///
+/// Fallback of `fetch_and_cache_attributes` for servers without `HEAD /files`.
+fn fetch_entry_from_parent(fs: &RemoteFS, path: &str) -> Option<RemoteEntry> {
+    let (parent_path, file_name) = path.rsplit_once('/').unwrap_or(("", path));
+    let entries = fs.block_on(get_files_from_server(&fs.client, parent_path, &fs.config.server_url)).ok()?;
+    entries.into_iter().find(|e| e.name == file_name)
+}
+
 /// FUSE `getattr` implementation.
 ///
 /// This function is a simple wrapper around `fetch_and_cache_attributes`.
@@ -86,22 +230,39 @@ pub fn getattr(fs: &mut RemoteFS, _req: &Request, ino: u64, reply: ReplyAttr) {
 /// This function handles requests to change file attributes.
 /// Currently supported operations:
 /// - **`chmod` (mode):** Sends a `PATCH` request to the server with the new permission string.
-/// - **`truncate` (size):** Performs a "Read-Modify-Write" operation. It fetches the
-///   entire file, resizes it locally, and `PUT`s the entire new file back.
+/// - **`truncate` (size):** Asks the server to resize the file in place (`/truncate`),
+///   so no content is transferred whether the file shrinks or grows. Only on a
+///   server without the endpoint (`405`/`501`, or `404` for a file `/stat` finds) it
+///   falls back to a "Read-Modify-Write":
+///   it fetches the entire file, resizes it locally, and `PUT`s the entire new file back.
+///   Any other error is reported (`ENOENT` for a missing file, `EISDIR` for a
+///   directory, `EIO` otherwise) without touching the content.
+///   Writes still buffered by open handles are cut at the new size, so their
+///   later upload does not extend the file again.
+/// - **`utimensat` (atime/mtime):** Stores the new times in `fs.time_overrides`, so
+///   every later `getattr` in this session reports them, then tries to persist
+///   them via the server's `/utimes` endpoint. A failed sync is logged, not reported:
+///   the times are still kept locally.
 ///
-/// Unsupported operations (e.g., changing UID, GID, timestamps) are ignored.
+/// Unsupported operations (e.g., changing UID, GID) are ignored.
 ///
 /// After any successful operation, the attribute cache for the Inode is invalidated.
-pub fn setattr(fs: &mut RemoteFS, _req: &Request<'_>, ino: u64, mode: Option<u32>, _uid: Option<u32>, _gid: Option<u32>, size: Option<u64>, _atime: Option<TimeOrNow>, _mtime: Option<TimeOrNow>, _ctime: Option<SystemTime>, _fh: Option<u64>, _crtime: Option<SystemTime>, _chgtime: Option<SystemTime>, _bkuptime: Option<SystemTime>, _flags: Option<u32>, reply: ReplyAttr) {
+pub fn setattr(fs: &mut RemoteFS, _req: &Request<'_>, ino: u64, mode: Option<u32>, _uid: Option<u32>, _gid: Option<u32>, size: Option<u64>, atime: Option<TimeOrNow>, mtime: Option<TimeOrNow>, _ctime: Option<SystemTime>, _fh: Option<u64>, _crtime: Option<SystemTime>, _chgtime: Option<SystemTime>, _bkuptime: Option<SystemTime>, _flags: Option<u32>, reply: ReplyAttr) {
 
-    let path = match fs.inode_to_path.get(&ino) {
-        Some(p) => p.clone(),
+    // Control files ignore attribute changes (e.g. the `O_TRUNC` of `echo 1 > .remotefs/flush`)
+    if let Some(attr) = crate::fs::control::attr(fs, ino) {
+        reply.attr(&TTL, &attr);
+        return;
+    }
+
+    let path = match fs.inodes.path(ino) {
+        Some(p) => p,
         None => { reply.error(ENOENT); return; }
     };
 
     // --- Handle `chmod` (mode change) ---
     if let Some(new_mode) = mode {
-        let res = fs.runtime.block_on(update_permissions(&fs.client, &path, new_mode, &fs.config.server_url));
+        let res = fs.block_on(update_permissions(&fs.client, &path, new_mode, &fs.config.server_url));
         if res.is_err() {
             reply.error(EIO);
             return;
@@ -109,35 +270,195 @@ pub fn setattr(fs: &mut RemoteFS, _req: &Request<'_>, ino: u64, mode: Option<u32
     }
 
     // --- Handle `truncate` (size change) ---
-    // This is a "Read-Modify-Write" operation.
-    if let Some(new_size) = size {
-        let old_content = match fs.runtime.block_on(get_file_content_from_server(&fs.client, &path,  &fs.config.server_url)) {
-            Ok(c) => c,
-            Err(_) => "".into() // File might be new or empty
+    // Resized on the server; "Read-Modify-Write" only as a fallback.
+    if let Some(new_size) = size
+        && let Err(err) = truncate(fs, ino, &path, new_size)
+    {
+        reply.error(err);
+        return;
+    }
+
+    // --- Handle `utimensat` (atime/mtime change) ---
+    if atime.is_some() || mtime.is_some() {
+        let resolve = |t: TimeOrNow| match t {
+            TimeOrNow::SpecificTime(t) => t,
+            TimeOrNow::Now => SystemTime::now(),
         };
-        let mut bytes = old_content.to_vec();
-        bytes.resize(new_size as usize, 0); // Truncate or extend with zeros
-
-        // This is a potential bug: assumes file content is valid UTF-8.
-        // `bytes` should be PUT directly.
-        if let Ok(new_content_str) = String::from_utf8(bytes) {
-            if fs.runtime.block_on(put_file_content_to_server(&fs.client, &path, new_content_str.into(),  &fs.config.server_url)).is_err() {
-                reply.error(EIO);
-                return;
+        let atime = atime.map(resolve);
+        let mtime = mtime.map(resolve);
+
+        // Keep the new times locally first, so a cache refresh can't lose them
+        let times = fs.time_overrides.entry(ino).or_default();
+        if atime.is_some() {
+            times.atime = atime;
+        }
+        if mtime.is_some() {
+            times.mtime = mtime;
+        }
+
+        // A later upload of an open handle must not overwrite them (e.g. `cp -p`)
+        if let Some(open_file) = fs.open_files.get_mut(&ino) {
+            if atime.is_some() {
+                open_file.times.atime = atime;
             }
-        } else {
-            // This will fail for non-UTF8 files (e.g., images)
-            reply.error(EIO);
-            return;
+            if mtime.is_some() {
+                open_file.times.mtime = mtime;
+            }
+        }
+
+        // Then try to persist them on the server
+        match fs.block_on(update_times(&fs.client, &path, atime, mtime, &fs.config.server_url)) {
+            Ok(_) => {
+                // The server now reports this mtime itself
+                if mtime.is_some() {
+                    fs.drop_mtime_override(ino);
+                }
+            }
+            Err(e) => eprintln!("[FUSE CLIENT] Could not sync times of '{}' to the server: {:?}", path, e),
         }
     }
 
     // After changes, invalidate cache and fetch new attributes
     println!("[CACHE] INVALIDATE: Removing attributes for Inode {} due to setattr.", ino);
     fs.attribute_cache.remove(&ino);
+    fs.invalidate_read_block(ino);
 
     match fetch_and_cache_attributes(fs, ino) {
         Some(attr) => reply.attr(&TTL, &attr),
         None => reply.error(ENOENT),
     }
-}
\ No newline at end of file
+}
+
+/// Resizes the file `ino` at `path` to `new_size`, on the server with `/truncate`.
+///
+/// A server without the endpoint answers `405`/`501`, or `404` (the route is
+/// unknown): the file is then resized with `truncate_read_modify_write`. Since
+/// a `404` is also the answer for a missing file, the file is first looked up
+/// with `/stat`, and is never recreated.
+///
+/// # Returns
+/// * `Ok(())` on success; writes still buffered by open handles are cut at `new_size`.
+/// * `Err(EFBIG)` if `new_size` is over the size limit of the client or of the server.
+/// * `Err(ENOENT)` if the file does not exist on the server.
+/// * `Err(EISDIR)` if `path` is a directory.
+/// * `Err(EIO)` for any other failure.
+fn truncate(fs: &mut RemoteFS, ino: u64, path: &str, new_size: u64) -> Result<(), libc::c_int> {
+    if fs.exceeds_max_file_size(new_size) {
+        return Err(EFBIG);
+    }
+    match fs.block_on(truncate_on_server(&fs.client, path, new_size, &fs.config.server_url)) {
+        Ok(()) => {
+            fs.drop_mtime_override(ino);
+        }
+        // Only a server without the endpoint gets the (costly) read-modify-write
+        Err(e @ (ApiError::NotSupported | ApiError::Server(reqwest::StatusCode::METHOD_NOT_ALLOWED))) => {
+            eprintln!("[FUSE CLIENT] /truncate not available for '{}' ({:?}): falling back to read-modify-write", path, e);
+            truncate_read_modify_write(fs, ino, path, new_size)?;
+        }
+        // A 404 comes both for a missing file and from a server without the route
+        Err(ApiError::NotFound) => match fs.block_on(stat_resource(&fs.client, path, &fs.config.server_url)) {
+            Ok(Some(entry)) if entry.is_dir() => return Err(EISDIR),
+            Ok(Some(_)) => {
+                eprintln!("[FUSE CLIENT] /truncate not found on the server for existing '{}': falling back to read-modify-write", path);
+                truncate_read_modify_write(fs, ino, path, new_size)?;
+            }
+            Ok(None) => return Err(ENOENT),
+            Err(e) => {
+                eprintln!("[FUSE CLIENT] Could not stat '{}' after /truncate answered 404: {:?}", path, e);
+                return Err(EIO);
+            }
+        },
+        Err(e) => {
+            eprintln!("[FUSE CLIENT] /truncate failed for '{}': {:?}", path, e);
+            return Err(match e {
+                ApiError::FileTooLarge => EFBIG,
+                ApiError::BadRequest => EISDIR,
+                _ => EIO,
+            });
+        }
+    }
+
+    // Writes still buffered by open handles must not bring back the cut tail (`ftruncate`)
+    if let Some(open_file) = fs.open_files.get_mut(&ino) {
+        trim_buffered_writes(open_file, new_size);
+    }
+    Ok(())
+}
+
+/// Resizes `path` to `new_size` with a "Read-Modify-Write": the whole file is
+/// downloaded, truncated or extended with zeros, and uploaded back.
+///
+/// Fallback of `truncate` for servers without the `/truncate` endpoint.
+///
+/// # Returns
+/// * `Ok(())` on success.
+/// * `Err(ENOENT)` if the file no longer exists on the server: it is not recreated.
+/// * `Err(EIO)` if the content cannot be downloaded, is corrupted, or the upload fails.
+/// * `Err(EFBIG)` if the server refuses the new size.
+fn truncate_read_modify_write(fs: &mut RemoteFS, ino: u64, path: &str, new_size: u64) -> Result<(), libc::c_int> {
+    let old_content = match fs.block_on(get_file_content_from_server(&fs.client, path, fs.config.verify_checksums, &fs.config.server_url)) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("[FUSE CLIENT] Could not download '{}' to truncate it: {:?}", path, e);
+            return Err(if matches!(e, ApiError::NotFound) { ENOENT } else { EIO });
+        }
+    };
+    let mut bytes = old_content.to_vec();
+    bytes.resize(new_size as usize, 0); // Truncate or extend with zeros
+
+    if let Err(e) = fs.block_on(put_file_content_to_server(&fs.client, path, bytes.into(), None, None, &fs.config.server_url)) {
+        return Err(if matches!(e, ApiError::FileTooLarge) { EFBIG } else { EIO });
+    }
+    // The content changed: the server's new mtime is the right one
+    fs.drop_mtime_override(ino);
+    Ok(())
+}
+
+/// Drops the part of the buffered writes of `open_file` past `size`.
+fn trim_buffered_writes(open_file: &mut OpenWriteFile, size: u64) {
+    open_file.buffer.retain(|&offset, _| (offset as u64) < size);
+    for (&offset, data) in open_file.buffer.iter_mut() {
+        data.truncate((size - offset as u64) as usize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::end_to_end::unmounted_fs;
+    use axum::{extract::Request as HttpRequest, http::StatusCode, middleware::{self, Next}, response::{IntoResponse, Response}};
+    use server::handlers::AppState;
+    use server::storage::InMemoryBackend;
+    use std::sync::Arc;
+
+    /// Answers `/truncate` like a server that predates it: axum's `404` for an unknown route.
+    async fn without_truncate(request: HttpRequest, next: Next) -> Response {
+        if request.uri().path().starts_with("/truncate/") {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        next.run(request).await
+    }
+
+    #[test]
+    fn test_truncate_without_server_endpoint() {
+        let storage = Arc::new(InMemoryBackend::new());
+        storage.insert_file("a.txt", "Hello, world!").unwrap();
+        storage.insert_file("dir/b.txt", "b").unwrap();
+        let state = AppState { min_free_bytes: 0, ..AppState::new(storage.clone()) };
+        let mut fs = unmounted_fs(server::app(state).layer(middleware::from_fn(without_truncate)));
+        let ino = fs.inodes.get_or_insert("a.txt", None);
+
+        // The file exists: the 404 is the missing route, resized by read-modify-write
+        assert_eq!(truncate(&mut fs, ino, "a.txt", 5), Ok(()));
+        assert_eq!(fs.block_on(get_file_content_from_server(&fs.client, "a.txt", false, &fs.config.server_url)).unwrap(), "Hello");
+        assert_eq!(truncate(&mut fs, ino, "a.txt", 7), Ok(()));
+        assert_eq!(fs.block_on(get_file_content_from_server(&fs.client, "a.txt", false, &fs.config.server_url)).unwrap(), "Hello\0\0");
+
+        // A missing file is reported, not created; a directory is not a file
+        let missing = fs.inodes.get_or_insert("missing.txt", None);
+        assert_eq!(truncate(&mut fs, missing, "missing.txt", 3), Err(ENOENT));
+        assert!(fs.block_on(stat_resource(&fs.client, "missing.txt", &fs.config.server_url)).unwrap().is_none());
+        let dir = fs.inodes.get_or_insert("dir", None);
+        assert_eq!(truncate(&mut fs, dir, "dir", 0), Err(EISDIR));
+    }
+}
@@ -1,6 +1,8 @@
 use fuser::FileAttr;
 use lru::LruCache;
 use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
 use std::num::NonZeroUsize;
 use std::time::{Duration, Instant};
 use crate::config::{Config, CacheStrategy};
@@ -20,8 +22,9 @@ pub(crate) struct TtlEntry {
 /// behaviors (TTL, LRU, or no caching at all).
 #[derive(Debug)]
 pub enum AttributeCache {
-    /// A Time-to-Live cache. Entries expire after a set `Duration`.
-    Ttl(HashMap<u64, TtlEntry>),
+    /// A Time-to-Live cache. Entries expire after a set `Duration`, randomly
+    /// lengthened or shortened by up to `jitter` of it (see `jittered_ttl`).
+    Ttl { entries: HashMap<u64, TtlEntry>, jitter: f64 },
     /// A Least-Recently-Used cache with a fixed capacity.
     Lru(LruCache<u64, FileAttr>),
     /// Caching is disabled. All `get` calls will result in a miss.
@@ -33,10 +36,13 @@ impl AttributeCache {
     ///
     /// # Arguments
     /// * `config` - The filesystem's `Config` struct, which specifies the
-    ///   desired `CacheStrategy` and (if applicable) LRU capacity.
+    ///   desired `CacheStrategy` and (if applicable) TTL jitter or LRU capacity.
     pub fn new(config: &Config) -> Self {
         match config.cache_strategy {
-            CacheStrategy::Ttl => AttributeCache::Ttl(HashMap::new()),
+            CacheStrategy::Ttl => AttributeCache::Ttl {
+                entries: HashMap::new(),
+                jitter: config.cache_ttl_jitter.clamp(0.0, 1.0),
+            },
             CacheStrategy::Lru => {
                 let capacity = NonZeroUsize::new(config.cache_lru_capacity).unwrap_or(NonZeroUsize::new(1).unwrap());
                 AttributeCache::Lru(LruCache::new(capacity))
@@ -49,7 +55,7 @@ impl AttributeCache {
     ///
     /// This method respects the rules of the active cache strategy:
     /// - `Ttl`: Returns the attributes only if they exist and have not expired.
-    ///   Expired entries are automatically removed upon check.
+    ///   Expired entries are kept, so that `get_stale` can still return them.
     /// - `Lru`: Returns the attributes and marks the entry as recently used.
     /// - `None`: Always returns `None`.
     ///
@@ -61,16 +67,15 @@ impl AttributeCache {
     /// * `None` on a cache miss or if the entry is expired.
     pub fn get(&mut self, ino: &u64) -> Option<FileAttr> {
         match self {
-            AttributeCache::Ttl(cache) => {
-                if let Some(entry) = cache.get(ino) {
+            AttributeCache::Ttl { entries, .. } => {
+                if let Some(entry) = entries.get(ino) {
                     if entry.expiry > Instant::now() {
 
                         println!("[CACHE] HIT (TTL): Found attributes for inode {}", ino);
                         return Some(entry.attr.clone());
                     } else {
 
-                        println!("[CACHE] MISS (Expired TTL): Removing attributes for inode {}", ino);
-                        cache.remove(ino);
+                        println!("[CACHE] MISS (Expired TTL): Attributes for inode {} are stale", ino);
                     }
                 }
             }
@@ -86,29 +91,53 @@ impl AttributeCache {
         None
     }
 
+    /// Returns the cached `FileAttr` of `ino` even if it has expired.
+    ///
+    /// Used with `serve_stale_on_error` when the server cannot be reached. The
+    /// `Lru` entry is not marked as recently used.
+    pub fn get_stale(&self, ino: &u64) -> Option<FileAttr> {
+        match self {
+            AttributeCache::Ttl { entries, .. } => entries.get(ino).map(|entry| entry.attr),
+            AttributeCache::Lru(cache) => cache.peek(ino).copied(),
+            AttributeCache::None => None,
+        }
+    }
+
     /// Inserts or updates a `FileAttr` in the cache.
     ///
     /// # Arguments
     /// * `ino` - The Inode number to cache.
     /// * `attr` - The `FileAttr` to store.
-    /// * `ttl_duration` - The `Duration` this entry should remain valid (only used by the `Ttl` strategy).
+    /// * `ttl_duration` - The `Duration` this entry should remain valid (only used by
+    ///   the `Ttl` strategy, which applies its jitter to it).
     pub fn put(&mut self, ino: u64, attr: FileAttr, ttl_duration: Duration) {
-        println!("[CACHE] PUT: Inserting attributes for inode {}", ino);
         match self {
-            AttributeCache::Ttl(cache) => {
+            AttributeCache::Ttl { entries, jitter } => {
+                let ttl = jittered_ttl(ttl_duration, *jitter);
+                println!("[CACHE] PUT: Inserting attributes for inode {} (TTL {:.1}s)", ino, ttl.as_secs_f64());
                 let entry = TtlEntry {
                     attr,
-                    expiry: Instant::now() + ttl_duration,
+                    expiry: Instant::now() + ttl,
                 };
-                cache.insert(ino, entry);
+                entries.insert(ino, entry);
             }
             AttributeCache::Lru(cache) => {
+                println!("[CACHE] PUT: Inserting attributes for inode {}", ino);
                 cache.put(ino, attr);
             }
             AttributeCache::None => {}
         }
     }
 
+    /// Removes every entry, e.g. after the server was unreachable for a while.
+    pub fn clear(&mut self) {
+        match self {
+            AttributeCache::Ttl { entries, .. } => entries.clear(),
+            AttributeCache::Lru(cache) => cache.clear(),
+            AttributeCache::None => {}
+        }
+    }
+
     /// Manually removes (invalidates) an Inode from the cache.
     ///
     /// This is typically called after an operation that modifies the file
@@ -118,8 +147,8 @@ impl AttributeCache {
     /// * `ino` - The Inode number to remove.
     pub fn remove(&mut self, ino: &u64) {
         match self {
-            AttributeCache::Ttl(cache) => {
-                cache.remove(ino);
+            AttributeCache::Ttl { entries, .. } => {
+                entries.remove(ino);
             }
             AttributeCache::Lru(cache) => {
                 cache.pop(ino);
@@ -127,4 +156,16 @@ impl AttributeCache {
             AttributeCache::None => {}
         }
     }
-}
\ No newline at end of file
+}
+
+/// Returns `ttl` scaled by a random factor in `1 - jitter ..= 1 + jitter`.
+///
+/// The randomness comes from the per-instance keys of `RandomState`, which is
+/// plenty to spread expiry times and needs no extra dependency.
+fn jittered_ttl(ttl: Duration, jitter: f64) -> Duration {
+    if jitter <= 0.0 {
+        return ttl;
+    }
+    let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64; // 0.0..=1.0
+    ttl.mul_f64(1.0 + jitter * (2.0 * random - 1.0))
+}
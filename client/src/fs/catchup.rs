@@ -0,0 +1,80 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use fuser::Notifier;
+use super::RemoteFS;
+use super::notify::{self, KernelInvalidation};
+use super::warmup::warm_up;
+use crate::api_client::changes_since;
+
+/// Subtracted from the time the client went offline before asking the server:
+/// covers mtimes in whole seconds and a small skew between the two clocks.
+const CLOCK_SKEW_MARGIN: Duration = Duration::from_secs(10);
+
+/// Brings the caches up to date after a period without notifications from the
+/// server, then warms them up again (`warm_up`).
+///
+/// Asks `GET /changes-since` for the entries under `root_prefix` modified since
+/// `offline_since` (minus `CLOCK_SKEW_MARGIN`) and drops only those, and their
+/// parent directories, from the client's caches and, with `notifier`, from the
+/// kernel's, as a `CHANGE` notification would. A deleted entry is covered by
+/// its parent directory, whose mtime changed.
+///
+/// If the server cannot answer (an older server without the endpoint, an error)
+/// or the list is truncated, every cache is dropped instead
+/// (`RemoteFS::invalidate_all_caches`), as before this endpoint existed.
+/// The result relies on the client and server clocks roughly agreeing.
+///
+/// The `RemoteFS` lock is not held while the request runs.
+///
+/// # Arguments
+/// * `fs_arc` - The shared `RemoteFS` state.
+/// * `offline_since` - The last time the client was known to be in sync with the server.
+/// * `notifier` - To invalidate the kernel caches too, if available.
+/// * `reason` - Why the caches are updated, for the log.
+pub async fn catch_up(fs_arc: Arc<Mutex<RemoteFS>>, offline_since: SystemTime, notifier: Option<Arc<Notifier>>, reason: &str) {
+    let (client, base_url, root_prefix) = {
+        let fs = fs_arc.lock().unwrap();
+        (fs.client.clone(), fs.config.server_url.clone(), fs.config.root_prefix.clone())
+    };
+    let since = offline_since
+        .checked_sub(CLOCK_SKEW_MARGIN)
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs() as i64);
+
+    match changes_since(&client, since, &root_prefix, &base_url).await {
+        Ok(changes) if !changes.truncated => {
+            let mut kernel_invalidations: Vec<KernelInvalidation> = Vec::new();
+            {
+                let mut fs = fs_arc.lock().unwrap();
+                for change in &changes.results {
+                    if let Some(ino) = fs.inodes.inode(&change.path) {
+                        fs.attribute_cache.remove(&ino);
+                        fs.invalidate_read_block(ino);
+                    }
+                    let parent_path = Path::new(&change.path)
+                        .parent()
+                        .map_or(String::new(), |p| p.to_string_lossy().to_string());
+                    if let Some(parent_ino) = fs.inodes.inode(&parent_path) {
+                        fs.attribute_cache.remove(&parent_ino);
+                    }
+                    kernel_invalidations.extend(notify::invalidations_for_path(&fs, &change.path));
+                }
+            }
+            println!("[CATCHUP] {}: {} path modificati da {}, invalidati solo quelli.", reason, changes.results.len(), since);
+            if let Some(notifier) = &notifier {
+                notify::send(notifier, kernel_invalidations);
+            }
+        }
+        Ok(_) => {
+            println!("[CATCHUP] {}: troppe modifiche da {}, invalido tutte le cache.", reason, since);
+            fs_arc.lock().unwrap().invalidate_all_caches();
+        }
+        Err(e) => {
+            eprintln!("[CATCHUP] {}: /changes-since non disponibile ({:?}), invalido tutte le cache.", reason, e);
+            fs_arc.lock().unwrap().invalidate_all_caches();
+        }
+    }
+
+    warm_up(fs_arc, reason).await;
+}
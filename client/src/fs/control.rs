@@ -0,0 +1,172 @@
+use super::prelude::*;
+
+/// Name of the virtual control directory in the mount root.
+pub const CONTROL_DIR_NAME: &str = ".remotefs";
+/// Name of the control file that uploads every buffered write when written.
+const FLUSH_FILE_NAME: &str = "flush";
+/// Name of the read-only control file with the client's counters.
+const STATS_FILE_NAME: &str = "stats";
+/// Name of the control file that compares the caches with the server when written.
+const FSCK_FILE_NAME: &str = "fsck";
+/// Reserved inode of `.remotefs`. `InodeTable` counts up from 2 and never gets here.
+const CONTROL_DIR_INO: u64 = u64::MAX - 1;
+/// Reserved inode of `.remotefs/flush`.
+const FLUSH_FILE_INO: u64 = u64::MAX - 2;
+/// Reserved inode of `.remotefs/stats`.
+const STATS_FILE_INO: u64 = u64::MAX - 3;
+/// Reserved inode of `.remotefs/fsck`.
+const FSCK_FILE_INO: u64 = u64::MAX - 4;
+
+/// Whether `ino` is one of the virtual control inodes, which do not exist on the server.
+pub fn is_control_inode(ino: u64) -> bool {
+    ino == CONTROL_DIR_INO || ino == FLUSH_FILE_INO || ino == STATS_FILE_INO || ino == FSCK_FILE_INO
+}
+
+/// Returns the attributes of a control inode, or `None` for any other inode.
+///
+/// They belong to the mounting user, like the root. The size of a control file
+/// is the length of its current content (see `read`).
+pub fn attr(fs: &RemoteFS, ino: u64) -> Option<FileAttr> {
+    let root = fs.root_attr();
+    match ino {
+        CONTROL_DIR_INO => Some(FileAttr { ino, ..root }),
+        FLUSH_FILE_INO => Some(FileAttr {
+            ino,
+            kind: FileType::RegularFile,
+            perm: 0o644,
+            nlink: 1,
+            size: flush_status(fs).len() as u64,
+            ..root
+        }),
+        STATS_FILE_INO => Some(FileAttr {
+            ino,
+            kind: FileType::RegularFile,
+            perm: 0o444,
+            nlink: 1,
+            size: stats(fs).len() as u64,
+            ..root
+        }),
+        FSCK_FILE_INO => Some(FileAttr {
+            ino,
+            kind: FileType::RegularFile,
+            perm: 0o644,
+            nlink: 1,
+            size: fs.control_fsck_report.len() as u64,
+            ..root
+        }),
+        _ => None,
+    }
+}
+
+/// Resolves `name` in `parent` if it is a control entry (`.remotefs` in the
+/// mount root, `flush`, `stats` and `fsck` in `.remotefs`).
+pub fn lookup(fs: &RemoteFS, parent: u64, name: &OsStr) -> Option<FileAttr> {
+    match (parent, name.to_str()?) {
+        (1, CONTROL_DIR_NAME) => attr(fs, CONTROL_DIR_INO),
+        (CONTROL_DIR_INO, FLUSH_FILE_NAME) => attr(fs, FLUSH_FILE_INO),
+        (CONTROL_DIR_INO, STATS_FILE_NAME) => attr(fs, STATS_FILE_INO),
+        (CONTROL_DIR_INO, FSCK_FILE_NAME) => attr(fs, FSCK_FILE_INO),
+        _ => None,
+    }
+}
+
+/// Whether `readdir` of `ino` lists `.remotefs`: only the root, and only with
+/// `list_control_files` enabled.
+pub fn listed_in(fs: &RemoteFS, ino: u64) -> bool {
+    ino == 1 && fs.config.list_control_files
+}
+
+/// Adds `.remotefs` to a `readdir` reply of the root, at offset `index`.
+///
+/// # Returns
+/// `true` if the reply buffer is full.
+pub fn add_control_dir(reply: &mut ReplyDirectory, index: i64) -> bool {
+    reply.add(CONTROL_DIR_INO, index, FileType::Directory, CONTROL_DIR_NAME)
+}
+
+/// Handles `readdir` of `.remotefs`, which contains `flush`, `stats` and `fsck`.
+pub fn readdir(offset: i64, mut reply: ReplyDirectory) {
+    let entries = [
+        (CONTROL_DIR_INO, FileType::Directory, "."),
+        (1, FileType::Directory, ".."),
+        (FLUSH_FILE_INO, FileType::RegularFile, FLUSH_FILE_NAME),
+        (STATS_FILE_INO, FileType::RegularFile, STATS_FILE_NAME),
+        (FSCK_FILE_INO, FileType::RegularFile, FSCK_FILE_NAME),
+    ];
+    for (i, (ino, kind, name)) in entries.into_iter().enumerate().skip(offset.max(0) as usize) {
+        if reply.add(ino, i as i64 + 1, kind, name) {
+            break;
+        }
+    }
+    reply.ok();
+}
+
+/// Handles `read` of a control file: for `flush` the number of files uploaded by
+/// the last write to it, for `stats` the counters of `stats`, for `fsck` the
+/// report of the last check (empty before the first).
+pub fn read(fs: &RemoteFS, ino: u64, offset: i64, size: u32, reply: ReplyData) {
+    let status = match ino {
+        STATS_FILE_INO => stats(fs),
+        FSCK_FILE_INO => fs.control_fsck_report.clone(),
+        _ => flush_status(fs),
+    };
+    let start = (offset.max(0) as usize).min(status.len());
+    let end = start.saturating_add(size as usize).min(status.len());
+    reply.data(&status.as_bytes()[start..end]);
+}
+
+/// Handles `write` to a control file (e.g. `echo 1 > /mnt/.remotefs/flush`).
+///
+/// Whatever is written, every open file with buffered writes is uploaded now,
+/// keeping its handles open (`RemoteFS::sync_open_files`). The write succeeds
+/// if all the uploads did; otherwise it fails with `EIO` and the writes that
+/// could not be uploaded stay buffered. `stats` is read-only (`EACCES`).
+///
+/// A write to `fsck` compares the caches with the server (`fsck::run`) and keeps
+/// the report for the next `read`: writing `repair` also drops the entries found
+/// stale, anything else only reports them. It fails with `EIO` if the server
+/// cannot be reached.
+pub fn write(fs: &mut RemoteFS, ino: u64, data: &[u8], reply: ReplyWrite) {
+    match ino {
+        FLUSH_FILE_INO => {}
+        STATS_FILE_INO => { reply.error(EACCES); return; }
+        FSCK_FILE_INO => {
+            let repair = String::from_utf8_lossy(data).trim() == "repair";
+            match crate::fs::fsck::run(fs, repair) {
+                Ok(report) => {
+                    fs.control_fsck_report = report;
+                    reply.written(data.len() as u32);
+                }
+                Err(errno) => reply.error(errno),
+            }
+            return;
+        }
+        _ => { reply.error(EISDIR); return; }
+    }
+    let (uploaded, failed) = fs.sync_open_files();
+    fs.control_flushed = uploaded;
+    println!("[CONTROL] .remotefs/flush: {} file caricati, {} falliti", uploaded, failed);
+    if failed > 0 {
+        reply.error(EIO);
+    } else {
+        reply.written(data.len() as u32);
+    }
+}
+
+/// Content of `flush`: the count of the last flush, followed by a newline.
+fn flush_status(fs: &RemoteFS) -> String {
+    format!("{}\n", fs.control_flushed)
+}
+
+/// Content of `stats`: one `name: value` line per counter.
+///
+/// * `unsynced_files`: files whose final upload failed and whose writes are waiting
+///   in `unsynced_dir` (see `write::save_unsynced`) to be recovered.
+/// * `slow_ops`: operations slower than `slow_op_threshold_ms` since the mount.
+fn stats(fs: &RemoteFS) -> String {
+    format!(
+        "unsynced_files: {}\nslow_ops: {}\n",
+        crate::fs::write::count_unsynced(&fs.config.unsynced_dir),
+        fs.slow_ops,
+    )
+}
@@ -2,92 +2,136 @@ use super::prelude::*;
 
 /// Handles the FUSE `create` operation (e.g., `touch file.txt` or `> file.txt`).
 ///
-/// This function performs two main tasks:
-/// 1. It immediately contacts the server via `PUT` to create an empty file.
-/// 2. It sets up the in-memory write cache (`OpenWriteFile`) for this new file.
+/// This function performs three main tasks:
+/// 1. It immediately creates the empty file on the server with `POST /touch`,
+///    without uploading any content: the data written afterwards is uploaded
+///    once, on `flush`/`release`.
+/// 2. It fetches the file's attributes back from the server, so the kernel
+///    sees the mode and timestamps the server actually applied.
+/// 3. It sets up the in-memory write cache (`OpenWriteFile`) for this new file.
+///
+/// If the file already exists on the server (e.g. created by another client
+/// since the last `lookup`), `O_EXCL` fails with `EEXIST`; otherwise the file
+/// is opened as it is, and emptied first if `O_TRUNC` was given.
+///
+/// If the file disappears between the `PUT` and the attribute fetch (e.g., it
+/// was deleted by another client), the new inode is discarded and `ENOENT` is returned.
+///
+/// A name hidden by `include_globs`/`exclude_globs` cannot be created (`EPERM`).
+/// A parent known to be a file fails with `ENOTDIR`, a name known to be a
+/// directory with `EISDIR`.
 ///
 /// A new file handle (`fh`) is generated and associated with the in-memory cache.
 /// This `fh` is returned to the kernel, which will use it for subsequent `write` calls.
 ///
 /// # Arguments
 /// * `fs` - The mutable `RemoteFS` state.
-/// * `req` - The FUSE request (used to get UID/GID for the new attributes).
 /// * `parent` - The inode of the parent directory.
 /// * `name` - The name of the file to create.
 /// * `reply` - The reply object to send the `fh` and attributes back to the kernel.
 pub fn create(
     fs: &mut RemoteFS,
-    req: &Request<'_>,
+    _req: &Request<'_>,
     parent: u64,
     name: &OsStr,
-    mode: u32,
+    _mode: u32,
     _umask: u32,
-    _flags: i32,
+    flags: i32,
     reply: ReplyCreate,
 ) {
-    let parent_path = match fs.inode_to_path.get(&parent) {
-        Some(p) => p.clone(),
+    let parent_path = match fs.inodes.path(parent) {
+        Some(p) => p,
         None => {
             reply.error(ENOENT);
             return;
         }
     };
-    let filename = name.to_str().unwrap();
-    let full_path = if parent_path.is_empty() {
-        filename.to_string()
-    } else {
-        format!("{}/{}", parent_path, filename)
+    if let Err(e) = fs.inodes.expect_kind(parent, true).and_then(|_| fs.check_writable()) {
+        reply.error(e);
+        return;
+    }
+    let filename = match utf8_name(name, "create") {
+        Ok(name) => name,
+        Err(e) => {
+            reply.error(e);
+            return;
+        }
     };
-
-    // 1. Create the empty file on the server immediately
-    if fs.runtime.block_on(put_file_content_to_server(&fs.client, &full_path, "".into(),  &fs.config.server_url)).is_err() {
-        reply.error(EIO);
+    let full_path = join_path(&parent_path, filename);
+    if fs.is_hidden(&full_path, false) {
+        reply.error(EPERM);
+        return;
+    }
+    // An existing directory with this name cannot be opened as a file
+    if let Some(existing) = fs.inodes.inode(&full_path)
+        && let Err(e) = fs.inodes.expect_kind(existing, false)
+    {
+        reply.error(e);
         return;
     }
 
-    // 2. Generate new identifiers
-    let inode = fs.next_inode;
-    fs.next_inode += 1;
-    let fh = fs.next_fh; // This is the handle for the write cache
-    fs.next_fh += 1;
-
-    // 3. Update internal maps
-    fs.inode_to_path.insert(inode, full_path.clone());
-    fs.path_to_inode.insert(full_path.clone(), inode);
-    fs.inode_to_type.insert(inode, FileType::RegularFile);
+    // 1. Create the empty file on the server immediately (no content upload)
+    match fs.block_on(touch_file(&fs.client, &full_path, &fs.config.server_url)) {
+        Ok(()) => {}
+        Err(ApiError::Conflict) => {
+            if flags & libc::O_EXCL != 0 {
+                reply.error(EEXIST);
+                return;
+            }
+            if flags & libc::O_TRUNC != 0 && fs.block_on(truncate_on_server(&fs.client, &full_path, 0, &fs.config.server_url)).is_err() {
+                reply.error(EIO);
+                return;
+            }
+        }
+        // Over the server's MAX_PATH_DEPTH / MAX_NAME_LENGTH
+        Err(ApiError::BadRequest) => {
+            reply.error(ENAMETOOLONG);
+            return;
+        }
+        Err(_) => {
+            reply.error(EIO);
+            return;
+        }
+    }
 
-    // 4. Create and store the in-memory write cache (buffer)
-    let open_file = OpenWriteFile {
-        path: full_path,
-        buffer: HashMap::new(),
-    };
-    fs.open_files.insert(fh, open_file);
+    // 2. Generate a new inode and update internal maps
+    let inode = fs.track_path(&full_path, None);
+    fs.inodes.set_kind(inode, FileType::RegularFile);
 
-    // 5. Create and cache stub attributes
-    let ts = SystemTime::now();
-    let attrs = FileAttr {
-        ino: inode, size: 0, blocks: 0, atime: ts, mtime: ts,
-        ctime: ts, crtime: ts, kind: FileType::RegularFile,
-        perm: mode as u16, nlink: 1, uid: req.uid(), gid: req.gid(), rdev: 0, flags: 0, blksize: 5120,
+    // 3. Fetch the real attributes (mode, size, mtime) as applied by the server
+    let attrs = match crate::fs::attr::fetch_and_cache_attributes(fs, inode) {
+        Some(attrs) => attrs,
+        None => {
+            // The file vanished between the PUT and the stat (e.g., deleted by another client)
+            fs.forget_inode(inode);
+            reply.error(ENOENT);
+            return;
+        }
     };
 
-    let ttl = Duration::from_secs(fs.config.cache_ttl_seconds);
-    
-    // CACHE IMMEDIATA: Salviamo il nuovo file
-    fs.attribute_cache.put(inode, attrs.clone(), ttl);
+    // 4. Create the in-memory write cache (buffer) and its handle
+    let fh = fs.open_write_handle(inode, full_path);
+    let open_flags = fs.register_direct_handle(fh, flags);
 
     // INVALIDAZIONE PADRE: La cartella contenitore è cambiata
     fs.attribute_cache.remove(&parent);
+    fs.save_inode_map();
 
-    // 6. Reply to the kernel with the new file handle (fh)
-    reply.created(&TTL, &attrs, 0, fh, 0);
+    // 5. Reply to the kernel with the new file handle (fh)
+    reply.created(&TTL, &attrs, 0, fh, open_flags);
 }
 
 /// Handles the FUSE `mkdir` operation (e.g., `mkdir my_dir`).
 ///
-/// This function contacts the server's `/mkdir` endpoint via a `POST` request.
+/// This function contacts the server's `/mkdir` endpoint via a `POST` request,
+/// without creating parents: if the directory already exists (e.g. created by
+/// another client), it fails with `EEXIST`.
 /// It then generates a new inode for the directory, updates the internal path mappings,
-/// and caches a set of locally-generated attributes.
+/// and fetches the attributes back from the server, so the kernel sees the real
+/// owner and mode (which `default_permissions` relies on).
+///
+/// A name hidden by `exclude_globs` cannot be created (`EPERM`), and a parent
+/// known to be a file fails with `ENOTDIR`.
 ///
 /// This operation does *not* use the `OpenWriteFile` cache, which is only for file I/O.
 ///
@@ -96,52 +140,67 @@ pub fn create(
 /// * `parent` - The inode of the parent directory.
 /// * `name` - The name of the directory to create.
 /// * `reply` - The reply object to send the new entry's attributes back.
-pub fn mkdir(fs: &mut RemoteFS, _req: &Request<'_>, parent: u64, name: &OsStr, mode: u32, _umask: u32, reply: ReplyEntry) {
-    let parent_path = match fs.inode_to_path.get(&parent) {
-        Some(p) => p.clone(),
+pub fn mkdir(fs: &mut RemoteFS, _req: &Request<'_>, parent: u64, name: &OsStr, _mode: u32, _umask: u32, reply: ReplyEntry) {
+    let parent_path = match fs.inodes.path(parent) {
+        Some(p) => p,
         None => {
             reply.error(ENOENT);
             return;
         }
     };
-    let dirname = name.to_str().unwrap();
-    let full_path = if parent_path.is_empty() {
-        dirname.to_string()
-    } else {
-        format!("{}/{}", parent_path, dirname)
+    if let Err(e) = fs.inodes.expect_kind(parent, true).and_then(|_| fs.check_writable()) {
+        reply.error(e);
+        return;
+    }
+    let dirname = match utf8_name(name, "mkdir") {
+        Ok(name) => name,
+        Err(e) => {
+            reply.error(e);
+            return;
+        }
     };
-
-    // Contact the server to create the directory
-    if fs.runtime.block_on(create_directory(&fs.client, &full_path, &fs.config.server_url)).is_err() {
-        reply.error(EIO);
+    let full_path = join_path(&parent_path, dirname);
+    if fs.is_hidden(&full_path, true) {
+        reply.error(EPERM);
         return;
     }
 
+    // Contact the server to create the directory. The kernel creates one level
+    // at a time (even for `mkdir -p`), so an existing directory is an error here
+    match fs.block_on(create_directory(&fs.client, &full_path, false, &fs.config.server_url)) {
+        Ok(()) => {}
+        Err(ApiError::Conflict) => {
+            reply.error(EEXIST);
+            return;
+        }
+        Err(ApiError::BadRequest) => {
+            reply.error(ENAMETOOLONG);
+            return;
+        }
+        Err(_) => {
+            reply.error(EIO);
+            return;
+        }
+    }
+
     // Generate new inode and update maps
-    let inode = fs.next_inode;
-    fs.next_inode += 1;
-    fs.inode_to_path.insert(inode, full_path.clone());
-    fs.path_to_inode.insert(full_path, inode);
-    fs.inode_to_type.insert(inode, FileType::Directory);
+    let inode = fs.track_path(&full_path, None);
+    fs.inodes.set_kind(inode, FileType::Directory);
 
-    // Create and cache stub attributes
-    let ts = SystemTime::now();
-    let attrs = FileAttr {
-        ino: inode, 
-        size: 4096, // CORRETTO: Dimensione standard directory Linux
-        blocks: 8,  // 4096 / 512 = 8 blocchi
-        atime: ts, mtime: ts,
-        ctime: ts, crtime: ts, kind: FileType::Directory,
-        perm: mode as u16, nlink: 2, uid: 501, gid: 20, rdev: 0, flags: 0, blksize: 5120,
+    // Fetch (and cache) the attributes as applied by the server
+    let attrs = match crate::fs::attr::fetch_and_cache_attributes(fs, inode) {
+        Some(attrs) => attrs,
+        None => {
+            // The directory vanished in the meantime (e.g., deleted by another client)
+            fs.forget_inode(inode);
+            reply.error(ENOENT);
+            return;
+        }
     };
 
-    let ttl = Duration::from_secs(fs.config.cache_ttl_seconds);
-    
-    // CACHE IMMEDIATA: Salviamo la nuova cartella con i dati corretti
-    fs.attribute_cache.put(inode, attrs.clone(), ttl);
-
     // INVALIDAZIONE PADRE: La cartella contenitore è cambiata
     fs.attribute_cache.remove(&parent);
+    fs.save_inode_map();
 
     // Reply with the new entry
     reply.entry(&TTL, &attrs, 0);
@@ -17,22 +17,24 @@ use super::prelude::*;
 /// * `name` - The name of the directory to remove.
 /// * `reply` - The reply object to send success or an error code.
 pub fn rmdir(fs: &mut RemoteFS, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
-    let parent_path = match fs.inode_to_path.get(&parent) {
-        Some(p) => p.clone(),
+    let parent_path = match fs.inodes.path(parent) {
+        Some(p) => p,
         None => {
             reply.error(ENOENT);
             return;
         }
     };
-    let dirname = name.to_str().unwrap();
-    let full_path = if parent_path.is_empty() {
-        dirname.to_string()
-    } else {
-        format!("{}/{}", parent_path, dirname)
+    let dirname = match utf8_name(name, "rmdir") {
+        Ok(name) => name,
+        Err(e) => {
+            reply.error(e);
+            return;
+        }
     };
+    let full_path = join_path(&parent_path, dirname);
 
     // Check if the directory is empty first
-    let entry_list = match fs.runtime.block_on(get_files_from_server(&fs.client, &full_path,  &fs.config.server_url)) {
+    let entry_list = match fs.block_on(get_files_from_server(&fs.client, &full_path,  &fs.config.server_url)) {
         Ok(list) => list,
         Err(_) => {
             reply.error(EIO);
@@ -57,7 +59,8 @@ pub fn rmdir(fs: &mut RemoteFS, req: &Request<'_>, parent: u64, name: &OsStr, re
 ///   remove all contents first.
 ///
 /// After a successful deletion, it removes the inode and path from all
-/// internal maps and invalidates the attribute cache.
+/// internal maps and invalidates the attribute cache, of the parent directory
+/// too: its `mtime` has changed.
 ///
 /// # Arguments
 /// * `fs` - The mutable `RemoteFS` state.
@@ -65,29 +68,31 @@ pub fn rmdir(fs: &mut RemoteFS, req: &Request<'_>, parent: u64, name: &OsStr, re
 /// * `name` - The name of the file or directory to remove.
 /// * `reply` - The reply object to send success or an error code.
 pub fn unlink(fs: &mut RemoteFS, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
-    let parent_path = match fs.inode_to_path.get(&parent) {
-        Some(p) => p.clone(),
+    let parent_path = match fs.inodes.path(parent) {
+        Some(p) => p,
         None => {
             reply.error(ENOENT);
             return;
         }
     };
-    let filename = name.to_str().unwrap();
-    let full_path = if parent_path.is_empty() {
-        filename.to_string()
-    } else {
-        format!("{}/{}", parent_path, filename)
+    let filename = match utf8_name(name, "unlink") {
+        Ok(name) => name,
+        Err(e) => {
+            reply.error(e);
+            return;
+        }
     };
+    let full_path = join_path(&parent_path, filename);
 
-    let inode = match fs.path_to_inode.get(&full_path) {
-        Some(&ino) => ino,
+    let inode = match fs.inodes.inode(&full_path) {
+        Some(ino) => ino,
         None => {
             reply.error(ENOENT);
             return;
         }
     };
 
-    let is_dir = fs.inode_to_type.get(&inode).copied() == Some(FileType::Directory);
+    let is_dir = fs.inodes.kind(inode) == Some(FileType::Directory);
 
     if is_dir {
         // Handle recursive deletion for directories
@@ -97,26 +102,30 @@ pub fn unlink(fs: &mut RemoteFS, _req: &Request<'_>, parent: u64, name: &OsStr,
         }
     } else {
         // Handle single file deletion
-        if fs.runtime.block_on(delete_resource(&fs.client, &full_path, &fs.config.server_url)).is_err() {
+        if fs.block_on(delete_resource(&fs.client, &full_path, &fs.config.server_url)).is_err() {
             reply.error(EIO);
             return;
         }
     }
 
     // On success, clean up all internal state
-    fs.attribute_cache.remove(&inode);
-    fs.path_to_inode.remove(&full_path);
-    fs.inode_to_path.remove(&inode);
-    fs.inode_to_type.remove(&inode);
+    fs.forget_inode(inode);
+    // INVALIDAZIONE PADRE: La cartella contenitore è cambiata
+    fs.attribute_cache.remove(&parent);
+    fs.save_inode_map();
 
     reply.ok();
 }
 
-/// A private helper function to recursively delete a directory's contents.
+/// A helper function to delete a directory together with all its contents.
 ///
 /// This is called by `unlink` when it receives a request to delete a directory.
-/// It lists all entries, deletes files, recurses into subdirectories, and
-/// *after* all children are deleted, it deletes the (now empty) directory itself.
+/// Instead of one `DELETE` per entry, it fetches the whole subtree with a single
+/// `GET /tree` (children listed before their parent) and removes everything,
+/// the directory itself last, with a single `POST /delete-batch`.
+///
+/// Paths that were deleted are dropped from the inode maps. Paths already gone
+/// on the server count as deleted.
 ///
 /// # Arguments
 /// * `fs` - The mutable `RemoteFS` state.
@@ -124,29 +133,55 @@ pub fn unlink(fs: &mut RemoteFS, _req: &Request<'_>, parent: u64, name: &OsStr,
 ///
 /// # Returns
 /// * `Ok(())` on success.
-/// * `Err(libc::c_int)` with an error code (e.g., `EIO`) on failure.
+/// * `Err(EBUSY)` if `path` is the root of the mount, which is never deleted
+///   (as `rmdir(2)` on a mount point).
+/// * `Err(libc::c_int)` on failure: `EACCES` or `ENOTEMPTY` if the server reported
+///   that for a path, `EIO` otherwise. The paths that couldn't be removed are logged.
 pub fn recursive_delete(fs: &mut RemoteFS, path: &str) -> Result<(), libc::c_int> {
-    let entry_list = match fs.runtime.block_on(get_files_from_server(&fs.client, path,  &fs.config.server_url)) {
-        Ok(list) => list,
-        Err(_) => return Err(libc::EIO),
+    if fs.is_root_path(path) {
+        eprintln!("[FUSE CLIENT] recursive_delete refused on the root of the mount");
+        return Err(EBUSY);
+    }
+
+    let tree = match fs.block_on(get_tree(&fs.client, path, &fs.config.server_url)) {
+        Ok(tree) => tree,
+        Err(_) => return Err(EIO),
     };
 
-    // Delete all children first
-    for entry in entry_list {
-        let full_path = format!("{}/{}", path, entry.name);
-        if entry.kind == "directory" {
-            recursive_delete(fs, &full_path)?;
-        } else {
-            if fs.runtime.block_on(delete_resource(&fs.client, &full_path, &fs.config.server_url)).is_err() {
-                return Err(libc::EIO);
+    let mut paths: Vec<String> = tree.into_iter().map(|entry| entry.path).collect();
+    paths.push(path.to_string());
+
+    let results = match fs.block_on(delete_batch(&fs.client, &paths, &fs.config.server_url)) {
+        Ok(results) => results,
+        Err(_) => return Err(EIO),
+    };
+
+    let mut failed: Vec<(String, String)> = Vec::new();
+    for result in results {
+        match result.error.as_deref() {
+            None | Some("not_found") => {
+                // Forget the descendants we knew about (the root is cleaned up by `unlink`)
+                if result.path != path
+                    && let Some(ino) = fs.inodes.inode(&result.path)
+                {
+                    fs.forget_inode(ino);
+                }
             }
+            Some(error) => failed.push((result.path, error.to_string())),
         }
     }
 
-    // After children are gone, delete the directory itself
-    if fs.runtime.block_on(delete_resource(&fs.client, path, &fs.config.server_url)).is_err() {
-        return Err(libc::EIO);
+    if failed.is_empty() {
+        return Ok(());
     }
-
-    Ok(())
-}
\ No newline at end of file
+    eprintln!("[FUSE CLIENT] recursive_delete of '{}': {} path(s) not removed: {:?}", path, failed.len(), failed);
+    // The first error that is not a consequence of another one decides the errno
+    let errno = failed.iter()
+        .find_map(|(_, error)| match error.as_str() {
+            "permission_denied" => Some(EACCES),
+            "io" => Some(EIO),
+            _ => None,
+        })
+        .unwrap_or(ENOTEMPTY);
+    Err(errno)
+}
@@ -0,0 +1,57 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use crate::config::Config;
+
+/// Decides which server entries are visible through the mount, from the
+/// `include_globs` and `exclude_globs` of the configuration.
+///
+/// A pattern matches an entry if it matches either its path relative to the
+/// mount root (`docs/a.md`) or just its name (`a.md`), so `.git` hides every
+/// `.git` directory while `docs/*.md` only applies under `docs`.
+///
+/// * An entry matching `exclude_globs` is hidden, whatever its kind.
+/// * If `include_globs` is not empty, a file must also match one of them.
+///   Directories are never hidden by `include_globs`, otherwise the files
+///   they contain could not be reached.
+#[derive(Debug)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl PathFilter {
+    /// Compiles the globs of `config`.
+    ///
+    /// # Returns
+    /// * `Ok(PathFilter)` with the compiled patterns.
+    /// * `Err(String)` with a readable message for the first invalid pattern.
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        Ok(Self {
+            include: build_glob_set(&config.include_globs)?,
+            exclude: build_glob_set(&config.exclude_globs)?,
+        })
+    }
+
+    /// Whether the entry at `path` (relative to the mount root) is hidden from the mount.
+    pub fn hides(&self, path: &str, is_dir: bool) -> bool {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let matches = |set: &GlobSet| set.is_match(path) || set.is_match(name);
+
+        if self.exclude.as_ref().is_some_and(matches) {
+            return true;
+        }
+        !is_dir && self.include.as_ref().is_some_and(|set| !matches(set))
+    }
+}
+
+/// Compiles `patterns` into a single `GlobSet`, or `None` if there are none.
+fn build_glob_set(patterns: &[String]) -> Result<Option<GlobSet>, String> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|e| format!("pattern glob non valido '{}': {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder.build().map(Some).map_err(|e| e.to_string())
+}
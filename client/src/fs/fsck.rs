@@ -0,0 +1,120 @@
+use std::fmt::Write as _;
+use super::prelude::*;
+use super::attr::{attr_from_entry, STAT_BATCH_SIZE};
+use super::notify::{self, KernelInvalidation};
+use crate::api_client::stat_batch;
+
+/// One difference between what the client holds for an inode and what the server reports.
+enum Discrepancy {
+    /// The path of the inode no longer exists on the server.
+    Dangling,
+    /// File on one side, directory on the other.
+    Kind { client: FileType, server: FileType },
+    /// The cached size differs from the server's.
+    Size { cached: u64, server: u64 },
+    /// The cached mtime differs from the server's (in whole seconds).
+    Mtime { cached: u64, server: u64 },
+}
+
+/// Compares the client's caches with the server, for `.remotefs/fsck` (see `control`).
+///
+/// Every inode the client tracks (the root and the control files excepted) is
+/// looked up again with `/stat-batch`, and compared with:
+/// * the inode table: the path must still exist, with the same kind (file or directory);
+/// * the attribute cache, if it holds the inode (expired entries included): size and mtime.
+///
+/// The server's metadata is turned into attributes as `getattr` would (timestamps
+/// set locally with `setattr` included), so only real differences are reported.
+/// Files with writes still buffered are skipped: their size is expected to differ.
+///
+/// With `repair`, each inode with a discrepancy has its cached attributes and read
+/// blocks dropped and its kind corrected, and the same entries are dropped from the
+/// kernel caches, as a `CHANGE` notification would. A dangling inode is forgotten
+/// unless a handle still holds it open. Nothing is ever written to the server.
+///
+/// # Returns
+/// The report: `name: value` lines (`checked`, `skipped`, `mismatches`, `repaired`),
+/// then one line per discrepancy. `Err(EIO)` if the server cannot be reached.
+pub fn run(fs: &mut RemoteFS, repair: bool) -> Result<String, i32> {
+    let tracked = fs.inodes.tracked();
+    let (client, base_url) = (fs.client.clone(), fs.config.server_url.clone());
+
+    let mut skipped = 0;
+    let mut checked = 0;
+    let mut found: Vec<(u64, String, Discrepancy)> = Vec::new();
+    for batch in tracked.chunks(STAT_BATCH_SIZE) {
+        let paths: Vec<String> = batch.iter().map(|(_, path)| path.clone()).collect();
+        let stats = match fs.block_on(stat_batch(&client, &paths, &base_url)) {
+            Ok(stats) => stats,
+            Err(e) => {
+                eprintln!("[FSCK] /stat-batch fallita, controllo interrotto: {:?}", e);
+                return Err(EIO);
+            }
+        };
+        for ((ino, path), entry) in batch.iter().zip(stats) {
+            if fs.open_files.get(ino).is_some_and(|f| !f.buffer.is_empty()) {
+                skipped += 1;
+                continue;
+            }
+            checked += 1;
+            let Some(entry) = entry else {
+                found.push((*ino, path.clone(), Discrepancy::Dangling));
+                continue;
+            };
+            let server = attr_from_entry(fs, *ino, &entry);
+            if let Some(client_kind) = fs.inodes.kind(*ino)
+                && client_kind != server.kind
+            {
+                found.push((*ino, path.clone(), Discrepancy::Kind { client: client_kind, server: server.kind }));
+                continue;
+            }
+            let Some(cached) = fs.attribute_cache.get_stale(ino) else { continue };
+            if cached.size != server.size {
+                found.push((*ino, path.clone(), Discrepancy::Size { cached: cached.size, server: server.size }));
+            }
+            let (cached_mtime, server_mtime) = (unix_seconds(cached.mtime), unix_seconds(server.mtime));
+            if cached_mtime != server_mtime {
+                found.push((*ino, path.clone(), Discrepancy::Mtime { cached: cached_mtime, server: server_mtime }));
+            }
+        }
+    }
+
+    let mut kernel_invalidations: Vec<KernelInvalidation> = Vec::new();
+    if repair {
+        for (ino, path, discrepancy) in &found {
+            kernel_invalidations.extend(notify::invalidations_for_path(fs, path));
+            match discrepancy {
+                // Come in `track_path`: un inode ancora aperto resta, ma senza cache
+                Discrepancy::Dangling if !fs.inodes.is_pinned(*ino) => fs.forget_inode(*ino),
+                Discrepancy::Kind { server, .. } => fs.inodes.set_kind(*ino, *server),
+                _ => {}
+            }
+            fs.attribute_cache.remove(ino);
+            fs.invalidate_read_block(*ino);
+        }
+        if let Some(notifier) = fs.kernel_notifier.clone() {
+            // Dopo la risposta: il kernel può aspettare la write in corso sul file di controllo
+            std::thread::spawn(move || notify::send(&notifier, kernel_invalidations));
+        }
+    }
+
+    let mut report = format!(
+        "checked: {}\nskipped: {}\nmismatches: {}\nrepaired: {}\n",
+        checked, skipped, found.len(), if repair { found.len() } else { 0 },
+    );
+    for (ino, path, discrepancy) in &found {
+        let _ = match discrepancy {
+            Discrepancy::Dangling => writeln!(report, "dangling {} (inode {}): non esiste più sul server", path, ino),
+            Discrepancy::Kind { client, server } => writeln!(report, "kind {} (inode {}): client {:?}, server {:?}", path, ino, client, server),
+            Discrepancy::Size { cached, server } => writeln!(report, "size {} (inode {}): cache {}, server {}", path, ino, cached, server),
+            Discrepancy::Mtime { cached, server } => writeln!(report, "mtime {} (inode {}): cache {}, server {}", path, ino, cached, server),
+        };
+    }
+    println!("[FSCK] {} inode controllati, {} saltati, {} discrepanze{}", checked, skipped, found.len(), if repair { " (riparate)" } else { "" });
+    Ok(report)
+}
+
+/// Seconds since the epoch of `time` (`0` before it), the precision of the server's mtimes.
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,388 @@
+use fuser::FileType;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+
+/// The root directory's inode number.
+const ROOT_INO: u64 = 1;
+
+/// One inode saved in the inode map file.
+#[derive(Serialize, Deserialize)]
+struct SavedInode {
+    ino: u64,
+    path: String,
+    #[serde(default)]
+    server_ino: Option<u64>,
+}
+
+/// Content of the inode map file (`Config::inode_map_file`).
+#[derive(Serialize, Deserialize)]
+struct InodeMapFile {
+    next_inode: u64,
+    inodes: Vec<SavedInode>,
+}
+
+/// The bidirectional inode <-> path mapping, bounded by an LRU policy.
+///
+/// The client never receives a reliable `forget` from every kernel, so on a
+/// long-running mount these maps would grow with every path ever looked up.
+/// When more than `max_tracked` inodes are known, the least recently used ones
+/// are evicted, except:
+/// - the root (inode 1), which is never evicted;
+/// - pinned inodes, i.e. files with at least one open handle (see `pin`).
+///
+/// An evicted path is simply re-resolved by the next `lookup` or `readdir` that
+/// reaches it. When the server reports its own inode number for the entry, the
+/// client inode is bound to it and reused for the whole session, so a file keeps
+/// its inode number across evictions (and remote renames).
+pub struct InodeTable {
+    /// Maps an Inode number (u64) to its full path string (e.g., 1 -> "").
+    inode_to_path: HashMap<u64, String>,
+    /// Maps a full path string to its Inode number (e.g., "" -> 1).
+    path_to_inode: HashMap<String, u64>,
+    /// Caches the `FileType` (File or Dir) for a known Inode.
+    inode_to_type: HashMap<u64, FileType>,
+    /// Usage order of every tracked inode except the root (most recent first).
+    recency: LruCache<u64, ()>,
+    /// Number of open file handles per inode: these inodes are never evicted.
+    open_handles: HashMap<u64, u32>,
+    /// Maps a server-side inode number to the client inode assigned to it.
+    /// Kept across evictions, dropped when the file is removed.
+    client_ino_of: HashMap<u64, u64>,
+    /// Reverse of `client_ino_of`.
+    server_ino_of: HashMap<u64, u64>,
+    /// Inodes loaded from the inode map file and not yet checked against the server.
+    unverified: HashSet<u64>,
+    /// A simple counter to generate new, unique Inode numbers.
+    next_inode: u64,
+    /// Maximum number of tracked inodes (`0` disables the limit).
+    max_tracked: usize,
+}
+
+impl InodeTable {
+    /// Creates a table that only knows the root directory: inode 1, mapped to
+    /// `root_path` (the configured `root_prefix`, `""` for the whole server).
+    pub fn new(max_tracked: usize, root_path: &str) -> Self {
+        let mut table = Self {
+            inode_to_path: HashMap::new(),
+            path_to_inode: HashMap::new(),
+            inode_to_type: HashMap::new(),
+            recency: LruCache::unbounded(),
+            open_handles: HashMap::new(),
+            client_ino_of: HashMap::new(),
+            server_ino_of: HashMap::new(),
+            unverified: HashSet::new(),
+            next_inode: ROOT_INO + 1,
+            max_tracked,
+        };
+        table.inode_to_path.insert(ROOT_INO, root_path.to_string());
+        table.path_to_inode.insert(root_path.to_string(), ROOT_INO);
+        table.inode_to_type.insert(ROOT_INO, FileType::Directory);
+        table
+    }
+
+    /// Returns the number of tracked inodes, root included.
+    fn len(&self) -> usize {
+        self.inode_to_path.len()
+    }
+
+    /// Returns the path of `ino` and marks it as recently used.
+    pub fn path(&mut self, ino: u64) -> Option<String> {
+        let path = self.inode_to_path.get(&ino)?.clone();
+        self.touch(ino);
+        Some(path)
+    }
+
+    /// Returns the inode of `path`, if known, without affecting its LRU position.
+    pub fn inode(&self, path: &str) -> Option<u64> {
+        self.path_to_inode.get(path).copied()
+    }
+
+    /// Returns the tracked direct children of `dir_path`, as `(inode, name)`.
+    pub fn children(&self, dir_path: &str) -> Vec<(u64, String)> {
+        self.inode_to_path
+            .iter()
+            .filter(|&(&ino, _)| ino != ROOT_INO)
+            .filter_map(|(&ino, path)| {
+                let name = if dir_path.is_empty() {
+                    path.as_str()
+                } else {
+                    path.strip_prefix(dir_path)?.strip_prefix('/')?
+                };
+                (!name.is_empty() && !name.contains('/')).then(|| (ino, name.to_string()))
+            })
+            .collect()
+    }
+
+    /// Returns every tracked inode but the root, as `(inode, path)`, without
+    /// touching the LRU order.
+    pub fn tracked(&self) -> Vec<(u64, String)> {
+        self.inode_to_path
+            .iter()
+            .filter(|&(&ino, _)| ino != ROOT_INO)
+            .map(|(&ino, path)| (ino, path.clone()))
+            .collect()
+    }
+
+    /// Returns the cached `FileType` of `ino`, if known.
+    pub fn kind(&self, ino: u64) -> Option<FileType> {
+        self.inode_to_type.get(&ino).copied()
+    }
+
+    /// Checks the cached `FileType` of `ino` against the kind an operation expects.
+    ///
+    /// # Returns
+    /// * `Err(EISDIR)` if a file operation (`directory == false`) targets a directory.
+    /// * `Err(ENOTDIR)` if a directory operation targets anything else.
+    /// * `Ok(())` otherwise, also when the type is unknown: the server decides.
+    pub fn expect_kind(&self, ino: u64, directory: bool) -> Result<(), libc::c_int> {
+        match self.kind(ino) {
+            Some(FileType::Directory) if !directory => Err(libc::EISDIR),
+            Some(kind) if directory && kind != FileType::Directory => Err(libc::ENOTDIR),
+            _ => Ok(()),
+        }
+    }
+
+    /// Records the `FileType` of `ino`.
+    pub fn set_kind(&mut self, ino: u64, kind: FileType) {
+        if self.inode_to_path.contains_key(&ino) {
+            self.inode_to_type.insert(ino, kind);
+        }
+    }
+
+    /// Returns the inode of `path`, allocating one if the path is not tracked.
+    ///
+    /// If `server_ino` (the server's inode number for the entry) was already seen
+    /// in this session and its client inode is not in use, that inode is reused.
+    /// The inode is marked as recently used. This never evicts: call
+    /// `evict_excess` once the caller is done with the table.
+    pub fn get_or_insert(&mut self, path: &str, server_ino: Option<u64>) -> u64 {
+        self.verify_restored(path, server_ino);
+        let ino = match self.path_to_inode.get(path) {
+            Some(&ino) => ino,
+            None => {
+                let known = server_ino
+                    .and_then(|server_ino| self.client_ino_of.get(&server_ino).copied())
+                    .filter(|ino| !self.inode_to_path.contains_key(ino));
+                let ino = known.unwrap_or_else(|| {
+                    let ino = self.next_inode;
+                    self.next_inode += 1;
+                    ino
+                });
+                self.inode_to_path.insert(ino, path.to_string());
+                self.path_to_inode.insert(path.to_string(), ino);
+                ino
+            }
+        };
+        if let Some(server_ino) = server_ino {
+            self.bind_server_inode(ino, server_ino);
+        }
+        self.touch(ino);
+        ino
+    }
+
+    /// Checks a restored inode the first time its path is resolved again.
+    ///
+    /// If the server now reports a different inode number for `path`, the file
+    /// was replaced while the client was not running: the saved inode is dropped
+    /// and the path gets a new one.
+    fn verify_restored(&mut self, path: &str, server_ino: Option<u64>) {
+        let Some(ino) = self.inode(path).filter(|ino| self.unverified.contains(ino)) else {
+            return;
+        };
+        self.unverified.remove(&ino);
+        let saved = self.server_ino_of.get(&ino).copied();
+        if let (Some(saved), Some(current)) = (saved, server_ino)
+            && saved != current
+        {
+            println!("[INODES] Inode {} di '{}' non più valido: il file è cambiato sul server.", ino, path);
+            self.remove(ino);
+        }
+    }
+
+    /// Records that client inode `ino` is the file the server numbers `server_ino`.
+    pub fn bind_server_inode(&mut self, ino: u64, server_ino: u64) {
+        if ino == ROOT_INO || self.server_ino_of.get(&ino) == Some(&server_ino) {
+            return;
+        }
+        if let Some(old) = self.server_ino_of.insert(ino, server_ino) {
+            self.client_ino_of.remove(&old);
+        }
+        self.client_ino_of.insert(server_ino, ino);
+    }
+
+    /// Forgets `ino`, its path and its server inode: the file no longer exists.
+    /// The root is never removed.
+    pub fn remove(&mut self, ino: u64) {
+        if ino == ROOT_INO {
+            return;
+        }
+        self.untrack(ino);
+        if let Some(server_ino) = self.server_ino_of.remove(&ino) {
+            self.client_ino_of.remove(&server_ino);
+        }
+    }
+
+    /// Drops the path of `ino` but keeps its server inode binding, so the same
+    /// inode number is given back when the file is looked up again.
+    fn untrack(&mut self, ino: u64) {
+        if let Some(path) = self.inode_to_path.remove(&ino) {
+            self.path_to_inode.remove(&path);
+        }
+        self.inode_to_type.remove(&ino);
+        self.recency.pop(&ino);
+        self.unverified.remove(&ino);
+    }
+
+    /// Restores the mappings saved by `save` in `path`, so inode numbers stay
+    /// stable across remounts.
+    ///
+    /// Restored inodes are checked against the server the first time their
+    /// path is looked up (see `verify_restored`). A missing file is not an error.
+    pub fn load(&mut self, path: &str) -> io::Result<()> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let saved: InodeMapFile = serde_json::from_str(&content)?;
+
+        for entry in saved.inodes {
+            if entry.ino == ROOT_INO || self.inode_to_path.contains_key(&entry.ino) || self.path_to_inode.contains_key(&entry.path) {
+                continue;
+            }
+            self.inode_to_path.insert(entry.ino, entry.path.clone());
+            self.path_to_inode.insert(entry.path, entry.ino);
+            self.recency.put(entry.ino, ());
+            self.unverified.insert(entry.ino);
+            if let Some(server_ino) = entry.server_ino {
+                self.bind_server_inode(entry.ino, server_ino);
+            }
+            self.next_inode = self.next_inode.max(entry.ino + 1);
+        }
+        self.next_inode = self.next_inode.max(saved.next_inode);
+        Ok(())
+    }
+
+    /// Writes every tracked inode (except the root) to `path`.
+    ///
+    /// The file is written next to `path` and renamed over it, so a crash
+    /// never leaves a truncated map behind.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let inodes = self.inode_to_path.iter()
+            .filter(|(ino, _)| **ino != ROOT_INO)
+            .map(|(&ino, path)| SavedInode { ino, path: path.clone(), server_ino: self.server_ino_of.get(&ino).copied() })
+            .collect();
+        let content = serde_json::to_string(&InodeMapFile { next_inode: self.next_inode, inodes })?;
+
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, path)
+    }
+
+    /// Moves every tracked path equal to `from`, or nested below it, under `to`.
+    ///
+    /// All moves are applied at once, which makes swaps (`a -> b` and `b -> a`) work.
+    /// The root keeps its path, even if a move covers the mounted subtree.
+    ///
+    /// # Returns
+    /// The relocated inodes, and the inodes that were forgotten because a
+    /// relocated path overwrote theirs.
+    pub fn relocate(&mut self, moves: &[(&str, &str)]) -> (Vec<u64>, Vec<u64>) {
+        let mut relocated: Vec<(u64, String, String)> = Vec::new();
+        for (&ino, path) in &self.inode_to_path {
+            if ino == ROOT_INO {
+                continue;
+            }
+            for &(from, to) in moves {
+                let new_path = if path == from {
+                    Some(to.to_string())
+                } else {
+                    path.strip_prefix(from)
+                        .and_then(|rest| rest.strip_prefix('/'))
+                        .map(|rest| format!("{}/{}", to, rest))
+                };
+                if let Some(new_path) = new_path {
+                    relocated.push((ino, path.clone(), new_path));
+                    break;
+                }
+            }
+        }
+
+        // Drop every old path first, so swapped entries don't clobber each other
+        for (_, old_path, _) in &relocated {
+            self.path_to_inode.remove(old_path);
+        }
+        let mut moved = Vec::new();
+        let mut replaced = Vec::new();
+        for (ino, _, new_path) in relocated {
+            if let Some(old_ino) = self.path_to_inode.insert(new_path.clone(), ino) {
+                self.inode_to_path.remove(&old_ino);
+                self.inode_to_type.remove(&old_ino);
+                self.recency.pop(&old_ino);
+                if let Some(server_ino) = self.server_ino_of.remove(&old_ino) {
+                    self.client_ino_of.remove(&server_ino);
+                }
+                replaced.push(old_ino);
+            }
+            self.inode_to_path.insert(ino, new_path);
+            moved.push(ino);
+        }
+        (moved, replaced)
+    }
+
+    /// Pins `ino` for the lifetime of an open file handle, so it cannot be evicted.
+    pub fn pin(&mut self, ino: u64) {
+        *self.open_handles.entry(ino).or_insert(0) += 1;
+    }
+
+    /// Returns `true` if `ino` has open file handles (see `pin`).
+    pub fn is_pinned(&self, ino: u64) -> bool {
+        self.open_handles.contains_key(&ino)
+    }
+
+    /// Releases a pin taken by `pin`.
+    pub fn unpin(&mut self, ino: u64) {
+        if let Some(count) = self.open_handles.get_mut(&ino) {
+            *count -= 1;
+            if *count == 0 {
+                self.open_handles.remove(&ino);
+            }
+        }
+    }
+
+    /// Evicts least recently used inodes until at most `max_tracked` are left.
+    ///
+    /// The root, pinned inodes and the most recently used inode (the one the
+    /// current operation is working on) are skipped, so the limit may be
+    /// exceeded temporarily when too many files are open.
+    ///
+    /// # Returns
+    /// The evicted inodes, whose cached state must be dropped by the caller.
+    pub fn evict_excess(&mut self) -> Vec<u64> {
+        if self.max_tracked == 0 || self.len() <= self.max_tracked {
+            return Vec::new();
+        }
+        let excess = self.len() - self.max_tracked;
+        let candidates = self.recency.len().saturating_sub(1);
+        let victims: Vec<u64> = self.recency.iter().rev()
+            .take(candidates)
+            .map(|(&ino, _)| ino)
+            .filter(|ino| !self.open_handles.contains_key(ino))
+            .take(excess)
+            .collect();
+        for &ino in &victims {
+            self.untrack(ino);
+        }
+        victims
+    }
+
+    /// Marks `ino` as the most recently used inode.
+    fn touch(&mut self, ino: u64) {
+        if ino != ROOT_INO {
+            self.recency.put(ino, ());
+        }
+    }
+}
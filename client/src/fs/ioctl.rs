@@ -0,0 +1,62 @@
+use super::prelude::*;
+use super::notify::{self, KernelInvalidation};
+
+/// The `ioctl` command that forces a refresh of a file or directory: `_IO('R', 1)`.
+///
+/// It carries no data, so the kernel forwards it to FUSE without knowing it.
+/// Issued by `client refresh <path>`.
+pub const REFRESH_IOCTL: u32 = ((b'R' as u32) << 8) | 1;
+
+/// Handles the FUSE `ioctl` operation.
+///
+/// Only `REFRESH_IOCTL` is recognized, any other command fails with `ENOTTY`.
+/// The refresh:
+/// 1. Uploads the writes buffered for the inode by any open handle (as `flush`
+///    would), so the server has the latest content. On failure the error is returned.
+/// 2. Drops the cached attributes and read block of the inode and, for a
+///    directory, of every child the client knows.
+/// 3. Asks the kernel to drop the same entries (attributes, pages and the
+///    dentries of the children), so the next `stat`/`ls`/`cat` goes to the server.
+///
+/// The kernel invalidations are sent from a separate thread after the reply:
+/// the kernel may wait for this very `ioctl` to complete before processing them.
+///
+/// # Arguments
+/// * `fs` - The mutable `RemoteFS` state.
+/// * `ino` - The inode the `ioctl` was issued on.
+/// * `cmd` - The `ioctl` command number.
+/// * `reply` - The reply object to send the result or an error code.
+pub fn ioctl(fs: &mut RemoteFS, ino: u64, cmd: u32, reply: ReplyIoctl) {
+    if cmd != REFRESH_IOCTL {
+        reply.error(ENOTTY);
+        return;
+    }
+    let Some(path) = fs.inodes.path(ino) else {
+        reply.error(ENOENT);
+        return;
+    };
+
+    // 1. Buffered writes first: dropping the caches must not lose data
+    if let Err(e) = super::write::upload_buffered_writes(fs, ino) {
+        reply.error(e);
+        return;
+    }
+
+    // 2. Client caches of the inode and of its known children
+    let mut kernel_invalidations = vec![KernelInvalidation::Inode(ino)];
+    fs.attribute_cache.remove(&ino);
+    fs.invalidate_read_block(ino);
+    for (child, name) in fs.inodes.children(&path) {
+        fs.attribute_cache.remove(&child);
+        fs.invalidate_read_block(child);
+        kernel_invalidations.push(KernelInvalidation::Entry { parent: ino, name });
+    }
+    println!("[IOCTL] Refresh di '{}' (inode {}): {} voci invalidate", path, ino, kernel_invalidations.len());
+    let notifier = fs.kernel_notifier.clone();
+    reply.ioctl(0, &[]);
+
+    // 3. Kernel caches, once this request has been answered
+    if let Some(notifier) = notifier {
+        std::thread::spawn(move || notify::send(&notifier, kernel_invalidations));
+    }
+}
@@ -1,20 +1,38 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 use fuser::{
     FileAttr, FileType, Filesystem, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEntry,
     ReplyOpen, ReplyWrite, Request, ReplyEmpty,
-    ReplyXattr
+    ReplyXattr, ReplyIoctl, ReplyBmap
 };
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::OsStr;
-use std::time::{Duration, UNIX_EPOCH, SystemTime};
+use std::time::{Duration, Instant, UNIX_EPOCH, SystemTime};
 use reqwest::header::{HeaderMap, HeaderValue};
+use bytes::Bytes;
+use lru::LruCache;
+use std::num::NonZeroUsize;
 use crate::config::Config;
 use crate::fs::cache::AttributeCache;
+use crate::fs::filter::PathFilter;
+use crate::fs::inodes::InodeTable;
+use crate::api_client::{self, DirListing, RemoteEntry};
+use std::future::Future;
+use tokio::sync::Semaphore;
 
 // --- Module Declarations ---
 // These files contain the logic for handling FUSE operations.
 pub mod cache;
+pub mod catchup;
+pub mod control;
+pub mod filter;
+pub mod fsck;
+pub mod inodes;
+pub mod ioctl;
+pub mod notify;
 pub mod prelude;
+pub mod timing;
+pub mod warmup;
 mod attr;
 mod read;
 mod write;
@@ -23,9 +41,38 @@ mod delete;
 mod rename;
 mod xattr;
 
+/// Joins a server-relative directory path and an entry name.
+///
+/// The root is the empty path, so its children have no leading `/`
+/// (`join_path("", "a") == "a"`, `join_path("dir", "a") == "dir/a"`).
+pub fn join_path(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+/// The name of an entry as UTF-8, as the server's paths need it.
+///
+/// Linux allows any bytes in a name except `/` and NUL, but the server's API
+/// only carries UTF-8: other names are refused with `EINVAL` (and logged),
+/// instead of panicking and bringing the whole mount down.
+///
+/// # Arguments
+/// * `name` - The name received from the kernel.
+/// * `op` - The FUSE operation, for the log.
+pub fn utf8_name<'a>(name: &'a OsStr, op: &str) -> Result<&'a str, i32> {
+    name.to_str().ok_or_else(|| {
+        eprintln!("[NAME] {}: nome non UTF-8 {:?} rifiutato (EINVAL)", op, name);
+        libc::EINVAL
+    })
+}
+
 /// Default Time-To-Live (TTL) for FUSE kernel attribute/entry caches.
 pub const TTL: Duration = Duration::from_secs(1);
 /// Static, hardcoded attributes for the root directory (inode 1).
+/// `uid`/`gid` are replaced with the mounting user's by `RemoteFS::root_attr`.
 pub const ROOT_DIR_ATTR: FileAttr = FileAttr {
     ino: 1, size: 0, blocks: 0, atime: UNIX_EPOCH, mtime: UNIX_EPOCH, ctime: UNIX_EPOCH,
     crtime: UNIX_EPOCH, kind: FileType::Directory, perm: 0o755, nlink: 2, uid: 501, gid: 20,
@@ -35,13 +82,167 @@ pub const ROOT_DIR_ATTR: FileAttr = FileAttr {
 /// Holds the in-memory cache for a file opened with write access.
 ///
 /// This is the core of the "cache-on-write" strategy. `write` calls
-/// store their data blocks in the `buffer` HashMap, indexed by offset.
-/// The `release` function later assembles these blocks for upload.
+/// store their data blocks in the `buffer` map, indexed by offset.
+/// The `flush` function (on `close(2)`) later assembles these blocks for upload.
+///
+/// There is one `OpenWriteFile` per inode, shared by all its write handles:
+/// writes through different handles land in the same buffer, and the entry is
+/// dropped when the last handle is released.
 pub struct OpenWriteFile {
     /// The server-relative path of the file (e.g., "dir/file.txt").
     pub(crate) path: String,
     /// In-memory cache of written data blocks, keyed by their file offset.
-    pub(crate) buffer: HashMap<i64, Vec<u8>>,
+    ///
+    /// Kept coalesced by `insert_block`: the blocks never overlap nor touch, so
+    /// they can be applied in any order, and a run of small sequential writes
+    /// grows a single block instead of adding one per `write`.
+    pub(crate) buffer: BTreeMap<i64, Vec<u8>>,
+    /// Timestamps set while the handle was open (e.g. `cp -p`), sent with the
+    /// upload so that it does not reset them to the time of the write.
+    pub(crate) times: TimeOverride,
+    /// When the last block was buffered, reported as the file's `mtime` until the upload.
+    pub(crate) last_write: Option<SystemTime>,
+    /// The number of write handles currently sharing this buffer.
+    pub(crate) handles: u32,
+}
+
+impl OpenWriteFile {
+    /// The end offset of the buffered data: the file size once it is uploaded,
+    /// unless the server's copy is longer.
+    pub fn buffered_end(&self) -> u64 {
+        // The blocks are disjoint: the last one ends last
+        self.buffer.last_key_value().map(|(offset, data)| (*offset).max(0) as u64 + data.len() as u64).unwrap_or(0)
+    }
+
+    /// Buffers `data` at `offset`, merging it with the blocks it overlaps or touches.
+    ///
+    /// On overlaps the new bytes win (last writer wins), as they would on a local
+    /// file, whatever the order of the offsets of the writes.
+    pub fn insert_block(&mut self, offset: i64, data: &[u8]) {
+        let start = offset.max(0);
+        let end = start + data.len() as i64;
+        // Blocks starting at or before `end` and ending at or after `start`, highest first
+        let touching: Vec<i64> = self.buffer
+            .range(..=end)
+            .rev()
+            .take_while(|(block_start, block)| **block_start + block.len() as i64 >= start)
+            .map(|(block_start, _)| *block_start)
+            .collect();
+        let Some(&first) = touching.last() else {
+            self.buffer.insert(start, data.to_vec());
+            return;
+        };
+
+        // The merged range is contiguous: every block touches the new one
+        let merged_start = first.min(start);
+        let merged_end = touching.iter().map(|o| o + self.buffer[o].len() as i64).max().unwrap_or(end).max(end);
+        // A block starting before the write is extended in place (e.g. sequential appends)
+        let mut merged = if first <= start { self.buffer.remove(&first).unwrap_or_default() } else { Vec::new() };
+        merged.resize((merged_end - merged_start) as usize, 0);
+        for block_start in touching {
+            if let Some(block) = self.buffer.remove(&block_start) {
+                let at = (block_start - merged_start) as usize;
+                merged[at..at + block.len()].copy_from_slice(&block);
+            }
+        }
+        let at = (start - merged_start) as usize;
+        merged[at..at + data.len()].copy_from_slice(data);
+        self.buffer.insert(merged_start, merged);
+    }
+}
+
+/// A chunked upload that has not been completed yet.
+///
+/// Kept after a failed upload so that re-uploading the same content (e.g. a
+/// retried `cp`) resumes from the last offset committed by the server.
+pub struct UploadSession {
+    /// The server-side session id.
+    pub(crate) id: String,
+    /// SHA-256 of the content being uploaded: a session is only resumed for identical data.
+    pub(crate) checksum: String,
+}
+
+/// The most recently downloaded block of a file.
+///
+/// `read` always fetches whole `transfer_block_size`-aligned blocks and keeps
+/// the last one here, so consecutive small reads of the same region are served
+/// from memory instead of issuing a new Range request each time.
+#[derive(Clone)]
+pub struct ReadBlock {
+    /// The inode the block belongs to.
+    pub(crate) ino: u64,
+    /// The file offset of the first byte in `data`.
+    pub(crate) offset: u64,
+    /// The downloaded bytes.
+    pub(crate) data: Bytes,
+    /// `true` if the server returned fewer bytes than requested (end of file reached).
+    pub(crate) eof: bool,
+}
+
+impl ReadBlock {
+    /// Returns the bytes in `start..end` if this block can answer the request.
+    ///
+    /// A request extending past the end of the block is only satisfiable when
+    /// the block reached EOF, in which case the result is truncated accordingly.
+    pub fn slice(&self, ino: u64, start: u64, end: u64) -> Option<Bytes> {
+        let block_end = self.offset.saturating_add(self.data.len() as u64);
+        if self.ino != ino || start < self.offset || (end > block_end && !self.eof) {
+            return None;
+        }
+        let from = (start.min(block_end) - self.offset) as usize;
+        let to = (end.min(block_end) - self.offset) as usize;
+        Some(self.data.slice(from..to))
+    }
+}
+
+/// The state of a handle opened for reading only, kept from `open` to `release`.
+///
+/// Two descriptors reading the same file at different positions (e.g. `pread`
+/// from several threads) each keep their own block, instead of evicting each
+/// other's `RemoteFS::read_block` at every call.
+pub struct OpenReadFile {
+    /// The inode the handle was opened on.
+    pub(crate) ino: u64,
+    /// The offset right after the last read, where a sequential read continues.
+    pub(crate) next_offset: u64,
+    /// Reads served so far, and how many of them continued the previous one.
+    pub(crate) reads: u64,
+    pub(crate) sequential_reads: u64,
+    /// The last block downloaded through this handle.
+    pub(crate) block: Option<ReadBlock>,
+}
+
+impl OpenReadFile {
+    pub fn new(ino: u64) -> Self {
+        OpenReadFile { ino, next_offset: 0, reads: 0, sequential_reads: 0, block: None }
+    }
+
+    /// Records a read of `start..end`: sequential if it starts where the previous one ended.
+    pub fn record_read(&mut self, start: u64, end: u64) {
+        if start == self.next_offset {
+            self.sequential_reads += 1;
+        }
+        self.reads += 1;
+        self.next_offset = end;
+    }
+}
+
+/// Number of recently downloaded blocks kept for `serve_stale_on_error` (see `RemoteFS::stale_blocks`).
+pub const STALE_BLOCK_CAPACITY: usize = 256;
+
+/// Number of directory listings kept for revalidation (see `RemoteFS::dir_listings`).
+pub const DIR_LISTING_CAPACITY: usize = 256;
+
+/// Timestamps set through `setattr` (e.g. `touch -d`) for a single inode.
+///
+/// These are layered on top of the attributes fetched from the server, so they
+/// survive cache refreshes within the session. `mtime` is dropped once the
+/// server has stored it (or the content changes); `atime` is kept for the whole
+/// session because the server does not report it.
+#[derive(Default, Clone, Copy)]
+pub struct TimeOverride {
+    pub(crate) atime: Option<SystemTime>,
+    pub(crate) mtime: Option<SystemTime>,
 }
 
 /// The main state struct for the remote filesystem.
@@ -50,37 +251,85 @@ pub struct OpenWriteFile {
 /// It holds all persistent state required to serve FUSE requests, including
 /// the asynchronous runtime, API client, and various caches.
 pub struct RemoteFS {
-    /// The `reqwest` client for making HTTP requests to the remote server.
-    pub(crate) client: reqwest::Client,
-    /// The Tokio `Runtime` used to execute asynchronous API calls (`block_on`).
-    pub(crate) runtime: tokio::runtime::Runtime,
-    /// Maps an Inode number (u64) to its full path string (e.g., 1 -> "").
+    /// The client for making HTTP requests to the remote server, with the retry
+    /// state of this mount (`api_client::RetryState`).
+    pub(crate) client: api_client::ApiClient,
+    /// The Tokio `Runtime` used to execute asynchronous API calls (`block_on`),
+    /// shared by every mount of the process (see `build_runtime`).
+    pub(crate) runtime: Arc<tokio::runtime::Runtime>,
+    /// Caps the requests in flight at `max_concurrent_requests` (see `block_on`).
+    pub(crate) request_limit: Arc<Semaphore>,
     pub(crate) client_id: String,
-    pub(crate) inode_to_path: HashMap<u64, String>,
-    /// Maps a full path string to its Inode number (e.g., "" -> 1).
-    pub(crate) path_to_inode: HashMap<String, u64>,
-    /// Caches the `FileType` (File or Dir) for a known Inode.
-    pub(crate) inode_to_type: HashMap<u64, FileType>,
-    /// A simple counter to generate new, unique Inode numbers.
-    pub(crate) next_inode: u64,
+    /// The inode <-> path mapping, capped at `max_tracked_inodes` entries (see `InodeTable`).
+    pub(crate) inodes: InodeTable,
     /// The attribute cache (LRU or TTL) for `getattr` calls.
     pub(crate) attribute_cache: AttributeCache,
     /// The loaded filesystem configuration.
     pub(crate) config: Config,
+    /// Hides the entries filtered out by `include_globs`/`exclude_globs`.
+    pub(crate) path_filter: PathFilter,
     /// The in-memory cache for files opened with write access.
-    /// Keyed by File Handle (`fh`).
+    /// Keyed by Inode, shared by all the write handles of the file.
     pub(crate) open_files: HashMap<u64, OpenWriteFile>,
+    /// The inode of every open write handle, keyed by File Handle (`fh`).
+    pub(crate) write_handles: HashMap<u64, u64>,
+    /// The handles opened with `O_DIRECT`: their reads always go to the server and
+    /// their writes are sent at once (see `register_direct_handle`).
+    pub(crate) direct_handles: HashSet<u64>,
     /// A simple counter to generate new, unique File Handle (fh) numbers.
     pub(crate) next_fh: u64,
+    /// The last block downloaded by `read` through a handle without read state
+    /// (a write handle), see `ReadBlock`.
+    pub(crate) read_block: Option<ReadBlock>,
+    /// The state of every read-only handle, keyed by File Handle (`fh`).
+    pub(crate) read_handles: HashMap<u64, OpenReadFile>,
+    /// With `serve_stale_on_error`, the last `STALE_BLOCK_CAPACITY` blocks downloaded
+    /// by `read`, keyed by inode and offset: served when the server cannot be reached.
+    pub(crate) stale_blocks: LruCache<(u64, u64), ReadBlock>,
+    /// Timestamps set via `setattr` that the server does not (yet) reflect, keyed by Inode.
+    pub(crate) time_overrides: HashMap<u64, TimeOverride>,
+    /// Whether the change watcher is currently connected to the server's WebSocket.
+    pub(crate) ws_connected: bool,
+    /// While the `/healthz` poller finds the server unable to store data, the errno
+    /// the writing operations fail with at once (see `RemoteFS::check_writable`).
+    pub(crate) degraded_errno: Option<i32>,
+    /// Files uploaded by the last write to `.remotefs/flush` (see `control`).
+    pub(crate) control_flushed: usize,
+    /// The report of the last write to `.remotefs/fsck` (see `fsck::run`).
+    pub(crate) control_fsck_report: String,
+    /// Sends cache invalidations to the kernel (see `notify`); set by `main` once mounted.
+    /// Shared with the watcher and the background invalidations (`Notifier` is not `Clone`).
+    pub(crate) kernel_notifier: Option<Arc<fuser::Notifier>>,
+    /// Unfinished chunked uploads, keyed by the file's path.
+    pub(crate) upload_sessions: HashMap<String, UploadSession>,
+    /// The listing taken by `opendir` for every open directory handle, keyed by
+    /// File Handle (`fh`): `readdir` iterates it until `releasedir` drops it.
+    pub(crate) dir_snapshots: HashMap<u64, Vec<RemoteEntry>>,
+    /// The last `DIR_LISTING_CAPACITY` directory listings, keyed by path, with the
+    /// directory's mtime: revalidated with `If-Modified-Since` (see `read::list_directory`).
+    pub(crate) dir_listings: LruCache<String, DirListing>,
+    /// Time spent on the server by the FUSE operation in progress (see `timing::OpTimer`).
+    pub(crate) server_time: timing::ServerTime,
+    /// `X-Request-ID` of the FUSE operation in progress, sent with all its requests
+    /// by `block_on` (empty between operations). Set by `timing::OpTimer`.
+    pub(crate) request_id: RefCell<String>,
+    /// Operations slower than `slow_op_threshold_ms` since the mount, reported in `.remotefs/stats`.
+    pub(crate) slow_ops: u64,
+    /// The inodes used most recently, whose attributes are fetched again when the
+    /// server comes back (see `warmup::warm_up`).
+    pub(crate) recent_paths: warmup::RecentPaths,
 }
 
 impl RemoteFS {
     /// Creates a new instance of the `RemoteFS`.
     ///
-    /// This initializes the Tokio runtime, the `reqwest` client, all caches,
-    /// and populates the maps with the root directory (inode 1).
-    pub fn new(config: Config) -> Self {
-        let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    /// This initializes the `reqwest` client, all caches, and populates the maps
+    /// with the root directory (inode 1). Requests run on `runtime`, built once
+    /// by `build_runtime` for all the mounts of the process.
+    ///
+    /// The connection pool is sized by `max_concurrent_requests`. Each mount keeps
+    /// its own pool, since every request carries the mount's `X-Client-ID`.
+    pub fn new(config: Config, runtime: Arc<tokio::runtime::Runtime>) -> Self {
         // 1. Genera un ID univoco basato sul tempo (semplice ed efficace)
         let client_id = format!("client-{}", SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos());
         println!("[CLIENT] ID Sessione generato: {}", client_id);
@@ -89,159 +338,671 @@ impl RemoteFS {
         let mut headers = HeaderMap::new();
         headers.insert("X-Client-ID", HeaderValue::from_str(&client_id).unwrap());
 
-        let client = reqwest::Client::builder()
+        let mut client_builder = reqwest::Client::builder()
             .default_headers(headers)
+            .user_agent(api_client::user_agent(config.user_agent.as_deref(), &client_id));
+        if config.max_concurrent_requests > 0 {
+            // No point keeping more idle connections than requests that can run at once
+            client_builder = client_builder.pool_max_idle_per_host(config.max_concurrent_requests);
+        }
+        let client = api_client::ApiClient::new(client_builder
             .build()
-            .unwrap();
+            .unwrap());
 
+        let config_warmup = config.warmup_recent_paths;
         let mut fs = Self {
             client,
             client_id,
             runtime,
-            inode_to_path: HashMap::new(),
-            path_to_inode: HashMap::new(),
-            inode_to_type: HashMap::new(),
-            next_inode: 2, // 1 is root
+            request_limit: Arc::new(Semaphore::new(request_permits(config.max_concurrent_requests))),
+            inodes: InodeTable::new(config.max_tracked_inodes, &config.root_prefix), // Knows only the root (1)
             attribute_cache: AttributeCache::new(&config),
+            // The globs are validated in `main` before mounting
+            path_filter: PathFilter::from_config(&config).expect("glob di include/exclude non validi"),
             config,
             open_files: HashMap::new(),
+            write_handles: HashMap::new(),
+            direct_handles: HashSet::new(),
             next_fh: 1,
+            read_block: None,
+            read_handles: HashMap::new(),
+            stale_blocks: LruCache::new(NonZeroUsize::new(STALE_BLOCK_CAPACITY).unwrap()),
+            time_overrides: HashMap::new(),
+            ws_connected: false,
+            degraded_errno: None,
+            control_flushed: 0,
+            control_fsck_report: String::new(),
+            kernel_notifier: None,
+            upload_sessions: HashMap::new(),
+            dir_snapshots: HashMap::new(),
+            dir_listings: LruCache::new(NonZeroUsize::new(DIR_LISTING_CAPACITY).unwrap()),
+            server_time: timing::ServerTime::default(),
+            request_id: RefCell::new(String::new()),
+            slow_ops: 0,
+            recent_paths: warmup::RecentPaths::new(config_warmup),
         };
 
+        // Restore the inode numbers of the previous mount, if configured
+        if let Some(path) = fs.config.inode_map_file.clone()
+            && let Err(e) = fs.inodes.load(&path)
+        {
+            eprintln!("[INODES] Impossibile leggere la mappa degli inode {}: {}", path, e);
+        }
+
+        fs.client.retry_state().set_window(Duration::from_secs(fs.config.server_retry_window_seconds));
+
         // Initialize root directory
-        fs.inode_to_path.insert(1, "".to_string());
-        fs.path_to_inode.insert("".to_string(), 1);
-        fs.inode_to_type.insert(1, FileType::Directory);
-        let ttl = Duration::from_secs(fs.config.cache_ttl_seconds);
-        fs.attribute_cache.put(1, ROOT_DIR_ATTR, ttl);
+        let ttl = fs.config.cache_ttl(true);
+        fs.attribute_cache.put(1, fs.root_attr(), ttl);
         fs
     }
+
+    /// Runs `future` (an `api_client` call) to completion on the runtime.
+    ///
+    /// Every call holds a permit of `request_limit` while it runs, so that no more
+    /// than `max_concurrent_requests` requests reach the server at once, whichever
+    /// thread they come from.
+    ///
+    /// The time spent, waiting for a permit included, is added to `server_time`.
+    /// The requests carry the `X-Request-ID` of the current operation (`request_id`).
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let started = Instant::now();
+        let request_id = self.request_id.borrow().clone();
+        let output = self.runtime.block_on(api_client::REQUEST_ID.scope(request_id, async {
+            let _permit = self.request_limit.acquire().await.expect("request_limit is never closed");
+            future.await
+        }));
+        self.server_time.record(started.elapsed());
+        output
+    }
+
+    /// Returns the attributes of the root directory, with the configured block size.
+    ///
+    /// The root is owned by the mounting user (or `force_uid`/`force_gid`); this
+    /// owner is also used for entries of servers that do not report one.
+    pub fn root_attr(&self) -> FileAttr {
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let uid = self.config.force_uid.unwrap_or(uid);
+        let gid = self.config.force_gid.unwrap_or(gid);
+        FileAttr { blksize: self.config.transfer_block_size, uid, gid, ..ROOT_DIR_ATTR }
+    }
+
+    /// Whether the server path `path` is the root of the mount (`root_prefix`).
+    pub fn is_root_path(&self, path: &str) -> bool {
+        path.trim_matches('/') == self.config.root_prefix
+    }
+
+    /// Whether the entry at server path `path` is hidden by `include_globs`/`exclude_globs`.
+    ///
+    /// The globs apply to the path relative to the mount root, without `root_prefix`.
+    /// A server entry named like the control directory (`.remotefs` in the root) is
+    /// always hidden, since that name is taken by the virtual one.
+    pub fn is_hidden(&self, path: &str, is_dir: bool) -> bool {
+        let prefix = self.config.root_prefix.as_str();
+        let relative = if prefix.is_empty() {
+            path
+        } else {
+            path.strip_prefix(prefix).and_then(|rest| rest.strip_prefix('/')).unwrap_or(path)
+        };
+        relative == control::CONTROL_DIR_NAME || self.path_filter.hides(relative, is_dir)
+    }
+
+    /// Whether a file of `size` bytes would be over `config.max_file_size_bytes`.
+    pub fn exceeds_max_file_size(&self, size: u64) -> bool {
+        self.config.max_file_size_bytes != 0 && size > self.config.max_file_size_bytes
+    }
+
+    /// Whether the operations that add data on the server (`write`, `create`, `mkdir`,
+    /// `fallocate`) may proceed.
+    ///
+    /// # Returns
+    /// `Err(errno)` while the `/healthz` poller finds the server degraded (`ENOSPC` for
+    /// a full disk, `EROFS` otherwise): the operation fails at once instead of buffering
+    /// data the server could not store. Reads and deletions are never refused.
+    pub fn check_writable(&self) -> Result<(), i32> {
+        self.degraded_errno.map_or(Ok(()), Err)
+    }
+
+    /// Forgets a locally-set `mtime` for `ino`, once the server's own value is authoritative.
+    pub fn drop_mtime_override(&mut self, ino: u64) {
+        if let Some(times) = self.time_overrides.get_mut(&ino) {
+            times.mtime = None;
+        }
+    }
+
+    /// Rewrites the cached path mappings after one or more server-side moves.
+    ///
+    /// Every known path equal to `from`, or nested below it, is moved under `to`
+    /// while keeping its inode number, so the kernel's references stay valid.
+    /// All moves are applied at once, which makes swaps (`a -> b` and `b -> a`) work.
+    /// An inode whose path gets overwritten by a move is forgotten.
+    pub fn relocate_cached_paths(&mut self, moves: &[(&str, &str)]) {
+        let (moved, replaced) = self.inodes.relocate(moves);
+        for ino in replaced {
+            self.drop_inode_state(ino);
+        }
+        for ino in moved {
+            self.attribute_cache.remove(&ino);
+        }
+    }
+
+    /// Returns the inode of `path`, allocating one if needed (see `InodeTable::get_or_insert`).
+    ///
+    /// `server_ino` is the server's inode number for the entry, when known.
+    /// If this pushes the table over `max_tracked_inodes`, the least recently
+    /// used inodes are evicted together with their cached state.
+    pub fn track_path(&mut self, path: &str, server_ino: Option<u64>) -> u64 {
+        let ino = self.inodes.get_or_insert(path, server_ino);
+        let evicted = self.inodes.evict_excess();
+        if !evicted.is_empty() {
+            println!("[CACHE] EVICT: {} inode(s) dropped (max_tracked_inodes = {})", evicted.len(), self.config.max_tracked_inodes);
+        }
+        for ino in evicted {
+            self.drop_inode_state(ino);
+        }
+        ino
+    }
+
+    /// Like `track_path`, for an entry the server listed as a directory (`is_dir`)
+    /// or not, whose kind is recorded.
+    ///
+    /// If the inode known for `path` has the other kind, another client replaced the
+    /// file with a directory (or vice versa): it is a different object, so the old
+    /// inode is forgotten with its cached state and the path gets a fresh one.
+    /// An inode with open handles is kept, with the new kind and no cached attributes.
+    ///
+    /// # Returns
+    /// The inode of `path`.
+    pub fn track_entry(&mut self, path: &str, server_ino: Option<u64>, is_dir: bool) -> u64 {
+        let kind = if is_dir { FileType::Directory } else { FileType::RegularFile };
+        if let Some(old) = self.inodes.inode(path)
+            && let Some(old_kind) = self.inodes.kind(old)
+            && (old_kind == FileType::Directory) != is_dir
+        {
+            println!("[INODES] '{}' è cambiato sul server da {:?} a {:?}", path, old_kind, kind);
+            self.dir_listings.pop(path);
+            if self.inodes.is_pinned(old) {
+                self.drop_inode_state(old);
+            } else {
+                self.forget_inode(old);
+            }
+        }
+        let ino = self.track_path(path, server_ino);
+        self.inodes.set_kind(ino, kind);
+        ino
+    }
+
+    /// Saves the inode mappings to `inode_map_file`, if configured.
+    ///
+    /// Called after operations that change the namespace (create, rename,
+    /// delete) and on unmount. Failures are logged: they only cost inode
+    /// stability across remounts.
+    pub fn save_inode_map(&self) {
+        if let Some(path) = &self.config.inode_map_file
+            && let Err(e) = self.inodes.save(path)
+        {
+            eprintln!("[INODES] Impossibile salvare la mappa degli inode {}: {}", path, e);
+        }
+    }
+
+    /// Forgets `ino` and everything cached for it.
+    pub fn forget_inode(&mut self, ino: u64) {
+        self.inodes.remove(ino);
+        self.drop_inode_state(ino);
+    }
+
+    /// Drops the attributes, local timestamps and read block cached for `ino`.
+    fn drop_inode_state(&mut self, ino: u64) {
+        self.attribute_cache.remove(&ino);
+        self.time_overrides.remove(&ino);
+        self.invalidate_read_block(ino);
+    }
+
+    /// Opens a new write handle on `ino`, sharing the write buffer of the
+    /// handles already open on it (or creating an empty one).
+    ///
+    /// The inode is pinned until the handle is released.
+    ///
+    /// # Returns
+    /// The new file handle (`fh`).
+    pub fn open_write_handle(&mut self, ino: u64, path: String) -> u64 {
+        let fh = self.next_fh;
+        self.next_fh += 1;
+
+        let open_file = self.open_files.entry(ino).or_insert_with(|| OpenWriteFile {
+            path,
+            buffer: BTreeMap::new(), // Buffer always starts empty
+            times: TimeOverride::default(),
+            last_write: None,
+            handles: 0,
+        });
+        open_file.handles += 1;
+        self.write_handles.insert(fh, ino);
+        self.inodes.pin(ino); // Unpinned by `release`
+        fh
+    }
+
+    /// Opens a new read-only handle on `ino`, with its own read state (see `OpenReadFile`).
+    ///
+    /// The inode is pinned until the handle is released.
+    ///
+    /// # Returns
+    /// The new file handle (`fh`).
+    pub fn open_read_handle(&mut self, ino: u64) -> u64 {
+        let fh = self.next_fh;
+        self.next_fh += 1;
+        self.read_handles.insert(fh, OpenReadFile::new(ino));
+        self.inodes.pin(ino); // Unpinned by `release`
+        fh
+    }
+
+    /// Records `fh` as a direct handle if the file was opened with `O_DIRECT`.
+    ///
+    /// A direct handle bypasses every cache of the content: `read` fetches exactly
+    /// the requested range without keeping it in `read_block`, and `write` goes
+    /// to the server like in `write_through` mode. Other handles on the same
+    /// file keep using the buffered paths.
+    ///
+    /// # Returns
+    /// The `FOPEN_*` flags to reply with: `FOPEN_DIRECT_IO` for a direct handle,
+    /// so the kernel's page cache is bypassed as well.
+    pub fn register_direct_handle(&mut self, fh: u64, flags: i32) -> u32 {
+        if flags & libc::O_DIRECT == 0 {
+            return 0;
+        }
+        self.direct_handles.insert(fh);
+        fuser::consts::FOPEN_DIRECT_IO
+    }
+
+    /// Returns the number of open files with buffered writes not yet uploaded.
+    pub fn pending_writes(&self) -> usize {
+        self.open_files.values().filter(|f| !f.buffer.is_empty()).count()
+    }
+
+    /// Uploads every buffered write still held in `open_files`.
+    ///
+    /// Used before a clean shutdown, so that data written through handles
+    /// the kernel has not released yet is not lost.
+    ///
+    /// # Returns
+    /// The number of files that could not be uploaded.
+    pub fn flush_pending_writes(&mut self) -> usize {
+        let mut failed = 0;
+        self.write_handles.clear();
+        for (ino, open_file) in std::mem::take(&mut self.open_files) {
+            if open_file.buffer.is_empty() {
+                continue;
+            }
+            match write::upload_open_file(self, &open_file) {
+                Ok(()) => {
+                    self.attribute_cache.remove(&ino);
+                    self.invalidate_read_block(ino);
+                    self.drop_mtime_override(ino);
+                }
+                Err(_) => failed += 1,
+            }
+        }
+        failed
+    }
+
+    /// Uploads the writes buffered by every open file, keeping the handles open.
+    ///
+    /// Used by `.remotefs/flush`. Unlike `flush_pending_writes` (shutdown), the
+    /// files stay open: a failed upload keeps its writes buffered.
+    ///
+    /// # Returns
+    /// The number of files uploaded and the number of files that could not be.
+    pub fn sync_open_files(&mut self) -> (usize, usize) {
+        let dirty: Vec<u64> = self.open_files.iter().filter(|(_, f)| !f.buffer.is_empty()).map(|(&ino, _)| ino).collect();
+        let mut uploaded = 0;
+        for &ino in &dirty {
+            if write::upload_buffered_writes(self, ino).is_ok() {
+                uploaded += 1;
+            }
+        }
+        (uploaded, dirty.len() - uploaded)
+    }
+
+    /// Drops every cached attribute and the cached read blocks.
+    ///
+    /// Used when the server comes back after an outage: anything may have
+    /// changed in the meantime without a watcher notification.
+    pub fn invalidate_all_caches(&mut self) {
+        self.attribute_cache.clear();
+        self.read_block = None;
+        for handle in self.read_handles.values_mut() {
+            handle.block = None;
+        }
+        self.stale_blocks.clear();
+    }
+
+    /// Drops the cached read blocks (the shared one and those of the read handles),
+    /// and the blocks kept for `serve_stale_on_error`, of `ino`.
+    ///
+    /// Must be called whenever the content of `ino` may have changed.
+    pub fn invalidate_read_block(&mut self, ino: u64) {
+        if self.read_block.as_ref().is_some_and(|b| b.ino == ino) {
+            self.read_block = None;
+        }
+        for handle in self.read_handles.values_mut().filter(|handle| handle.ino == ino) {
+            handle.block = None;
+        }
+        let stale: Vec<(u64, u64)> = self.stale_blocks.iter().map(|(&key, _)| key).filter(|&(i, _)| i == ino).collect();
+        for key in stale {
+            self.stale_blocks.pop(&key);
+        }
+    }
+
+    /// Applies a configuration re-read from `config.toml` (on `SIGHUP`) without remounting.
+    ///
+    /// Hot-reloadable fields:
+    /// * `cache_strategy`, `cache_ttl_seconds`, `dir_cache_ttl_seconds`, `file_cache_ttl_seconds`,
+    ///   `cache_ttl_jitter`, `cache_lru_capacity`: the attribute cache is rebuilt empty with the new settings.
+    /// * `max_concurrent_requests`: the `request_limit` semaphore is replaced (the
+    ///   idle connection pool of the HTTP client keeps its startup size).
+    /// * `verify_checksums`, `chunked_upload_threshold`, `upload_chunk_size`, `spill_threshold_bytes`,
+    ///   `max_file_size_bytes`, `list_control_files`, `serve_stale_on_error`,
+    ///   `write_through`, `release_upload_retries`, `unsynced_dir`, `slow_op_threshold_ms`:
+    ///   read at every operation.
+    /// * `server_retry_window_seconds`: applies to the next requests (`api_client::RetryState::set_window`).
+    /// * `warmup_recent_paths`: the list of recent paths is resized, keeping the newest.
+    ///
+    /// Every other field is bound to the mount, the HTTP client or a background
+    /// thread started at mount time: a change is logged and ignored until the next mount.
+    /// Open files, write buffers and inodes are never touched.
+    pub fn reload_config(&mut self, new_config: Config) {
+        let mut config = self.config.clone();
+        config.cache_strategy = new_config.cache_strategy;
+        config.cache_ttl_seconds = new_config.cache_ttl_seconds;
+        config.dir_cache_ttl_seconds = new_config.dir_cache_ttl_seconds;
+        config.file_cache_ttl_seconds = new_config.file_cache_ttl_seconds;
+        config.cache_ttl_jitter = new_config.cache_ttl_jitter;
+        config.cache_lru_capacity = new_config.cache_lru_capacity;
+        config.max_concurrent_requests = new_config.max_concurrent_requests;
+        config.verify_checksums = new_config.verify_checksums;
+        config.chunked_upload_threshold = new_config.chunked_upload_threshold;
+        config.upload_chunk_size = new_config.upload_chunk_size;
+        config.spill_threshold_bytes = new_config.spill_threshold_bytes;
+        config.max_file_size_bytes = new_config.max_file_size_bytes;
+        config.list_control_files = new_config.list_control_files;
+        config.serve_stale_on_error = new_config.serve_stale_on_error;
+        config.server_retry_window_seconds = new_config.server_retry_window_seconds;
+        config.write_through = new_config.write_through;
+        config.release_upload_retries = new_config.release_upload_retries;
+        config.unsynced_dir = new_config.unsynced_dir.clone();
+        config.slow_op_threshold_ms = new_config.slow_op_threshold_ms;
+        config.warmup_recent_paths = new_config.warmup_recent_paths;
+
+        let fixed_fields = [
+            ("server_url", config.server_url != new_config.server_url),
+            ("root_prefix", config.root_prefix != new_config.root_prefix),
+            ("daemon", config.daemon != new_config.daemon),
+            ("transfer_block_size", config.transfer_block_size != new_config.transfer_block_size),
+            ("keepalive_interval_seconds", config.keepalive_interval_seconds != new_config.keepalive_interval_seconds),
+            ("keepalive_timeout_seconds", config.keepalive_timeout_seconds != new_config.keepalive_timeout_seconds),
+            ("ws_connect_timeout_seconds", config.ws_connect_timeout_seconds != new_config.ws_connect_timeout_seconds),
+            ("health_poll_seconds", config.health_poll_seconds != new_config.health_poll_seconds),
+            ("runtime_worker_threads", config.runtime_worker_threads != new_config.runtime_worker_threads),
+            ("max_tracked_inodes", config.max_tracked_inodes != new_config.max_tracked_inodes),
+            ("inode_map_file", config.inode_map_file != new_config.inode_map_file),
+            ("include_globs", config.include_globs != new_config.include_globs),
+            ("exclude_globs", config.exclude_globs != new_config.exclude_globs),
+            ("force_uid", config.force_uid != new_config.force_uid),
+            ("force_gid", config.force_gid != new_config.force_gid),
+            ("mount_options", config.mount_options != new_config.mount_options),
+            ("pid_file", config.pid_file != new_config.pid_file),
+            ("daemon_stdout", config.daemon_stdout != new_config.daemon_stdout),
+            ("daemon_stderr", config.daemon_stderr != new_config.daemon_stderr),
+            ("user_agent", config.user_agent != new_config.user_agent),
+        ];
+        for (field, _) in fixed_fields.iter().filter(|(_, changed)| *changed) {
+            println!("[RELOAD] '{}' non è modificabile a caldo: ignorato fino al prossimo mount.", field);
+        }
+
+        if config.cache_strategy != self.config.cache_strategy
+            || config.cache_ttl_seconds != self.config.cache_ttl_seconds
+            || config.dir_cache_ttl_seconds != self.config.dir_cache_ttl_seconds
+            || config.file_cache_ttl_seconds != self.config.file_cache_ttl_seconds
+            || config.cache_ttl_jitter != self.config.cache_ttl_jitter
+            || config.cache_lru_capacity != self.config.cache_lru_capacity
+        {
+            println!("[RELOAD] Cache degli attributi ricreata: {:?}, TTL directory {}s, file {}s (±{}), capacità LRU {}.", config.cache_strategy, config.cache_ttl(true).as_secs(), config.cache_ttl(false).as_secs(), config.cache_ttl_jitter, config.cache_lru_capacity);
+            self.attribute_cache = AttributeCache::new(&config);
+            let ttl = config.cache_ttl(true);
+            let root = self.root_attr();
+            self.attribute_cache.put(1, root, ttl);
+        }
+        if config.server_retry_window_seconds != self.config.server_retry_window_seconds {
+            println!("[RELOAD] Finestra di retry verso il server: {}s.", config.server_retry_window_seconds);
+            self.client.retry_state().set_window(Duration::from_secs(config.server_retry_window_seconds));
+        }
+        if config.warmup_recent_paths != self.config.warmup_recent_paths {
+            println!("[RELOAD] Path recenti da rimettere in cache alla riconnessione: {}.", config.warmup_recent_paths);
+            self.recent_paths.resize(config.warmup_recent_paths);
+        }
+        if config.max_concurrent_requests != self.config.max_concurrent_requests {
+            println!("[RELOAD] Richieste concorrenti massime: {}.", config.max_concurrent_requests);
+            self.request_limit = Arc::new(Semaphore::new(request_permits(config.max_concurrent_requests)));
+        }
+
+        self.config = config;
+        println!("[RELOAD] Configurazione applicata: {:?}", self.config);
+    }
+}
+
+/// Builds the Tokio runtime shared by all the mounts of the process, with
+/// `worker_threads` threads (`runtime_worker_threads`, `0` = one per CPU core).
+pub fn build_runtime(worker_threads: usize) -> Arc<tokio::runtime::Runtime> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    if worker_threads > 0 {
+        builder.worker_threads(worker_threads);
+    }
+    Arc::new(builder.enable_all().build().unwrap())
+}
+
+/// Permits of the `request_limit` semaphore for `max_concurrent_requests` (`0` = unlimited).
+fn request_permits(max_concurrent_requests: usize) -> usize {
+    match max_concurrent_requests {
+        0 => Semaphore::MAX_PERMITS,
+        n => n,
+    }
 }
 
 #[derive(Clone)]
 pub struct FsWrapper(pub Arc<Mutex<RemoteFS>>);
 
+impl FsWrapper {
+    /// Locks the state for the FUSE operation `name` on `ino`, and starts timing it.
+    /// The caller passes the `OpTimer` back with `OpTimer::finish` once it replied.
+    fn lock_op(&self, name: &'static str, ino: u64) -> (MutexGuard<'_, RemoteFS>, timing::OpTimer) {
+        let started = Instant::now();
+        let fs = self.0.lock().unwrap();
+        let timer = timing::OpTimer::start(&fs, name, ino, started);
+        (fs, timer)
+    }
+}
+
 /// Main FUSE trait implementation.
 ///
 /// This block acts as a simple "dispatcher" or "router". All FUSE kernel
 /// calls land here, and are immediately forwarded to the appropriate
-/// function in one of the sub-modules (e.g., `attr::getattr`).
+/// function in one of the sub-modules (e.g., `attr::getattr`), timed by
+/// `timing::OpTimer` for `slow_op_threshold_ms`.
 impl Filesystem for FsWrapper {
     // --- Attribute Operations (attr.rs) ---
 
     /// Delegates `getattr` to `attr::getattr`.
     fn getattr(&mut self, req: &Request, ino: u64, reply: ReplyAttr) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("getattr", ino);
         attr::getattr(&mut fs, req, ino, reply);
+        timer.finish(&mut fs);
     }
 
     /// Delegates `setattr` to `attr::setattr`.
     fn setattr(&mut self, req: &Request<'_>, ino: u64, mode: Option<u32>, uid: Option<u32>, gid: Option<u32>, size: Option<u64>, atime: Option<fuser::TimeOrNow>, mtime: Option<fuser::TimeOrNow>, ctime: Option<std::time::SystemTime>, fh: Option<u64>, crtime: Option<std::time::SystemTime>, chgtime: Option<std::time::SystemTime>, bkuptime: Option<std::time::SystemTime>, flags: Option<u32>, reply: ReplyAttr) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("setattr", ino);
         attr::setattr(&mut fs, req, ino, mode, uid, gid, size, atime, mtime, ctime, fh, crtime, chgtime, bkuptime, flags, reply);
+        timer.finish(&mut fs);
     }
 
     // --- Read Operations (read.rs) ---
 
     /// Delegates `lookup` to `read::lookup`.
     fn lookup(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("lookup", parent);
         read::lookup(&mut fs, req, parent, name, reply);
+        timer.finish(&mut fs);
+    }
+
+    /// Delegates `opendir` to `read::opendir`.
+    fn opendir(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        let (mut fs, timer) = self.lock_op("opendir", ino);
+        read::opendir(&mut fs, req, ino, flags, reply);
+        timer.finish(&mut fs);
     }
 
     /// Delegates `readdir` to `read::readdir`.
     fn readdir(&mut self, req: &Request, ino: u64, fh: u64, offset: i64, reply: ReplyDirectory) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("readdir", ino);
         read::readdir(&mut fs, req, ino, fh, offset, reply);
+        timer.finish(&mut fs);
+    }
+
+    /// Delegates `releasedir` to `read::releasedir`.
+    fn releasedir(&mut self, req: &Request<'_>, ino: u64, fh: u64, flags: i32, reply: ReplyEmpty) {
+        let (mut fs, timer) = self.lock_op("releasedir", ino);
+        read::releasedir(&mut fs, req, ino, fh, flags, reply);
+        timer.finish(&mut fs);
     }
 
     /// Delegates `read` to `read::read`.
     fn read(&mut self, req: &Request<'_>, ino: u64, fh: u64, offset: i64, size: u32, flags: i32, lock_owner: Option<u64>, reply: ReplyData) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("read", ino);
         read::read(&mut fs, req, ino, fh, offset, size, flags, lock_owner, reply);
+        timer.finish(&mut fs);
     }
 
     /// Delegates `open` to `read::open`.
     fn open(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("open", ino);
         read::open(&mut fs, req, ino, flags, reply);
+        timer.finish(&mut fs);
     }
 
     // --- Write Operations (write.rs) ---
 
     /// Delegates `write` to `write::write`.
     fn write(&mut self, req: &Request<'_>, ino: u64, fh: u64, offset: i64, data: &[u8], write_flags: u32, flags: i32, lock_owner: Option<u64>, reply: ReplyWrite) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("write", ino);
         write::write(&mut fs, req, ino, fh, offset, data, write_flags, flags, lock_owner, reply);
+        timer.finish(&mut fs);
     }
 
     /// Delegates `release` to `write::release`.
     fn release(&mut self, _req: &Request<'_>, _ino: u64, _fh: u64, _flags: i32, _lock_owner: Option<u64>, _flush: bool, reply: ReplyEmpty) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("release", _ino);
         write::release(&mut fs, _req, _ino, _fh, _flags, _lock_owner, _flush, reply);
+        timer.finish(&mut fs);
     }
 
     /// Delegates `flush` to `write::flush`.
     fn flush(&mut self, _req: &Request<'_>, _ino: u64, _fh: u64, _lock_owner: u64, reply: ReplyEmpty) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("flush", _ino);
         write::flush(&mut fs, _req, _ino, _fh, _lock_owner, reply);
+        timer.finish(&mut fs);
+    }
+
+    /// Delegates `fallocate` to `write::fallocate`.
+    fn fallocate(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, length: i64, mode: i32, reply: ReplyEmpty) {
+        let (mut fs, timer) = self.lock_op("fallocate", ino);
+        write::fallocate(&mut fs, ino, offset, length, mode, reply);
+        timer.finish(&mut fs);
     }
 
     // --- Create Operations (create.rs) ---
 
     /// Delegates `create` to `create::create`.
     fn create(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, mode: u32, umask: u32, flags: i32, reply: ReplyCreate) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("create", parent);
         create::create(&mut fs, req, parent, name, mode, umask, flags, reply);
+        timer.finish(&mut fs);
     }
 
     /// Delegates `mkdir` to `create::mkdir`.
     fn mkdir(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, mode: u32, umask: u32, reply: ReplyEntry) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("mkdir", parent);
         create::mkdir(&mut fs, req, parent, name, mode, umask, reply);
+        timer.finish(&mut fs);
     }
 
     // --- Delete Operations (delete.rs) ---
 
     /// Delegates `unlink` to `delete::unlink`.
     fn unlink(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("unlink", parent);
         delete::unlink(&mut fs, req, parent, name, reply);
+        timer.finish(&mut fs);
     }
 
     /// Delegates `rmdir` to `delete::rmdir`.
     fn rmdir(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("rmdir", parent);
         delete::rmdir(&mut fs, req, parent, name, reply);
+        timer.finish(&mut fs);
     }
 
     // --- Rename Operations (rename.rs) ---
 
     /// Delegates `rename` to `rename::rename`.
     fn rename(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, newparent: u64, newname: &OsStr, flags: u32, reply: ReplyEmpty) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("rename", parent);
         rename::rename(&mut fs, req, parent, name, newparent, newname, flags, reply);
+        timer.finish(&mut fs);
+    }
+
+    // --- IOCTL Operations (ioctl.rs) ---
+
+    /// Delegates `ioctl` to `ioctl::ioctl`.
+    fn ioctl(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, _flags: u32, cmd: u32, _in_data: &[u8], _out_size: u32, reply: ReplyIoctl) {
+        let (mut fs, timer) = self.lock_op("ioctl", ino);
+        ioctl::ioctl(&mut fs, ino, cmd, reply);
+        timer.finish(&mut fs);
+    }
+
+    // --- Block Mapping ---
+
+    /// Handles `bmap` (`FIBMAP`, used by e.g. bootloaders and `filefrag`): always `ENOSYS`.
+    ///
+    /// Files live on the server, not on a block device, so there is no block to
+    /// map. The kernel remembers `ENOSYS` and stops sending `bmap` for this mount.
+    fn bmap(&mut self, _req: &Request<'_>, ino: u64, _blocksize: u32, idx: u64, reply: ReplyBmap) {
+        println!("[BMAP] Inode {}, blocco {}: non supportato (nessun dispositivo a blocchi)", ino, idx);
+        reply.error(libc::ENOSYS);
     }
 
     // --- XATTR Operations (xattr.rs) [macOS Support] ---
 
     fn getxattr(&mut self, req: &Request, ino: u64, name: &OsStr, size: u32, reply: ReplyXattr) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("getxattr", ino);
         xattr::getxattr(&mut fs, req, ino, name, size, reply);
+        timer.finish(&mut fs);
     }
 
     fn setxattr(&mut self, req: &Request, ino: u64, name: &OsStr, value: &[u8], flags: i32, position: u32, reply: ReplyEmpty) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("setxattr", ino);
         xattr::setxattr(&mut fs, req, ino, name, value, flags, position, reply);
+        timer.finish(&mut fs);
     }
 
     fn listxattr(&mut self, req: &Request, ino: u64, size: u32, reply: ReplyXattr) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("listxattr", ino);
         xattr::listxattr(&mut fs, req, ino, size, reply);
+        timer.finish(&mut fs);
     }
 
     fn removexattr(&mut self, req: &Request, ino: u64, name: &OsStr, reply: ReplyEmpty) {
-        let mut fs = self.0.lock().unwrap();
+        let (mut fs, timer) = self.lock_op("removexattr", ino);
         xattr::removexattr(&mut fs, req, ino, name, reply);
+        timer.finish(&mut fs);
     }
 }
\ No newline at end of file
@@ -0,0 +1,61 @@
+use std::ffi::OsStr;
+use std::path::Path;
+use fuser::Notifier;
+use super::RemoteFS;
+
+/// A kernel cache entry to drop after a change made by another client.
+///
+/// The invalidations are collected while holding the `RemoteFS` lock and sent
+/// with `send` after releasing it: while processing a notification the kernel
+/// may wait for FUSE requests in flight on the same inode, and those need the lock.
+#[derive(Debug)]
+pub enum KernelInvalidation {
+    /// The dentry `name` inside the directory `parent` (also negative dentries).
+    Entry { parent: u64, name: String },
+    /// The attributes and the page cache of an inode.
+    Inode(u64),
+}
+
+/// Lists the kernel caches affected by a change of the server path `path`.
+///
+/// * The dentry of `path` in its parent, so the next `lookup` asks the client again
+///   (a new file becomes visible, a deleted one disappears).
+/// * The inode of `path`, if the client knows it: its attributes and content.
+/// * The inode of the parent directory, whose size and mtime have changed.
+///
+/// Paths without an inode are skipped: if the client never returned (or has since
+/// forgotten) an inode, the kernel holds no cache for it that the client can address.
+pub fn invalidations_for_path(fs: &RemoteFS, path: &str) -> Vec<KernelInvalidation> {
+    let mut invalidations = Vec::new();
+    let parent_path = Path::new(path).parent().map_or(String::new(), |p| p.to_string_lossy().to_string());
+    let name = Path::new(path).file_name().map(|n| n.to_string_lossy().to_string());
+
+    if let Some(parent) = fs.inodes.inode(&parent_path) {
+        if let Some(name) = name {
+            invalidations.push(KernelInvalidation::Entry { parent, name });
+        }
+        invalidations.push(KernelInvalidation::Inode(parent));
+    }
+    if let Some(ino) = fs.inodes.inode(path) {
+        invalidations.push(KernelInvalidation::Inode(ino));
+    }
+    invalidations
+}
+
+/// Sends the invalidations to the kernel. Must be called WITHOUT holding the `RemoteFS` lock.
+///
+/// `ENOENT` means the kernel had nothing cached for that entry/inode and is not an error.
+pub fn send(notifier: &Notifier, invalidations: Vec<KernelInvalidation>) {
+    for invalidation in invalidations {
+        let result = match &invalidation {
+            KernelInvalidation::Entry { parent, name } => notifier.inval_entry(*parent, OsStr::new(name)),
+            // offset 0, len 0: tutti gli attributi e tutte le pagine in cache
+            KernelInvalidation::Inode(ino) => notifier.inval_inode(*ino, 0, 0),
+        };
+        match result {
+            Ok(()) => {}
+            Err(e) if e.raw_os_error() == Some(libc::ENOENT) => {}
+            Err(e) => eprintln!("[WATCHER_CLIENT] Invalidazione del kernel fallita ({:?}): {}", invalidation, e),
+        }
+    }
+}
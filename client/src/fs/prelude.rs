@@ -9,7 +9,7 @@
 pub use fuser::{
     FileAttr, FileType, ReplyAttr, ReplyCreate, ReplyData,
     ReplyDirectory, ReplyEntry, ReplyOpen, ReplyWrite, Request, ReplyEmpty,
-    TimeOrNow,
+    TimeOrNow, ReplyIoctl,
     // --- MACOS ---
     ReplyXattr
 };
@@ -21,15 +21,31 @@ pub use libc::{
     ENOENT,  // File/Dir non trovata
     EBADF,   // Bad file descriptor
     ENOTEMPTY, // Directory non vuota
+    EEXIST,  // File già esistente
+    EINVAL,  // Argomento non valido
+    EACCES,  // Permesso negato
+    EFBIG,   // File troppo grande
+    EOPNOTSUPP, // Operazione non supportata
+    EBUSY,   // Risorsa occupata
+    EPERM,   // Operazione non permessa
+    ENOTTY,  // Comando ioctl non riconosciuto
+    EISDIR,  // È una directory
+    ENAMETOOLONG, // Path troppo profondo o nome troppo lungo
 };
 #[cfg(not(target_os = "macos"))]
 pub use libc::ENODATA;
 #[cfg(target_os = "macos")]
 pub use libc::ENOATTR;
 
+// --- Rename Flags ---
+/// Re-exports the `renameat2` flags under their Linux names (macOS calls them `RENAME_EXCL`/`RENAME_SWAP`).
+#[cfg(not(target_os = "macos"))]
+pub use libc::{RENAME_EXCHANGE, RENAME_NOREPLACE};
+#[cfg(target_os = "macos")]
+pub use libc::{RENAME_SWAP as RENAME_EXCHANGE, RENAME_EXCL as RENAME_NOREPLACE};
+
 // --- Standard Library Types ---
 /// Re-exports common types from the Rust standard library.
-pub use std::collections::HashMap;
 pub use std::ffi::OsStr;
 pub use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -44,10 +60,31 @@ pub use crate::api_client::{
     put_file_content_to_server,
     get_file_content_from_server,
     get_files_from_server,
+    get_files_if_modified,
+    stream_files_from_server,
+    get_files_page,
+    touch_file,
     delete_resource,
     create_directory,
     update_permissions,
-    get_file_chunk_from_server
+    get_file_chunk_from_server,
+    stat_resource,
+    head_file_from_server,
+    rename_resource,
+    exchange_resources,
+    update_times,
+    truncate_on_server,
+    write_at_offset,
+    fallocate_on_server,
+    start_upload,
+    get_upload_offset,
+    upload_chunk,
+    complete_upload,
+    get_tree,
+    delete_batch,
+    stat_batch,
+    ApiError,
+    RemoteEntry
 };
 
 // --- Internal `fs` Module Types ---
@@ -55,6 +92,10 @@ pub use crate::api_client::{
 pub use super::{
     RemoteFS,      // The main filesystem state struct
     OpenWriteFile, // The struct for the in-memory write cache
+    UploadSession, // An unfinished chunked upload that can be resumed
+    TimeOverride,  // Timestamps set locally through `setattr`
+    ReadBlock,     // The last block downloaded by `read`
     TTL,           // The default Time-To-Live for kernel caches
-    ROOT_DIR_ATTR, // The static attributes for the root directory
+    join_path,     // Joins a directory path and an entry name
+    utf8_name,     // A name from the kernel as UTF-8, or EINVAL
 };
\ No newline at end of file
@@ -5,40 +5,55 @@ use super::prelude::*;
 /// This is called by the kernel to find a file or directory by name within a
 /// parent directory.
 ///
-/// 1. It fetches the parent directory's contents from the remote server.
+/// 1. It fetches the parent directory's contents from the remote server, or
+///    revalidates the cached listing (see `list_directory`).
 /// 2. It searches the list for an entry matching `name`.
 /// 3. If found, it gets or creates a new inode for that entry, storing the
 ///    path-to-inode and inode-to-path mappings.
 /// 4. It then calls `fetch_and_cache_attributes` to get the full metadata
 ///    (either from the cache or a fresh server call) and replies with it.
 ///
+/// Entries hidden by `include_globs`/`exclude_globs` are reported as `ENOENT`.
+///
+/// With `serve_stale_on_error`, if the server cannot be reached, an entry the
+/// client already knows is answered with its cached attributes (see `stale_lookup`).
+///
 /// # Arguments
 /// * `fs` - The mutable `RemoteFS` state.
 /// * `parent` - The inode of the directory to search within.
 /// * `name` - The name of the entry to look up.
 /// * `reply` - The reply object to send the entry's attributes back.
 pub fn lookup(fs: &mut RemoteFS, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
-    let parent_path = match fs.inode_to_path.get(&parent) {
-        Some(p) => p.clone(),
+    if let Some(attr) = crate::fs::control::lookup(fs, parent, name) {
+        reply.entry(&TTL, &attr, 0);
+        return;
+    }
+    let parent_path = match fs.inodes.path(parent) {
+        Some(p) => p,
         None => { reply.error(ENOENT); return; }
     };
 
-    let entry_list = match fs.runtime.block_on(get_files_from_server(&fs.client, &parent_path, &fs.config.server_url)) {
-        Ok(list) => list,
-        Err(_) => { reply.error(ENOENT); return; }
+    // Il server non può avere un file con un nome non UTF-8: non esiste
+    let Some(name_str) = name.to_str() else {
+        reply.error(ENOENT);
+        return;
     };
+    let full_path = join_path(&parent_path, name_str);
 
-    let name_str = name.to_str().unwrap();
-    if let Some(_entry) = entry_list.iter().find(|e| e.name == name_str) {
-        let full_path = if parent_path.is_empty() { name_str.to_string() } else { format!("{}/{}", parent_path, name_str) };
+    let entry_list = match list_directory(fs, &parent_path) {
+        Ok(list) => list,
+        Err(e) => {
+            match stale_lookup(fs, &full_path, &e) {
+                Some(attr) => reply.entry(&TTL, &attr, 0),
+                None => reply.error(ENOENT),
+            }
+            return;
+        }
+    };
+    if let Some(entry) = entry_list.iter().find(|e| e.name == name_str && !fs.is_hidden(&full_path, e.is_dir())) {
 
-        // Get or create a new inode for this path
-        let inode = *fs.path_to_inode.entry(full_path.clone()).or_insert_with_key(|_key| {
-            let new_ino = fs.next_inode;
-            fs.next_inode += 1;
-            fs.inode_to_path.insert(new_ino, full_path);
-            new_ino
-        });
+        // Get or create a new inode for this path (also re-resolves evicted paths)
+        let inode = fs.track_entry(&full_path, entry.ino, entry.is_dir());
 
         // Get attributes (from cache or server) and reply
         if let Some(attr) = crate::fs::attr::fetch_and_cache_attributes(fs, inode) {
@@ -51,110 +66,399 @@ pub fn lookup(fs: &mut RemoteFS, _req: &Request, parent: u64, name: &OsStr, repl
     }
 }
 
+/// Lists `dir_path`, reusing the cached listing while the directory is unchanged.
+///
+/// A listing the server sent with a `Last-Modified` (the directory's mtime) is kept
+/// in `fs.dir_listings`; the next call sends that date as `If-Modified-Since`, and a
+/// `304` reuses the cached entries without transferring them again. Only the
+/// directory's mtime is checked: use the entries for names, kinds and inode numbers,
+/// not for the metadata of the files, which can change without touching it.
+///
+/// # Returns
+/// The entries of the directory, or the error of the request.
+pub(crate) fn list_directory(fs: &mut RemoteFS, dir_path: &str) -> Result<Vec<RemoteEntry>, ApiError> {
+    let since = fs.dir_listings.get(dir_path).and_then(|listing| listing.last_modified.clone());
+    match fs.block_on(get_files_if_modified(&fs.client, dir_path, since.as_deref(), &fs.config.server_url))? {
+        Some(listing) => {
+            let entries = listing.entries.clone();
+            if listing.last_modified.is_some() {
+                fs.dir_listings.put(dir_path.to_string(), listing);
+            } else {
+                fs.dir_listings.pop(dir_path);
+            }
+            Ok(entries)
+        }
+        None => {
+            println!("[LISTING] '{}' invariata (304), uso la copia in cache", dir_path);
+            Ok(fs.dir_listings.get(dir_path).map(|listing| listing.entries.clone()).unwrap_or_default())
+        }
+    }
+}
+
+/// Resolves `full_path` from the client's caches after a failed listing, if
+/// `serve_stale_on_error` is enabled and `err` is a connection failure.
+///
+/// # Returns
+/// The cached (possibly expired) attributes of the inode of `full_path`, or `None`
+/// if the path is unknown or the error must be reported.
+fn stale_lookup(fs: &RemoteFS, full_path: &str, err: &ApiError) -> Option<FileAttr> {
+    if !fs.config.serve_stale_on_error || !err.is_connection_error() {
+        return None;
+    }
+    let attr = fs.inodes.inode(full_path).and_then(|ino| fs.attribute_cache.get_stale(&ino))?;
+    println!("[STALE] Server irraggiungibile: lookup di '{}' servito dalla cache", full_path);
+    Some(attr)
+}
+
+/// Number of entries requested per page by the `readdir` calls after the first one.
+const READDIR_PAGE_SIZE: u64 = 256;
+
+/// Handles the FUSE `opendir` operation.
+///
+/// Takes a snapshot of the directory listing (`/list`, revalidated through
+/// `list_directory`) and stores it under a new
+/// directory handle, so that all the `readdir` calls of one `opendir`/`closedir`
+/// cycle iterate the same listing: files created or deleted on the server in the
+/// meantime cannot shift the offsets, which would skip or repeat entries.
+/// The snapshot is dropped by `releasedir`.
+///
+/// An inode known to be a file fails with `ENOTDIR`.
+///
+/// The control directory has no listing on the server and gets handle 0. If the
+/// listing cannot be fetched the handle is 0 as well, and `readdir` falls back
+/// to listing the directory from the server at every call.
+///
+/// # Arguments
+/// * `fs` - The mutable `RemoteFS` state.
+/// * `ino` - The inode of the directory to open.
+/// * `reply` - The reply object to send the directory handle back.
+pub fn opendir(fs: &mut RemoteFS, _req: &Request<'_>, ino: u64, _flags: i32, reply: ReplyOpen) {
+    if crate::fs::control::is_control_inode(ino) {
+        reply.opened(0, 0);
+        return;
+    }
+    if let Err(e) = fs.inodes.expect_kind(ino, true) {
+        reply.error(e);
+        return;
+    }
+    let dir_path = match fs.inodes.path(ino) {
+        Some(p) => p,
+        None => { reply.error(ENOENT); return; }
+    };
+
+    let entries = match list_directory(fs, &dir_path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("[READDIR] Snapshot di '{}' non disponibile: {}", dir_path, e);
+            reply.opened(0, 0);
+            return;
+        }
+    };
+    let fh = fs.next_fh;
+    fs.next_fh += 1;
+    fs.dir_snapshots.insert(fh, entries);
+    reply.opened(fh, 0);
+}
+
+/// Handles the FUSE `releasedir` operation (`closedir`): drops the snapshot of the handle.
+///
+/// # Arguments
+/// * `fs` - The mutable `RemoteFS` state.
+/// * `fh` - The directory handle returned by `opendir`.
+/// * `reply` - The reply object to acknowledge the release.
+pub fn releasedir(fs: &mut RemoteFS, _req: &Request<'_>, _ino: u64, fh: u64, _flags: i32, reply: ReplyEmpty) {
+    fs.dir_snapshots.remove(&fh);
+    reply.ok();
+}
+
 /// Handles the FUSE `readdir` operation (e.g., `ls`).
 ///
 /// This function lists the contents of a directory.
 ///
 /// 1. It always adds the special `.` (current) and `..` (parent) entries
 ///    for `offset == 0`.
-/// 2. It fetches the directory's contents from the remote server.
-/// 3. It iterates the list, creating inodes for any new entries, and adds
-///    each entry to the reply buffer.
-/// 4. It respects the `offset` to handle large directories that require
-///    multiple `readdir` calls.
+/// 2. If `fh` has a snapshot taken by `opendir`, the entries come from it: every
+///    call of the same handle sees the same listing, and steps 3-4 are skipped.
+///    Without a snapshot the directory is listed from the server:
+/// 3. On the first call, it streams the directory's contents from the remote
+///    server (`/list-stream`), so the reply buffer is filled while the listing
+///    is still arriving. Small directories are complete after this call.
+/// 4. When the buffer is full, the kernel calls `readdir` again from the next
+///    `offset`. These calls fetch pages of `READDIR_PAGE_SIZE` entries starting
+///    at that offset (`/list?offset=&limit=`), instead of listing the whole
+///    directory again just to skip what was already returned.
+/// 5. Every entry gets an inode and is added to the reply buffer until it is full.
+///    Entries hidden by `include_globs`/`exclude_globs` are skipped (they keep
+///    their position, so the offsets still match the server's listing).
+/// 6. The attributes of the returned entries are prefetched with a single
+///    `/stat-batch` request (see `attr::prewarm_attributes`).
+///
+/// An inode known to be a file fails with `ENOTDIR`.
+///
+/// With `list_control_files`, the root also lists `.remotefs` at offset 3, right
+/// after `..`: the server entries then start one position later.
 ///
 /// # Arguments
 /// * `fs` - The mutable `RemoteFS` state.
 /// * `ino` - The inode of the directory to read.
+/// * `fh` - The directory handle returned by `opendir`.
 /// * `offset` - The entry offset to start from.
 /// * `reply` - The reply buffer to fill with directory entries.
-pub fn readdir(fs: &mut RemoteFS, _req: &Request, ino: u64, _fh: u64, offset: i64, mut reply: ReplyDirectory) {
-    let dir_path = match fs.inode_to_path.get(&ino) {
-        Some(p) => p.clone(),
+pub fn readdir(fs: &mut RemoteFS, _req: &Request, ino: u64, fh: u64, offset: i64, mut reply: ReplyDirectory) {
+    if crate::fs::control::is_control_inode(ino) {
+        crate::fs::control::readdir(offset, reply);
+        return;
+    }
+    if let Err(e) = fs.inodes.expect_kind(ino, true) {
+        reply.error(e);
+        return;
+    }
+    let dir_path = match fs.inodes.path(ino) {
+        Some(p) => p,
         None => { reply.error(ENOENT); return; }
     };
 
-    let mut entries_to_add: Vec<(u64, FileType, String)> = vec![];
-    if offset == 0 {
-        // Add '.' entry
-        entries_to_add.push((ino, FileType::Directory, ".".to_string()));
-
-        // Add '..' entry
-        let parent_ino = if ino == 1 { 1 } else {
-            let parent_p = dir_path.rsplit_once('/').map_or("", |(p, _)| p);
-            *fs.path_to_inode.get(parent_p).unwrap_or(&1)
-        };
-        entries_to_add.push((parent_ino, FileType::Directory, "..".to_string()));
+    // Add '.' (offset 1) and '..' (offset 2) entries, unless already returned
+    if offset < 1 && reply.add(ino, 1, FileType::Directory, ".") {
+        reply.ok();
+        return;
     }
-
-    // Add server entries (only if we haven't finished with '.' and '..')
     if offset < 2 {
-        let entry_list = match fs.runtime.block_on(get_files_from_server(&fs.client, &dir_path,  &fs.config.server_url)) {
-            Ok(list) => list,
-            Err(_) => { reply.ok(); return; } // Empty dir is fine
-        };
+        let parent_ino = if ino == 1 { 1 } else { parent_inode(fs, &dir_path) };
+        if reply.add(parent_ino, 2, FileType::Directory, "..") {
+            reply.ok();
+            return;
+        }
+    }
 
-        for entry in entry_list {
-            let full_path = if dir_path.is_empty() { entry.name.clone() } else { format!("{}/{}", dir_path, &entry.name) };
-            let inode = *fs.path_to_inode.entry(full_path.clone()).or_insert_with_key(|_key| {
-                let new_ino = fs.next_inode;
-                fs.next_inode += 1;
-                fs.inode_to_path.insert(new_ino, full_path);
-                new_ino
-            });
-
-            let kind = if entry.kind.eq_ignore_ascii_case("dir") || entry.kind.eq_ignore_ascii_case("directory") { FileType::Directory } else { FileType::RegularFile };
-            fs.inode_to_type.insert(inode, kind);
-            entries_to_add.push((inode, kind, entry.name));
+    // Virtual entries between '..' and the server entries (`.remotefs` in the root)
+    let mut first_server_index = 2;
+    if crate::fs::control::listed_in(fs, ino) {
+        first_server_index += 1;
+        if offset < first_server_index && crate::fs::control::add_control_dir(&mut reply, first_server_index) {
+            reply.ok();
+            return;
         }
     }
 
-    // Add entries to the reply buffer, respecting the offset
-    for (i, (ino_to_add, kind_to_add, name_to_add)) in entries_to_add.into_iter().enumerate().skip(offset as usize) {
-        if reply.add(ino_to_add, (i + 1) as i64, kind_to_add, &name_to_add) {
-            // Buffer is full
-            break;
+    // Every entry is added with its 1-based position as offset: the offset the
+    // kernel passes to the next call to continue after it
+    let mut index = offset.max(first_server_index);
+    let mut added = Vec::new();
+
+    if let Some(snapshot) = fs.dir_snapshots.remove(&fh) {
+        // Snapshot of `opendir`: the offsets index it directly. It is taken out of
+        // the map while the entries get their inodes, then put back for the next call
+        let start = (offset.max(first_server_index) - first_server_index) as usize;
+        for entry in snapshot.iter().skip(start) {
+            index += 1;
+            if add_dir_entry(fs, &dir_path, entry, index, &mut reply, &mut added) {
+                break;
+            }
+        }
+        fs.dir_snapshots.insert(fh, snapshot);
+    } else if offset <= first_server_index {
+        // First call: add server entries as they arrive
+        let mut listing = match fs.block_on(stream_files_from_server(&fs.client, &dir_path, &fs.config.server_url)) {
+            Ok(listing) => listing,
+            Err(_) => { reply.ok(); return; } // Empty dir is fine
+        };
+        while let Ok(Some(entry)) = fs.block_on(listing.next_entry()) {
+            index += 1;
+            if add_dir_entry(fs, &dir_path, &entry, index, &mut reply, &mut added) {
+                break;
+            }
+        }
+    } else {
+        // Continuation: fetch only the pages after the entries already returned
+        // ('.', '..' and the virtual entries are not on the server)
+        let mut server_offset = Some((offset - first_server_index) as u64);
+        'pages: while let Some(page_offset) = server_offset {
+            let page = match fs.block_on(get_files_page(&fs.client, &dir_path, page_offset, READDIR_PAGE_SIZE, &fs.config.server_url)) {
+                Ok(page) => page,
+                Err(_) => break,
+            };
+            server_offset = page.next_offset;
+            for entry in page.entries {
+                index += 1;
+                if add_dir_entry(fs, &dir_path, &entry, index, &mut reply, &mut added) {
+                    break 'pages;
+                }
+            }
         }
     }
+
+    // The kernel usually stats every entry next (`ls -l`): fetch them all at once
+    crate::fs::attr::prewarm_attributes(fs, &added);
     reply.ok();
 }
 
+/// Tracks the inode of `entry` (a child of `dir_path`) and adds it to `reply`
+/// with offset `index`. Added entries are recorded in `added` with their path.
+///
+/// # Returns
+/// `true` if the reply buffer is full: the entry was not added, and the kernel
+/// will ask for it again from offset `index - 1`.
+fn add_dir_entry(fs: &mut RemoteFS, dir_path: &str, entry: &RemoteEntry, index: i64, reply: &mut ReplyDirectory, added: &mut Vec<(u64, String)>) -> bool {
+    let full_path = join_path(dir_path, &entry.name);
+    let is_dir = entry.is_dir();
+    if fs.is_hidden(&full_path, is_dir) {
+        return false;
+    }
+    let inode = fs.track_entry(&full_path, entry.ino, is_dir);
+
+    let kind = if is_dir { FileType::Directory } else { FileType::RegularFile };
+    if reply.add(inode, index, kind, &entry.name) {
+        return true;
+    }
+    added.push((inode, full_path));
+    false
+}
+
+/// Returns the inode of the parent directory of `dir_path` (never the root itself).
+///
+/// The parent may not be tracked (e.g. evicted from the inode table, or never
+/// looked up): it is then resolved on the server, so that `..` always points at
+/// the true parent instead of the root.
+fn parent_inode(fs: &mut RemoteFS, dir_path: &str) -> u64 {
+    let parent_path = dir_path.rsplit_once('/').map_or("", |(p, _)| p);
+    if fs.is_root_path(parent_path) {
+        return 1;
+    }
+    if let Some(parent_ino) = fs.inodes.inode(parent_path) {
+        return parent_ino;
+    }
+
+    let server_ino = fs.block_on(head_file_from_server(&fs.client, parent_path, &fs.config.server_url))
+        .ok()
+        .flatten()
+        .and_then(|entry| entry.ino);
+    let parent_ino = fs.track_path(parent_path, server_ino);
+    fs.inodes.set_kind(parent_ino, FileType::Directory);
+    parent_ino
+}
+
 /// Handles the FUSE `read` operation.
 ///
-/// This function fetches the *entire* file content from the server upon every
-/// read request, and then replies with the specific byte range (`offset` to
-/// `offset + size`) requested by the kernel.
+/// Reads are served in whole blocks of `transfer_block_size` bytes:
+///
+/// 1. If the last block downloaded through the handle `fh` covers the requested
+///    range, the bytes are sliced from memory without contacting the server.
+///    A read-only handle keeps its own block (`OpenReadFile`), so descriptors
+///    reading different parts of the same file do not evict each other's;
+///    reads through a write handle share `fs.read_block`.
+/// 2. Otherwise the request is widened to the enclosing block-aligned range,
+///    fetched with a single HTTP Range request, and kept as the handle's new
+///    block for subsequent calls. The response also carries the file's
+///    metadata, which refreshes the attribute cache at no extra cost.
+///
+/// With `serve_stale_on_error`, downloaded blocks are also kept in `stale_blocks`.
+/// If the server then cannot be reached, a request within one of those blocks is
+/// served from it (logged as `[STALE]`) instead of failing with `EIO`.
+///
+/// A handle opened with `O_DIRECT` skips all of this: exactly the requested range
+/// is fetched from the server, and is neither kept in `read_block` nor served stale.
+///
+/// A read of `size == 0` succeeds at once with no data, at any offset (past EOF too).
 ///
 /// # Arguments
 /// * `fs` - The mutable `RemoteFS` state.
 /// * `ino` - The inode of the file to read.
 /// * `offset` - The byte offset in the file to start reading from.
 /// * `size` - The maximum number of bytes to read.
-/// * `reply` - The reply object to send the data bytes back.
-pub fn read(fs: &mut RemoteFS, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, size: u32, _flags: i32, _lock_owner: Option<u64>, reply: ReplyData) {
-    if let Some(file_path) = fs.inode_to_path.get(&ino) {
-
-        // Fetch the entire file content
-        let content_result = fs.runtime.block_on(async {
-            get_file_chunk_from_server(
-                &fs.client,
-                file_path,
-                offset as u64,
-                size,
-                &fs.config.server_url
-            ).await
-        });
-
-        match content_result {
-            Ok(content) => {
-                reply.data(&content);
-            },
-            Err(_) => {
-                reply.error(EIO);
+/// * `reply` - The reply object to send the data bytes back, or `EINVAL` for a
+///   negative offset or a range ending past `u64::MAX`.
+pub fn read(fs: &mut RemoteFS, _req: &Request<'_>, ino: u64, fh: u64, offset: i64, size: u32, _flags: i32, _lock_owner: Option<u64>, reply: ReplyData) {
+    if crate::fs::control::is_control_inode(ino) {
+        crate::fs::control::read(fs, ino, offset, size, reply);
+        return;
+    }
+    let file_path = match fs.inodes.path(ino) {
+        Some(p) => p,
+        None => { reply.error(ENOENT); return; }
+    };
+
+    // All the offset math is done in `u64` with checked operations, so that huge
+    // offsets (or 32-bit builds) never wrap around or panic
+    let Some((start, end)) = u64::try_from(offset).ok().and_then(|start| Some((start, start.checked_add(size as u64)?))) else {
+        reply.error(EINVAL);
+        return;
+    };
+    // Nothing to read: no request to the server (whose range would be empty), even past EOF
+    if size == 0 {
+        reply.data(&[]);
+        return;
+    }
+    let direct = fs.direct_handles.contains(&fh);
+    if let Some(handle) = fs.read_handles.get_mut(&fh) {
+        handle.record_read(start, end);
+    }
+
+    // 1. Serve from the last block downloaded through this handle if possible
+    let last_block = match fs.read_handles.get(&fh) {
+        Some(handle) => handle.block.as_ref(),
+        None => fs.read_block.as_ref(),
+    };
+    if !direct && let Some(data) = last_block.and_then(|b| b.slice(ino, start, end)) {
+        reply.data(&data);
+        return;
+    }
+
+    // 2. Fetch the enclosing block-aligned range (exactly the requested one for O_DIRECT)
+    let (block_start, block_len) = if direct {
+        (start, size as u64)
+    } else {
+        let block_size = fs.config.transfer_block_size.max(1) as u64;
+        let block_start = start - start % block_size;
+        let block_end = end.div_ceil(block_size).checked_mul(block_size).unwrap_or(end);
+        (block_start, block_end - block_start)
+    };
+
+    let content_result = fs.block_on(async {
+        get_file_chunk_from_server(
+            &fs.client,
+            &file_path,
+            block_start,
+            u32::try_from(block_len).unwrap_or(u32::MAX),
+            &fs.config.server_url
+        ).await
+    });
+
+    match content_result {
+        Ok((content, entry)) => {
+            if let Some(entry) = entry {
+                crate::fs::attr::cache_entry_attributes(fs, ino, &entry);
             }
+            let block = ReadBlock {
+                ino,
+                offset: block_start,
+                eof: (content.len() as u64) < block_len,
+                data: content,
+            };
+            reply.data(&block.slice(ino, start, end).unwrap_or_default());
+            if direct {
+                return;
+            }
+            if fs.config.serve_stale_on_error {
+                fs.stale_blocks.put((ino, block_start), block.clone());
+            }
+            match fs.read_handles.get_mut(&fh) {
+                Some(handle) => handle.block = Some(block),
+                None => fs.read_block = Some(block),
+            }
+        },
+        Err(e) if !direct && fs.config.serve_stale_on_error && e.is_connection_error() => {
+            match fs.stale_blocks.get(&(ino, block_start)).and_then(|b| b.slice(ino, start, end)) {
+                Some(data) => {
+                    println!("[STALE] Server irraggiungibile: lettura di '{}' ({} byte da {}) servita dalla cache", file_path, data.len(), start);
+                    reply.data(&data);
+                }
+                None => reply.error(EIO),
+            }
+        },
+        Err(_) => {
+            reply.error(EIO);
         }
-    } else {
-        reply.error(ENOENT);
     }
 }
 
@@ -162,12 +466,17 @@ pub fn read(fs: &mut RemoteFS, _req: &Request<'_>, ino: u64, _fh: u64, offset: i
 ///
 /// This function is critical for the write-caching strategy.
 ///
-/// - If a file is opened for **reading only**, it replies with a dummy
-///   file handle (`fh = 0`).
+/// - If a file is opened for **reading only**, it replies with a new file
+///   handle whose read state (last offset, sequential reads, last block) is
+///   kept in `fs.read_handles` until `release` (see `OpenReadFile`).
 /// - If a file is opened for **writing** (with `O_WRONLY` or `O_RDWR`), it
-///   generates a new, unique file handle (`fh`), creates an empty in-memory
-///   write buffer (`OpenWriteFile`), and stores it in the `fs.open_files` map.
+///   fails with `EISDIR` on a directory. Otherwise it
+///   generates a new, unique file handle (`fh`) bound to the inode's in-memory
+///   write buffer (`OpenWriteFile` in `fs.open_files`). The buffer is created
+///   empty by the first write handle and shared by the following ones.
 ///   This `fh` is then used by subsequent `write` and `release` calls.
+/// - With `O_DIRECT` the handle bypasses the content caches, see
+///   `RemoteFS::register_direct_handle`.
 ///
 /// # Arguments
 /// * `fs` - The mutable `RemoteFS` state.
@@ -181,38 +490,43 @@ pub fn open(
     flags: i32,
     reply: ReplyOpen,
 ) {
+    // Control files have no buffer and are read uncached: their content changes at every write
+    if crate::fs::control::is_control_inode(ino) {
+        reply.opened(0, fuser::consts::FOPEN_DIRECT_IO);
+        return;
+    }
+
     // Check if the open flags include write access
     // (O_WRONLY = 1, O_RDWR = 2)
     let write_access = (flags & libc::O_WRONLY != 0) || (flags & libc::O_RDWR != 0);
 
     if write_access {
         // --- WRITE PATH ---
-        let relative_path = match fs.inode_to_path.get(&ino) {
-            Some(p) => p.clone(),
+        // A directory never gets a write buffer
+        if let Err(e) = fs.inodes.expect_kind(ino, false) {
+            reply.error(e);
+            return;
+        }
+        let relative_path = match fs.inodes.path(ino) {
+            Some(p) => p,
             None => {
                 reply.error(ENOENT);
                 return;
             }
         };
 
-        // Generate a new, unique file handle
-        let fh = fs.next_fh;
-        fs.next_fh += 1;
-
-        // Create a new, empty write cache for this handle
-        let open_file = OpenWriteFile {
-            path: relative_path,
-            buffer: HashMap::new(), // Buffer always starts empty
-        };
-
-        fs.open_files.insert(fh, open_file);
+        // Generate a new, unique file handle on the inode's write cache
+        let fh = fs.open_write_handle(ino, relative_path);
+        let open_flags = fs.register_direct_handle(fh, flags);
 
         // Reply with the new file handle
-        reply.opened(fh, 0);
+        reply.opened(fh, open_flags);
 
     } else {
         // --- READ-ONLY PATH ---
-        // No special handle needed for reading.
-        reply.opened(0, 0);
+        // Un handle proprio, con la sua posizione e il suo blocco (vedi `OpenReadFile`)
+        let fh = fs.open_read_handle(ino);
+        let open_flags = fs.register_direct_handle(fh, flags);
+        reply.opened(fh, open_flags);
     }
 }
\ No newline at end of file
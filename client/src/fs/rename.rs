@@ -23,44 +23,52 @@ fn recursive_move_client_side(
 ) -> Result<(), libc::c_int> {
 
     // 1. Create the new destination directory
-    if fs.runtime.block_on(create_directory(&fs.client, new_path, &fs.config.server_url)).is_err() {
+    match fs.block_on(create_directory(&fs.client, new_path, true, &fs.config.server_url)) {
+        Ok(()) => {}
+        // Over the server's path limits (e.g. a directory moved into itself, level after level)
+        Err(ApiError::BadRequest) => return Err(ENAMETOOLONG),
         // This might fail if the dir already exists, but for a rename,
         // it should be a new path. We treat this as a critical error.
-        return Err(EIO);
+        Err(_) => return Err(EIO),
     }
 
     // 2. List the contents of the old directory
-    let entry_list = match fs.runtime.block_on(get_files_from_server(&fs.client, old_path,  &fs.config.server_url)) {
+    let entry_list = match fs.block_on(get_files_from_server(&fs.client, old_path,  &fs.config.server_url)) {
         Ok(list) => list,
         Err(_) => return Err(EIO),
     };
 
     // 3. Move all children recursively
     for entry in entry_list {
-        let old_child_path = format!("{}/{}", old_path, entry.name);
-        let new_child_path = format!("{}/{}", new_path, entry.name);
+        let old_child_path = join_path(old_path, &entry.name);
+        let new_child_path = join_path(new_path, &entry.name);
 
         if entry.kind == "directory" {
             // Recursive call for subdirectories
             recursive_move_client_side(fs, &old_child_path, &new_child_path)?;
         } else {
             // "Copy + Delete" logic for files
-            let content = match fs.runtime.block_on(get_file_content_from_server(&fs.client, &old_child_path,  &fs.config.server_url)) {
+            let content = match fs.block_on(get_file_content_from_server(&fs.client, &old_child_path, fs.config.verify_checksums, &fs.config.server_url)) {
                 Ok(c) => c,
+                Err(ApiError::ChecksumMismatch) => return Err(EIO),
                 Err(_) => return Err(ENOENT),
             };
-            if fs.runtime.block_on(put_file_content_to_server(&fs.client, &new_child_path, content,  &fs.config.server_url)).is_err() {
-                return Err(EIO);
+            // The copy keeps the original modification time
+            let mtime = Some(UNIX_EPOCH + Duration::from_secs(entry.mtime.max(0) as u64));
+            match fs.block_on(put_file_content_to_server(&fs.client, &new_child_path, content, None, mtime, &fs.config.server_url)) {
+                Ok(()) => {}
+                Err(ApiError::BadRequest) => return Err(ENAMETOOLONG),
+                Err(_) => return Err(EIO),
             }
             // Delete the old file after successful copy
-            if fs.runtime.block_on(delete_resource(&fs.client, &old_child_path, &fs.config.server_url)).is_err() {
+            if fs.block_on(delete_resource(&fs.client, &old_child_path, &fs.config.server_url)).is_err() {
                 return Err(EIO);
             }
         }
     }
 
     // 4. Delete the now-empty old directory
-    if fs.runtime.block_on(delete_resource(&fs.client, old_path, &fs.config.server_url)).is_err() {
+    if fs.block_on(delete_resource(&fs.client, old_path, &fs.config.server_url)).is_err() {
         return Err(EIO);
     }
 
@@ -70,8 +78,13 @@ fn recursive_move_client_side(
 
 /// Handles the FUSE `rename` operation (e.g., `mv old.txt dir/new.txt`).
 ///
-/// This function implements the move logic entirely on the client side,
-/// using only the existing server API endpoints.
+/// # Same-Directory Logic
+/// When `parent == newparent`, the rename is delegated to the server's
+/// `/rename` endpoint, which performs it atomically in a single call.
+/// This is the common case (e.g., editors saving `file~` then renaming it onto `file`).
+///
+/// Moves across directories are implemented entirely on the client side,
+/// using only the basic file endpoints:
 ///
 /// # File Logic
 /// 1. Fetches (`GET`) the content of the source file.
@@ -84,8 +97,17 @@ fn recursive_move_client_side(
 ///    moves all child files (using the file logic), and then
 ///    deletes the original directory structure.
 ///
+/// # Flags
+/// * `RENAME_NOREPLACE`: fails with `EEXIST` if the destination already exists.
+/// * `RENAME_EXCHANGE`: atomically swaps source and destination via the server's
+///   `/exchange` endpoint. Fails with `EINVAL` if the destination does not exist.
+/// * No flags: the destination is silently overwritten.
+///
+/// A destination hidden by `include_globs`/`exclude_globs` is refused with `EPERM`,
+/// since the entry would disappear from the mount.
+///
 /// # Warning
-/// This operation is **NOT ATOMIC** and may be slow for large directories.
+/// Cross-directory moves are **NOT ATOMIC** and may be slow for large directories.
 ///
 /// # Arguments
 /// * `fs` - The mutable `RemoteFS` state.
@@ -93,50 +115,95 @@ fn recursive_move_client_side(
 /// * `name` - The name of the source file/directory.
 /// * `newparent` - The inode of the destination directory.
 /// * `newname` - The new name for the file/directory.
+/// * `flags` - The `renameat2` flags (`RENAME_NOREPLACE`, `RENAME_EXCHANGE`).
 /// * `reply` - The reply object to send success or an error code.
-pub fn rename(fs: &mut RemoteFS, _req: &Request<'_>, parent: u64, name: &OsStr, newparent: u64, newname: &OsStr, _flags: u32, reply: ReplyEmpty) {
-    let old_parent_path = match fs.inode_to_path.get(&parent) {
-        Some(p) => p.clone(),
+pub fn rename(fs: &mut RemoteFS, _req: &Request<'_>, parent: u64, name: &OsStr, newparent: u64, newname: &OsStr, flags: u32, reply: ReplyEmpty) {
+    let old_parent_path = match fs.inodes.path(parent) {
+        Some(p) => p,
         None => {
             reply.error(ENOENT);
             return;
         }
     };
-    let new_parent_path = match fs.inode_to_path.get(&newparent) {
-        Some(p) => p.clone(),
+    let new_parent_path = match fs.inodes.path(newparent) {
+        Some(p) => p,
         None => {
             reply.error(ENOENT);
             return;
         }
     };
 
-    let old_name = name.to_str().unwrap();
-    let new_name = newname.to_str().unwrap();
-
-    let old_full_path = if old_parent_path.is_empty() {
-        old_name.to_string()
-    } else {
-        format!("{}/{}", old_parent_path, old_name)
+    let (old_name, new_name) = match (utf8_name(name, "rename"), utf8_name(newname, "rename")) {
+        (Ok(old_name), Ok(new_name)) => (old_name, new_name),
+        (Err(e), _) | (_, Err(e)) => {
+            reply.error(e);
+            return;
+        }
     };
 
-    let new_full_path = if new_parent_path.is_empty() {
-        new_name.to_string()
-    } else {
-        format!("{}/{}", new_parent_path, new_name)
-    };
+    let old_full_path = join_path(&old_parent_path, old_name);
+    let new_full_path = join_path(&new_parent_path, new_name);
 
-    let inode = match fs.path_to_inode.get(&old_full_path) {
-        Some(&ino) => ino,
+    let inode = match fs.inodes.inode(&old_full_path) {
+        Some(ino) => ino,
         None => {
             reply.error(ENOENT);
             return;
         }
     };
 
-    let is_dir = fs.inode_to_type.get(&inode).copied() == Some(FileType::Directory);
+    let exchange = flags & RENAME_EXCHANGE != 0;
+    let no_replace = flags & RENAME_NOREPLACE != 0;
+    if exchange && no_replace {
+        reply.error(EINVAL);
+        return;
+    }
+
+    // --- FLAG CHECKS ---
+    if exchange || no_replace {
+        let destination = match fs.block_on(stat_resource(&fs.client, &new_full_path, &fs.config.server_url)) {
+            Ok(d) => d,
+            Err(_) => { reply.error(EIO); return; }
+        };
+        if no_replace && destination.is_some() {
+            reply.error(EEXIST);
+            return;
+        }
+        if exchange && destination.is_none() {
+            reply.error(EINVAL);
+            return;
+        }
+    }
+
+    if exchange {
+        if fs.block_on(exchange_resources(&fs.client, &old_full_path, &new_full_path, &fs.config.server_url)).is_err() {
+            reply.error(EIO);
+            return;
+        }
+        fs.relocate_cached_paths(&[(&old_full_path, &new_full_path), (&new_full_path, &old_full_path)]);
+        invalidate_parents(fs, &old_parent_path, &new_parent_path);
+        fs.save_inode_map();
+        reply.ok();
+        return;
+    }
+
+    let is_dir = fs.inodes.kind(inode) == Some(FileType::Directory);
+    if fs.is_hidden(&new_full_path, is_dir) {
+        reply.error(EPERM);
+        return;
+    }
 
     // --- LOGIC DISPATCH ---
-    if is_dir {
+    if parent == newparent {
+        // Fast path: within one directory this is just a name change, which the
+        // server performs with a single atomic rename (no copy, no recursion).
+        match fs.block_on(rename_resource(&fs.client, &old_full_path, &new_full_path, &fs.config.server_url)) {
+            Ok(()) => {}
+            // The new name is over the server's MAX_NAME_LENGTH
+            Err(ApiError::BadRequest) => { reply.error(ENAMETOOLONG); return; }
+            Err(_) => { reply.error(EIO); return; }
+        }
+    } else if is_dir {
         // Use the new recursive helper function for directories
         match recursive_move_client_side(fs, &old_full_path, &new_full_path) {
             Ok(_) => { /* Success, continue to cache update */ },
@@ -147,36 +214,41 @@ pub fn rename(fs: &mut RemoteFS, _req: &Request<'_>, parent: u64, name: &OsStr,
         }
     } else {
         // Use the original "Copy + Delete" logic for files
-        let content = match fs.runtime.block_on(get_file_content_from_server(&fs.client, &old_full_path,  &fs.config.server_url)) {
+        let content = match fs.block_on(get_file_content_from_server(&fs.client, &old_full_path, fs.config.verify_checksums, &fs.config.server_url)) {
             Ok(c) => c,
+            Err(ApiError::ChecksumMismatch) => { reply.error(EIO); return; }
             Err(_) => { reply.error(ENOENT); return; }
         };
-        if fs.runtime.block_on(put_file_content_to_server(&fs.client, &new_full_path, content,  &fs.config.server_url)).is_err() {
-            reply.error(EIO);
-            return;
+        // The copy keeps the original times (the cached atime may come from a local `touch`)
+        let attr = crate::fs::attr::fetch_and_cache_attributes(fs, inode);
+        let (atime, mtime) = (attr.map(|a| a.atime), attr.map(|a| a.mtime));
+        match fs.block_on(put_file_content_to_server(&fs.client, &new_full_path, content, atime, mtime, &fs.config.server_url)) {
+            Ok(()) => {}
+            Err(ApiError::BadRequest) => { reply.error(ENAMETOOLONG); return; }
+            Err(_) => { reply.error(EIO); return; }
         }
         // Delete the old file
-        if fs.runtime.block_on(delete_resource(&fs.client, &old_full_path, &fs.config.server_url)).is_err() {
+        if fs.block_on(delete_resource(&fs.client, &old_full_path, &fs.config.server_url)).is_err() {
             reply.error(EIO);
             return;
         }
     }
     // --- END LOGIC DISPATCH ---
 
-    // Update internal caches (this logic is correct)
-    if let Some(&inode) = fs.path_to_inode.get(&old_full_path) {
-        fs.attribute_cache.remove(&inode);
-        fs.path_to_inode.remove(&old_full_path);
-        fs.path_to_inode.insert(new_full_path.clone(), inode);
-        fs.inode_to_path.insert(inode, new_full_path);
-    }
-    // Invalidate parent directory caches
-    if let Some(&inode_parent) = fs.path_to_inode.get(&old_parent_path) {
+    // Update internal caches: the inode (and its children) now live at the new path
+    fs.relocate_cached_paths(&[(&old_full_path, &new_full_path)]);
+    invalidate_parents(fs, &old_parent_path, &new_parent_path);
+    fs.save_inode_map();
+
+    reply.ok();
+}
+
+/// Invalidates the cached attributes of the source and destination parent directories.
+fn invalidate_parents(fs: &mut RemoteFS, old_parent_path: &str, new_parent_path: &str) {
+    if let Some(inode_parent) = fs.inodes.inode(old_parent_path) {
         fs.attribute_cache.remove(&inode_parent);
     }
-    if let Some(&inode_newparent) = fs.path_to_inode.get(&new_parent_path) {
+    if let Some(inode_newparent) = fs.inodes.inode(new_parent_path) {
         fs.attribute_cache.remove(&inode_newparent);
     }
-
-    reply.ok();
-}
\ No newline at end of file
+}
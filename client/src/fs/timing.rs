@@ -0,0 +1,74 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+use super::RemoteFS;
+use crate::api_client::new_request_id;
+
+/// Time spent waiting for the server by the FUSE operation in progress.
+///
+/// Summed by `RemoteFS::block_on` for every request, and reset when the next
+/// operation takes the `RemoteFS` lock (see `OpTimer::start`).
+#[derive(Default)]
+pub struct ServerTime {
+    elapsed: Cell<Duration>,
+    requests: Cell<u32>,
+}
+
+impl ServerTime {
+    /// Adds a request to the server that took `elapsed`.
+    pub fn record(&self, elapsed: Duration) {
+        self.elapsed.set(self.elapsed.get() + elapsed);
+        self.requests.set(self.requests.get() + 1);
+    }
+
+    /// Starts counting from zero, for a new operation.
+    fn reset(&self) {
+        self.elapsed.set(Duration::ZERO);
+        self.requests.set(0);
+    }
+}
+
+/// Timing of one FUSE operation, from its arrival in `FsWrapper` to its reply.
+///
+/// The total is split into the wait for the `RemoteFS` lock (held by another
+/// operation, or by the watcher, health and reload threads), the round trips to
+/// the server (`ServerTime`) and the rest, spent in the client itself.
+pub struct OpTimer {
+    name: &'static str,
+    ino: u64,
+    started: Instant,
+    lock_wait: Duration,
+}
+
+impl OpTimer {
+    /// Starts timing the operation `name` on `ino`, which arrived at `started`
+    /// and got the lock on `fs` only now, and gives it a new `X-Request-ID`.
+    pub fn start(fs: &RemoteFS, name: &'static str, ino: u64, started: Instant) -> Self {
+        fs.server_time.reset();
+        fs.request_id.replace(new_request_id());
+        OpTimer { name, ino, started, lock_wait: started.elapsed() }
+    }
+
+    /// Ends the operation, dropping its request id: if it took at least `slow_op_threshold_ms` (`0` = never),
+    /// logs a `[SLOW]` line with the breakdown of its time and counts it in `fs.slow_ops`.
+    pub fn finish(self, fs: &mut RemoteFS) {
+        let request_id = fs.request_id.take();
+        let threshold = fs.config.slow_op_threshold_ms;
+        let total = self.started.elapsed();
+        if threshold == 0 || total < Duration::from_millis(threshold) {
+            return;
+        }
+        fs.slow_ops += 1;
+        let server = fs.server_time.elapsed.get();
+        eprintln!(
+            "[SLOW] {} (inode {}, richiesta {}): {:?} totali = {:?} in attesa del lock + {:?} in {} richieste al server + {:?} nel client",
+            self.name,
+            self.ino,
+            request_id,
+            total,
+            self.lock_wait,
+            server,
+            fs.server_time.requests.get(),
+            total.saturating_sub(self.lock_wait).saturating_sub(server),
+        );
+    }
+}
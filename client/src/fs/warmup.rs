@@ -0,0 +1,98 @@
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use lru::LruCache;
+use super::RemoteFS;
+use super::attr::{cache_entry_attributes, STAT_BATCH_SIZE};
+use crate::api_client::stat_batch;
+
+/// The inodes whose attributes were asked for most recently, at most
+/// `warmup_recent_paths` (`0` = none are kept).
+///
+/// Filled by `attr::fetch_and_cache_attributes`, cache hits included, and read
+/// by `warm_up` when the server comes back after an outage.
+pub struct RecentPaths {
+    inodes: LruCache<u64, ()>,
+    enabled: bool,
+}
+
+impl RecentPaths {
+    /// Creates an empty list keeping at most `capacity` inodes.
+    pub fn new(capacity: usize) -> Self {
+        RecentPaths {
+            inodes: LruCache::new(NonZeroUsize::new(capacity.max(1)).unwrap()),
+            enabled: capacity > 0,
+        }
+    }
+
+    /// Marks `ino` as the most recently used.
+    pub fn record(&mut self, ino: u64) {
+        if self.enabled {
+            self.inodes.put(ino, ());
+        }
+    }
+
+    /// Changes the maximum size (on `SIGHUP`), dropping the oldest inodes if it shrinks.
+    pub fn resize(&mut self, capacity: usize) {
+        self.inodes.resize(NonZeroUsize::new(capacity.max(1)).unwrap());
+        self.enabled = capacity > 0;
+        if !self.enabled {
+            self.inodes.clear();
+        }
+    }
+
+    /// The inodes kept, from the most recently used.
+    fn inodes(&self) -> Vec<u64> {
+        self.inodes.iter().map(|(&ino, _)| ino).collect()
+    }
+}
+
+/// Refills the attribute cache with the recently used paths (see `RecentPaths`),
+/// with `/stat-batch` requests.
+///
+/// Called by `catchup::catch_up` when the server is reachable again or the
+/// watcher reconnects, after dropping what changed in the meantime, so the applications
+/// that were using the mount find their attributes already cached instead of
+/// waiting for one request each.
+///
+/// The `RemoteFS` lock is not held while the requests run. An inode forgotten
+/// or renamed in the meantime is skipped; a path gone from the server is left
+/// uncached, so `getattr` will find out as usual. This is only an optimization:
+/// if the server does not answer, nothing is cached.
+///
+/// # Arguments
+/// * `fs_arc` - The shared `RemoteFS` state.
+/// * `reason` - Why the cache is warmed up, for the log.
+pub async fn warm_up(fs_arc: Arc<Mutex<RemoteFS>>, reason: &str) {
+    let (client, base_url, entries) = {
+        let mut fs = fs_arc.lock().unwrap();
+        let entries: Vec<(u64, String)> = fs.recent_paths.inodes().into_iter()
+            .filter_map(|ino| fs.inodes.path(ino).map(|path| (ino, path)))
+            .collect();
+        (fs.client.clone(), fs.config.server_url.clone(), entries)
+    };
+    if entries.is_empty() {
+        return;
+    }
+
+    let mut warmed = 0;
+    for batch in entries.chunks(STAT_BATCH_SIZE) {
+        let paths: Vec<String> = batch.iter().map(|(_, path)| path.clone()).collect();
+        let stats = match stat_batch(&client, &paths, &base_url).await {
+            Ok(stats) => stats,
+            Err(e) => {
+                eprintln!("[WARMUP] /stat-batch non disponibile, cache non riscaldata: {:?}", e);
+                return;
+            }
+        };
+        let mut fs = fs_arc.lock().unwrap();
+        for ((ino, path), entry) in batch.iter().zip(stats) {
+            if let Some(entry) = entry
+                && fs.inodes.path(*ino).as_deref() == Some(path.as_str())
+            {
+                cache_entry_attributes(&mut fs, *ino, &entry);
+                warmed += 1;
+            }
+        }
+    }
+    println!("[WARMUP] {}: attributi di {} path recenti su {} rimessi in cache.", reason, warmed, entries.len());
+}
@@ -1,4 +1,13 @@
 use super::prelude::*;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// How many times a failing chunk is retried before a chunked upload gives up.
+const MAX_CHUNK_RETRIES: u32 = 3;
+
+/// `fallocate(2)` mode flags (Linux values, as sent by the kernel and expected by the server).
+const FALLOC_FL_KEEP_SIZE: i32 = 0x01;
+const FALLOC_FL_PUNCH_HOLE: i32 = 0x02;
 
 /// Handles the FUSE `write` operation.
 ///
@@ -6,22 +15,31 @@ use super::prelude::*;
 /// Instead of contacting the server on every write, this function is designed
 /// to be extremely fast.
 ///
-/// It stores the incoming `data` block and its `offset` directly into an
-/// in-memory `HashMap` (`fs.open_files`) associated with the file handle (`fh`).
+/// It stores the incoming `data` block and its `offset` directly into the
+/// in-memory write buffer of the file (`fs.open_files`), shared by all the
+/// write handles open on it.
+///
+/// The actual upload to the server is deferred until the descriptor is closed
+/// (`flush`, with `release` as a last resort).
 ///
-/// The actual upload to the server is deferred until the `release` function is called.
+/// With `write_through`, or on a handle opened with `O_DIRECT`, the block is
+/// instead sent to the server at once (see `write_through`), and the reply waits for it.
+///
+/// A write that would take the file past `max_file_size_bytes` is refused with
+/// `EFBIG` and not buffered, a write to a directory with `EISDIR`. A write of
+/// empty `data` succeeds with `0` bytes written and changes nothing.
 ///
 /// # Arguments
 /// * `fs` - The mutable `RemoteFS` state.
-/// * `fh` - The file handle (generated by `create` or `open`) that identifies the
-///   in-memory buffer.
+/// * `ino` - The inode of the file, which identifies the in-memory buffer.
+/// * `fh` - The file handle (generated by `create` or `open`), which must be a write handle.
 /// * `offset` - The byte offset within the file to write the data to.
 /// * `data` - The block of bytes to be cached.
 /// * `reply` - The reply object to send the number of bytes written back to the kernel.
 pub fn write(
     fs: &mut RemoteFS,
     _req: &Request<'_>,
-    _ino: u64,
+    ino: u64,
     fh: u64,
     offset: i64,
     data: &[u8],
@@ -30,10 +48,44 @@ pub fn write(
     _lock_owner: Option<u64>,
     reply: ReplyWrite,
 ) {
-    // Find the in-memory buffer for this file handle
-    if let Some(open_file) = fs.open_files.get_mut(&fh) {
-        // Store a copy of the data in the buffer at the specified offset
-        open_file.buffer.insert(offset, data.to_vec());
+    if crate::fs::control::is_control_inode(ino) {
+        crate::fs::control::write(fs, ino, data, reply);
+        return;
+    }
+    // A directory has no write buffer to put the data in
+    if let Err(e) = fs.inodes.expect_kind(ino, false).and_then(|_| fs.check_writable()) {
+        reply.error(e);
+        return;
+    }
+
+    // Nothing to write: no empty block in the buffer, no size check, and no `/write`
+    // that could extend the file to `offset` (as `write(2)` of 0 bytes, no effect)
+    if data.is_empty() {
+        reply.written(0);
+        return;
+    }
+
+    // Refuse writes past the size limit before buffering them
+    if fs.exceeds_max_file_size(offset.max(0) as u64 + data.len() as u64) {
+        reply.error(EFBIG);
+        return;
+    }
+
+    if fs.config.write_through || fs.direct_handles.contains(&fh) {
+        write_through(fs, ino, fh, offset, data, reply);
+        return;
+    }
+
+    // Find the in-memory buffer of the file, through a write handle
+    let open_file = match fs.write_handles.get(&fh) {
+        Some(handle_ino) if *handle_ino == ino => fs.open_files.get_mut(&ino),
+        _ => None,
+    };
+    if let Some(open_file) = open_file {
+        // Store a copy of the data in the buffer at the specified offset,
+        // merged with the blocks already buffered around it
+        open_file.insert_block(offset, data);
+        open_file.last_write = Some(SystemTime::now());
         // Immediately reply to the kernel
         reply.written(data.len() as u32);
     } else {
@@ -42,23 +94,354 @@ pub fn write(
     }
 }
 
-/// Handles the FUSE `release` operation (file close).
+/// Sends one `write` block straight to the server (`write_through` mode).
 ///
-/// This is the most critical part of the write-caching strategy.
-/// When a file handle is released, this function "flushes" the cached writes
-/// to the server by performing a full "Read-Modify-Write" cycle:
+/// Writes buffered before the mode was enabled (`SIGHUP`) are uploaded first,
+/// so they cannot later overwrite this newer block. On success the cached
+/// attributes and read block of the file are dropped, since the server now has
+/// a new size and mtime.
+///
+/// # Arguments
+/// * `fs` - The mutable `RemoteFS` state.
+/// * `ino` - The inode of the file.
+/// * `fh` - The file handle, which must be a write handle.
+/// * `offset` - The byte offset within the file to write the data to.
+/// * `data` - The block of bytes to send.
+/// * `reply` - The reply object: the number of bytes written, `EFBIG` if the
+///   server refuses the size, `EIO` on any other failure.
+fn write_through(fs: &mut RemoteFS, ino: u64, fh: u64, offset: i64, data: &[u8], reply: ReplyWrite) {
+    let path = match fs.write_handles.get(&fh) {
+        Some(handle_ino) if *handle_ino == ino => fs.open_files.get(&ino).map(|f| f.path.clone()),
+        _ => None,
+    };
+    let Some(path) = path else {
+        reply.error(EBADF);
+        return;
+    };
+    if let Err(e) = upload_buffered_writes(fs, ino) {
+        reply.error(e);
+        return;
+    }
+
+    match fs.block_on(write_at_offset(&fs.client, &path, offset.max(0) as u64, data.to_vec(), &fs.config.server_url)) {
+        Ok(()) => {
+            fs.attribute_cache.remove(&ino);
+            fs.invalidate_read_block(ino);
+            fs.drop_mtime_override(ino);
+            reply.written(data.len() as u32);
+        }
+        Err(e) => {
+            eprintln!("[WRITE] Scrittura diretta di {} byte su '{}' (offset {}) fallita: {}", data.len(), path, offset, e);
+            reply.error(if matches!(e, ApiError::FileTooLarge) { EFBIG } else { EIO });
+        }
+    }
+}
+
+/// The content of a file being rewritten by `upload_open_file`: in memory, or in
+/// a temporary file for files of at least `spill_threshold_bytes`.
+enum MergedContent {
+    Memory(Vec<u8>),
+    Spilled(std::fs::File),
+}
+
+/// Size of the blocks in which a spilled file is hashed.
+const SPILL_READ_BLOCK: usize = 1024 * 1024;
+
+impl MergedContent {
+    /// The length of the content in bytes.
+    fn len(&self) -> std::io::Result<u64> {
+        match self {
+            MergedContent::Memory(data) => Ok(data.len() as u64),
+            MergedContent::Spilled(file) => Ok(file.metadata()?.len()),
+        }
+    }
+
+    /// Writes `data` at `offset`, extending the content with zeros if it ends past it.
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> std::io::Result<()> {
+        match self {
+            MergedContent::Memory(content) => {
+                let start = offset as usize;
+                let end = start + data.len();
+                // Automatically resize the vector if the write is past the end of the file
+                if end > content.len() {
+                    content.resize(end, 0);
+                }
+                content[start..end].copy_from_slice(data);
+                Ok(())
+            }
+            // A write past the end leaves a hole, which reads back as zeros
+            MergedContent::Spilled(file) => {
+                file.seek(SeekFrom::Start(offset))?;
+                file.write_all(data)
+            }
+        }
+    }
+
+    /// The SHA-256 of the content, as lowercase hex. A spilled file is read back in blocks.
+    fn sha256(&mut self) -> std::io::Result<String> {
+        match self {
+            MergedContent::Memory(data) => Ok(format!("{:x}", Sha256::digest(data))),
+            MergedContent::Spilled(file) => {
+                file.seek(SeekFrom::Start(0))?;
+                let mut hasher = Sha256::new();
+                let mut block = vec![0; SPILL_READ_BLOCK];
+                loop {
+                    let n = file.read(&mut block)?;
+                    if n == 0 {
+                        break;
+                    }
+                    hasher.update(&block[..n]);
+                }
+                Ok(format!("{:x}", hasher.finalize()))
+            }
+        }
+    }
+
+    /// The `len` bytes at `offset` (fewer at the end of the content).
+    fn chunk(&mut self, offset: u64, len: u64) -> std::io::Result<Bytes> {
+        match self {
+            MergedContent::Memory(data) => {
+                let start = (offset as usize).min(data.len());
+                let end = (start + len as usize).min(data.len());
+                Ok(Bytes::copy_from_slice(&data[start..end]))
+            }
+            MergedContent::Spilled(file) => {
+                file.seek(SeekFrom::Start(offset))?;
+                let mut chunk = Vec::with_capacity(len as usize);
+                file.take(len).read_to_end(&mut chunk)?;
+                Ok(Bytes::from(chunk))
+            }
+        }
+    }
+}
+
+/// Uploads the buffered writes of `open_file` with a "Read-Modify-Write" cycle.
 ///
 /// 1. Fetches (`GET`) the file's current content from the server.
 /// 2. Merges all data blocks from the in-memory cache (`open_file.buffer`)
 ///    with the original content, applying them at their correct offsets. This
-///    correctly handles appends, overwrites, and sparse writes.
+///    correctly handles appends, overwrites, and sparse writes; overlapping
+///    writes were already coalesced by `OpenWriteFile::insert_block`.
 /// 3. Uploads (`PUT`) the complete, merged file back to the server.
-/// 4. Invalidates the attribute cache for the inode.
+///
+/// Files of at least `spill_threshold_bytes` (by their last known size) are
+/// downloaded to a temporary file instead of memory (`download_file_to_spill`),
+/// merged there, and uploaded in chunks read from it: the client's memory stays
+/// bounded by the chunk size, whatever the size of the file.
+///
+/// If the merged content is identical to what the server already has (e.g. an
+/// editor saving an unchanged file), the upload is skipped: no new mtime, and
+/// no change notification for the other clients. Times set while the file was
+/// open are still sent, through `/utimes`.
+///
+/// # Arguments
+/// * `fs` - The mutable `RemoteFS` state.
+/// * `open_file` - The write buffer to upload (not borrowed from `open_files`).
+///
+/// # Returns
+/// * `Ok(())` on success.
+/// * `Err(EFBIG)` if the merged file is over `max_file_size_bytes` (nothing is
+///   uploaded) or the server refuses its size.
+/// * `Err(EIO)` if the current content is corrupted, the temporary file cannot
+///   be written, or the upload fails.
+pub(super) fn upload_open_file(fs: &mut RemoteFS, open_file: &OpenWriteFile) -> Result<(), libc::c_int> {
+    let io_error = |e: std::io::Error| {
+        eprintln!("[FUSE CLIENT] Temporary file of '{}' failed: {}", open_file.path, e);
+        EIO
+    };
+
+    // 1. Download the current content.
+    // Start with the old content, or an empty one if the file is new/empty.
+    // Corrupted content must never be merged and written back.
+    let (mut content, old_checksum) = match download_current_content(fs, &open_file.path)? {
+        Some(mut content) => {
+            let checksum = content.sha256().map_err(io_error)?;
+            (content, Some(checksum))
+        }
+        None => (MergedContent::Memory(Vec::new()), None),
+    };
+
+    // 2. Apply all cached modifications
+    for (&offset, data) in &open_file.buffer {
+        content.write_at(offset as u64, data).map_err(io_error)?;
+    }
+
+    // The buffered writes are checked in `write`, but the server's content may add to them
+    let len = content.len().map_err(io_error)?;
+    if fs.exceeds_max_file_size(len) {
+        eprintln!("[FUSE CLIENT] '{}' exceeds max_file_size_bytes: upload refused", open_file.path);
+        return Err(EFBIG);
+    }
+
+    // 3. Skip the upload if nothing actually changed
+    let new_checksum = content.sha256().map_err(io_error)?;
+    if old_checksum.is_some_and(|old| old == new_checksum) {
+        println!("[FUSE CLIENT] '{}' unchanged: upload skipped", open_file.path);
+        let times = open_file.times;
+        if (times.atime.is_some() || times.mtime.is_some())
+            && let Err(e) = fs.block_on(update_times(&fs.client, &open_file.path, times.atime, times.mtime, &fs.config.server_url))
+        {
+            eprintln!("[FUSE CLIENT] Could not sync times of '{}' to the server: {:?}", open_file.path, e);
+        }
+        return Ok(());
+    }
+
+    // 4. Upload the new, merged content (in resumable chunks if the file is large).
+    // Times set while the file was open are applied by the server after the write
+    let new_data = match content {
+        MergedContent::Memory(data) if len <= fs.config.chunked_upload_threshold => Bytes::from(data),
+        mut content => return upload_chunked(fs, &open_file.path, &mut content, new_checksum, open_file.times),
+    };
+    let put_result = fs.block_on(
+        api_client::put_file_content_to_server(
+            &fs.client,
+            &open_file.path,
+            new_data,
+            open_file.times.atime,
+            open_file.times.mtime,
+            &fs.config.server_url
+        )
+    );
+
+    put_result.map_err(|e| {
+        eprintln!("[FUSE CLIENT] Critical error during PUT of '{}': {:?}", open_file.path, e);
+        if matches!(e, ApiError::FileTooLarge) { EFBIG } else { EIO }
+    })
+}
+
+/// Downloads the current content of `path` for `upload_open_file`: to a temporary
+/// file if its last known size (cached attributes, else a `HEAD`) reaches
+/// `spill_threshold_bytes`, to memory otherwise.
+///
+/// # Returns
+/// * `Ok(None)` if the content cannot be fetched (e.g. the file is new).
+/// * `Err(EIO)` if the content is corrupted or the temporary file cannot be written.
+fn download_current_content(fs: &mut RemoteFS, path: &str) -> Result<Option<MergedContent>, libc::c_int> {
+    let threshold = fs.config.spill_threshold_bytes;
+    let spill = threshold > 0 && {
+        let cached = fs.inodes.inode(path).and_then(|ino| fs.attribute_cache.get_stale(&ino)).map(|attr| attr.size);
+        let size = cached.or_else(|| fs.block_on(head_file_from_server(&fs.client, path, &fs.config.server_url)).ok().flatten().map(|entry| entry.size));
+        size.is_some_and(|size| size >= threshold)
+    };
+    let result = if spill {
+        println!("[FUSE CLIENT] '{}' is large: merging the writes in a temporary file", path);
+        fs.block_on(api_client::download_file_to_spill(&fs.client, path, fs.config.verify_checksums, &fs.config.server_url))
+            .map(MergedContent::Spilled)
+    } else {
+        fs.block_on(get_file_content_from_server(&fs.client, path, fs.config.verify_checksums, &fs.config.server_url))
+            .map(|data| MergedContent::Memory(data.to_vec()))
+    };
+    match result {
+        Ok(content) => Ok(Some(content)),
+        Err(e @ (ApiError::ChecksumMismatch | ApiError::Io(_))) => {
+            eprintln!("[FUSE CLIENT] Current content of '{}' unusable: {}", path, e);
+            Err(EIO)
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Uploads `content` (whose SHA-256 is `checksum`) to `path` through a resumable
+/// chunked upload session.
+///
+/// The content is sent in `upload_chunk_size` chunks, read one at a time (from
+/// the temporary file, for a spilled content). When a chunk fails, the
+/// committed offset is re-read from the server and the upload resumes from
+/// there, up to `MAX_CHUNK_RETRIES` times in a row. If the upload still fails,
+/// the session is kept in `fs.upload_sessions`: a later upload of the same
+/// content to the same path resumes it instead of starting over.
+///
+/// `times` are applied by the server when the upload is completed.
+///
+/// # Returns
+/// * `Ok(())` once the server has moved the complete file into place.
+/// * `Err(EFBIG)` if the server refuses the size of the file (not retried).
+/// * `Err(EIO)` if the upload could not be completed.
+fn upload_chunked(fs: &mut RemoteFS, path: &str, content: &mut MergedContent, checksum: String, times: TimeOverride) -> Result<(), libc::c_int> {
+    let base_url = fs.config.server_url.clone();
+    let chunk_size = fs.config.upload_chunk_size.max(1);
+
+    // 1. Resume the previous session for identical content, or open a new one
+    let resumed = fs.upload_sessions.get(path)
+        .filter(|session| session.checksum == checksum)
+        .and_then(|session| {
+            let offset = fs.block_on(get_upload_offset(&fs.client, &session.id, &base_url)).ok()?;
+            Some((session.id.clone(), offset))
+        });
+    let (id, mut offset) = match resumed {
+        Some((id, offset)) => {
+            println!("[FUSE CLIENT] Resuming upload of '{}' at offset {}", path, offset);
+            (id, offset)
+        }
+        None => match fs.block_on(start_upload(&fs.client, path, &base_url)) {
+            Ok(status) => (status.id, status.offset),
+            Err(e) => {
+                eprintln!("[FUSE CLIENT] Could not start chunked upload of '{}': {:?}", path, e);
+                return Err(EIO);
+            }
+        },
+    };
+    fs.upload_sessions.insert(path.to_string(), UploadSession { id: id.clone(), checksum });
+
+    // 2. Send the remaining chunks, resyncing with the server's offset on failure
+    let total = content.len().map_err(|_| EIO)?;
+    let mut retries = 0;
+    while offset < total {
+        let chunk = match content.chunk(offset, chunk_size) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                eprintln!("[FUSE CLIENT] Could not read chunk at offset {} of '{}': {}", offset, path, e);
+                return Err(EIO);
+            }
+        };
+        match fs.block_on(upload_chunk(&fs.client, &id, offset, chunk, &base_url)) {
+            Ok(committed) => {
+                offset = committed;
+                retries = 0;
+            }
+            Err(ApiError::FileTooLarge) => {
+                eprintln!("[FUSE CLIENT] Chunked upload of '{}' refused: over the server's size limit", path);
+                fs.upload_sessions.remove(path);
+                return Err(EFBIG);
+            }
+            Err(e) => {
+                retries += 1;
+                eprintln!("[FUSE CLIENT] Chunk at offset {} of '{}' failed ({}/{}): {:?}", offset, path, retries, MAX_CHUNK_RETRIES, e);
+                if retries > MAX_CHUNK_RETRIES {
+                    return Err(EIO);
+                }
+                if let Ok(committed) = fs.block_on(get_upload_offset(&fs.client, &id, &base_url)) {
+                    offset = committed;
+                }
+            }
+        }
+    }
+
+    // 3. Move the assembled file into place
+    if let Err(e) = fs.block_on(complete_upload(&fs.client, &id, times.atime, times.mtime, &base_url)) {
+        eprintln!("[FUSE CLIENT] Could not complete chunked upload of '{}': {:?}", path, e);
+        return Err(EIO);
+    }
+    fs.upload_sessions.remove(path);
+    Ok(())
+}
+
+/// Handles the FUSE `release` operation (file close).
+///
+/// Writes are normally uploaded by `flush`, which runs on every `close(2)`.
+/// `release` is the best-effort final flush: when the last write handle of a
+/// file is released, whatever is still buffered (e.g. writes after a failed
+/// `flush`) is uploaded via `upload_open_file`, then the attribute cache for
+/// the inode is invalidated. Releasing any other write handle only drops its reference.
+///
+/// The kernel ignores errors returned here, so a failed upload (`EIO`) is retried
+/// up to `release_upload_retries` times with `api_client::backoff_delay`; if it
+/// still fails, the buffered writes are saved by `save_unsynced` instead of being lost.
 ///
 /// # Arguments
 /// * `fs` - The mutable `RemoteFS` state.
 /// * `ino` - The inode of the file (used for cache invalidation).
-/// * `fh` - The file handle to find and flush from the `open_files` cache.
+/// * `fh` - The file handle being released.
 /// * `reply` - The reply object to send success or an error code.
 pub fn release(
     fs: &mut RemoteFS,
@@ -70,9 +453,30 @@ pub fn release(
     _flush: bool,
     reply: ReplyEmpty,
 ) {
-    // Attempt to remove the write buffer from the cache.
-    // If it doesn't exist, this was probably a read-only handle, so we're done.
-    if let Some(open_file) = fs.open_files.remove(&fh) {
+    // Every handle (read-only ones too) pinned the inode in `open`/`create`
+    fs.inodes.unpin(ino);
+    fs.direct_handles.remove(&fh);
+    if let Some(handle) = fs.read_handles.remove(&fh) {
+        println!("[READ] Handle {} chiuso: {} letture, {} sequenziali.", fh, handle.reads, handle.sequential_reads);
+    }
+
+    // Only write handles have a buffer; a read-only handle has nothing to flush.
+    if fs.write_handles.remove(&fh).is_none() {
+        reply.ok();
+        return;
+    }
+
+    // Other write handles still share the buffer: the last one uploads it
+    let last_handle = fs.open_files.get_mut(&ino).is_some_and(|open_file| {
+        open_file.handles -= 1;
+        open_file.handles == 0
+    });
+    if !last_handle {
+        reply.ok();
+        return;
+    }
+
+    if let Some(open_file) = fs.open_files.remove(&ino) {
 
         // If no data was written (e.g., `touch` or `cat > file`), do nothing.
         // The empty file was already created by `create`.
@@ -81,63 +485,290 @@ pub fn release(
             return;
         }
 
-        // 1. Download the current content
-        let old_content_result = fs.runtime.block_on(
-            api_client::get_file_content_from_server(&fs.client, &open_file.path,  &fs.config.server_url)
-        );
-
-        // Start with the old content, or an empty Vec if the file is new/empty
-        let mut new_data_vec = match old_content_result {
-            Ok(bytes) => bytes.to_vec(),
-            Err(_) => Vec::new(),
-        };
-
-        // 2. Apply all cached modifications
-        for (offset, data) in open_file.buffer {
-            let start = offset as usize;
-            let end = start + data.len();
-            // Automatically resize the vector if the write is past the end of the file
-            if end > new_data_vec.len() {
-                new_data_vec.resize(end, 0);
-            }
-            new_data_vec[start..end].copy_from_slice(&data);
-        }
-
-        // 3. Upload the new, merged content
-        let put_result = fs.runtime.block_on(
-            api_client::put_file_content_to_server(
-                &fs.client,
-                &open_file.path,
-                Bytes::from(new_data_vec), // Convert Vec<u8> to Bytes
-                &fs.config.server_url
-            )
-        );
-
-        match put_result {
-            Ok(_) => {
+        match upload_with_retries(fs, &open_file) {
+            Ok(()) => {
                 // Invalidate the attribute cache so the next `ls -l` shows the new size
                 fs.attribute_cache.remove(&ino);
+                fs.invalidate_read_block(ino);
+                fs.drop_mtime_override(ino);
                 reply.ok();
             }
             Err(e) => {
-                eprintln!("[FUSE CLIENT] Critical error during PUT in release: {:?}", e);
-                reply.error(EIO);
+                match save_unsynced(&fs.config.unsynced_dir, &open_file) {
+                    Ok(saved) => eprintln!(
+                        "[UNSYNCED] !!! Final upload of '{}' failed: its buffered writes were NOT sent to the server and are saved in {} !!!",
+                        open_file.path, saved.display()
+                    ),
+                    Err(save_error) => eprintln!(
+                        "[UNSYNCED] !!! Buffered writes to '{}' are lost: final upload failed and they could not be saved in {}: {} !!!",
+                        open_file.path, fs.config.unsynced_dir, save_error
+                    ),
+                }
+                reply.error(e);
             }
         }
     } else {
-        // This was a read-only file handle, or one we didn't cache.
-        // Nothing to do.
         reply.ok();
     }
 }
 
-/// Handles the FUSE `flush` operation.
+/// Uploads `open_file` via `upload_open_file`, retrying a failed upload (`EIO`)
+/// up to `release_upload_retries` times with `api_client::backoff_delay` between
+/// attempts. Other errors (e.g. `EFBIG`) would fail again and are returned at once.
+fn upload_with_retries(fs: &mut RemoteFS, open_file: &OpenWriteFile) -> Result<(), libc::c_int> {
+    let mut retry = 0;
+    loop {
+        match upload_open_file(fs, open_file) {
+            Err(EIO) if retry < fs.config.release_upload_retries => {
+                let delay = api_client::backoff_delay(retry);
+                retry += 1;
+                eprintln!("[FUSE CLIENT] Final upload of '{}' failed, retry {}/{} in {:?}", open_file.path, retry, fs.config.release_upload_retries, delay);
+                std::thread::sleep(delay);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Saves the buffered writes of `open_file` in `dir`, after its final upload failed.
+///
+/// The file is named after the server path, with `%` and `/` escaped (`%25`, `%2F`)
+/// and a `.N` suffix if a previous save of the same path is still there. Each block
+/// is written at its offset: the bytes that were not written through the mount
+/// are holes (zeros), since the server's content could not be merged in.
+///
+/// # Returns
+/// The path of the saved file, or the error of creating it.
+pub(crate) fn save_unsynced(dir: &str, open_file: &OpenWriteFile) -> std::io::Result<std::path::PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let name = open_file.path.replace('%', "%25").replace('/', "%2F");
+    let mut target = std::path::Path::new(dir).join(&name);
+    let mut n = 0;
+    while target.exists() {
+        n += 1;
+        target = std::path::Path::new(dir).join(format!("{}.{}", name, n));
+    }
+
+    let mut file = std::fs::File::create_new(&target)?;
+    for (&offset, data) in &open_file.buffer {
+        file.seek(SeekFrom::Start(offset.max(0) as u64))?;
+        file.write_all(data)?;
+    }
+    file.sync_all()?;
+    Ok(target)
+}
+
+/// Counts the files saved in `dir` by `save_unsynced` and not yet removed
+/// (`0` if the directory does not exist).
+pub(crate) fn count_unsynced(dir: &str) -> usize {
+    std::fs::read_dir(dir).map(|entries| entries.filter(|e| e.is_ok()).count()).unwrap_or(0)
+}
+
+/// Handles the FUSE `flush` operation, called on every `close(2)` of a descriptor.
+///
+/// Uploads the writes buffered for the file (by this handle or any other one
+/// open on it) via `upload_open_file`. Unlike `release`, the error returned
+/// here is what `close(2)` returns, so an application can tell that its data
+/// did not reach the server.
+///
+/// On failure the writes stay buffered: a later `flush`, or the final
+/// `release`, tries again. Read-only handles have nothing to upload.
 ///
-/// In this implementation, `flush` is a no-op (it does nothing).
-/// All write-caching logic is handled in `release` when the file handle is
-/// fully closed, not during intermediate `flush` calls.
+/// # Arguments
+/// * `fs` - The mutable `RemoteFS` state.
+/// * `ino` - The inode of the file.
+/// * `fh` - The file handle being closed.
+/// * `reply` - The reply object to send success or an error code.
+pub fn flush(fs: &mut RemoteFS, _req: &Request<'_>, ino: u64, fh: u64, _lock_owner: u64, reply: ReplyEmpty) {
+    if !fs.write_handles.contains_key(&fh) {
+        reply.ok();
+        return;
+    }
+    match upload_buffered_writes(fs, ino) {
+        Ok(()) => reply.ok(),
+        Err(e) => reply.error(e),
+    }
+}
+
+/// Uploads the writes buffered for `ino` by any of its open handles, keeping
+/// the handles open. Nothing to do if the file has no buffered writes.
 ///
-/// We simply reply `ok` to acknowledge the call.
-pub fn flush(_fs: &mut RemoteFS, _req: &Request<'_>, _ino: u64, _fh: u64, _lock_owner: u64, reply: ReplyEmpty) {
+/// On failure the writes stay buffered for the next attempt.
+///
+/// # Returns
+/// * `Ok(())` if there was nothing to upload or the upload succeeded.
+/// * `Err(libc::c_int)` with the error of `upload_open_file`.
+pub(super) fn upload_buffered_writes(fs: &mut RemoteFS, ino: u64) -> Result<(), libc::c_int> {
+    let Some(open_file) = fs.open_files.get_mut(&ino) else {
+        return Ok(());
+    };
+    if open_file.buffer.is_empty() {
+        return Ok(());
+    }
+
+    // Upload the buffered writes; the shared entry stays for the other handles
+    let pending = OpenWriteFile {
+        path: open_file.path.clone(),
+        buffer: std::mem::take(&mut open_file.buffer),
+        times: open_file.times,
+        last_write: open_file.last_write,
+        handles: 0,
+    };
+    match upload_open_file(fs, &pending) {
+        Ok(()) => {
+            fs.attribute_cache.remove(&ino);
+            fs.invalidate_read_block(ino);
+            fs.drop_mtime_override(ino);
+            Ok(())
+        }
+        Err(e) => {
+            // Keep the writes for the next attempt
+            if let Some(open_file) = fs.open_files.get_mut(&ino) {
+                open_file.buffer = pending.buffer;
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Handles the FUSE `fallocate` operation (`fallocate(2)`, `posix_fallocate(3)`).
+///
+/// The request is forwarded to the server's `/fallocate` endpoint, which runs
+/// `fallocate(2)` on the real file. Supported modes:
+/// * `0` - preallocates the range, extending the file if needed;
+/// * `FALLOC_FL_KEEP_SIZE` - preallocates without changing the size;
+/// * `FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE` - deallocates the range. The
+///   same range is zeroed in the writes still buffered for the file, so their
+///   upload cannot fill the hole again.
+///
+/// Any other mode fails with `EOPNOTSUPP`. The attribute cache of the inode is
+/// invalidated afterwards, since the size may have changed.
+///
+/// # Arguments
+/// * `fs` - The mutable `RemoteFS` state.
+/// * `ino` - The inode of the file.
+/// * `offset` / `length` - The byte range.
+/// * `mode` - The `fallocate` flags.
+/// * `reply` - The reply object to send success or an error code.
+pub fn fallocate(fs: &mut RemoteFS, ino: u64, offset: i64, length: i64, mode: i32, reply: ReplyEmpty) {
+    if offset < 0 || length <= 0 {
+        reply.error(EINVAL);
+        return;
+    }
+    let (offset, length) = (offset as u64, length as u64);
+    let supported = [0, FALLOC_FL_KEEP_SIZE, FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE];
+    if !supported.contains(&mode) {
+        reply.error(EOPNOTSUPP);
+        return;
+    }
+    if let Err(e) = fs.check_writable() {
+        reply.error(e);
+        return;
+    }
+    let Some(path) = fs.inodes.path(ino) else {
+        reply.error(ENOENT);
+        return;
+    };
+    if mode & FALLOC_FL_KEEP_SIZE == 0 && fs.exceeds_max_file_size(offset + length) {
+        reply.error(EFBIG);
+        return;
+    }
+
+    match fs.block_on(fallocate_on_server(&fs.client, &path, offset, length, mode, &fs.config.server_url)) {
+        Ok(()) => {}
+        Err(e) => {
+            eprintln!("[FUSE CLIENT] fallocate of '{}' failed: {:?}", path, e);
+            let code = match e {
+                ApiError::FileTooLarge => EFBIG,
+                ApiError::NotSupported => EOPNOTSUPP,
+                _ => EIO,
+            };
+            reply.error(code);
+            return;
+        }
+    }
+
+    if mode & FALLOC_FL_PUNCH_HOLE != 0
+        && let Some(open_file) = fs.open_files.get_mut(&ino)
+    {
+        zero_buffered_range(open_file, offset, offset + length);
+    }
+
+    fs.attribute_cache.remove(&ino);
+    fs.invalidate_read_block(ino);
+    fs.drop_mtime_override(ino);
     reply.ok();
-}
\ No newline at end of file
+}
+
+/// Zeroes the bytes of the buffered writes of `open_file` inside `start..end`.
+fn zero_buffered_range(open_file: &mut OpenWriteFile, start: u64, end: u64) {
+    for (&offset, data) in open_file.buffer.iter_mut() {
+        let block_start = offset as u64;
+        let block_end = block_start + data.len() as u64;
+        if block_end <= start || block_start >= end {
+            continue;
+        }
+        let from = start.saturating_sub(block_start) as usize;
+        let to = (end.min(block_end) - block_start) as usize;
+        data[from..to].fill(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::end_to_end::unmounted_fs;
+    use server::handlers::AppState;
+    use server::storage::InMemoryBackend;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    /// A write buffer for `path` holding the `(offset, data)` writes, in order.
+    fn buffered(path: &str, writes: &[(i64, &[u8])]) -> OpenWriteFile {
+        let mut open_file = OpenWriteFile {
+            path: path.to_string(),
+            buffer: BTreeMap::new(),
+            times: TimeOverride::default(),
+            last_write: None,
+            handles: 1,
+        };
+        for &(offset, data) in writes {
+            open_file.insert_block(offset, data);
+        }
+        open_file
+    }
+
+    #[test]
+    fn test_insert_block_coalesces() {
+        // Disjoint blocks stay apart; one touching the previous block extends it
+        let open_file = buffered("f", &[(0, b"AA"), (5, b"BB"), (2, b"CC")]);
+        assert_eq!(open_file.buffer, BTreeMap::from([(0, b"AACC".to_vec()), (5, b"BB".to_vec())]));
+
+        // Overlaps in any order: the last write wins
+        let open_file = buffered("f", &[(4, b"EEEE"), (2, b"FF"), (5, b"GGG")]);
+        assert_eq!(open_file.buffer, BTreeMap::from([(2, b"FFEGGG".to_vec())]));
+        let open_file = buffered("f", &[(0, b"AAAAAAAA"), (2, b"B"), (10, b"C"), (1, b"DDDDDDDDDDDD")]);
+        assert_eq!(open_file.buffer, BTreeMap::from([(0, b"ADDDDDDDDDDDD".to_vec())]));
+        assert_eq!(open_file.buffered_end(), 13);
+    }
+
+    #[test]
+    fn test_offset_writes_merged_on_upload() {
+        let storage = Arc::new(InMemoryBackend::new());
+        storage.insert_file("offsets.bin", "AAAAAAAAAA").unwrap();
+        let mut fs = unmounted_fs(server::app(AppState { min_free_bytes: 0, ..AppState::new(storage) }));
+        let mut upload = |writes: &[(i64, &[u8])]| {
+            upload_open_file(&mut fs, &buffered("offsets.bin", writes)).unwrap();
+            fs.block_on(get_file_content_from_server(&fs.client, "offsets.bin", false, &fs.config.server_url)).unwrap()
+        };
+
+        // Overwrite at offset 0: the rest of the server's content is kept
+        assert_eq!(upload(&[(0, b"BB")]), &b"BBAAAAAAAA"[..]);
+        // Write past the end: the gap is filled with zeros
+        assert_eq!(upload(&[(15, b"CC")]), &b"BBAAAAAAAA\0\0\0\0\0CC"[..]);
+        // Write across the existing data and the gap
+        assert_eq!(upload(&[(8, b"DDDD")]), &b"BBAAAAAADDDD\0\0\0CC"[..]);
+        // Several blocks in one buffer, overlapping and out of order
+        assert_eq!(upload(&[(4, b"EEEE"), (2, b"FF"), (5, b"GGG")]), &b"BBFFEGGGDDDD\0\0\0CC"[..]);
+    }
+}
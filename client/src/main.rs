@@ -2,31 +2,50 @@
 //!
 //! This binary is responsible for:
 //! 1. Loading the configuration from `config.toml`.
-//! 2. Parsing the mountpoint from command-line arguments.
-//! 3. Creating an instance of the `RemoteFS` filesystem.
-//! 4. Mounting the filesystem at the specified mountpoint.
+//! 2. Parsing the mountpoint from command-line arguments, and the `[[mount]]` tables of the configuration.
+//! 3. Creating an instance of the `RemoteFS` filesystem for each mount, on a shared Tokio runtime.
+//! 4. Mounting every filesystem at its mountpoint, and unmounting each one independently.
 
 // Make the API client public so the `fs` module can access it.
 pub mod api_client;
 mod config;
+mod daemon;
 mod fs;
+// End-to-end tests against an in-process server (`cargo test -- --ignored`, needs /dev/fuse).
+#[cfg(test)]
+mod end_to_end;
 
+use api_client::ServerHealth;
 use fs::{RemoteFS, FsWrapper};
-use fuser::MountOption;
-use std::sync::{Arc, Mutex};
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
-use url::Url;
-use futures_util::StreamExt;
-use clap::Parser;
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio_tungstenite::{connect_async, tungstenite::{client::IntoClientRequest, protocol::{frame::coding::CloseCode, Message}}};
+use futures_util::{SinkExt, StreamExt};
+use clap::{Parser, Subcommand};
 use crate::config::CacheStrategy;
 use daemonize::Daemonize; 
 use std::fs::File;
+use std::path::PathBuf;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use fuser::MountOption;
+
+/// How often the main thread checks whether the FUSE session ended on its own
+/// (the filesystem was unmounted from outside) while waiting for a shutdown signal.
+const SESSION_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(subcommand_negates_reqs = true)]
 struct Cli {
-    /// Il punto di mount per il filesystem.
-    mountpoint: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Il punto di mount per il filesystem (facoltativo se il file di configurazione
+    /// definisce dei `[[mount]]`, che vengono montati insieme a questo).
+    mountpoint: Option<String>,
 
     /// Esegui il processo come demone in background.
     #[arg(long)]
@@ -43,39 +62,666 @@ struct Cli {
     /// Sovrascrive la capacità della cache LRU (usato con --cache-strategy=lru).
     #[arg(long)]
     cache_lru_capacity: Option<usize>,
+
+    /// Sovrascrive la dimensione del blocco di trasferimento in byte (blksize e letture a range).
+    #[arg(long)]
+    transfer_block_size: Option<u32>,
+
+    /// Sovrascrive la dimensione massima in byte dei file scritti (0 = nessun limite).
+    #[arg(long)]
+    max_file_size: Option<u64>,
+
+    /// Il file di configurazione da leggere, invece di cercare `config.toml` nei percorsi di default.
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Sovrascrive il percorso del file PID del demone (usato anche da `status` e `unmount`).
+    #[arg(long, global = true)]
+    pid_file: Option<String>,
+
+    /// Sovrascrive il file su cui il demone scrive lo stdout.
+    #[arg(long)]
+    daemon_stdout: Option<String>,
+
+    /// Sovrascrive il file su cui il demone scrive lo stderr.
+    #[arg(long)]
+    daemon_stderr: Option<String>,
+
+    /// Aggiunge un'opzione di mount FUSE (es. `allow_other`), ripetibile.
+    /// Si somma a `mount_options` del file di configurazione.
+    #[arg(long = "mount-option", value_name = "OPZIONE")]
+    mount_options: Vec<String>,
+
+    /// Monta anche su una directory non vuota, il cui contenuto resta nascosto
+    /// finché il filesystem è montato (aggiunge l'opzione di mount `nonempty`).
+    #[arg(long)]
+    allow_nonempty: bool,
+}
+
+// Comandi per interagire con un client già avviato come demone.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Mostra se il demone è attivo, lo stato del WebSocket e le scritture in sospeso.
+    Status {
+        /// Il mountpoint del demone (serve a trovare il file PID se `--pid-file` non è indicato).
+        mountpoint: Option<String>,
+    },
+    /// Chiede al demone di caricare le scritture in sospeso e smontare il filesystem.
+    Unmount {
+        /// Il mountpoint del demone (serve a trovare il file PID se `--pid-file` non è indicato).
+        mountpoint: Option<String>,
+    },
+    /// Cerca file e directory per nome direttamente sul server (senza attraversare il mount).
+    Search {
+        /// Parte del nome da cercare, o un pattern glob con `--glob`.
+        query: String,
+        /// La directory in cui cercare, relativa alla radice remota.
+        #[arg(long, default_value = "")]
+        path: String,
+        /// Interpreta la query come pattern glob (es. `*.txt`).
+        #[arg(long)]
+        glob: bool,
+        /// Numero massimo di risultati.
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Stampa lo SHA-256 di un file calcolato dal server, senza scaricarlo (formato di `sha256sum`).
+    Checksum {
+        /// Il file, relativo alla radice remota (es. `docs/a.txt`).
+        path: String,
+    },
+    /// Forza il caricamento delle scritture in sospeso e invalida le cache di un file o di una directory montata.
+    Refresh {
+        /// Il file o la directory, attraverso il mount (es. `/mnt/remote/docs`).
+        path: PathBuf,
+    },
+    /// Confronta le cache del client con il server e stampa le discrepanze (`.remotefs/fsck`).
+    Fsck {
+        /// Il mountpoint del client da controllare.
+        mountpoint: PathBuf,
+        /// Invalida anche le voci trovate non aggiornate.
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Scrive un `config.toml` con tutte le opzioni, commentate e ai valori di default.
+    InitConfig {
+        /// Il file da creare.
+        #[arg(default_value = "config.toml")]
+        path: PathBuf,
+        /// Sovrascrive il file se esiste già.
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// The configuration values given on the command line.
+///
+/// They win over `config.toml` both at startup and on every reload (`SIGHUP`),
+/// so a reload never undoes a CLI override.
+#[derive(Debug, Clone)]
+struct ConfigOverrides {
+    cache_strategy: Option<CacheStrategy>,
+    cache_ttl_seconds: Option<u64>,
+    cache_lru_capacity: Option<usize>,
+    transfer_block_size: Option<u32>,
+    max_file_size: Option<u64>,
+    pid_file: Option<String>,
+    daemon_stdout: Option<String>,
+    daemon_stderr: Option<String>,
+    mount_options: Vec<String>,
+}
+
+impl ConfigOverrides {
+    fn from_cli(cli: &Cli) -> Self {
+        Self {
+            cache_strategy: cli.cache_strategy,
+            cache_ttl_seconds: cli.cache_ttl_seconds,
+            cache_lru_capacity: cli.cache_lru_capacity,
+            transfer_block_size: cli.transfer_block_size,
+            max_file_size: cli.max_file_size,
+            pid_file: cli.pid_file.clone(),
+            daemon_stdout: cli.daemon_stdout.clone(),
+            daemon_stderr: cli.daemon_stderr.clone(),
+            mount_options: cli.mount_options.iter().cloned()
+                .chain(cli.allow_nonempty.then(|| "nonempty".to_string()))
+                .collect(),
+        }
+    }
+
+    /// Overwrites the values of `config` with the ones given on the command line.
+    fn apply(&self, config: &mut config::Config) {
+        if let Some(strategy) = self.cache_strategy {
+            config.cache_strategy = strategy;
+            println!("INFO: Strategia cache sovrascritta da CLI: {:?}", strategy);
+        }
+        if let Some(ttl) = self.cache_ttl_seconds {
+            config.cache_ttl_seconds = ttl;
+            println!("INFO: TTL cache sovrascritto da CLI: {}s", ttl);
+        }
+        if let Some(capacity) = self.cache_lru_capacity {
+            config.cache_lru_capacity = capacity;
+            println!("INFO: Capacità LRU sovrascritta da CLI: {}", capacity);
+        }
+        if let Some(block_size) = self.transfer_block_size {
+            config.transfer_block_size = block_size;
+            println!("INFO: Dimensione blocco sovrascritta da CLI: {} byte", block_size);
+        }
+        if let Some(max_size) = self.max_file_size {
+            config.max_file_size_bytes = max_size;
+            println!("INFO: Dimensione massima dei file sovrascritta da CLI: {} byte", max_size);
+        }
+        if self.pid_file.is_some() {
+            config.pid_file = self.pid_file.clone();
+        }
+        if self.daemon_stdout.is_some() {
+            config.daemon_stdout = self.daemon_stdout.clone();
+        }
+        if self.daemon_stderr.is_some() {
+            config.daemon_stderr = self.daemon_stderr.clone();
+        }
+        config.mount_options.extend(self.mount_options.iter().cloned());
+    }
+}
+
+/// Spawns a thread that re-reads `config_path` on every `SIGHUP` and applies it
+/// with `RemoteFS::reload_config` to each of the `mounts`, without remounting.
+///
+/// The file is read and parsed before taking the `RemoteFS` locks, which are then
+/// held only to swap the settings. A file that cannot be read or parsed leaves
+/// the running configuration untouched. Mounts added to the file are only
+/// reported: they are mounted at the next start of the process.
+fn spawn_reload_handler(
+    mounts: Vec<(String, Arc<Mutex<RemoteFS>>)>,
+    config_path: PathBuf,
+    cli_mountpoint: Option<String>,
+    overrides: ConfigOverrides,
+    rt: tokio::runtime::Handle,
+) {
+    std::thread::spawn(move || {
+        let mut sighup = rt.block_on(async { signal(SignalKind::hangup()).expect("Impossibile registrare SIGHUP") });
+
+        while rt.block_on(sighup.recv()).is_some() {
+            println!("[RELOAD] Ricevuto SIGHUP: rileggo {}...", config_path.display());
+            let configs = config::read_config(&config_path)
+                .and_then(|config| resolve_mounts(&config, cli_mountpoint.as_deref(), &overrides));
+            let configs = match configs {
+                Ok(configs) => configs,
+                Err(e) => {
+                    eprintln!("[RELOAD] {}: configurazione invariata.", e);
+                    continue;
+                }
+            };
+            for (mountpoint, config) in configs {
+                match mounts.iter().find(|(mounted, _)| *mounted == mountpoint) {
+                    Some((_, fs_arc)) => fs_arc.lock().unwrap_or_else(PoisonError::into_inner).reload_config(config),
+                    None => println!("[RELOAD] {} non è tra i mount attivi: ignorato fino al riavvio del processo.", mountpoint),
+                }
+            }
+        }
+    });
+}
+
+/// Resolves the mounts served by this process: `cli_mountpoint` (already absolute) with
+/// the top-level configuration, if given, then every `[[mount]]` table of `config`
+/// (see `Config::mount_configs`). The CLI overrides are applied to each of them.
+///
+/// # Returns
+/// * `Ok(Vec<(String, Config)>)` with the absolute mountpoint and the configuration of each mount.
+/// * `Err(String)` if a `[[mount]]` table is invalid or a mountpoint appears twice.
+fn resolve_mounts(
+    config: &config::Config,
+    cli_mountpoint: Option<&str>,
+    overrides: &ConfigOverrides,
+) -> Result<Vec<(String, config::Config)>, String> {
+    let mut mounts = Vec::new();
+    if let Some(mountpoint) = cli_mountpoint {
+        mounts.push((mountpoint.to_string(), config::Config { mounts: Vec::new(), ..config.clone() }));
+    }
+    for (mountpoint, mount_config) in config.mount_configs()? {
+        mounts.push((absolute_mountpoint(&mountpoint), mount_config));
+    }
+    for (_, mount_config) in &mut mounts {
+        overrides.apply(mount_config);
+    }
+    for (index, (mountpoint, _)) in mounts.iter().enumerate() {
+        if mounts[..index].iter().any(|(other, _)| other == mountpoint) {
+            return Err(format!("il mountpoint {} compare più volte", mountpoint));
+        }
+    }
+    Ok(mounts)
+}
+
+/// Checks the configuration of a mount before mounting it: its FUSE options,
+/// its glob filters and its `root_prefix` (see `check_root_prefix`).
+///
+/// # Returns
+/// The parsed mount options, or a readable message for the first invalid setting.
+fn validate_mount(config: &config::Config) -> Result<Vec<MountOption>, String> {
+    let options = config.fuse_mount_options()?;
+    fs::filter::PathFilter::from_config(config)?;
+    check_root_prefix(config)?;
+    Ok(options)
+}
+
+/// Checks that `mountpoint` can be mounted on, so that the usual mistakes get a
+/// clear message instead of the generic error of `fuser::spawn_mount2`.
+///
+/// The mountpoint must be an existing directory, and an empty one unless
+/// `options` has `nonempty` (`--allow-nonempty`). A mountpoint that cannot even be
+/// read is usually the leftover of a client that died without unmounting.
+///
+/// # Returns
+/// A readable message, with what to do, for the first problem found.
+fn check_mountpoint(mountpoint: &str, options: &[MountOption]) -> Result<(), String> {
+    let mut entries = match std::fs::read_dir(mountpoint) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(format!("il mountpoint non esiste: crealo con `mkdir -p {}`", mountpoint));
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotADirectory => {
+            return Err("il mountpoint non è una directory".to_string());
+        }
+        Err(e) if e.raw_os_error() == Some(libc::ENOTCONN) => {
+            return Err(format!("il mountpoint è un mount FUSE non più servito: smontalo con `fusermount -u {}`", mountpoint));
+        }
+        Err(e) => return Err(format!("impossibile leggere il mountpoint: {}", e)),
+    };
+    let allow_nonempty = options.iter().any(|option| *option == MountOption::CUSTOM("nonempty".to_string()));
+    if !allow_nonempty && entries.next().is_some() {
+        return Err("il mountpoint non è vuoto: usa --allow-nonempty (o `nonempty` in mount_options) per montarci sopra comunque".to_string());
+    }
+    Ok(())
+}
+
+/// Implements the `search` subcommand: prints the matching paths, one per line
+/// (directories with a trailing `/`).
+///
+/// # Returns
+/// The process exit code: `0` if at least one entry matched, `1` if none did,
+/// `2` if the search failed.
+fn run_search(config: &config::Config, query: &str, path: &str, glob: bool, limit: Option<usize>) -> i32 {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let client = api_client::ApiClient::default();
+    // `--path` and the printed paths are relative to `root_prefix`, as in the mount
+    let server_path = fs::join_path(&config.root_prefix, path.trim_matches('/'));
+    let response = match runtime.block_on(api_client::search(&client, query, server_path.trim_end_matches('/'), glob, limit, &config.server_url)) {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("Ricerca fallita: {}", e);
+            return 2;
+        }
+    };
+
+    for result in &response.results {
+        let suffix = if result.entry.kind == "directory" { "/" } else { "" };
+        let path = result.path.strip_prefix(config.root_prefix.as_str()).unwrap_or(&result.path);
+        println!("{}{}", path.trim_start_matches('/'), suffix);
+    }
+    if response.truncated {
+        eprintln!("Risultati troncati: restringi la ricerca con --path o una query più specifica.");
+    }
+    if response.results.is_empty() { 1 } else { 0 }
+}
+
+/// Implements `client checksum`: asks the server for the SHA-256 of `path` (relative to
+/// `root_prefix`, as in the mount) and prints it like `sha256sum`, so it can be compared
+/// with a local copy without downloading the file.
+///
+/// # Returns
+/// The process exit code: `0` on success, `1` if the file does not exist, `2` on other errors.
+fn run_checksum(config: &config::Config, path: &str) -> i32 {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let client = api_client::ApiClient::default();
+    let server_path = fs::join_path(&config.root_prefix, path.trim_matches('/'));
+    match runtime.block_on(api_client::get_checksum_from_server(&client, &server_path, &config.server_url)) {
+        Ok(Some(checksum)) => {
+            println!("{}  {}", checksum.sha256, path);
+            0
+        }
+        Ok(None) => {
+            eprintln!("{}: file non trovato sul server", path);
+            1
+        }
+        Err(e) => {
+            eprintln!("Checksum di {} fallito: {}", path, e);
+            2
+        }
+    }
+}
+
+/// Implements `client refresh`: opens `path` through the mount and issues `REFRESH_IOCTL` on it.
+///
+/// The daemon handles it in `fs::ioctl::ioctl` (buffered writes uploaded, caches dropped).
+///
+/// # Returns
+/// The process exit code: `0` on success, `1` if the file cannot be opened or the ioctl fails
+/// (e.g. `ENOTTY` if `path` is not on a remote-fs mount).
+fn run_refresh(path: &std::path::Path) -> i32 {
+    use std::os::fd::AsRawFd;
+
+    // Funziona anche per le directory: `open` in sola lettura è permesso
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Impossibile aprire {}: {}", path.display(), e);
+            return 1;
+        }
+    };
+    let result = unsafe { libc::ioctl(file.as_raw_fd(), fs::ioctl::REFRESH_IOCTL as _) };
+    if result < 0 {
+        eprintln!("Refresh di {} fallito: {}", path.display(), std::io::Error::last_os_error());
+        return 1;
+    }
+    println!("Refresh di {} completato.", path.display());
+    0
+}
+
+/// Implements `client fsck`: writes `check` (or `repair`) to `.remotefs/fsck` under
+/// `mountpoint` and prints the report the daemon produced (see `fs::fsck::run`).
+///
+/// # Returns
+/// The process exit code: `0` if no discrepancy was found, `1` if some were,
+/// `2` if the check could not run (not a remote-fs mount, server unreachable).
+fn run_fsck(mountpoint: &std::path::Path, repair: bool) -> i32 {
+    let control = mountpoint.join(fs::control::CONTROL_DIR_NAME).join("fsck");
+    if let Err(e) = std::fs::write(&control, if repair { "repair\n" } else { "check\n" }) {
+        eprintln!("Controllo di {} fallito: {}", mountpoint.display(), e);
+        return 2;
+    }
+    let report = match std::fs::read_to_string(&control) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Impossibile leggere {}: {}", control.display(), e);
+            return 2;
+        }
+    };
+    print!("{}", report);
+    let clean = report.lines().any(|line| line == "mismatches: 0");
+    if clean { 0 } else { 1 }
+}
+
+/// Writes the commented default configuration (`config::default_config_template`) to `path`.
+///
+/// An existing file is left untouched unless `force` is set.
+///
+/// # Returns
+/// The exit code of the `init-config` subcommand.
+fn run_init_config(path: &std::path::Path, force: bool) -> i32 {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        // create_new: il controllo di esistenza e la creazione sono un'unica operazione
+        options.create_new(true);
+    }
+    let result = options.open(path).and_then(|mut file| {
+        std::io::Write::write_all(&mut file, config::default_config_template().as_bytes())
+    });
+    match result {
+        Ok(()) => {
+            println!("Configurazione di default scritta in {}.", path.display());
+            0
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            eprintln!("{} esiste già: usa --force per sovrascriverlo.", path.display());
+            1
+        }
+        Err(e) => {
+            eprintln!("Impossibile scrivere {}: {}", path.display(), e);
+            1
+        }
+    }
+}
+
+/// Checks that `root_prefix` exists on the server and is a directory, before mounting it.
+///
+/// # Returns
+/// * `Ok(())` if the prefix is empty (the whole server) or a directory.
+/// * `Err(String)` with a readable message otherwise, or if the server cannot be reached.
+fn check_root_prefix(config: &config::Config) -> Result<(), String> {
+    if config.root_prefix.is_empty() {
+        return Ok(());
+    }
+    if config.root_prefix.split('/').any(|component| component == ".." || component == ".") {
+        return Err(format!("root_prefix '{}' non può contenere '.' o '..'", config.root_prefix));
+    }
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let client = api_client::ApiClient::default();
+    match runtime.block_on(api_client::stat_resource(&client, &config.root_prefix, &config.server_url)) {
+        Ok(Some(entry)) if entry.is_dir() => Ok(()),
+        Ok(Some(_)) => Err(format!("root_prefix '{}' non è una directory sul server", config.root_prefix)),
+        Ok(None) => Err(format!("root_prefix '{}' non esiste sul server", config.root_prefix)),
+        Err(e) => Err(format!("impossibile verificare root_prefix '{}' su {}: {}", config.root_prefix, config.server_url, e)),
+    }
+}
+
+/// Returns the absolute form of `mountpoint`, or `mountpoint` itself if it cannot be resolved.
+///
+/// The default daemon file names are derived from this path, so the daemon and
+/// the `status`/`unmount` subcommands must resolve it the same way.
+fn absolute_mountpoint(mountpoint: &str) -> String {
+    std::fs::canonicalize(mountpoint)
+        .map_or(mountpoint.to_string(), |p| p.to_string_lossy().to_string())
+}
+
+/// Finds the PID file of the daemon targeted by a subcommand.
+///
+/// Uses `pid_file` from the configuration/CLI if set, otherwise the default
+/// derived from `mountpoint`, or from the first `[[mount]]` of the configuration
+/// (the daemon takes its files from its first mount). Exits with an error if
+/// none is available.
+fn subcommand_pid_file(config: &config::Config, mountpoint: Option<String>) -> String {
+    let first_mount = || config.mount_configs().ok().and_then(|mounts| mounts.into_iter().next());
+    match (&config.pid_file, mountpoint) {
+        (Some(pid_file), _) => pid_file.clone(),
+        (None, Some(mountpoint)) => config.daemon_paths(&absolute_mountpoint(&mountpoint)).pid_file,
+        (None, None) => match first_mount() {
+            Some((mountpoint, mount_config)) => mount_config.daemon_paths(&absolute_mountpoint(&mountpoint)).pid_file,
+            None => {
+                eprintln!("Specifica il mountpoint del demone oppure --pid-file.");
+                std::process::exit(2);
+            }
+        },
+    }
+}
+
+/// A filesystem served by this process, from its mount to its unmount.
+struct ActiveMount {
+    /// Absolute path of the mountpoint.
+    mountpoint: String,
+    fs: Arc<Mutex<RemoteFS>>,
+    /// The FUSE session, `None` if the mount failed.
+    session: Option<fuser::BackgroundSession>,
+    /// Dropped to stop the background threads of the mount (see `spawn_mount_task`).
+    stop: watch::Sender<()>,
+}
+
+impl ActiveMount {
+    /// Whether the FUSE session ended on its own (the filesystem was unmounted from outside).
+    fn session_ended(&self) -> bool {
+        self.session.as_ref().is_some_and(|session| session.guard.is_finished())
+    }
+
+    /// Unmounts the filesystem (if still mounted), stops its background threads and
+    /// saves its inode map for the next mount (if `inode_map_file` is configured).
+    fn finish(self) {
+        let ActiveMount { fs, session, stop, .. } = self;
+        // Il drop della sessione smonta il filesystem (se ancora montato)
+        drop(session);
+        drop(stop);
+        // Il lock di un mount andato in panic è avvelenato, ma gli inode vanno salvati comunque
+        let mut fs = fs.lock().unwrap_or_else(PoisonError::into_inner);
+        // La sessione è chiusa: niente più notifiche al kernel
+        fs.kernel_notifier = None;
+        fs.save_inode_map();
+    }
+}
+
+/// Runs `task`, a background loop of a mount, until it ends or `stop` is dropped.
+///
+/// The loops block on the `RemoteFS` lock (a `std::sync::Mutex`), so each one gets
+/// a thread of its own driving the shared `runtime`, instead of a worker of the
+/// runtime: the workers must stay free for the requests of the FUSE operations.
+fn spawn_mount_task<F>(runtime: &tokio::runtime::Runtime, stop: &watch::Sender<()>, task: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let handle = runtime.handle().clone();
+    let mut stopped = stop.subscribe();
+    std::thread::spawn(move || {
+        handle.block_on(async move {
+            tokio::select! {
+                _ = task => {}
+                _ = stopped.changed() => {}
+            }
+        })
+    });
+}
+
+/// Creates the `RemoteFS` of a mount on the shared `runtime` and mounts it at `mountpoint`,
+/// then starts its `/health` poller and its WebSocket watcher.
+///
+/// A mount that fails is reported and returned without a session, so that
+/// the other mounts of the process are served anyway.
+fn start_mount(mountpoint: String, config: config::Config, options: &[MountOption], runtime: &Arc<tokio::runtime::Runtime>) -> ActiveMount {
+    let fs_arc = Arc::new(Mutex::new(RemoteFS::new(config, runtime.clone())));
+    let (stop, _) = watch::channel(());
+
+    println!("Mounting filesystem at {:?} with {:?}", mountpoint, options);
+    let session = match fuser::spawn_mount2(FsWrapper(fs_arc.clone()), &mountpoint, options) {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("Failed to mount filesystem at {}: {}", mountpoint, e);
+            return ActiveMount { mountpoint, fs: fs_arc, session: None, stop };
+        }
+    };
+
+    // Controllo periodico dell'API HTTP, indipendente dal WebSocket
+    spawn_mount_task(runtime, &stop, poll_health(fs_arc.clone()));
+
+    // Il Notifier della sessione invalida le cache del kernel (dentry, attributi,
+    // pagine): lo usano il watcher per le modifiche remote e `client refresh`.
+    // (IMPORTANTE: il thread del watcher viene creato DOPO il daemonize, quindi sopravvive nel processo figlio)
+    let notifier = Arc::new(session.notifier());
+    fs_arc.lock().unwrap().kernel_notifier = Some(notifier.clone());
+    spawn_mount_task(runtime, &stop, connect_and_watch(fs_arc.clone(), notifier));
+
+    ActiveMount { mountpoint, fs: fs_arc, session: Some(session), stop }
 }
 
 fn main() {
     // 1. Leggi gli argomenti da riga di comando
     let cli = Cli::parse();
 
-    // 2. Carica la configurazione di base dal file config.toml
-    let mut config = config::load_config();
-    println!("Configurazione da file: {:?}", config);
+    // init-config genera il file di configurazione, quindi non ne legge nessuno
+    if let Some(Command::InitConfig { path, force }) = &cli.command {
+        std::process::exit(run_init_config(path, *force));
+    }
 
-    // 3. Sovrascrivi i valori con gli argomenti della CLI, se forniti
-    if let Some(strategy) = cli.cache_strategy {
-        config.cache_strategy = strategy;
-        println!("INFO: Strategia cache sovrascritta da CLI: {:?}", strategy);
+    // 2. Carica la configurazione di base da --config o dal primo config.toml trovato,
+    //    prima del daemonize (che sposta la working directory in "/")
+    let (mut config, config_file) = match config::load_config(cli.config.as_deref()) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("Errore di configurazione: {}", e);
+            std::process::exit(2);
+        }
+    };
+    // server_url validato subito: lo usano anche i sottocomandi, e un URL malformato
+    // non deve emergere solo più tardi nel thread del watcher
+    config.server_url = match config::normalize_server_url(&config.server_url) {
+        Ok(server_url) => server_url,
+        Err(e) => {
+            eprintln!("Errore di configurazione: {}", e);
+            std::process::exit(2);
+        }
+    };
+    if cli.pid_file.is_some() {
+        config.pid_file = cli.pid_file.clone();
     }
-    if let Some(ttl) = cli.cache_ttl_seconds {
-        config.cache_ttl_seconds = ttl;
-        println!("INFO: TTL cache sovrascritto da CLI: {}s", ttl);
+    let overrides = ConfigOverrides::from_cli(&cli);
+    // Percorso assoluto: dopo il daemonize la working directory è "/", ma SIGHUP deve rileggere lo stesso file
+    // (senza un file all'avvio, il config.toml della directory di lancio)
+    let config_path = config_file.unwrap_or_else(|| {
+        std::env::current_dir().map(|dir| dir.join("config.toml")).unwrap_or_else(|_| PathBuf::from("config.toml"))
+    });
+
+    // I sottocomandi parlano con un demone già in esecuzione e terminano subito
+    match cli.command {
+        Some(Command::Status { mountpoint }) => {
+            std::process::exit(daemon::print_status(&subcommand_pid_file(&config, mountpoint)))
+        }
+        Some(Command::Unmount { mountpoint }) => {
+            std::process::exit(daemon::request_unmount(&subcommand_pid_file(&config, mountpoint)))
+        }
+        Some(Command::Search { query, path, glob, limit }) => {
+            std::process::exit(run_search(&config, &query, &path, glob, limit))
+        }
+        Some(Command::Checksum { path }) => {
+            std::process::exit(run_checksum(&config, &path))
+        }
+        Some(Command::Refresh { path }) => {
+            std::process::exit(run_refresh(&path))
+        }
+        Some(Command::Fsck { mountpoint, repair }) => {
+            std::process::exit(run_fsck(&mountpoint, repair))
+        }
+        Some(Command::InitConfig { .. }) => unreachable!("init-config è gestito prima di leggere la configurazione"),
+        None => {}
     }
-    if let Some(capacity) = cli.cache_lru_capacity {
-        config.cache_lru_capacity = capacity;
-        println!("INFO: Capacità LRU sovrascritta da CLI: {}", capacity);
+    println!("Configurazione da file: {:?}", config);
+
+    // 3. I mount da servire (il mountpoint della CLI e le tabelle [[mount]]), ognuno
+    //    con i valori sovrascritti dagli argomenti della CLI
+    let cli_mountpoint = cli.mountpoint.as_deref().map(absolute_mountpoint);
+    let mounts = match resolve_mounts(&config, cli_mountpoint.as_deref(), &overrides) {
+        Ok(mounts) if !mounts.is_empty() => mounts,
+        Ok(_) => {
+            eprintln!("Specifica il mountpoint, oppure dei [[mount]] nel file di configurazione.");
+            std::process::exit(2);
+        }
+        Err(e) => {
+            eprintln!("Errore di configurazione: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    // Opzioni di mount, filtri glob e root_prefix validati subito: un errore deve essere
+    // visibile prima del daemonize. Un mount non valido viene saltato, gli altri partono.
+    let mounts: Vec<_> = mounts.into_iter().filter_map(|(mountpoint, mount_config)| {
+        println!("Configurazione finale di {}: {:?}", mountpoint, mount_config);
+        match validate_mount(&mount_config) {
+            Ok(options) => match check_mountpoint(&mountpoint, &options) {
+                Ok(()) => Some((mountpoint, mount_config, options)),
+                Err(e) => {
+                    eprintln!("Impossibile montare {}: {}", mountpoint, e);
+                    None
+                }
+            },
+            Err(e) => {
+                eprintln!("Errore di configurazione di {}: {}", mountpoint, e);
+                None
+            }
+        }
+    }).collect();
+    if mounts.is_empty() {
+        std::process::exit(2);
     }
-    
-    println!("Configurazione finale: {:?}", config);
+
+    // File del demone (log e PID): di default diversi per ogni mountpoint, qui quelli del primo mount
+    let daemon_paths = mounts[0].1.daemon_paths(&mounts[0].0);
+
     let should_daemonize = cli.daemon || config.daemon;
     // Deve essere eseguita PRIMA di spawnare qualsiasi thread (watcher) o creare connessioni.
     if should_daemonize {
-        let stdout = File::create("/tmp/fuse_client.out").unwrap();
-        let stderr = File::create("/tmp/fuse_client.err").unwrap();
+        println!("INFO: Demone: stdout={} stderr={} pid={}", daemon_paths.stdout, daemon_paths.stderr, daemon_paths.pid_file);
+        let stdout = File::create(&daemon_paths.stdout).unwrap();
+        let stderr = File::create(&daemon_paths.stderr).unwrap();
 
         let daemonize = Daemonize::new()
-            .pid_file("/tmp/fuse_client.pid") // Crea file PID per gestire il processo
+            .pid_file(&daemon_paths.pid_file) // Crea file PID per gestire il processo
             .chown_pid_file(true)
             .working_directory("/") // Buona norma per i demoni
             .stdout(stdout)  // Redireziona stdout su file
@@ -91,61 +737,222 @@ fn main() {
     }
     // --------------------------------
 
-    // 4. Prendi il mountpoint dalla CLI
-    let mountpoint = std::ffi::OsString::from(cli.mountpoint);
+    // 4. Un unico runtime Tokio per le richieste HTTP e i thread di servizio di tutti i mount
+    let runtime = fs::build_runtime(config.runtime_worker_threads);
 
-    // 5. Crea l'istanza di RemoteFS con la configurazione finale
-    let fs_inner = RemoteFS::new(config.clone());
-    let fs_wrapper = FsWrapper(Arc::new(Mutex::new(fs_inner)));
+    // 5. Monta ogni filesystem con il suo RemoteFS: le sessioni girano in thread in background
+    let mut mounted: Vec<ActiveMount> = Vec::new();
+    for (mountpoint, mount_config, options) in mounts {
+        let mount = start_mount(mountpoint, mount_config, &options, &runtime);
+        if mount.session.is_some() {
+            mounted.push(mount);
+        } else {
+            // Niente da servire, ma la mappa degli inode va salvata come per gli altri
+            mount.finish();
+        }
+    }
+    let filesystems: Vec<_> = mounted.iter().map(|mount| (mount.mountpoint.clone(), mount.fs.clone())).collect();
 
-    // 6. Avvia il watcher in un thread separato
-    // (IMPORTANTE: Questo thread viene creato DOPO il daemonize, quindi sopravvive nel processo figlio)
-    let fs_clone_for_watcher = fs_wrapper.0.clone();
-    std::thread::spawn(move || {
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(async {
-            connect_and_watch(fs_clone_for_watcher).await;
-        });
-    });
+    // 6. Smontaggio pulito su SIGTERM/SIGINT (es. `client unmount`) e file di stato per `client status`
+    let (shutdown_tx, shutdown_rx) = std::sync::mpsc::channel();
+    daemon::spawn_shutdown_handler(filesystems.iter().map(|(_, fs)| fs.clone()).collect(), runtime.handle().clone(), shutdown_tx);
+    // Ricarica a caldo di config.toml su SIGHUP (cache, limiti di richieste e upload)
+    spawn_reload_handler(filesystems.clone(), config_path, cli_mountpoint, overrides, runtime.handle().clone());
+    if should_daemonize {
+        daemon::spawn_status_writer(filesystems, &daemon_paths.pid_file);
+    }
 
-    // 7. Monta il filesystem (bloccante)
-    let filesystem = fs_wrapper;
-    let options = vec![
-        MountOption::AutoUnmount,
-        MountOption::FSName("remoteFS".to_string()),
-        MountOption::RW, 
-        // MountOption::Debug, // Utile, ma ricorda che l'output va su file se sei in daemon mode
-    ];
-    
-    println!("Mounting filesystem at {:?}", mountpoint);
-    if let Err(e) = fuser::mount2(filesystem, &mountpoint, &options) {
-        eprintln!("Failed to mount filesystem: {}", e);
+    // 7. Il thread principale attende un segnale di arresto, che smonta tutto, o lo smontaggio
+    // dall'esterno di un mount (es. `fusermount -u`), che termina solo la sua sessione
+    while !mounted.is_empty() {
+        match shutdown_rx.recv_timeout(SESSION_POLL_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => {
+                let (ended, running): (Vec<_>, Vec<_>) = mounted.into_iter().partition(ActiveMount::session_ended);
+                mounted = running;
+                for mount in ended {
+                    println!("[DAEMON] Sessione FUSE terminata: {} è stato smontato.", mount.mountpoint);
+                    mount.finish();
+                }
+            }
+            Ok(signal_name) => {
+                for mount in mounted.drain(..) {
+                    println!("[DAEMON] Arresto richiesto da {}: smontaggio di {}...", signal_name, mount.mountpoint);
+                    mount.finish();
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                eprintln!("[DAEMON] Gestore dei segnali terminato: smontaggio di tutti i mount.");
+                mounted.drain(..).for_each(ActiveMount::finish);
+            }
+        }
+    }
+    if should_daemonize {
+        daemon::cleanup_files(&daemon_paths.pid_file);
     }
 }
 
-async fn connect_and_watch(fs_arc: Arc<Mutex<RemoteFS>>) {
+/// Polls `GET /health` every `health_poll_seconds` to detect outages of the HTTP API
+/// (see `api_client::check_alive`).
+///
+/// On a down -> up transition the paths changed since the last successful poll
+/// are invalidated, because those changes may have been missed (the WebSocket
+/// may not have noticed the outage at all), then the recently used paths are
+/// fetched again in the background (`fs::catchup::catch_up`).
+///
+/// A server that answers but cannot store data (read-only or full data directory,
+/// as reported by `GET /healthz`, see `api_client::check_health`) puts the mount in degraded mode: `RemoteFS::degraded_errno` makes the writing
+/// operations fail at once, until a later poll finds the server healthy again.
+///
+/// Every result is also reported to the mount's `api_client::RetryState`, which
+/// bounds the retries of `server_retry_window_seconds` to the start of the outage.
+async fn poll_health(fs_arc: Arc<Mutex<RemoteFS>>) {
+    let (client, base_url, poll_seconds) = {
+        let fs = fs_arc.lock().unwrap();
+        (fs.client.clone(), fs.config.server_url.clone(), fs.config.health_poll_seconds)
+    };
+    if poll_seconds == 0 {
+        return;
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(poll_seconds));
+    let mut server_up = true;
+    let mut down_since = Instant::now();
+    let mut last_healthy = SystemTime::now();
+    loop {
+        interval.tick().await;
+        let polled_at = SystemTime::now();
+        let healthy = api_client::check_alive(&client, &base_url).await.is_ok();
+        // Le richieste in attesa del server smettono di riprovare se l'interruzione dura troppo
+        client.retry_state().set_server_reachable(healthy);
+        // Lo stato dello storage solo se il server risponde: /healthz non decide se è raggiungibile
+        if healthy
+            && let Ok(health) = api_client::check_health(&client, &base_url).await
+        {
+            let degraded_errno = match &health {
+                ServerHealth::Healthy => None,
+                ServerHealth::Degraded { out_of_space: true, .. } => Some(libc::ENOSPC),
+                ServerHealth::Degraded { .. } => Some(libc::EROFS),
+            };
+            let mut fs = fs_arc.lock().unwrap();
+            match (&health, fs.degraded_errno.is_some()) {
+                (ServerHealth::Degraded { reason, .. }, false) => {
+                    eprintln!("[HEALTH] Il server {} non accetta dati ({}): le scritture falliscono subito.", base_url, reason);
+                }
+                (ServerHealth::Healthy, true) => println!("[HEALTH] Il server {} accetta di nuovo le scritture.", base_url),
+                _ => {}
+            }
+            fs.degraded_errno = degraded_errno;
+        }
+        match (server_up, healthy) {
+            (true, false) => {
+                eprintln!("[HEALTH] Il server {} non risponde.", base_url);
+                down_since = Instant::now();
+            }
+            (false, true) => {
+                println!("[HEALTH] Server di nuovo raggiungibile dopo {:?}: aggiorno le cache.", down_since.elapsed());
+                tokio::spawn(fs::catchup::catch_up(fs_arc.clone(), last_healthy, None, "Server di nuovo raggiungibile"));
+            }
+            _ => {}
+        }
+        if healthy {
+            last_healthy = polled_at;
+        }
+        server_up = healthy;
+    }
+}
+
+/// Keeps a WebSocket connection to the server's watcher and applies its notifications.
+///
+/// `CHANGE` and `RENAME` messages from other clients invalidate the client's caches
+/// and, through `notifier` (from the mounted session), the kernel's. After a
+/// reconnection the paths changed while disconnected are invalidated and the
+/// recently used ones fetched again (`fs::catchup::catch_up`), since the
+/// notifications sent in the meantime are lost.
+async fn connect_and_watch(fs_arc: Arc<Mutex<RemoteFS>>, notifier: Arc<fuser::Notifier>) {
     // Recuperiamo URL e ID Client proteggendo l'accesso con il lock
-    let (url_str, my_client_id) = {
+    let (ws_url, my_client_id, keepalive_interval, keepalive_timeout, connect_timeout) = {
         let fs = fs_arc.lock().unwrap();
-        // Costruiamo l'URL WS basandoci sulla config HTTP (es. http://... -> ws://...)
-        let base = fs.config.server_url.replace("https://", "wss://").replace("http://", "ws://");
-        (format!("{}/ws", base), fs.client_id.clone())
+        // Costruiamo l'URL WS basandoci sulla config HTTP (es. http://... -> ws://.../ws)
+        (
+            config::ws_url_from(&fs.config.server_url),
+            fs.client_id.clone(),
+            Duration::from_secs(fs.config.keepalive_interval_seconds.max(1)),
+            Duration::from_secs(fs.config.keepalive_timeout_seconds),
+            Duration::from_secs(fs.config.ws_connect_timeout_seconds.max(1)),
+        )
     };
+    // server_url è già stato validato all'avvio: qui non dovrebbe mai fallire
+    let url = match ws_url {
+        Ok(url) => url,
+        Err(e) => {
+            eprintln!("[WATCHER_CLIENT] URL WebSocket non valido, watcher disattivato: {}", e);
+            return;
+        }
+    };
+    let url_str = url.to_string();
 
-    let url = Url::parse(&url_str).expect("URL WebSocket non valido");
-    
     println!("[WATCHER_CLIENT] Il mio Client ID è: {}", my_client_id);
     println!("[WATCHER_CLIENT] Avvio loop di connessione verso {}", url_str);
 
+    // Ultimo messaggio ricevuto prima della disconnessione (nessuno al primo collegamento)
+    let mut offline_since: Option<SystemTime> = None;
     loop {
-        match connect_async(url.clone()).await {
+        // L'ID anche nell'handshake, così il server può elencare questo client (GET /admin/clients)
+        let mut request = url.as_str().into_client_request().expect("URL WebSocket non valido");
+        if let Ok(id) = my_client_id.parse() {
+            request.headers_mut().insert("X-Client-ID", id);
+        }
+        // Un host che scarta i pacchetti non risponde mai: senza timeout il tentativo
+        // resterebbe appeso per minuti. Lo scadere conta come un tentativo fallito.
+        let attempt = match tokio::time::timeout(connect_timeout, connect_async(request)).await {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(_) => Err(format!("timeout dopo {:?}", connect_timeout)),
+        };
+        match attempt {
             Ok((ws_stream, _)) => {
                 println!("[WATCHER_CLIENT] Connesso al watcher del server.");
-                let (_, mut read) = ws_stream.split();
+                fs_arc.lock().unwrap().ws_connected = true;
+                // Le notifiche perse mentre eravamo disconnessi: invalidiamo i path
+                // modificati nel frattempo e riscarichiamo gli attributi usati di recente
+                if let Some(since) = offline_since.take() {
+                    tokio::spawn(fs::catchup::catch_up(fs_arc.clone(), since, Some(notifier.clone()), "Watcher riconnesso"));
+                }
+                let (mut write, mut read) = ws_stream.split();
+
+                // --- KEEPALIVE ---
+                // Inviamo un Ping periodico: se il server non risponde (nessun frame, nemmeno
+                // il Pong) entro il timeout, la connessione è morta (es. NAT/proxy che la
+                // chiude in silenzio) e forziamo la riconnessione.
+                let mut ping_interval = tokio::time::interval(keepalive_interval);
+                let mut last_seen = Instant::now();
+                // Ultima notifica ricevuta: se il server chiude perché siamo rimasti indietro,
+                // le notifiche perse sono quelle successive a questa
+                let mut last_notification = Instant::now();
+                let mut lagged = false;
+
+                loop {
+                    let message = tokio::select! {
+                        message = read.next() => match message {
+                            Some(message) => message,
+                            None => break,
+                        },
+                        _ = ping_interval.tick() => {
+                            if last_seen.elapsed() > keepalive_timeout {
+                                println!("[WATCHER_CLIENT] Nessuna risposta dal server da {:?}: connessione persa.", last_seen.elapsed());
+                                break;
+                            }
+                            if let Err(e) = write.send(Message::Ping(Vec::new())).await {
+                                eprintln!("[WATCHER_CLIENT] Errore nell'invio del ping: {}", e);
+                                break;
+                            }
+                            continue;
+                        }
+                    };
+                    last_seen = Instant::now();
 
-                while let Some(message) = read.next().await {
                     match message {
                         Ok(Message::Text(text)) => {
+                            last_notification = Instant::now();
                             // --- LOGICA ECHO SUPPRESSION ---
                             let (clean_text, sender_id) = if let Some((msg, id)) = text.rsplit_once("|BY:") {
                                 (msg, Some(id))
@@ -161,14 +968,42 @@ async fn connect_and_watch(fs_arc: Arc<Mutex<RemoteFS>>) {
                             }
                             // -------------------------------
 
-                            if let Some(path_str) = clean_text.strip_prefix("CHANGE:") {
+                            // Invalidazioni per le cache del kernel, inviate dopo aver rilasciato il lock
+                            let mut kernel_invalidations = Vec::new();
+
+                            if let Some((from, to)) = clean_text.strip_prefix("RENAME:").and_then(|r| r.split_once("|TO:")) {
+                                println!("[WATCHER_CLIENT] Rinomina remota: {} -> {}", from, to);
+                                let mut fs = fs_arc.lock().unwrap();
+
+                                // 1. SPOSTIAMO GLI INODE (l'eventuale inode sovrascritto viene dimenticato)
+                                fs.relocate_cached_paths(&[(from, to)]);
+                                if let Some(ino) = fs.inodes.inode(to) {
+                                    fs.invalidate_read_block(ino);
+                                }
+
+                                // 2. INVALIDIAMO ENTRAMBE LE CARTELLE PADRE
+                                for path in [from, to] {
+                                    let parent_path = std::path::Path::new(path)
+                                        .parent()
+                                        .map_or("".to_string(), |p| p.to_string_lossy().to_string());
+                                    if let Some(parent_ino) = fs.inodes.inode(&parent_path) {
+                                        fs.attribute_cache.remove(&parent_ino);
+                                    }
+                                }
+
+                                // 3. LE STESSE VOCI NELLE CACHE DEL KERNEL (origine e destinazione)
+                                for path in [from, to] {
+                                    kernel_invalidations.extend(fs::notify::invalidations_for_path(&fs, path));
+                                }
+                            } else if let Some(path_str) = clean_text.strip_prefix("CHANGE:") {
                                 println!("[WATCHER_CLIENT] Notifica rilevante per: {}", path_str);
                                 let mut fs = fs_arc.lock().unwrap();
                                 
                                 // 1. INVALIDIAMO IL FILE STESSO (Se esiste in cache)
-                                if let Some(&ino) = fs.path_to_inode.get(path_str) {
+                                if let Some(ino) = fs.inodes.inode(path_str) {
                                     println!("[WATCHER_CLIENT] -> Invalido cache FILE (inode {})", ino);
                                     fs.attribute_cache.remove(&ino);
+                                    fs.invalidate_read_block(ino);
                                 }
 
                                 // 2. INVALIDIAMO LA CARTELLA PADRE
@@ -176,14 +1011,26 @@ async fn connect_and_watch(fs_arc: Arc<Mutex<RemoteFS>>) {
                                     .parent()
                                     .map_or("".to_string(), |p| p.to_string_lossy().to_string());
                                 
-                                if let Some(&parent_ino) = fs.path_to_inode.get(&parent_path) {
+                                if let Some(parent_ino) = fs.inodes.inode(&parent_path) {
                                     println!("[WATCHER_CLIENT] -> Invalido cache PARENT (inode {})", parent_ino);
                                     fs.attribute_cache.remove(&parent_ino);
                                 }
+
+                                // 3. INVALIDIAMO ANCHE LE CACHE DEL KERNEL (dentry, attributi e pagine)
+                                // Se il client non conosce il path non c'è nulla da invalidare
+                                kernel_invalidations = fs::notify::invalidations_for_path(&fs, path_str);
                             }
+
+                            fs::notify::send(&notifier, kernel_invalidations);
                         }
-                        Ok(Message::Close(_)) => {
-                            println!("[WATCHER_CLIENT] Il server ha chiuso la connessione.");
+                        Ok(Message::Close(frame)) => {
+                            // 1013 (Try Again Later): il server ha scartato notifiche destinate a noi
+                            lagged = frame.is_some_and(|frame| frame.code == CloseCode::Again);
+                            if lagged {
+                                println!("[WATCHER_CLIENT] Il server ha chiuso la connessione: notifiche perse, risincronizzo.");
+                            } else {
+                                println!("[WATCHER_CLIENT] Il server ha chiuso la connessione.");
+                            }
                             break;
                         }
                         Err(e) => {
@@ -193,6 +1040,9 @@ async fn connect_and_watch(fs_arc: Arc<Mutex<RemoteFS>>) {
                         _ => {}
                     }
                 }
+                fs_arc.lock().unwrap().ws_connected = false;
+                let last_synced = if lagged { last_notification } else { last_seen };
+                offline_since = Some(SystemTime::now() - last_synced.elapsed());
                 println!("[WATCHER_CLIENT] Disconnesso. Riconnessione...");
             }
             Err(e) => {
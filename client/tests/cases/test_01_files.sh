@@ -76,5 +76,28 @@ test_command "Verificare che l'originale non esista più" "[ ! -f file_principal
 test_command "Verificare che la copia esista ancora" "[ -f copia.txt ]"
 test_command "Rimuovere la copia" "rm copia.txt"
 
+# 5. Stat di un file aperto in scrittura: la dimensione include i dati ancora nel buffer
+exec 3> file_aperto.txt
+printf 'dodici byte!' >&3
+sleep 2 # Oltre il TTL del kernel: lo stat arriva al client
+test_command "Verificare la dimensione prima della chiusura" "[ \"\$(stat -c '%s' file_aperto.txt)\" = '12' ]"
+test_command "Verificare che mtime sia quello della scrittura" "[ \$(( \$(date +%s) - \$(stat -c '%Y' file_aperto.txt) )) -lt 10 ]"
+exec 3>&-
+test_command "Rimuovere il file aperto" "rm file_aperto.txt"
+
+# 6. File binari: tutti i 256 valori di byte devono sopravvivere a ogni passaggio (nessuna conversione UTF-8)
+BIN_REF=$(mktemp)
+for i in $(seq 0 255); do printf "\\$(printf '%03o' "$i")"; done > "$BIN_REF"
+test_command "Creare un file con tutti i 256 valori di byte" "[ \"\$(stat -c '%s' \"$BIN_REF\")\" = '256' ] && cp \"$BIN_REF\" binario.bin"
+test_command "Rileggere il file binario byte per byte" "cmp \"$BIN_REF\" binario.bin"
+test_command "Scrivere i 256 byte in coda a un offset" "dd if=\"$BIN_REF\" of=binario.bin bs=256 seek=1 conv=notrunc status=none"
+sleep 1
+test_command "Rileggere il file binario dopo la scrittura" "cat \"$BIN_REF\" \"$BIN_REF\" | cmp - binario.bin"
+test_command "Troncare il file binario" "truncate -s 200 binario.bin"
+sleep 1
+test_command "Rileggere il file binario dopo il troncamento" "head -c 200 \"$BIN_REF\" | cmp - binario.bin"
+test_command "Rimuovere il file binario" "rm binario.bin"
+rm -f "$BIN_REF"
+
 # --- Esito Finale ---
 exit $FAILED_TESTS
\ No newline at end of file
@@ -51,8 +51,31 @@ test_command_fails() {
 
 # 1. Creazione e Struttura
 test_command "Creare una directory 'dir1'" "mkdir dir1 && [ -d dir1 ]"
+test_command_fails "Fallire nel ricreare 'dir1' senza '-p'" "mkdir dir1"
+test_command "Ricreare 'dir1' con '-p' senza errori" "mkdir -p dir1"
 test_command "Creare una struttura di directory annidata con '-p'" "mkdir -p dir1/subdir/subsubdir && [ -d dir1/subdir/subsubdir ]"
 test_command "Listare il contenuto per verificare la creazione" "ls dir1 | grep -q 'subdir'"
+test_command "Verificare che '..' in una directory annidata punti al padre reale" "[ \"\$(ls -ai dir1/subdir/subsubdir | awk '\$2 == \"..\" { print \$1 }')\" = \"\$(stat -c '%i' dir1/subdir)\" ]"
+
+# Directory più grande del buffer di una singola readdir: il kernel la legge in più chiamate
+test_command "Creare 300 file in una directory" "mkdir dir1/big && for i in \$(seq 1 300); do : > dir1/big/file_\$i; done"
+test_command "Listare tutti i 300 file" "[ \"\$(ls dir1/big | wc -l)\" = '300' ]"
+# `ls -l` usa gli attributi precaricati da readdir con /stat-batch
+test_command "Scrivere 10 byte in uno dei file" "printf 'dieci byte' > dir1/big/file_1"
+sleep 1
+test_command "Listare i 300 file con i metadati (ls -l)" "[ \"\$(ls -l dir1/big | grep -c '^-')\" = '300' ]"
+test_command "Verificare la dimensione riportata da ls -l" "[ \"\$(ls -l dir1/big | awk '\$NF == \"file_1\" { print \$5 }')\" = '10' ]"
+# Cancellare entry già restituite durante la lettura non deve far saltare né ripetere le altre:
+# le readdir successive dello stesso handle leggono lo snapshot preso da opendir
+test_command "Listare tutti i 300 file cancellandone 100 durante la lettura" "python3 -c \"
+import os
+it = os.scandir('dir1/big')
+seen = [next(it).name for _ in range(100)]
+for name in seen:
+    os.unlink(os.path.join('dir1/big', name))
+seen += [e.name for e in it]
+assert len(seen) == 300 and len(set(seen)) == 300, len(seen)
+\" && [ \"\$(ls dir1/big | wc -l)\" = '200' ]"
 
 # 2. Spostamento e Gestione Contenuto
 test_command "Creare un file da spostare" "echo 'contenuto da spostare' > file_da_spostare.txt"
@@ -63,9 +86,46 @@ test_command "Verificare che il file non sia più nella vecchia posizione" "[ !
 test_command "Rimuovere una directory vuota con 'rmdir'" "rmdir dir1/subdir/subsubdir"
 test_command_fails "Fallire nel rimuovere una directory non vuota con 'rmdir'" "rmdir dir1/subdir"
 test_command_fails "Fallire nel rimuovere un file con 'rmdir'" "rmdir dir1/subdir/file_da_spostare.txt"
+# Errori POSIX attesi dalle applicazioni: EISDIR scrivendo una directory, ENOTDIR usando un file come directory
+test_command "Aprire una directory in scrittura fallisce con EISDIR" "python3 -c \"import os
+try:
+    os.open('dir1/subdir', os.O_WRONLY)
+except IsADirectoryError: pass
+else: raise SystemExit(1)\""
+test_command "Elencare un file come directory fallisce con ENOTDIR" "python3 -c \"import os
+try:
+    os.listdir('dir1/subdir/file_da_spostare.txt')
+except NotADirectoryError: pass
+else: raise SystemExit(1)\""
+test_command "Creare un file dentro un file fallisce con ENOTDIR" "python3 -c \"
+try:
+    open('dir1/subdir/file_da_spostare.txt/nuovo.txt', 'w')
+except NotADirectoryError: pass
+else: raise SystemExit(1)\""
 sleep 1
+# `rm -r` di una directory annidata partendo dalla root: il resto della root non deve essere toccato
+test_command "Creare un file nella root da preservare" "echo 'resta' > root_keep.txt"
 test_command "Rimuovere una directory e il suo contenuto con 'rm -r'" "rm -r dir1"
 test_command "Verificare che la directory non esista più" "[ ! -d dir1 ]"
+sleep 1
+test_command "Verificare che la root e i suoi file siano intatti" "ls . > /dev/null && [ \"\$(cat root_keep.txt)\" = 'resta' ]"
+test_command "Pulizia: Rimuovere il file della root" "rm root_keep.txt"
+
+# 4. mtime della cartella padre (make e i watcher confrontano l'mtime delle directory)
+# L'mtime ha una risoluzione di un secondo: si attende prima di ogni modifica
+ROOT_MTIME=$(stat -c %Y .)
+sleep 1.1
+test_command "Creare una directory nella root" "mkdir mtime_dir"
+test_command "Verificare che l'mtime della root avanzi" "[ \"\$(stat -c %Y .)\" -gt $ROOT_MTIME ]"
+DIR_MTIME=$(stat -c %Y mtime_dir)
+sleep 1.1
+test_command "Creare un file nella directory" "touch mtime_dir/child.txt"
+test_command "Verificare che l'mtime della directory avanzi dopo la creazione" "[ \"\$(stat -c %Y mtime_dir)\" -gt $DIR_MTIME ]"
+DIR_MTIME=$(stat -c %Y mtime_dir)
+sleep 1.1
+test_command "Rimuovere il file dalla directory" "rm mtime_dir/child.txt"
+test_command "Verificare che l'mtime della directory avanzi dopo la rimozione" "[ \"\$(stat -c %Y mtime_dir)\" -gt $DIR_MTIME ]"
+test_command "Pulizia: Rimuovere la directory" "rmdir mtime_dir"
 
 # --- Esito Finale ---
 exit $FAILED_TESTS
\ No newline at end of file
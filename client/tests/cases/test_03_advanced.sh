@@ -64,10 +64,17 @@ touch file_per_test_avanzati.txt || {
 if [ -f "file_per_test_avanzati.txt" ]; then
   test_command "Rinominare un file ('rename' implementato)" "mv file_per_test_avanzati.txt file_rinominato.txt && [ -f file_rinominato.txt ] && ! [ -f file_per_test_avanzati.txt ]"
   test_command "Modificare i permessi ('setattr' implementato)" "chmod 777 file_rinominato.txt && [ \"\$(stat -c '%a' file_rinominato.txt)\" = '777' ]"
+  test_command "Verificare la dimensione di blocco configurata (blksize)" "[ \"\$(stat -c '%o' file_rinominato.txt)\" = '${TRANSFER_BLOCK_SIZE:-5120}' ]"
   test_command "Modificare la dimensione ('setattr' implementato)" "truncate -s 100 file_rinominato.txt && [ \"\$(stat -c '%s' file_rinominato.txt)\" = '100' ]"
+  test_command "Impostare mtime con 'touch -d' ('setattr' utimensat)" "touch -d @1000000000 file_rinominato.txt && [ \"\$(stat -c '%Y' file_rinominato.txt)\" = '1000000000' ]"
   test_command "Pulizia: Rimuovere il file di test rinominato" "rm file_rinominato.txt"
 fi
 
+# Rename nella stessa directory (fast path lato server): i figli devono seguire la directory
+test_command "Rinominare una directory nella stessa cartella" "mkdir dir_da_rinominare && echo 'figlio' > dir_da_rinominare/figlio.txt && sleep 1 && mv dir_da_rinominare dir_rinominata && [ ! -d dir_da_rinominare ]"
+test_command "Verificare il contenuto del figlio dopo il rename" "[ \"\$(cat dir_rinominata/figlio.txt)\" = 'figlio' ]"
+test_command "Pulizia: Rimuovere la directory rinominata" "rm -r dir_rinominata"
+
 # 3. Test di Carico Leggero
 echo "Sezione 3: Test di carico leggero"
 test_command "Creare 10 file in un ciclo" "for i in {1..10}; do touch file_ciclo_\$i.txt; done && [ \$(ls | grep file_ciclo_ | wc -l) -eq 10 ]"
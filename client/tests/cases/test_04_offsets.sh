@@ -0,0 +1,171 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+cd "$MOUNT_POINT"
+FAILED_TESTS=0
+# Copia locale di riferimento: ogni scrittura viene replicata qui e poi confrontata con `cmp`.
+EXPECTED=$(mktemp)
+trap 'rm -f "$EXPECTED" "$EXPECTED.src"' EXIT
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# Scrive `data` all'offset `seek` sia nel file montato che nella copia di riferimento,
+# senza troncare (ogni invocazione è un ciclo completo open -> write -> release).
+write_at() {
+  local file=$1
+  local seek=$2
+  local data=$3
+  printf '%s' "$data" | dd of="$file" bs=1 seek="$seek" conv=notrunc status=none &&
+  printf '%s' "$data" | dd of="$EXPECTED" bs=1 seek="$seek" conv=notrunc status=none
+}
+
+# Apre lo stesso file due volte (fd 3 in lettura/scrittura, fd 4 in append) e scrive
+# in zone disgiunte, chiudendo i descrittori in ordine inverso rispetto alle scritture.
+write_two_fds() {
+  local file=$1
+  exec 3<>"$file" 4>>"$file" &&
+  printf 'AAAA' >&3 &&
+  printf 'BBBB' >&4 &&
+  exec 4>&- 3>&- &&
+  printf 'AAAA' | dd of="$EXPECTED" bs=1 seek=0 conv=notrunc status=none &&
+  printf 'BBBB' >> "$EXPECTED"
+}
+
+# --- Esecuzione dei Test ---
+# Ogni `release` deve unire i blocchi in buffer con il contenuto già presente sul server.
+
+# 1. Contenuto iniziale
+test_command "Creare il file con contenuto iniziale" "write_at offsets.bin 0 'AAAAAAAAAA'"
+sleep 1
+test_command "Verificare il contenuto iniziale" "cmp offsets.bin \"\$EXPECTED\""
+
+# 2. Sovrascrittura all'offset 0 (il resto del file deve restare invariato)
+test_command "Sovrascrivere i primi byte all'offset 0" "write_at offsets.bin 0 'BB'"
+sleep 1
+test_command "Verificare la sovrascrittura all'offset 0" "cmp offsets.bin \"\$EXPECTED\""
+
+# 3. Scrittura oltre la fine del file (il buco deve essere riempito di zeri)
+test_command "Scrivere oltre la fine lasciando un buco" "write_at offsets.bin 15 'CC'"
+sleep 1
+test_command "Verificare la dimensione dopo la scrittura sparsa" "[ \"\$(stat -c '%s' offsets.bin)\" = '17' ]"
+test_command "Verificare che il buco contenga zeri" "cmp offsets.bin \"\$EXPECTED\""
+
+# 4. Scrittura nel mezzo, a cavallo tra dati esistenti e buco
+test_command "Sovrascrivere a cavallo del buco" "write_at offsets.bin 8 'DDDD'"
+sleep 1
+test_command "Verificare il contenuto finale" "cmp offsets.bin \"\$EXPECTED\""
+
+# 5. Molti blocchi nello stesso file handle (un solo release con buffer multi-blocco)
+test_command "Scrivere 300KB in blocchi da 64KB" "head -c 307200 /dev/urandom > \"\$EXPECTED.src\" && dd if=\"\$EXPECTED.src\" of=multi_block.bin bs=65536 status=none"
+sleep 1
+test_command "Verificare il file multi-blocco" "cmp multi_block.bin \"\$EXPECTED.src\""
+
+# 6. File oltre la soglia di upload a blocchi (16MB di default): sessione /upload riprendibile
+test_command "Scrivere un file da 20MB" "head -c 20971520 /dev/urandom > \"\$EXPECTED.src\" && cp \"\$EXPECTED.src\" chunked.bin"
+sleep 1
+test_command "Verificare il file caricato a blocchi" "cmp chunked.bin \"\$EXPECTED.src\""
+test_command "Leggere a posizioni alterne da due fd sullo stesso file" "python3 -c \"
+import os, sys
+expected = open(sys.argv[1], 'rb').read()
+a, b = os.open('chunked.bin', os.O_RDONLY), os.open('chunked.bin', os.O_RDONLY)
+for i in range(64):
+    for fd, offset in ((a, i * 4096), (b, 10485760 + i * 4096)):
+        assert os.pread(fd, 4096, offset) == expected[offset:offset + 4096], offset
+\" \"\$EXPECTED.src\""
+
+# 7. Riscrittura con contenuto identico: nessun upload, quindi il mtime sul server non cambia
+test_command "Impostare un mtime noto" "touch -m -d @1000000000 offsets.bin"
+test_command "Riscrivere gli stessi byte all'offset 0" "write_at offsets.bin 0 'BB'"
+sleep 1
+test_command "Verificare che il mtime non sia cambiato" "[ \"\$(stat -c '%Y' offsets.bin)\" = '1000000000' ]"
+test_command "Verificare il contenuto dopo la riscrittura" "cmp offsets.bin \"\$EXPECTED\""
+
+# 8. Due file descriptor aperti in scrittura sullo stesso file: un solo buffer condiviso,
+# caricato all'ultimo release, quindi entrambe le scritture devono sopravvivere
+test_command "Scrivere da due fd aperti sullo stesso file" "write_two_fds offsets.bin"
+sleep 1
+test_command "Verificare che entrambe le scritture siano presenti" "cmp offsets.bin \"\$EXPECTED\""
+
+# 9. Truncate lato server (/truncate): estensione con zeri e accorciamento, anche su dati binari
+test_command "Estendere il file con truncate" "truncate -s 64 offsets.bin && truncate -s 64 \"\$EXPECTED\""
+sleep 1
+test_command "Verificare il file esteso con zeri" "cmp offsets.bin \"\$EXPECTED\""
+test_command "Accorciare il file da 20MB con truncate" "truncate -s 1000 chunked.bin && truncate -s 1000 \"\$EXPECTED.src\""
+sleep 1
+test_command "Verificare il file binario accorciato" "cmp chunked.bin \"\$EXPECTED.src\""
+
+# 10. Truncate con scritture ancora nel buffer: la coda tagliata non deve tornare alla chiusura
+truncate_open_file() {
+  local file=$1
+  exec 3>"$file" &&
+  printf 'AAAAAAAA' >&3 &&
+  truncate -s 4 "$file" &&
+  exec 3>&-
+}
+test_command "Accorciare un file con scritture in buffer" "truncate_open_file truncated.bin"
+sleep 1
+test_command "Verificare che la coda non sia tornata" "[ \"\$(cat truncated.bin)\" = 'AAAA' ]"
+
+# 11. fallocate: la preallocazione estende il file, il buco (punch hole) azzera i byte
+if command -v fallocate > /dev/null; then
+  test_command "Preallocare 4096 byte con fallocate" "fallocate -l 4096 fallocated.bin"
+  test_command "Verificare la dimensione preallocata" "[ \"\$(stat -c '%s' fallocated.bin)\" = '4096' ]"
+  test_command "Creare un buco con fallocate --punch-hole" "printf 'ZZZZZZZZ' > punched.bin && fallocate -p -o 2 -l 4 punched.bin"
+  sleep 1
+  test_command "Verificare che il buco contenga zeri" "cmp punched.bin <(printf 'ZZ\\0\\0\\0\\0ZZ')"
+  test_command "Pulizia: Rimuovere i file di fallocate" "rm fallocated.bin punched.bin"
+else
+  echo "  - Salto: comando fallocate non disponibile."
+fi
+
+# 12. Scritture sovrapposte a offset fuori ordine nello stesso file handle: vince l'ultima,
+# qualunque sia l'offset (il buffer le unisce in ordine di scrittura, non di offset)
+pwrite_overlapping() {
+  python3 - "$@" <<'PY'
+import os, sys
+writes = [(20, b'C' * 10), (0, b'A' * 16), (8, b'B' * 16), (4, b'D' * 4), (30, b'E' * 2), (26, b'F' * 6)]
+for path in sys.argv[1:]:
+    fd = os.open(path, os.O_WRONLY | os.O_CREAT | os.O_TRUNC, 0o644)
+    for offset, data in writes:
+        os.pwrite(fd, data, offset)
+    os.close(fd)
+PY
+}
+test_command "Scrivere blocchi sovrapposti fuori ordine" "pwrite_overlapping overlapping.bin \"\$EXPECTED\""
+sleep 1
+test_command "Verificare che vinca l'ultima scrittura" "cmp overlapping.bin \"\$EXPECTED\" && [ \"\$(cat overlapping.bin)\" = 'AAAADDDDBBBBBBBBBBBBBBBBCCFFFFFF' ]"
+
+test_command "Pulizia: Rimuovere i file di test" "rm offsets.bin multi_block.bin chunked.bin truncated.bin overlapping.bin"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
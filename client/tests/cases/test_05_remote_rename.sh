@@ -0,0 +1,85 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+if [ -z "$SERVER_DATA_DIR" ]; then
+    echo "Errore: la variabile d'ambiente SERVER_DATA_DIR non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+cd "$MOUNT_POINT"
+FAILED_TESTS=0
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+# Le rinomine vengono fatte direttamente nella directory dati del server, come farebbe
+# un altro client: il watcher invia un evento RENAME e il mount deve seguirlo.
+
+# 1. Preparazione: i file vengono letti dal mount, così i loro inode finiscono in cache
+test_command "Creare i file di partenza" "echo 'contenuto A' > remote_a.txt && echo 'contenuto B' > remote_b.txt"
+sleep 1
+test_command "Leggere i file dal mount" "cat remote_a.txt remote_b.txt > /dev/null"
+
+# 2. Rinomina remota verso un nome libero
+test_command "Rinominare remote_a.txt sul server" "mv \"\$SERVER_DATA_DIR/remote_a.txt\" \"\$SERVER_DATA_DIR/remote_moved.txt\""
+sleep 2
+test_command "Verificare che il vecchio nome sia sparito" "[ ! -e remote_a.txt ]"
+test_command "Verificare il contenuto al nuovo nome" "[ \"\$(cat remote_moved.txt)\" = 'contenuto A' ]"
+
+# 3. Rinomina remota che sovrascrive un file già in cache
+test_command "Rinominare remote_moved.txt sopra remote_b.txt sul server" "mv \"\$SERVER_DATA_DIR/remote_moved.txt\" \"\$SERVER_DATA_DIR/remote_b.txt\""
+sleep 2
+test_command "Verificare che la sorgente sia sparita" "[ ! -e remote_moved.txt ]"
+test_command "Verificare che la destinazione abbia il nuovo contenuto" "[ \"\$(cat remote_b.txt)\" = 'contenuto A' ]"
+
+test_command "Pulizia: Rimuovere i file di test" "rm remote_b.txt"
+
+# 4. File sparso creato sul server: il mount riporta i blocchi realmente allocati, non size / 512
+test_command "Creare un file sparso da 64 MiB sul server" "mkdir \"\$SERVER_DATA_DIR/remote_sparse\" && echo 'inizio' > \"\$SERVER_DATA_DIR/remote_sparse/sparse.bin\" && truncate -s 64M \"\$SERVER_DATA_DIR/remote_sparse/sparse.bin\""
+sleep 2
+test_command "Verificare la dimensione logica sul mount" "[ \"\$(stat -c '%s' remote_sparse/sparse.bin)\" = '67108864' ]"
+test_command "Verificare che 'du' sul mount coincida con 'du' sul server" "[ \"\$(du -k remote_sparse/sparse.bin | cut -f1)\" = \"\$(du -k \"\$SERVER_DATA_DIR/remote_sparse/sparse.bin\" | cut -f1)\" ]"
+test_command "Verificare i blocchi della directory" "[ \"\$(stat -c '%b' remote_sparse)\" = \"\$(stat -c '%b' \"\$SERVER_DATA_DIR/remote_sparse\")\" ]"
+test_command "Pulizia: Rimuovere la directory del file sparso" "rm -r remote_sparse"
+
+# 5. Modifica remota entro il TTL del kernel (1s): il watcher invalida anche gli attributi
+#    e le pagine in cache nel kernel, quindi il mount vede subito la nuova dimensione
+test_command "Creare e leggere remote_grow.txt" "echo 'corto' > remote_grow.txt && sleep 1 && stat remote_grow.txt > /dev/null && cat remote_grow.txt > /dev/null"
+test_command "Allungare il file sul server" "echo 'contenuto molto più lungo' > \"\$SERVER_DATA_DIR/remote_grow.txt\""
+sleep 0.5
+test_command "Verificare la nuova dimensione prima della scadenza del TTL" "[ \"\$(stat -c '%s' remote_grow.txt)\" = \"\$(stat -c '%s' \"\$SERVER_DATA_DIR/remote_grow.txt\")\" ]"
+test_command "Verificare il nuovo contenuto" "[ \"\$(cat remote_grow.txt)\" = 'contenuto molto più lungo' ]"
+test_command "Pulizia: Rimuovere remote_grow.txt" "rm remote_grow.txt"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,91 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che le variabili d'ambiente siano impostate
+if [ -z "$SERVER_DATA_DIR" ] || [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: le variabili d'ambiente SERVER_DATA_DIR e CLIENT_BIN devono essere impostate."
+    exit 1
+fi
+
+# Servono file di altri utenti (chown) e un utente non privilegiato che acceda al mount
+if [ "$(id -u)" -ne 0 ]; then
+    echo "  - Salto: i test dei permessi richiedono root."
+    exit 0
+fi
+
+# --- Configurazione Iniziale ---
+# Secondo mount con controllo dei permessi lato kernel; allow_other permette l'accesso a `nobody`
+PERM_MOUNT_POINT="/tmp/remote_fs_perm_mount"
+PERM_CLIENT_LOG="/tmp/client_perm.log"
+NOBODY_UID=65534
+FAILED_TESTS=0
+
+mkdir -p "$PERM_MOUNT_POINT"
+# Nessun config.toml nella directory di lavoro: configurazione di default (niente demone)
+(cd /tmp && "$CLIENT_BIN" "$PERM_MOUNT_POINT" --mount-option default_permissions --mount-option allow_other &> "$PERM_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_perm() {
+  umount -l "$PERM_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" 2>/dev/null || true
+  rm -rf "$PERM_MOUNT_POINT" "$PERM_CLIENT_LOG"
+  rm -f "$SERVER_DATA_DIR/perm_private.txt" "$SERVER_DATA_DIR/perm_public.txt" "$SERVER_DATA_DIR/perm_nobody.txt"
+}
+trap cleanup_perm EXIT
+
+timeout=20
+while ! mount | grep -q "$PERM_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount con default_permissions non pronto."
+    cat "$PERM_CLIENT_LOG"
+    exit 1
+  fi
+done
+cd "$PERM_MOUNT_POINT"
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# Esegue `command` come utente non privilegiato (nobody)
+as_nobody() {
+  setpriv --reuid="$NOBODY_UID" --regid="$NOBODY_UID" --clear-groups sh -c "$1"
+}
+
+# --- Esecuzione dei Test ---
+# I file vengono creati direttamente sul server con proprietario e permessi reali:
+# il kernel li confronta con l'utente che accede grazie a `default_permissions`.
+
+test_command "Creare i file sul server" "echo segreto > \"\$SERVER_DATA_DIR/perm_private.txt\" && chmod 600 \"\$SERVER_DATA_DIR/perm_private.txt\" && echo pubblico > \"\$SERVER_DATA_DIR/perm_public.txt\" && chmod 644 \"\$SERVER_DATA_DIR/perm_public.txt\" && echo mio > \"\$SERVER_DATA_DIR/perm_nobody.txt\" && chmod 600 \"\$SERVER_DATA_DIR/perm_nobody.txt\" && chown $NOBODY_UID:$NOBODY_UID \"\$SERVER_DATA_DIR/perm_nobody.txt\""
+sleep 1
+
+test_command "Verificare il proprietario reale" "[ \"\$(stat -c '%u %a' perm_nobody.txt)\" = '$NOBODY_UID 600' ]"
+test_command "Negare la lettura di un file 0600 di un altro utente" "! as_nobody 'cat $PERM_MOUNT_POINT/perm_private.txt'"
+test_command "Permettere la lettura di un file 0644" "[ \"\$(as_nobody 'cat $PERM_MOUNT_POINT/perm_public.txt')\" = 'pubblico' ]"
+test_command "Permettere la lettura di un file 0600 al proprietario" "[ \"\$(as_nobody 'cat $PERM_MOUNT_POINT/perm_nobody.txt')\" = 'mio' ]"
+
+# --- Esito Finale ---
+cd /
+exit $FAILED_TESTS
@@ -0,0 +1,104 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+if [ -z "$SERVER_DATA_DIR" ]; then
+    echo "Errore: la variabile d'ambiente SERVER_DATA_DIR non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+cd "$MOUNT_POINT"
+FAILED_TESTS=0
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+# Il server deve rifiutare l'upload: il file viene sostituito, sul server, da una
+# directory con lo stesso nome. La PUT fallisce e `close(2)` deve restituire l'errore.
+
+# Serve un programma che controlli il valore di ritorno di close (bash lo ignora)
+if ! command -v python3 > /dev/null; then
+    echo "  - Salto: i test sugli errori di close richiedono python3."
+    exit 0
+fi
+
+# Chiude il descrittore 3 (ereditato) e fallisce se close(2) restituisce un errore
+close_fd3() {
+  python3 -c 'import os; os.close(3)'
+}
+
+# 1. Scrittura bufferizzata su un descrittore che resta aperto
+exec 3> close_err.txt
+printf 'dati persi' >&3
+
+# 2. Il server non può più scrivere il file
+test_command "Sostituire il file con una directory sul server" "rm \"\$SERVER_DATA_DIR/close_err.txt\" && mkdir \"\$SERVER_DATA_DIR/close_err.txt\""
+sleep 1
+
+# 3. La close deve riportare l'errore di upload
+test_command_fails "close() restituisce l'errore dell'upload fallito" "close_fd3"
+exec 3>&-
+
+# 4. Una scrittura che va a buon fine chiude senza errori
+test_command "Pulizia della directory sul server" "rmdir \"\$SERVER_DATA_DIR/close_err.txt\""
+sleep 1
+exec 3> close_ok.txt
+printf 'dati salvati' >&3
+test_command "close() ha successo quando l'upload va a buon fine" "close_fd3"
+exec 3>&-
+sleep 1
+test_command "Verificare il contenuto caricato" "[ \"\$(cat close_ok.txt)\" = 'dati salvati' ]"
+
+test_command "Pulizia: Rimuovere i file di test" "rm close_ok.txt"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
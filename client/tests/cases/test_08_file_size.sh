@@ -0,0 +1,122 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+if [ -z "$CLIENT_BIN" ] || [ -z "$SERVER_MAX_FILE_SIZE" ]; then
+    echo "Errore: le variabili d'ambiente CLIENT_BIN e SERVER_MAX_FILE_SIZE devono essere impostate."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Secondo mount con un limite lato client piccolo; il mount principale non ha limiti
+# lato client, quindi lì vale solo quello del server (SERVER_MAX_FILE_SIZE).
+LIMIT_MOUNT_POINT="/tmp/remote_fs_limit_mount"
+LIMIT_CLIENT_LOG="/tmp/client_limit.log"
+CLIENT_MAX_FILE_SIZE=4096
+FAILED_TESTS=0
+
+mkdir -p "$LIMIT_MOUNT_POINT"
+# Nessun config.toml nella directory di lavoro: configurazione di default (niente demone)
+(cd /tmp && "$CLIENT_BIN" "$LIMIT_MOUNT_POINT" --max-file-size "$CLIENT_MAX_FILE_SIZE" &> "$LIMIT_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_limit() {
+  umount -l "$LIMIT_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" 2>/dev/null || true
+  rm -rf "$LIMIT_MOUNT_POINT" "$LIMIT_CLIENT_LOG"
+}
+trap cleanup_limit EXIT
+
+timeout=20
+while ! mount | grep -q "$LIMIT_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount con --max-file-size non pronto."
+    cat "$LIMIT_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+# `dd` controlla sia le write che la close del file di output: un EFBIG in `write`
+# (limite del client) o in `flush` (413 dal server) lo fa uscire con errore.
+
+# Scrive `size` byte di zeri in `file`
+write_bytes() {
+  local file=$1
+  local size=$2
+  head -c "$size" /dev/zero | dd of="$file" bs=65536 status=none
+}
+
+# 1. Limite del client: nessun upload oltre CLIENT_MAX_FILE_SIZE
+cd "$LIMIT_MOUNT_POINT"
+test_command "Scrivere esattamente $CLIENT_MAX_FILE_SIZE byte (limite del client)" "write_bytes client_limit.bin $CLIENT_MAX_FILE_SIZE"
+sleep 1
+test_command "Verificare la dimensione del file al limite" "[ \"\$(stat -c '%s' client_limit.bin)\" = '$CLIENT_MAX_FILE_SIZE' ]"
+test_command_fails "Scrivere un byte oltre il limite del client (EFBIG)" "write_bytes client_over.bin $((CLIENT_MAX_FILE_SIZE + 1))"
+test_command_fails "Estendere un file oltre il limite con truncate (EFBIG)" "truncate -s $((CLIENT_MAX_FILE_SIZE + 1)) client_limit.bin"
+test_command "Pulizia: Rimuovere i file del limite del client" "rm -f client_limit.bin client_over.bin"
+
+# 2. Limite del server: la PUT oltre SERVER_MAX_FILE_SIZE riceve 413, che la close riporta come EFBIG
+cd "$MOUNT_POINT"
+test_command "Scrivere esattamente $SERVER_MAX_FILE_SIZE byte (limite del server)" "write_bytes server_limit.bin $SERVER_MAX_FILE_SIZE"
+sleep 1
+test_command "Verificare la dimensione del file al limite" "[ \"\$(stat -c '%s' server_limit.bin)\" = '$SERVER_MAX_FILE_SIZE' ]"
+test_command_fails "Scrivere un byte oltre il limite del server (413 -> EFBIG)" "write_bytes server_over.bin $((SERVER_MAX_FILE_SIZE + 1))"
+sleep 1
+test_command "Verificare che il file oltre il limite non sia stato caricato" "[ \"\$(stat -c '%s' server_over.bin)\" = '0' ]"
+
+test_command "Pulizia: Rimuovere i file di test" "rm -f server_limit.bin server_over.bin"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,144 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato, avviato da una directory con il proprio config.toml: il test lo
+# riscrive e invia SIGHUP al client, senza smontare.
+RELOAD_MOUNT_POINT="/tmp/remote_fs_reload_mount"
+RELOAD_WORK_DIR=$(mktemp -d)
+RELOAD_CLIENT_LOG="/tmp/client_reload.log"
+FAILED_TESTS=0
+
+# Scrive config.toml con il limite di dimensione e il server indicati
+write_config() {
+  cat > "$RELOAD_WORK_DIR/config.toml" <<TOML
+server_url = "$2"
+cache_strategy = "ttl"
+cache_ttl_seconds = 60
+cache_lru_capacity = 1000
+max_file_size_bytes = $1
+TOML
+}
+
+mkdir -p "$RELOAD_MOUNT_POINT"
+write_config 4096 "http://localhost:8080"
+(cd "$RELOAD_WORK_DIR" && "$CLIENT_BIN" "$RELOAD_MOUNT_POINT" &> "$RELOAD_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_reload() {
+  umount -l "$RELOAD_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" 2>/dev/null || true
+  rm -rf "$RELOAD_MOUNT_POINT" "$RELOAD_WORK_DIR" "$RELOAD_CLIENT_LOG"
+}
+trap cleanup_reload EXIT
+
+timeout=20
+while ! mount | grep -q "$RELOAD_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount per il test di reload non pronto."
+    cat "$RELOAD_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$RELOAD_MOUNT_POINT"
+
+# Scrive `size` byte di zeri in `file`
+write_bytes() {
+  local file=$1
+  local size=$2
+  head -c "$size" /dev/zero | dd of="$file" bs=65536 status=none
+}
+
+# 1. Prima del reload vale il limite di config.toml
+test_command_fails "Scrivere 8192 byte con il limite di 4096 (EFBIG)" "write_bytes reload_big.bin 8192"
+rm -f reload_big.bin
+
+# 2. Un file aperto durante il reload non deve perdere i dati in buffer
+exec 3> reload_open.txt
+printf 'prima ' >&3
+
+# 3. Reload: il limite cambia, server_url non è modificabile a caldo e viene ignorato
+write_config 0 "http://localhost:9999"
+test_command "Inviare SIGHUP al client" "kill -HUP $CLIENT_PID"
+sleep 1
+test_command "Verificare che il client sia ancora in esecuzione" "kill -0 $CLIENT_PID"
+test_command "Verificare che server_url sia segnalato come ignorato" "grep -q \"'server_url' non è modificabile a caldo\" \"$RELOAD_CLIENT_LOG\""
+
+printf 'dopo' >&3
+exec 3>&-
+sleep 1
+test_command "Verificare il file rimasto aperto durante il reload" "[ \"\$(cat reload_open.txt)\" = 'prima dopo' ]"
+test_command "Scrivere 8192 byte senza limite dopo il reload" "write_bytes reload_big.bin 8192"
+sleep 1
+test_command "Verificare la dimensione del file (server_url invariato)" "[ \"\$(stat -c '%s' reload_big.bin)\" = '8192' ]"
+
+# 4. Un config.toml non valido lascia la configurazione in uso
+echo 'non è toml valido' > "$RELOAD_WORK_DIR/config.toml"
+test_command "Inviare SIGHUP con un config.toml non valido" "kill -HUP $CLIENT_PID"
+sleep 1
+test_command "Verificare che la configurazione sia rimasta invariata" "grep -q 'configurazione invariata' \"$RELOAD_CLIENT_LOG\" && write_bytes reload_big.bin 8192"
+
+test_command "Pulizia: Rimuovere i file di test" "rm -f reload_open.txt reload_big.bin"
+cd /
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
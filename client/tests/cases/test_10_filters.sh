@@ -0,0 +1,128 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+if [ -z "$CLIENT_BIN" ] || [ -z "$SERVER_DATA_DIR" ]; then
+    echo "Errore: le variabili d'ambiente CLIENT_BIN e SERVER_DATA_DIR devono essere impostate."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato con include_globs/exclude_globs nel proprio config.toml.
+# I file vengono creati direttamente sul server, in una sottodirectory dedicata.
+FILTER_MOUNT_POINT="/tmp/remote_fs_filter_mount"
+FILTER_WORK_DIR=$(mktemp -d)
+FILTER_CLIENT_LOG="/tmp/client_filter.log"
+FILTER_DIR="$SERVER_DATA_DIR/filter_test"
+FAILED_TESTS=0
+
+cat > "$FILTER_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:8080"
+cache_strategy = "ttl"
+cache_ttl_seconds = 60
+cache_lru_capacity = 1000
+include_globs = ["*.md"]
+exclude_globs = [".git"]
+TOML
+
+mkdir -p "$FILTER_DIR/.git" "$FILTER_DIR/docs"
+echo 'ref' > "$FILTER_DIR/.git/HEAD"
+echo '# note' > "$FILTER_DIR/notes.md"
+echo 'binario' > "$FILTER_DIR/image.bin"
+echo '# guida' > "$FILTER_DIR/docs/guide.md"
+
+mkdir -p "$FILTER_MOUNT_POINT"
+(cd "$FILTER_WORK_DIR" && "$CLIENT_BIN" "$FILTER_MOUNT_POINT" &> "$FILTER_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_filter() {
+  cd /
+  umount -l "$FILTER_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" 2>/dev/null || true
+  rm -rf "$FILTER_MOUNT_POINT" "$FILTER_WORK_DIR" "$FILTER_CLIENT_LOG" "$FILTER_DIR"
+}
+trap cleanup_filter EXIT
+
+timeout=20
+while ! mount | grep -q "$FILTER_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount con i filtri glob non pronto."
+    cat "$FILTER_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$FILTER_MOUNT_POINT/filter_test"
+
+# 1. exclude_globs: la directory .git è invisibile
+test_command "Verificare che '.git' non compaia in 'ls -a'" "! ls -a | grep -qx '.git'"
+test_command_fails "Eseguire 'stat' su .git (ENOENT)" "stat .git"
+test_command_fails "Leggere un file dentro .git" "cat .git/HEAD"
+test_command_fails "Creare una directory .git (EPERM)" "mkdir .git"
+
+# 2. include_globs: solo i file *.md, le directory restano visibili
+test_command "Verificare che notes.md sia visibile" "[ \"\$(cat notes.md)\" = '# note' ]"
+test_command "Verificare che image.bin non compaia in 'ls'" "! ls | grep -qx 'image.bin'"
+test_command_fails "Eseguire 'stat' su image.bin (ENOENT)" "stat image.bin"
+test_command "Verificare che le directory restino visibili" "[ \"\$(cat docs/guide.md)\" = '# guida' ]"
+test_command_fails "Creare un file escluso da include_globs (EPERM)" "touch nuovo.txt"
+test_command_fails "Rinominare un file verso un nome escluso (EPERM)" "mv notes.md notes.txt"
+test_command "Verificare che il file non sia stato rinominato" "[ -f notes.md ] && [ -f \"\$FILTER_DIR/notes.md\" ]"
+
+# 3. I file nascosti restano sul server
+test_command "Verificare che i file nascosti esistano sul server" "[ -f \"\$FILTER_DIR/.git/HEAD\" ] && [ -f \"\$FILTER_DIR/image.bin\" ]"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,137 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+if [ -z "$CLIENT_BIN" ] || [ -z "$SERVER_DATA_DIR" ]; then
+    echo "Errore: le variabili d'ambiente CLIENT_BIN e SERVER_DATA_DIR devono essere impostate."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato della sola sottodirectory prefix_test/alice (root_prefix nel proprio config.toml).
+PREFIX_MOUNT_POINT="/tmp/remote_fs_prefix_mount"
+PREFIX_WORK_DIR=$(mktemp -d)
+PREFIX_CLIENT_LOG="/tmp/client_prefix.log"
+PREFIX_DIR="$SERVER_DATA_DIR/prefix_test"
+FAILED_TESTS=0
+
+# Scrive config.toml con la root_prefix indicata (e un server_url, di default con '/' finale da normalizzare)
+write_config() {
+  cat > "$PREFIX_WORK_DIR/config.toml" <<TOML
+server_url = "${2:-http://localhost:8080/}"
+cache_strategy = "ttl"
+cache_ttl_seconds = 60
+cache_lru_capacity = 1000
+root_prefix = "$1"
+TOML
+}
+
+mkdir -p "$PREFIX_DIR/alice/sub" "$PREFIX_DIR/bob"
+echo 'di alice' > "$PREFIX_DIR/alice/a.txt"
+echo 'annidato' > "$PREFIX_DIR/alice/sub/b.txt"
+echo 'di bob' > "$PREFIX_DIR/bob/secret.txt"
+
+mkdir -p "$PREFIX_MOUNT_POINT"
+write_config "/prefix_test/alice/"
+(cd "$PREFIX_WORK_DIR" && "$CLIENT_BIN" "$PREFIX_MOUNT_POINT" &> "$PREFIX_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_prefix() {
+  cd /
+  umount -l "$PREFIX_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" 2>/dev/null || true
+  rm -rf "$PREFIX_MOUNT_POINT" "$PREFIX_WORK_DIR" "$PREFIX_CLIENT_LOG" "$PREFIX_DIR"
+}
+trap cleanup_prefix EXIT
+
+timeout=20
+while ! mount | grep -q "$PREFIX_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount con root_prefix non pronto."
+    cat "$PREFIX_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$PREFIX_MOUNT_POINT"
+
+# 1. La radice del mount è prefix_test/alice
+test_command "Verificare i file della sottodirectory nella radice" "[ \"\$(cat a.txt)\" = 'di alice' ] && [ \"\$(cat sub/b.txt)\" = 'annidato' ]"
+test_command "Verificare che il resto del server non sia visibile" "! ls -a | grep -qx 'bob' && ! ls -a | grep -qx 'prefix_test'"
+test_command_fails "Leggere un file di un'altra sottodirectory" "cat bob/secret.txt"
+
+# 2. Le scritture finiscono sotto root_prefix sul server
+test_command "Creare un file nella radice del mount" "echo 'nuovo' > nuovo.txt"
+sleep 1
+test_command "Verificare il file sul server sotto root_prefix" "[ \"\$(cat \"\$PREFIX_DIR/alice/nuovo.txt\")\" = 'nuovo' ]"
+test_command "Rimuovere ricorsivamente una directory" "rm -r sub"
+sleep 1
+test_command "Verificare che sul server sia sparita solo quella" "[ ! -e \"\$PREFIX_DIR/alice/sub\" ] && [ -f \"\$PREFIX_DIR/alice/a.txt\" ] && [ -f \"\$PREFIX_DIR/bob/secret.txt\" ]"
+
+# 3. Una root_prefix inesistente viene rifiutata all'avvio (exit code 2, nessun mount)
+cd /
+write_config "prefix_test/missing"
+test_command "Rifiutare una root_prefix inesistente" "(cd \"\$PREFIX_WORK_DIR\" && timeout 10 \"\$CLIENT_BIN\" /tmp/remote_fs_prefix_missing &> /dev/null); [ \$? -eq 2 ]"
+write_config "prefix_test/alice/a.txt"
+test_command "Rifiutare una root_prefix che non è una directory" "(cd \"\$PREFIX_WORK_DIR\" && timeout 10 \"\$CLIENT_BIN\" /tmp/remote_fs_prefix_missing &> /dev/null); [ \$? -eq 2 ]"
+
+# 4. Anche un server_url malformato viene rifiutato all'avvio (exit code 2)
+write_config "prefix_test/alice" "ftp://localhost:8080"
+test_command "Rifiutare un server_url con uno schema non supportato" "(cd \"\$PREFIX_WORK_DIR\" && timeout 10 \"\$CLIENT_BIN\" /tmp/remote_fs_prefix_missing &> /dev/null); [ \$? -eq 2 ]"
+write_config "prefix_test/alice" "localhost:8080"
+test_command "Rifiutare un server_url senza schema" "(cd \"\$PREFIX_WORK_DIR\" && timeout 10 \"\$CLIENT_BIN\" /tmp/remote_fs_prefix_missing &> /dev/null); [ \$? -eq 2 ]"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,91 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+if [ -z "$CLIENT_BIN" ] || [ -z "$SERVER_DATA_DIR" ]; then
+    echo "Errore: le variabili d'ambiente CLIENT_BIN e SERVER_DATA_DIR devono essere impostate."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# `client refresh` parla con il mount principale tramite ioctl: non serve un mount dedicato.
+cd "$MOUNT_POINT"
+FAILED_TESTS=0
+OUTSIDE_FILE=$(mktemp)
+trap 'exec 3>&- 2>/dev/null; rm -f "$OUTSIDE_FILE"' EXIT
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+
+# 1. Scrittura ancora nel buffer di un file aperto: refresh la carica sul server senza chiuderlo
+test_command "Creare refresh_open.txt" "echo 'vecchio' > refresh_open.txt"
+exec 3> refresh_open.txt
+printf 'nel buffer' >&3
+test_command "Eseguire 'client refresh' sul file aperto" "\"\$CLIENT_BIN\" refresh refresh_open.txt"
+test_command "Verificare che il server abbia il contenuto nel buffer" "[ \"\$(cat \"\$SERVER_DATA_DIR/refresh_open.txt\")\" = 'nel buffer' ]"
+printf ' e dopo' >&3
+exec 3>&-
+test_command "Verificare il contenuto dopo la chiusura" "[ \"\$(cat refresh_open.txt)\" = 'nel buffer e dopo' ]"
+
+# 2. Directory: i figli noti al client vengono invalidati e riletti dal server
+test_command "Creare refresh_dir con un file" "mkdir refresh_dir && echo 'uno' > refresh_dir/a.txt && ls refresh_dir > /dev/null"
+test_command "Eseguire 'client refresh' sulla directory" "\"\$CLIENT_BIN\" refresh refresh_dir"
+test_command "Verificare il contenuto dopo il refresh" "[ \"\$(cat refresh_dir/a.txt)\" = 'uno' ]"
+
+# 3. Percorsi non validi
+test_command_fails "Eseguire 'client refresh' su un file inesistente" "\"\$CLIENT_BIN\" refresh refresh_missing.txt"
+test_command_fails "Eseguire 'client refresh' fuori dal mount (ENOTTY)" "\"\$CLIENT_BIN\" refresh \"\$OUTSIDE_FILE\""
+
+test_command "Pulizia: Rimuovere i file di test" "rm -r refresh_open.txt refresh_dir"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,128 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato verso un server minimale (Python) che elenca la radice come array
+# di soli nomi e la sottodirectory con oggetti completi, come il server del progetto.
+FORMAT_PORT=8091
+FORMAT_MOUNT_POINT="/tmp/remote_fs_format_mount"
+FORMAT_WORK_DIR=$(mktemp -d)
+FORMAT_CLIENT_LOG="/tmp/client_format.log"
+FAILED_TESTS=0
+
+cat > "$FORMAT_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:$FORMAT_PORT"
+cache_strategy = "ttl"
+cache_ttl_seconds = 60
+cache_lru_capacity = 1000
+TOML
+
+cat > "$FORMAT_WORK_DIR/fake_server.py" <<'PY'
+import json, sys
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+NAMES = ["plain.txt", "nested/"]
+ENTRIES = [{"name": "full.txt", "kind": "file", "size": 5, "mtime": 0, "perm": "600"}]
+
+class Handler(BaseHTTPRequestHandler):
+    def send(self, status, body, content_type="application/json"):
+        self.send_response(status)
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        self.end_headers()
+        self.wfile.write(body)
+
+    def do_GET(self):
+        path = self.path.split("?")[0]
+        if path in ("/list", "/list/"):
+            self.send(200, json.dumps(NAMES).encode())
+        elif path == "/list-stream":
+            self.send(200, "".join(json.dumps(n) + "\n" for n in NAMES).encode(), "application/x-ndjson")
+        elif path == "/list/nested":
+            self.send(200, json.dumps(ENTRIES).encode())
+        elif path == "/list-stream/nested":
+            self.send(200, "".join(json.dumps(e) + "\n" for e in ENTRIES).encode(), "application/x-ndjson")
+        elif path == "/files/nested/full.txt":
+            self.send(200, b"ciao\n", "application/octet-stream")
+        else:
+            self.send(404, b"")
+
+    # Niente HEAD /files: il client ricava gli attributi dal listing del padre
+    def do_HEAD(self):
+        self.send(405, b"")
+
+    def log_message(self, *args):
+        pass
+
+HTTPServer(("localhost", int(sys.argv[1])), Handler).serve_forever()
+PY
+
+python3 "$FORMAT_WORK_DIR/fake_server.py" "$FORMAT_PORT" &
+SERVER_PID=$!
+mkdir -p "$FORMAT_MOUNT_POINT"
+(cd "$FORMAT_WORK_DIR" && "$CLIENT_BIN" "$FORMAT_MOUNT_POINT" &> "$FORMAT_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_format() {
+  cd /
+  umount -l "$FORMAT_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" "$SERVER_PID" 2>/dev/null || true
+  rm -rf "$FORMAT_MOUNT_POINT" "$FORMAT_WORK_DIR" "$FORMAT_CLIENT_LOG"
+}
+trap cleanup_format EXIT
+
+timeout=20
+while ! mount | grep -q "$FORMAT_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount verso il server minimale non pronto."
+    cat "$FORMAT_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$FORMAT_MOUNT_POINT"
+
+# 1. Array di soli nomi: un nome che termina con '/' è una directory, gli altri file vuoti
+test_command "Verificare che 'ls' mostri i nomi della radice" "[ \"\$(ls | tr '\n' ' ')\" = 'nested plain.txt ' ]"
+test_command "Verificare che 'nested/' sia una directory" "[ \"\$(stat -c '%F' nested)\" = 'directory' ]"
+test_command "Verificare i valori di default di plain.txt" "[ \"\$(stat -c '%F %s %a' plain.txt)\" = 'regular empty file 0 644' ]"
+
+# 2. Array di oggetti: i metadati del server vengono usati
+test_command "Verificare che 'ls nested' mostri full.txt" "[ \"\$(ls nested)\" = 'full.txt' ]"
+test_command "Verificare dimensione e permessi di full.txt" "[ \"\$(stat -c '%s %a' nested/full.txt)\" = '5 600' ]"
+test_command "Leggere full.txt" "[ \"\$(cat nested/full.txt)\" = 'ciao' ]"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
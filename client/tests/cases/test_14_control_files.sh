@@ -0,0 +1,89 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+if [ -z "$CLIENT_BIN" ] || [ -z "$SERVER_DATA_DIR" ]; then
+    echo "Errore: le variabili d'ambiente CLIENT_BIN e SERVER_DATA_DIR devono essere impostate."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# La directory virtuale .remotefs esiste in ogni mount: si usa quello principale.
+cd "$MOUNT_POINT"
+FAILED_TESTS=0
+trap 'exec 3>&- 2>/dev/null' EXIT
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+
+# 1. La directory di controllo è raggiungibile ma non elencata (list_control_files = false)
+test_command "Verificare che .remotefs non compaia in 'ls -a'" "! ls -a | grep -qx '.remotefs'"
+test_command "Verificare che .remotefs sia una directory" "[ -d .remotefs ] && [ \"\$(ls .remotefs | tr '\\n' ' ')\" = 'flush stats fsck ' ]"
+test_command "Verificare che .remotefs non esista sul server" "[ ! -e \"\$SERVER_DATA_DIR/.remotefs\" ]"
+
+# 2. Scrivere su flush carica i buffer dei file ancora aperti
+test_command "Creare control_open.txt" "echo 'vecchio' > control_open.txt"
+exec 3> control_open.txt
+printf 'nel buffer' >&3
+test_command "Scrivere su .remotefs/flush" "echo 1 > .remotefs/flush"
+test_command "Verificare che il server abbia il contenuto nel buffer" "[ \"\$(cat \"\$SERVER_DATA_DIR/control_open.txt\")\" = 'nel buffer' ]"
+test_command "Leggere il numero di file caricati" "[ \"\$(cat .remotefs/flush)\" = '1' ]"
+exec 3>&-
+
+# 3. Senza file aperti non c'è nulla da caricare
+test_command "Scrivere di nuovo su .remotefs/flush" "echo 1 > .remotefs/flush && [ \"\$(cat .remotefs/flush)\" = '0' ]"
+test_command_fails "Creare un file dentro .remotefs" "touch .remotefs/nuovo"
+
+test_command "Pulizia: Rimuovere control_open.txt" "rm control_open.txt"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
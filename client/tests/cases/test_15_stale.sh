@@ -0,0 +1,161 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato con serve_stale_on_error verso un server minimale (Python),
+# che il test spegne per simulare un'interruzione del server.
+STALE_PORT=8092
+STALE_MOUNT_POINT="/tmp/remote_fs_stale_mount"
+STALE_WORK_DIR=$(mktemp -d)
+STALE_CLIENT_LOG="/tmp/client_stale.log"
+FAILED_TESTS=0
+
+cat > "$STALE_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:$STALE_PORT"
+cache_strategy = "ttl"
+cache_ttl_seconds = 1
+cache_lru_capacity = 1000
+serve_stale_on_error = true
+TOML
+
+cat > "$STALE_WORK_DIR/fake_server.py" <<'PY'
+import json, sys
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+FILES = {"data.txt": b"contenuto in cache\n", "other.txt": b"altro file\n"}
+ENTRIES = [{"name": n, "kind": "file", "size": len(c), "mtime": 0, "perm": "644"} for n, c in FILES.items()]
+
+class Handler(BaseHTTPRequestHandler):
+    def send(self, status, body, content_type="application/json", with_body=True):
+        self.send_response(status)
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        if self.path.startswith("/files/"):
+            self.send_header("X-Kind", "file")
+            self.send_header("X-Mtime", "0")
+            self.send_header("X-Perm", "644")
+        self.end_headers()
+        if with_body:
+            self.wfile.write(body)
+
+    def do_GET(self):
+        path = self.path.split("?")[0]
+        if path in ("/list", "/list/"):
+            self.send(200, json.dumps(ENTRIES).encode())
+        elif path == "/list-stream":
+            self.send(200, "".join(json.dumps(e) + "\n" for e in ENTRIES).encode(), "application/x-ndjson")
+        elif path.removeprefix("/files/") in FILES:
+            # Ignora il Range: il client ritaglia il blocco dalla risposta completa
+            self.send(200, FILES[path.removeprefix("/files/")], "application/octet-stream")
+        else:
+            self.send(404, b"")
+
+    def do_HEAD(self):
+        if self.path.removeprefix("/files/") in FILES:
+            self.send(200, FILES[self.path.removeprefix("/files/")], "application/octet-stream", with_body=False)
+        else:
+            self.send(404, b"", with_body=False)
+
+    def log_message(self, *args):
+        pass
+
+HTTPServer(("localhost", int(sys.argv[1])), Handler).serve_forever()
+PY
+
+python3 "$STALE_WORK_DIR/fake_server.py" "$STALE_PORT" &
+SERVER_PID=$!
+mkdir -p "$STALE_MOUNT_POINT"
+(cd "$STALE_WORK_DIR" && "$CLIENT_BIN" "$STALE_MOUNT_POINT" &> "$STALE_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_stale() {
+  cd /
+  umount -l "$STALE_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" "$SERVER_PID" 2>/dev/null || true
+  rm -rf "$STALE_MOUNT_POINT" "$STALE_WORK_DIR" "$STALE_CLIENT_LOG"
+}
+trap cleanup_stale EXIT
+
+timeout=20
+while ! mount | grep -q "$STALE_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount verso il server minimale non pronto."
+    cat "$STALE_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$STALE_MOUNT_POINT"
+
+# 1. Con il server attivo i file vengono letti (e i loro blocchi restano in cache);
+#    other.txt per ultimo, così data.txt non è più l'ultimo blocco letto
+test_command "Leggere data.txt con il server attivo" "[ \"\$(cat data.txt)\" = 'contenuto in cache' ]"
+test_command "Leggere other.txt con il server attivo" "[ \"\$(cat other.txt)\" = 'altro file' ]"
+
+# 2. Server spento: attesa oltre il TTL del kernel e della cache degli attributi
+kill "$SERVER_PID"
+wait "$SERVER_PID" 2>/dev/null
+sleep 2
+
+test_command "Leggere data.txt a server spento (dati in cache)" "[ \"\$(cat data.txt)\" = 'contenuto in cache' ]"
+test_command "Verificare la dimensione di data.txt a server spento" "[ \"\$(stat -c '%s' data.txt)\" = '19' ]"
+test_command "Verificare che le letture siano segnalate nel log" "grep -q '\\[STALE\\]' '$STALE_CLIENT_LOG'"
+
+# 3. Senza dati in cache, e per le scritture, l'errore resta
+test_command_fails "Leggere un file mai visto a server spento" "cat mai_visto.txt"
+test_command_fails "Scrivere data.txt a server spento" "echo 'nuovo' > data.txt"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
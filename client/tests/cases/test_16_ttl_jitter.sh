@@ -0,0 +1,116 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato con il proprio config.toml, per leggere dal log del client
+# la durata assegnata a ogni voce della cache degli attributi.
+JITTER_MOUNT_POINT="/tmp/remote_fs_jitter_mount"
+JITTER_WORK_DIR=$(mktemp -d)
+JITTER_CLIENT_LOG="/tmp/client_jitter.log"
+FAILED_TESTS=0
+
+# Scrive config.toml con TTL e jitter indicati; il terzo argomento (opzionale) aggiunge altre righe
+write_config() {
+  cat > "$JITTER_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:8080"
+cache_strategy = "ttl"
+cache_ttl_seconds = $1
+cache_ttl_jitter = $2
+cache_lru_capacity = 1000
+${3:-}
+TOML
+}
+
+mkdir -p "$JITTER_MOUNT_POINT"
+write_config 100 0.1
+(cd "$JITTER_WORK_DIR" && "$CLIENT_BIN" "$JITTER_MOUNT_POINT" &> "$JITTER_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_jitter() {
+  cd /
+  rm -rf "$JITTER_MOUNT_POINT/jitter_dir" 2>/dev/null || true
+  umount -l "$JITTER_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" 2>/dev/null || true
+  rm -rf "$JITTER_MOUNT_POINT" "$JITTER_WORK_DIR" "$JITTER_CLIENT_LOG"
+}
+trap cleanup_jitter EXIT
+
+timeout=20
+while ! mount | grep -q "$JITTER_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount per il test del jitter non pronto."
+    cat "$JITTER_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# Stampa le durate (in secondi) delle voci messe in cache dalla riga `$1` del log in poi
+ttl_values() {
+  tail -n +"$1" "$JITTER_CLIENT_LOG" | grep -o '(TTL [0-9.]*s)' | tr -dc '0-9.\n'
+}
+
+# --- Esecuzione dei Test ---
+cd "$JITTER_MOUNT_POINT"
+
+# 1. Molte voci messe in cache insieme (creazione + `ls -l`) con jitter del 10%
+mkdir jitter_dir
+for i in $(seq 1 20); do echo "$i" > "jitter_dir/file_$i.txt"; done
+ls -l jitter_dir > /dev/null
+
+test_command "Verificare che le voci abbiano durate diverse" "[ \"\$(ttl_values 1 | sort -u | wc -l)\" -gt 1 ]"
+test_command "Verificare che le durate restino entro ±10% del TTL" "ttl_values 1 | awk '\$1 < 90 || \$1 > 110 { exit 1 }'"
+
+# 2. Reload con jitter 0: la cache viene ricreata e ogni voce dura esattamente il TTL
+write_config 50 0
+test_command "Inviare SIGHUP al client" "kill -HUP $CLIENT_PID"
+sleep 1
+FIRST_LINE=$(( $(wc -l < "$JITTER_CLIENT_LOG") + 1 ))
+ls -l jitter_dir > /dev/null
+test_command "Verificare che dopo il reload ci siano nuove voci" "[ -n \"\$(ttl_values $FIRST_LINE)\" ]"
+test_command "Verificare che senza jitter tutte le voci durino 50s" "[ \"\$(ttl_values $FIRST_LINE | sort -u)\" = '50.0' ]"
+
+# 3. TTL separati: le directory restano in cache 300s, i file 20s
+write_config 50 0 $'dir_cache_ttl_seconds = 300\nfile_cache_ttl_seconds = 20'
+test_command "Inviare SIGHUP al client" "kill -HUP $CLIENT_PID"
+sleep 1
+FIRST_LINE=$(( $(wc -l < "$JITTER_CLIENT_LOG") + 1 ))
+ls -l jitter_dir > /dev/null
+DIR_INO=$(stat -c %i jitter_dir)
+test_command "Verificare che la directory duri 300s" "tail -n +$FIRST_LINE \"\$JITTER_CLIENT_LOG\" | grep -q 'inode $DIR_INO (TTL 300.0s)'"
+test_command "Verificare che i file durino 20s" "[ \"\$(ttl_values $FIRST_LINE | sort -u | tr '\n' ' ')\" = '20.0 300.0 ' ]"
+
+test_command "Pulizia: Rimuovere i file di test" "rm -rf jitter_dir"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,90 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato con force_uid/force_gid: ogni voce deve risultare di quel proprietario.
+OWNER_MOUNT_POINT="/tmp/remote_fs_owner_mount"
+OWNER_WORK_DIR=$(mktemp -d)
+OWNER_CLIENT_LOG="/tmp/client_owner.log"
+FORCED_UID=12345
+FORCED_GID=54321
+FAILED_TESTS=0
+
+cat > "$OWNER_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:8080"
+cache_strategy = "ttl"
+cache_ttl_seconds = 60
+cache_lru_capacity = 1000
+force_uid = $FORCED_UID
+force_gid = $FORCED_GID
+TOML
+
+mkdir -p "$OWNER_MOUNT_POINT"
+(cd "$OWNER_WORK_DIR" && "$CLIENT_BIN" "$OWNER_MOUNT_POINT" &> "$OWNER_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_owner() {
+  cd /
+  rm -rf "$OWNER_MOUNT_POINT/owner_dir" 2>/dev/null || true
+  umount -l "$OWNER_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" 2>/dev/null || true
+  rm -rf "$OWNER_MOUNT_POINT" "$OWNER_WORK_DIR" "$OWNER_CLIENT_LOG"
+}
+trap cleanup_owner EXIT
+
+timeout=20
+while ! mount | grep -q "$OWNER_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount per il test del proprietario non pronto."
+    cat "$OWNER_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$OWNER_MOUNT_POINT"
+
+mkdir owner_dir
+echo 'contenuto' > owner_dir/file.txt
+
+test_command "Verificare il proprietario della radice" "[ \"\$(stat -c '%u %g' .)\" = '$FORCED_UID $FORCED_GID' ]"
+test_command "Verificare il proprietario di una directory" "[ \"\$(stat -c '%u %g' owner_dir)\" = '$FORCED_UID $FORCED_GID' ]"
+test_command "Verificare il proprietario di un file" "[ \"\$(stat -c '%u %g' owner_dir/file.txt)\" = '$FORCED_UID $FORCED_GID' ]"
+test_command "Verificare il proprietario in 'ls -ln'" "ls -ln owner_dir | grep -q ' $FORCED_UID $FORCED_GID '"
+test_command "Verificare che il file resti leggibile" "[ \"\$(cat owner_dir/file.txt)\" = 'contenuto' ]"
+
+test_command "Pulizia: Rimuovere i file di test" "rm -rf owner_dir"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
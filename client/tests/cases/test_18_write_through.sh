@@ -0,0 +1,95 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+if [ -z "$SERVER_DATA_DIR" ]; then
+    echo "Errore: la variabile d'ambiente SERVER_DATA_DIR non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato con write_through: ogni write deve arrivare al server prima della close.
+WT_MOUNT_POINT="/tmp/remote_fs_wt_mount"
+WT_WORK_DIR=$(mktemp -d)
+WT_CLIENT_LOG="/tmp/client_wt.log"
+FAILED_TESTS=0
+
+cat > "$WT_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:8080"
+cache_strategy = "ttl"
+cache_ttl_seconds = 60
+cache_lru_capacity = 1000
+write_through = true
+TOML
+
+mkdir -p "$WT_MOUNT_POINT"
+(cd "$WT_WORK_DIR" && "$CLIENT_BIN" "$WT_MOUNT_POINT" &> "$WT_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_wt() {
+  cd /
+  exec 3>&- 2>/dev/null || true
+  rm -rf "$WT_MOUNT_POINT/wt_dir" 2>/dev/null || true
+  umount -l "$WT_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" 2>/dev/null || true
+  rm -rf "$WT_MOUNT_POINT" "$WT_WORK_DIR" "$WT_CLIENT_LOG"
+}
+trap cleanup_wt EXIT
+
+timeout=20
+while ! mount | grep -q "$WT_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount per il test write-through non pronto."
+    cat "$WT_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$WT_MOUNT_POINT"
+
+mkdir wt_dir
+
+# Il descrittore resta aperto: con il buffer il server vedrebbe un file vuoto
+exec 3> wt_dir/file.txt
+printf 'primo' >&3
+test_command "Verificare i dati sul server prima della close" "[ \"\$(cat \"\$SERVER_DATA_DIR/wt_dir/file.txt\")\" = 'primo' ]"
+exec 3>&-
+
+test_command "Scrivere a un offset senza troncare" "printf 'XY' | dd of=wt_dir/file.txt bs=1 seek=2 conv=notrunc status=none"
+test_command "Verificare che cambi solo l'intervallo scritto" "[ \"\$(cat \"\$SERVER_DATA_DIR/wt_dir/file.txt\")\" = 'prXYo' ]"
+test_command "Verificare il contenuto dal mount" "[ \"\$(cat wt_dir/file.txt)\" = 'prXYo' ]"
+
+test_command "Pulizia: Rimuovere i file di test" "rm -rf wt_dir"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,176 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato con server_retry_window_seconds verso un server minimale (Python),
+# che il test spegne e riavvia per simulare un riavvio del server.
+RETRY_PORT=8093
+RETRY_MOUNT_POINT="/tmp/remote_fs_retry_mount"
+RETRY_WORK_DIR=$(mktemp -d)
+RETRY_CLIENT_LOG="/tmp/client_retry.log"
+FAILED_TESTS=0
+
+cat > "$RETRY_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:$RETRY_PORT"
+cache_strategy = "ttl"
+cache_ttl_seconds = 1
+cache_lru_capacity = 1000
+health_poll_seconds = 1
+server_retry_window_seconds = 8
+TOML
+
+cat > "$RETRY_WORK_DIR/fake_server.py" <<'PY'
+import json, sys
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+FILES = {"data.txt": b"contenuto in cache\n", "other.txt": b"altro file\n"}
+ENTRIES = [{"name": n, "kind": "file", "size": len(c), "mtime": 0, "perm": "644"} for n, c in FILES.items()]
+
+class Handler(BaseHTTPRequestHandler):
+    def send(self, status, body, content_type="application/json", with_body=True):
+        self.send_response(status)
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        if self.path.startswith("/files/"):
+            self.send_header("X-Kind", "file")
+            self.send_header("X-Mtime", "0")
+            self.send_header("X-Perm", "644")
+        self.end_headers()
+        if with_body:
+            self.wfile.write(body)
+
+    def do_GET(self):
+        path = self.path.split("?")[0]
+        if path == "/health":
+            self.send(200, b"OK", "text/plain")
+        elif path in ("/list", "/list/"):
+            self.send(200, json.dumps(ENTRIES).encode())
+        elif path == "/list-stream":
+            self.send(200, "".join(json.dumps(e) + "\n" for e in ENTRIES).encode(), "application/x-ndjson")
+        elif path.removeprefix("/files/") in FILES:
+            # Ignora il Range: il client ritaglia il blocco dalla risposta completa
+            self.send(200, FILES[path.removeprefix("/files/")], "application/octet-stream")
+        else:
+            self.send(404, b"")
+
+    def do_HEAD(self):
+        if self.path.removeprefix("/files/") in FILES:
+            self.send(200, FILES[self.path.removeprefix("/files/")], "application/octet-stream", with_body=False)
+        else:
+            self.send(404, b"", with_body=False)
+
+    def log_message(self, *args):
+        pass
+
+HTTPServer.allow_reuse_address = True
+HTTPServer(("localhost", int(sys.argv[1])), Handler).serve_forever()
+PY
+
+start_server() {
+  python3 "$RETRY_WORK_DIR/fake_server.py" "$RETRY_PORT" &
+  SERVER_PID=$!
+}
+stop_server() {
+  kill "$SERVER_PID" 2>/dev/null
+  wait "$SERVER_PID" 2>/dev/null
+}
+
+start_server
+mkdir -p "$RETRY_MOUNT_POINT"
+(cd "$RETRY_WORK_DIR" && "$CLIENT_BIN" "$RETRY_MOUNT_POINT" &> "$RETRY_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_retry() {
+  cd /
+  umount -l "$RETRY_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" 2>/dev/null || true
+  stop_server
+  rm -rf "$RETRY_MOUNT_POINT" "$RETRY_WORK_DIR" "$RETRY_CLIENT_LOG"
+}
+trap cleanup_retry EXIT
+
+timeout=20
+while ! mount | grep -q "$RETRY_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount verso il server minimale non pronto."
+    cat "$RETRY_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$RETRY_MOUNT_POINT"
+
+# 1. Con il server attivo il file viene letto normalmente
+test_command "Leggere data.txt con il server attivo" "[ \"\$(cat data.txt)\" = 'contenuto in cache' ]"
+
+# 2. Riavvio breve: il server torna dopo 3 secondi, dentro la finestra di 8.
+#    La lettura (cache scaduta) resta in attesa e riesce senza errori
+stop_server
+sleep 1.5
+(sleep 1.5; exec python3 "$RETRY_WORK_DIR/fake_server.py" "$RETRY_PORT") &
+SERVER_PID=$!
+test_command "Leggere other.txt durante il riavvio del server" "[ \"\$(cat other.txt)\" = 'altro file' ]"
+test_command "Verificare che i tentativi siano segnalati nel log" "grep -q '\\[RETRY\\]' '$RETRY_CLIENT_LOG'"
+
+# 3. Interruzione più lunga della finestra: le operazioni falliscono subito,
+#    senza attendere di nuovo l'intera finestra
+stop_server
+sleep 10
+START=$(date +%s)
+test_command_fails "Leggere data.txt dopo un'interruzione più lunga della finestra" "cat data.txt"
+test_command "Verificare che l'errore arrivi senza attesa" "[ \$(( \$(date +%s) - $START )) -lt 3 ]"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
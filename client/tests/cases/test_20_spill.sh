@@ -0,0 +1,103 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+if [ -z "$SERVER_DATA_DIR" ]; then
+    echo "Errore: la variabile d'ambiente SERVER_DATA_DIR non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato con una soglia di spill bassa: il contenuto di un file da 3 MiB
+# viene unito alle scritture in un file temporaneo e caricato a chunk da lì.
+SPILL_MOUNT_POINT="/tmp/remote_fs_spill_mount"
+SPILL_WORK_DIR=$(mktemp -d)
+SPILL_CLIENT_LOG="/tmp/client_spill.log"
+FAILED_TESTS=0
+# Copia locale di riferimento del file, confrontata con `cmp`
+EXPECTED=$(mktemp)
+
+cat > "$SPILL_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:8080"
+cache_strategy = "ttl"
+cache_ttl_seconds = 60
+cache_lru_capacity = 1000
+spill_threshold_bytes = 1048576
+chunked_upload_threshold = 1048576
+upload_chunk_size = 262144
+TOML
+
+mkdir -p "$SPILL_MOUNT_POINT"
+(cd "$SPILL_WORK_DIR" && "$CLIENT_BIN" "$SPILL_MOUNT_POINT" &> "$SPILL_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_spill() {
+  cd /
+  rm -rf "$SPILL_MOUNT_POINT/spill_dir" 2>/dev/null || true
+  umount -l "$SPILL_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" 2>/dev/null || true
+  rm -rf "$SPILL_MOUNT_POINT" "$SPILL_WORK_DIR" "$SPILL_CLIENT_LOG" "$EXPECTED"
+}
+trap cleanup_spill EXIT
+
+timeout=20
+while ! mount | grep -q "$SPILL_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount per il test dello spill non pronto."
+    cat "$SPILL_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$SPILL_MOUNT_POINT"
+
+mkdir spill_dir
+
+# 1. File da 3 MiB (sopra la soglia) creato sul mount
+test_command "Creare un file da 3 MiB" "head -c 3145728 /dev/urandom > \"\$EXPECTED\" && cp \"\$EXPECTED\" spill_dir/big.bin"
+test_command "Verificare il file sul server" "cmp -s \"\$EXPECTED\" \"\$SERVER_DATA_DIR/spill_dir/big.bin\""
+
+# 2. Scrittura a un offset: il contenuto attuale viene scaricato su file temporaneo
+test_command "Scrivere a metà del file senza troncare" "printf 'SPILL' | dd of=spill_dir/big.bin bs=1 seek=2000000 conv=notrunc status=none && printf 'SPILL' | dd of=\"\$EXPECTED\" bs=1 seek=2000000 conv=notrunc status=none"
+test_command "Verificare che il merge sia avvenuto su file temporaneo" "grep -q 'merging the writes in a temporary file' '$SPILL_CLIENT_LOG'"
+test_command "Verificare il contenuto sul server" "cmp -s \"\$EXPECTED\" \"\$SERVER_DATA_DIR/spill_dir/big.bin\""
+
+# 3. Scrittura oltre la fine: il buco viene riempito di zeri
+test_command "Scrivere oltre la fine del file" "printf 'CODA' | dd of=spill_dir/big.bin bs=1 seek=3200000 conv=notrunc status=none && printf 'CODA' | dd of=\"\$EXPECTED\" bs=1 seek=3200000 conv=notrunc status=none"
+test_command "Verificare contenuto e zeri sul server" "cmp -s \"\$EXPECTED\" \"\$SERVER_DATA_DIR/spill_dir/big.bin\""
+test_command "Verificare che non restino file temporanei" "! ls /tmp | grep -q '^remotefs-spill-'"
+
+test_command "Pulizia: Rimuovere i file di test" "rm -rf spill_dir"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
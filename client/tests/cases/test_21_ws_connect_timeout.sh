@@ -0,0 +1,132 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato verso un server minimale (Python) il cui endpoint /ws accetta la
+# connessione TCP ma non risponde mai all'handshake WebSocket, come un host irraggiungibile.
+WSTO_PORT=8094
+WSTO_MOUNT_POINT="/tmp/remote_fs_ws_timeout_mount"
+WSTO_WORK_DIR=$(mktemp -d)
+WSTO_CLIENT_LOG="/tmp/client_ws_timeout.log"
+FAILED_TESTS=0
+
+cat > "$WSTO_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:$WSTO_PORT"
+cache_strategy = "ttl"
+cache_ttl_seconds = 1
+cache_lru_capacity = 1000
+ws_connect_timeout_seconds = 2
+TOML
+
+cat > "$WSTO_WORK_DIR/fake_server.py" <<'PY'
+import json, sys, time
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+
+ENTRIES = [{"name": "data.txt", "kind": "file", "size": 0, "mtime": 0, "perm": "644"}]
+
+class Handler(BaseHTTPRequestHandler):
+    def send(self, status, body, content_type="application/json"):
+        self.send_response(status)
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        self.end_headers()
+        self.wfile.write(body)
+
+    def do_GET(self):
+        path = self.path.split("?")[0]
+        if path == "/ws":
+            # Handshake mai completato: il client deve rinunciare da solo
+            time.sleep(3600)
+        elif path == "/health":
+            self.send(200, b"OK", "text/plain")
+        elif path in ("/list", "/list/"):
+            self.send(200, json.dumps(ENTRIES).encode())
+        elif path == "/list-stream":
+            self.send(200, "".join(json.dumps(e) + "\n" for e in ENTRIES).encode(), "application/x-ndjson")
+        else:
+            self.send(404, b"")
+
+    def log_message(self, *args):
+        pass
+
+ThreadingHTTPServer.allow_reuse_address = True
+ThreadingHTTPServer.daemon_threads = True
+ThreadingHTTPServer(("localhost", int(sys.argv[1])), Handler).serve_forever()
+PY
+
+python3 "$WSTO_WORK_DIR/fake_server.py" "$WSTO_PORT" &
+SERVER_PID=$!
+mkdir -p "$WSTO_MOUNT_POINT"
+(cd "$WSTO_WORK_DIR" && "$CLIENT_BIN" "$WSTO_MOUNT_POINT" &> "$WSTO_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_ws_timeout() {
+  cd /
+  umount -l "$WSTO_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" 2>/dev/null || true
+  kill "$SERVER_PID" 2>/dev/null || true
+  rm -rf "$WSTO_MOUNT_POINT" "$WSTO_WORK_DIR" "$WSTO_CLIENT_LOG"
+}
+trap cleanup_ws_timeout EXIT
+
+timeout=20
+while ! mount | grep -q "$WSTO_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount verso il server minimale non pronto."
+    cat "$WSTO_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+count_timeouts() {
+  grep -c 'Connessione fallita: timeout' "$WSTO_CLIENT_LOG"
+}
+
+# --- Esecuzione dei Test ---
+cd "$WSTO_MOUNT_POINT"
+
+# 1. Il mount resta utilizzabile anche se il watcher non riesce a connettersi
+test_command "Elencare la radice con il watcher bloccato" "ls | grep -q '^data.txt$'"
+
+# 2. Il tentativo bloccato scade dopo ws_connect_timeout_seconds (2) e viene segnalato
+sleep 3
+test_command "Verificare che il tentativo scada" "[ \$(count_timeouts) -ge 1 ]"
+
+# 3. Lo scadere conta come un tentativo fallito: dopo 5 secondi si riprova
+#    (e scade di nuovo), invece di restare appesi per sempre
+sleep 8
+test_command "Verificare che il loop di riconnessione continui" "[ \$(count_timeouts) -ge 2 ]"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,123 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicati avviati dalla directory "/", dove non c'è nessun config.toml:
+# la configurazione arriva da --config o da $XDG_CONFIG_HOME/remotefs/config.toml.
+CFG_MOUNT_POINT="/tmp/remote_fs_config_mount"
+CFG_WORK_DIR=$(mktemp -d)
+CFG_CLIENT_LOG="/tmp/client_config.log"
+CFG_PID_FILE="$CFG_WORK_DIR/client.pid"
+FAILED_TESTS=0
+
+cat > "$CFG_WORK_DIR/custom.toml" <<TOML
+server_url = "http://localhost:8080"
+cache_strategy = "ttl"
+cache_ttl_seconds = 1
+cache_lru_capacity = 1000
+max_file_size_bytes = 4096
+TOML
+mkdir -p "$CFG_WORK_DIR/xdg/remotefs"
+cp "$CFG_WORK_DIR/custom.toml" "$CFG_WORK_DIR/xdg/remotefs/config.toml"
+
+mkdir -p "$CFG_MOUNT_POINT"
+cleanup_config() {
+  cd /
+  umount -l "$CFG_MOUNT_POINT" 2>/dev/null || true
+  [ -f "$CFG_PID_FILE" ] && kill "$(cat "$CFG_PID_FILE")" 2>/dev/null
+  [ -n "$CLIENT_PID" ] && kill "$CLIENT_PID" 2>/dev/null
+  rm -rf "$CFG_MOUNT_POINT" "$CFG_WORK_DIR" "$CFG_CLIENT_LOG"
+}
+trap cleanup_config EXIT
+
+wait_for_mount() {
+  local timeout=20
+  while ! mount | grep -q "$CFG_MOUNT_POINT"; do
+    sleep 0.5
+    timeout=$((timeout - 1))
+    if [ "$timeout" -eq 0 ]; then
+      return 1
+    fi
+  done
+}
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# Scrive `size` byte di zeri in `file`
+write_bytes() {
+  head -c "$2" /dev/zero | dd of="$1" bs=65536 status=none
+}
+
+# --- Esecuzione dei Test ---
+cd /
+
+# 1. --config con un file inesistente: errore esplicito, nessun mount
+test_command "Rifiutare un --config inesistente (exit 2)" "\"$CLIENT_BIN\" \"$CFG_MOUNT_POINT\" --config \"$CFG_WORK_DIR/missing.toml\" &> /dev/null; [ \$? -eq 2 ]"
+
+# 2. Demone con --config: il file è letto prima del daemonize (che si sposta in "/")
+"$CLIENT_BIN" "$CFG_MOUNT_POINT" --daemon --config "$CFG_WORK_DIR/custom.toml" \
+  --pid-file "$CFG_PID_FILE" --daemon-stdout "$CFG_CLIENT_LOG" --daemon-stderr "$CFG_WORK_DIR/client.err" &> "$CFG_WORK_DIR/launch.log"
+test_command "Montare il demone con --config" "wait_for_mount"
+test_command "Verificare il file di configurazione nel log" "grep -q \"Configurazione letta da $CFG_WORK_DIR/custom.toml (--config)\" \"$CFG_WORK_DIR/launch.log\" \"$CFG_CLIENT_LOG\""
+test_command_fails "Scrivere 8192 byte con il limite di custom.toml (EFBIG)" "write_bytes \"$CFG_MOUNT_POINT/config_big.bin\" 8192"
+rm -f "$CFG_MOUNT_POINT/config_big.bin"
+test_command "Smontare il demone" "\"$CLIENT_BIN\" unmount --pid-file \"$CFG_PID_FILE\" && sleep 2 && ! mount | grep -q \"$CFG_MOUNT_POINT\""
+
+# 3. Senza --config: il primo config.toml dei percorsi di default, qui in $XDG_CONFIG_HOME
+(XDG_CONFIG_HOME="$CFG_WORK_DIR/xdg" "$CLIENT_BIN" "$CFG_MOUNT_POINT" &> "$CFG_CLIENT_LOG") &
+CLIENT_PID=$!
+test_command "Montare con la configurazione di \$XDG_CONFIG_HOME" "wait_for_mount"
+test_command "Verificare il file di configurazione nel log" "grep -q \"Configurazione letta da $CFG_WORK_DIR/xdg/remotefs/config.toml\" \"$CFG_CLIENT_LOG\""
+test_command_fails "Scrivere 8192 byte con il limite della configurazione trovata (EFBIG)" "write_bytes \"$CFG_MOUNT_POINT/config_big.bin\" 8192"
+rm -f "$CFG_MOUNT_POINT/config_big.bin"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
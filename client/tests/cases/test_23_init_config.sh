@@ -0,0 +1,79 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Nessun mount: `init-config` lavora solo su file locali.
+INIT_WORK_DIR=$(mktemp -d)
+INIT_CONFIG="$INIT_WORK_DIR/config.toml"
+FAILED_TESTS=0
+trap 'rm -rf "$INIT_WORK_DIR"' EXIT
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+
+# 1. Generazione: ogni opzione con il suo commento e il valore di default
+test_command "Generare il file" "\"$CLIENT_BIN\" init-config \"$INIT_CONFIG\""
+test_command "Verificare un valore di default" "grep -qx 'server_url = \"http://localhost:8080\"' \"$INIT_CONFIG\""
+test_command "Verificare i commenti" "grep -B1 -x 'cache_strategy = \"ttl\"' \"$INIT_CONFIG\" | grep -q '^# '"
+test_command "Verificare un'opzione senza default (commentata)" "grep -q '^# force_uid = ' \"$INIT_CONFIG\""
+
+# 2. Il file generato è una configurazione valida
+test_command "Rileggere il file con --config" "\"$CLIENT_BIN\" --config \"$INIT_CONFIG\" status --pid-file \"$INIT_WORK_DIR/none.pid\" &> \"$INIT_WORK_DIR/status.log\"; grep -q 'Configurazione letta da $INIT_CONFIG (--config)' \"$INIT_WORK_DIR/status.log\""
+
+# 3. Un file esistente non viene sovrascritto senza --force
+echo 'server_url = "http://example.com:9000"' > "$INIT_CONFIG"
+test_command_fails "Rigenerare sopra un file esistente" "\"$CLIENT_BIN\" init-config \"$INIT_CONFIG\""
+test_command "Verificare che il file sia intatto" "grep -qx 'server_url = \"http://example.com:9000\"' \"$INIT_CONFIG\""
+test_command "Sovrascrivere con --force" "\"$CLIENT_BIN\" init-config --force \"$INIT_CONFIG\" && grep -qx 'server_url = \"http://localhost:8080\"' \"$INIT_CONFIG\""
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
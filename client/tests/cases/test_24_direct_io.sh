@@ -0,0 +1,125 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+if [ -z "$SERVER_DATA_DIR" ]; then
+    echo "Errore: la variabile d'ambiente SERVER_DATA_DIR non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato con cache lunga e senza write_through: solo i descrittori
+# aperti con O_DIRECT devono scavalcare la cache e il buffer di scrittura.
+DIO_MOUNT_POINT="/tmp/remote_fs_dio_mount"
+DIO_WORK_DIR=$(mktemp -d)
+DIO_CLIENT_LOG="/tmp/client_dio.log"
+FAILED_TESTS=0
+
+cat > "$DIO_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:8080"
+cache_strategy = "ttl"
+cache_ttl_seconds = 60
+cache_lru_capacity = 1000
+TOML
+
+mkdir -p "$DIO_MOUNT_POINT"
+(cd "$DIO_WORK_DIR" && "$CLIENT_BIN" "$DIO_MOUNT_POINT" &> "$DIO_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_dio() {
+  cd /
+  rm -rf "$DIO_MOUNT_POINT/dio_dir" 2>/dev/null || true
+  umount -l "$DIO_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" 2>/dev/null || true
+  rm -rf "$DIO_MOUNT_POINT" "$DIO_WORK_DIR" "$DIO_CLIENT_LOG"
+}
+trap cleanup_dio EXIT
+
+timeout=20
+while ! mount | grep -q "$DIO_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount per il test O_DIRECT non pronto."
+    cat "$DIO_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# Scrive `data` in `file` tenendo aperto il descrittore, e stampa il contenuto
+# del file sul server (`server_file`) prima della close. `direct` = 1 apre con O_DIRECT.
+write_and_peek() {
+  python3 - "$@" <<'PY'
+import os, sys
+file, server_file, data, direct = sys.argv[1], sys.argv[2], sys.argv[3], sys.argv[4] == "1"
+flags = os.O_WRONLY | os.O_CREAT | os.O_TRUNC | (os.O_DIRECT if direct else 0)
+fd = os.open(file, flags, 0o644)
+os.write(fd, data.encode())
+try:
+    with open(server_file) as f:
+        print(f.read(), end="")
+finally:
+    os.close(fd)
+PY
+}
+
+# Legge `file` dall'inizio, con O_DIRECT se `direct` = 1
+read_file() {
+  python3 - "$@" <<'PY'
+import os, sys
+file, direct = sys.argv[1], sys.argv[2] == "1"
+fd = os.open(file, os.O_RDONLY | (os.O_DIRECT if direct else 0))
+try:
+    print(os.read(fd, 4096).decode(), end="")
+finally:
+    os.close(fd)
+PY
+}
+
+# --- Esecuzione dei Test ---
+cd "$DIO_MOUNT_POINT"
+
+mkdir dio_dir
+
+# 1. Scritture: il descrittore O_DIRECT arriva al server prima della close, quello normale no
+test_command "Scrittura O_DIRECT visibile sul server prima della close" "[ \"\$(write_and_peek dio_dir/direct.txt \"\$SERVER_DATA_DIR/dio_dir/direct.txt\" diretto 1)\" = 'diretto' ]"
+test_command "Scrittura normale ancora nel buffer prima della close" "[ -z \"\$(write_and_peek dio_dir/buffered.txt \"\$SERVER_DATA_DIR/dio_dir/buffered.txt\" bufferizzato 0)\" ]"
+test_command "Scrittura normale caricata alla close" "[ \"\$(cat \"\$SERVER_DATA_DIR/dio_dir/buffered.txt\")\" = 'bufferizzato' ]"
+
+# 2. Letture: il blocco già scaricato non viene riusato da un descrittore O_DIRECT
+printf 'versione-1' > dio_dir/read.txt
+test_command "Leggere il file (popola la cache)" "[ \"\$(read_file dio_dir/read.txt 0)\" = 'versione-1' ]"
+printf 'versione-2' > "$SERVER_DATA_DIR/dio_dir/read.txt"
+test_command "Lettura O_DIRECT del contenuto aggiornato sul server" "[ \"\$(read_file dio_dir/read.txt 1)\" = 'versione-2' ]"
+
+test_command "Pulizia: Rimuovere i file di test" "rm -rf dio_dir"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
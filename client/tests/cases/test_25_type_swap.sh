@@ -0,0 +1,66 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+if [ -z "$SERVER_DATA_DIR" ]; then
+    echo "Errore: la variabile d'ambiente SERVER_DATA_DIR non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+cd "$MOUNT_POINT"
+FAILED_TESTS=0
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+# Un altro client sostituisce un file con una directory con lo stesso nome (e viceversa),
+# direttamente nella directory dati del server: il mount deve riportare il nuovo tipo
+# invece di quello rimasto in cache per il vecchio inode.
+
+# 1. File sostituito da una directory
+test_command "Creare swap_entry come file" "echo 'file' > swap_entry && sleep 1 && [ \"\$(stat -c '%F' swap_entry)\" = 'regular file' ]"
+test_command "Sostituire il file con una directory sul server" "rm \"\$SERVER_DATA_DIR/swap_entry\" && mkdir \"\$SERVER_DATA_DIR/swap_entry\" && echo 'dentro' > \"\$SERVER_DATA_DIR/swap_entry/inner.txt\""
+sleep 2
+test_command "Verificare che 'ls -l' riporti una directory" "ls -l | grep ' swap_entry\$' | grep -q '^d'"
+test_command "Verificare il tipo con stat" "[ \"\$(stat -c '%F' swap_entry)\" = 'directory' ]"
+test_command "Entrare nella directory e leggerne il contenuto" "[ \"\$(cd swap_entry && cat inner.txt)\" = 'dentro' ]"
+
+# 2. Directory sostituita da un file
+test_command "Sostituire la directory con un file sul server" "rm -r \"\$SERVER_DATA_DIR/swap_entry\" && echo 'di nuovo file' > \"\$SERVER_DATA_DIR/swap_entry\""
+sleep 2
+test_command "Verificare il tipo con stat" "[ \"\$(stat -c '%F' swap_entry)\" = 'regular file' ]"
+test_command "Verificare il contenuto del file" "[ \"\$(cat swap_entry)\" = 'di nuovo file' ]"
+
+test_command "Pulizia: Rimuovere swap_entry" "rm swap_entry"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
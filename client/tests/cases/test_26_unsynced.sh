@@ -0,0 +1,170 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato verso un server minimale (Python) che rifiuta ogni scrittura con 500:
+# il caricamento finale di release viene ritentato e poi salvato in unsynced_dir.
+UNSYNCED_PORT=8093
+UNSYNCED_MOUNT_POINT="/tmp/remote_fs_unsynced_mount"
+UNSYNCED_WORK_DIR=$(mktemp -d)
+UNSYNCED_CLIENT_LOG="/tmp/client_unsynced.log"
+FAILED_TESTS=0
+
+cat > "$UNSYNCED_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:$UNSYNCED_PORT"
+cache_strategy = "ttl"
+cache_ttl_seconds = 1
+cache_lru_capacity = 1000
+verify_checksums = false
+release_upload_retries = 2
+unsynced_dir = "$UNSYNCED_WORK_DIR/unsynced"
+TOML
+
+cat > "$UNSYNCED_WORK_DIR/fake_server.py" <<'PY'
+import json, sys
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+FILES = {"data.txt": b"contenuto iniziale\n"}
+ENTRIES = [{"name": n, "kind": "file", "size": len(c), "mtime": 0, "perm": "644"} for n, c in FILES.items()]
+
+class Handler(BaseHTTPRequestHandler):
+    def send(self, status, body, content_type="application/json", with_body=True):
+        self.send_response(status)
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        if self.path.startswith("/files/"):
+            self.send_header("X-Kind", "file")
+            self.send_header("X-Mtime", "0")
+            self.send_header("X-Perm", "644")
+        self.end_headers()
+        if with_body:
+            self.wfile.write(body)
+
+    def do_GET(self):
+        path = self.path.split("?")[0]
+        if path in ("/list", "/list/"):
+            self.send(200, json.dumps(ENTRIES).encode())
+        elif path == "/list-stream":
+            self.send(200, "".join(json.dumps(e) + "\n" for e in ENTRIES).encode(), "application/x-ndjson")
+        elif path.removeprefix("/files/") in FILES:
+            self.send(200, FILES[path.removeprefix("/files/")], "application/octet-stream")
+        else:
+            self.send(404, b"")
+
+    def do_HEAD(self):
+        if self.path.removeprefix("/files/") in FILES:
+            self.send(200, FILES[self.path.removeprefix("/files/")], "application/octet-stream", with_body=False)
+        else:
+            self.send(404, b"", with_body=False)
+
+    # Ogni scrittura fallisce: il corpo viene letto, poi la richiesta è rifiutata
+    def do_PUT(self):
+        self.rfile.read(int(self.headers.get("Content-Length", 0)))
+        self.send(500, b"scritture disabilitate")
+
+    do_POST = do_PUT
+
+    def log_message(self, *args):
+        pass
+
+HTTPServer(("localhost", int(sys.argv[1])), Handler).serve_forever()
+PY
+
+python3 "$UNSYNCED_WORK_DIR/fake_server.py" "$UNSYNCED_PORT" &
+SERVER_PID=$!
+mkdir -p "$UNSYNCED_MOUNT_POINT"
+(cd "$UNSYNCED_WORK_DIR" && "$CLIENT_BIN" "$UNSYNCED_MOUNT_POINT" &> "$UNSYNCED_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_unsynced() {
+  cd /
+  umount -l "$UNSYNCED_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" "$SERVER_PID" 2>/dev/null || true
+  rm -rf "$UNSYNCED_MOUNT_POINT" "$UNSYNCED_WORK_DIR" "$UNSYNCED_CLIENT_LOG"
+}
+trap cleanup_unsynced EXIT
+
+timeout=20
+while ! mount | grep -q "$UNSYNCED_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount verso il server minimale non pronto."
+    cat "$UNSYNCED_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$UNSYNCED_MOUNT_POINT"
+SAVED="$UNSYNCED_WORK_DIR/unsynced/data.txt"
+
+# 1. Nessun file da recuperare all'avvio
+test_command "Verificare che stats non riporti file non sincronizzati" "grep -qx 'unsynced_files: 0' .remotefs/stats"
+
+# 2. Append a data.txt: la close fallisce (flush), poi release ritenta e salva il buffer
+test_command_fails "Scrivere in append su data.txt (close con errore)" "python3 -c \"import os; fd = os.open('data.txt', os.O_WRONLY | os.O_APPEND); os.write(fd, b'aggiunto'); os.close(fd)\""
+sleep 3
+test_command "Verificare i nuovi tentativi nel log" "grep -q 'retry 2/2' '$UNSYNCED_CLIENT_LOG'"
+test_command "Verificare l'avviso nel log" "grep -q '\\[UNSYNCED\\]' '$UNSYNCED_CLIENT_LOG'"
+
+# 3. Le scritture sono salvate al loro offset (dopo i 19 byte del file sul server)
+test_command "Verificare che il buffer sia stato salvato" "[ -f \"\$SAVED\" ]"
+test_command "Verificare i dati salvati" "[ \"\$(tail -c 8 \"\$SAVED\")\" = 'aggiunto' ] && [ \"\$(stat -c '%s' \"\$SAVED\")\" = '27' ]"
+test_command "Verificare il conteggio in stats" "grep -qx 'unsynced_files: 1' .remotefs/stats"
+test_command_fails "Scrivere su .remotefs/stats" "echo 1 > .remotefs/stats"
+
+# 4. Una volta recuperati i dati, il file salvato viene rimosso a mano
+test_command "Rimuovere il file salvato" "rm \"\$SAVED\" && grep -qx 'unsynced_files: 0' .remotefs/stats"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,70 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+cd "$MOUNT_POINT"
+FAILED_TESTS=0
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# Legge `count` byte di `file` dall'offset `offset` con pread, con O_DIRECT se `direct` = 1
+# (le letture arrivano al client anche oltre la fine del file), e ne stampa la lunghezza
+pread_len() {
+  python3 - "$@" <<'PY'
+import os, sys
+file, offset, count, direct = sys.argv[1], int(sys.argv[2]), int(sys.argv[3]), sys.argv[4] == "1"
+fd = os.open(file, os.O_RDONLY | (os.O_DIRECT if direct else 0))
+try:
+    print(len(os.pread(fd, count, offset)))
+finally:
+    os.close(fd)
+PY
+}
+
+# --- Esecuzione dei Test ---
+# Offset enormi su un file piccolo: la lettura deve restituire 0 byte (fine del file)
+# senza che l'aritmetica degli offset nel client vada in overflow.
+
+test_command "Creare il file di prova" "echo 'file piccolo' > offset_test.txt"
+
+# 1. Oltre i 32 bit (troncati a 0 su un target a 32 bit senza controlli) e vicino a i64::MAX
+test_command "Leggere oltre 4 GiB con O_DIRECT" "[ \"\$(pread_len offset_test.txt 4294967297 4096 1)\" = '0' ]"
+test_command "Leggere vicino al massimo offset con O_DIRECT" "[ \"\$(pread_len offset_test.txt 9223372036854771712 4096 1)\" = '0' ]"
+test_command "Leggere vicino al massimo offset senza O_DIRECT" "[ \"\$(pread_len offset_test.txt 9223372036854771712 4096 0)\" = '0' ]"
+
+# 2. Il client è ancora vivo e legge normalmente
+test_command "Rileggere il file dall'inizio" "[ \"\$(cat offset_test.txt)\" = 'file piccolo' ]"
+
+test_command "Pulizia: Rimuovere il file di prova" "rm offset_test.txt"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,89 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+if [ -z "$SERVER_DATA_DIR" ]; then
+    echo "Errore: la variabile d'ambiente SERVER_DATA_DIR non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+cd "$MOUNT_POINT"
+FAILED_TESTS=0
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# Crea `file` con O_CREAT | O_EXCL, come fanno i lockfile: fallisce se esiste già
+create_excl() {
+  python3 -c "import os, sys; os.close(os.open(sys.argv[1], os.O_WRONLY | os.O_CREAT | os.O_EXCL, 0o644))" "$1"
+}
+
+# --- Esecuzione dei Test ---
+# La create del client usa POST /touch, che sul server crea il file solo se non esiste
+# (create_new): O_EXCL resta atomico anche rispetto agli altri client.
+
+# 1. Lockfile creato dal mount
+test_command "Prendere il lock (O_CREAT | O_EXCL)" "create_excl excl.lock"
+test_command_fails "Prendere di nuovo lo stesso lock" "create_excl excl.lock"
+test_command_fails "Sovrascrivere con noclobber (set -C)" "(set -C; echo 'dati' > excl.lock)"
+test_command "Rilasciare il lock" "rm excl.lock"
+test_command "Riprendere il lock dopo il rilascio" "create_excl excl.lock && rm excl.lock"
+
+# 2. Lockfile preso da un altro client (creato direttamente sul server) dopo che il mount
+#    ha visto il nome libero: EEXIST dal lookup, o dal 409 di /touch se il mount non lo vede ancora
+test_command "Verificare che il nome sia libero" "[ ! -e remote.lock ]"
+test_command "Creare il lock sul server" "echo 'altro client' > \"\$SERVER_DATA_DIR/remote.lock\""
+test_command_fails "Prendere il lock già preso dall'altro client" "create_excl remote.lock"
+test_command "Verificare che il lock dell'altro client sia intatto" "[ \"\$(cat \"\$SERVER_DATA_DIR/remote.lock\")\" = 'altro client' ]"
+test_command "Pulizia: Rimuovere il lock" "rm remote.lock"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
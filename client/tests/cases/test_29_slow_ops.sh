@@ -0,0 +1,154 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Mount dedicato verso un server minimale (Python) che risponde con 300ms di ritardo,
+# con slow_op_threshold_ms = 200: le operazioni che lo contattano vengono segnalate come lente.
+SLOW_PORT=8094
+SLOW_MOUNT_POINT="/tmp/remote_fs_slow_mount"
+SLOW_WORK_DIR=$(mktemp -d)
+SLOW_CLIENT_LOG="/tmp/client_slow.log"
+FAILED_TESTS=0
+
+cat > "$SLOW_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:$SLOW_PORT"
+cache_strategy = "ttl"
+cache_ttl_seconds = 1
+cache_lru_capacity = 1000
+slow_op_threshold_ms = 200
+TOML
+
+cat > "$SLOW_WORK_DIR/fake_server.py" <<'PY'
+import json, sys, time
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+FILES = {"data.txt": b"risposta lenta\n"}
+DELAY = 0.3
+ENTRIES = [{"name": n, "kind": "file", "size": len(c), "mtime": 0, "perm": "644"} for n, c in FILES.items()]
+
+class Handler(BaseHTTPRequestHandler):
+    def send(self, status, body, content_type="application/json", with_body=True):
+        self.send_response(status)
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        if self.path.startswith("/files/"):
+            self.send_header("X-Kind", "file")
+            self.send_header("X-Mtime", "0")
+            self.send_header("X-Perm", "644")
+        self.end_headers()
+        if with_body:
+            self.wfile.write(body)
+
+    def do_GET(self):
+        time.sleep(DELAY)
+        path = self.path.split("?")[0]
+        if path in ("/list", "/list/"):
+            self.send(200, json.dumps(ENTRIES).encode())
+        elif path == "/list-stream":
+            self.send(200, "".join(json.dumps(e) + "\n" for e in ENTRIES).encode(), "application/x-ndjson")
+        elif path.removeprefix("/files/") in FILES:
+            self.send(200, FILES[path.removeprefix("/files/")], "application/octet-stream")
+        else:
+            self.send(404, b"")
+
+    def do_HEAD(self):
+        time.sleep(DELAY)
+        if self.path.removeprefix("/files/") in FILES:
+            self.send(200, FILES[self.path.removeprefix("/files/")], "application/octet-stream", with_body=False)
+        else:
+            self.send(404, b"", with_body=False)
+
+    def log_message(self, *args):
+        pass
+
+HTTPServer(("localhost", int(sys.argv[1])), Handler).serve_forever()
+PY
+
+python3 "$SLOW_WORK_DIR/fake_server.py" "$SLOW_PORT" &
+SERVER_PID=$!
+mkdir -p "$SLOW_MOUNT_POINT"
+(cd "$SLOW_WORK_DIR" && "$CLIENT_BIN" "$SLOW_MOUNT_POINT" &> "$SLOW_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_slow() {
+  cd /
+  umount -l "$SLOW_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" "$SERVER_PID" 2>/dev/null || true
+  rm -rf "$SLOW_MOUNT_POINT" "$SLOW_WORK_DIR" "$SLOW_CLIENT_LOG"
+}
+trap cleanup_slow EXIT
+
+timeout=20
+while ! mount | grep -q "$SLOW_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount verso il server minimale non pronto."
+    cat "$SLOW_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$SLOW_MOUNT_POINT"
+
+# 1. Le operazioni che non contattano il server restano sotto la soglia
+test_command "Leggere stats (nessuna richiesta al server)" "grep -qx 'slow_ops: 0' .remotefs/stats"
+
+# 2. Ogni richiesta al server impiega almeno 300ms: lookup e read vengono segnalate
+test_command "Leggere data.txt" "[ \"\$(cat data.txt)\" = 'risposta lenta' ]"
+test_command "Verificare la segnalazione nel log" "grep -q '\\[SLOW\\] read (inode' '$SLOW_CLIENT_LOG'"
+test_command "Verificare il tempo passato sul server" "grep '\\[SLOW\\] read (inode' '$SLOW_CLIENT_LOG' | grep -q 'in 1 richieste al server'"
+test_command "Verificare il conteggio in stats" "[ \"\$(sed -n 's/^slow_ops: //p' .remotefs/stats)\" -gt 0 ]"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
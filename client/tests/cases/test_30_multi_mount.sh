@@ -0,0 +1,174 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+if [ -z "$CLIENT_BIN" ] || [ -z "$SERVER_DATA_DIR" ]; then
+    echo "Errore: le variabili d'ambiente CLIENT_BIN e SERVER_DATA_DIR devono essere impostate."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Un solo processo client con due [[mount]] nel proprio config.toml (nessun mountpoint sulla CLI):
+# multi_test/alice su MULTI_MOUNT_A e multi_test/bob su MULTI_MOUNT_B, con strategie di cache diverse.
+MULTI_MOUNT_A="/tmp/remote_fs_multi_a"
+MULTI_MOUNT_B="/tmp/remote_fs_multi_b"
+MULTI_WORK_DIR=$(mktemp -d)
+MULTI_CLIENT_LOG="/tmp/client_multi.log"
+MULTI_DIR="$SERVER_DATA_DIR/multi_test"
+FAILED_TESTS=0
+
+# Scrive config.toml con i due mount; il secondo usa la root_prefix indicata (default: bob)
+write_config() {
+  cat > "$MULTI_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:8080"
+cache_strategy = "ttl"
+cache_ttl_seconds = 60
+cache_lru_capacity = 1000
+
+[[mount]]
+mountpoint = "$MULTI_MOUNT_A"
+root_prefix = "multi_test/alice"
+
+[[mount]]
+mountpoint = "$MULTI_MOUNT_B"
+root_prefix = "${1:-multi_test/bob}"
+cache_strategy = "none"
+TOML
+}
+
+# Avvia il client in background (nella directory del config.toml) e ne salva il PID
+start_client() {
+  (cd "$MULTI_WORK_DIR" && exec "$CLIENT_BIN" &> "$MULTI_CLIENT_LOG") &
+  CLIENT_PID=$!
+}
+
+# Attende che il mountpoint $1 compaia tra i mount
+wait_mounted() {
+  local timeout=20
+  while ! mount | grep -q "$1"; do
+    sleep 0.5
+    timeout=$((timeout - 1))
+    if [ "$timeout" -eq 0 ]; then
+      echo "Errore: mount $1 non pronto."
+      cat "$MULTI_CLIENT_LOG"
+      exit 1
+    fi
+  done
+}
+
+# Attende fino a 10 secondi che il processo client termini
+wait_exited() {
+  for _ in $(seq 1 20); do
+    kill -0 "$CLIENT_PID" 2>/dev/null || return 0
+    sleep 0.5
+  done
+  return 1
+}
+
+mkdir -p "$MULTI_DIR/alice" "$MULTI_DIR/bob"
+echo 'di alice' > "$MULTI_DIR/alice/a.txt"
+echo 'di bob' > "$MULTI_DIR/bob/b.txt"
+
+mkdir -p "$MULTI_MOUNT_A" "$MULTI_MOUNT_B"
+write_config
+start_client
+cleanup_multi() {
+  cd /
+  umount -l "$MULTI_MOUNT_A" 2>/dev/null || true
+  umount -l "$MULTI_MOUNT_B" 2>/dev/null || true
+  kill "$CLIENT_PID" 2>/dev/null || true
+  rm -rf "$MULTI_MOUNT_A" "$MULTI_MOUNT_B" "$MULTI_WORK_DIR" "$MULTI_CLIENT_LOG" "$MULTI_DIR"
+}
+trap cleanup_multi EXIT
+
+wait_mounted "$MULTI_MOUNT_A"
+wait_mounted "$MULTI_MOUNT_B"
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd /
+
+# 1. Ogni mount vede solo la sua root_prefix, entrambi serviti dallo stesso processo
+test_command "Leggere il file di alice dal primo mount" "[ \"\$(cat \"\$MULTI_MOUNT_A/a.txt\")\" = 'di alice' ]"
+test_command "Leggere il file di bob dal secondo mount" "[ \"\$(cat \"\$MULTI_MOUNT_B/b.txt\")\" = 'di bob' ]"
+test_command_fails "Non vedere i file di bob nel primo mount" "cat \"\$MULTI_MOUNT_A/b.txt\""
+test_command "Verificare che un solo processo serva entrambi i mount" "[ \"\$(grep -c 'Mounting filesystem at' \"\$MULTI_CLIENT_LOG\")\" -eq 2 ] && kill -0 \$CLIENT_PID"
+
+# 2. Le scritture di ogni mount finiscono sotto la sua root_prefix
+test_command "Scrivere un file in ciascun mount" "echo 'nuovo a' > \"\$MULTI_MOUNT_A/nuovo.txt\" && echo 'nuovo b' > \"\$MULTI_MOUNT_B/nuovo.txt\""
+sleep 1
+test_command "Verificare i file sul server" "[ \"\$(cat \"\$MULTI_DIR/alice/nuovo.txt\")\" = 'nuovo a' ] && [ \"\$(cat \"\$MULTI_DIR/bob/nuovo.txt\")\" = 'nuovo b' ]"
+
+# 3. Smontare un mount dall'esterno non tocca l'altro né il processo
+test_command "Smontare il primo mount dall'esterno" "umount \"\$MULTI_MOUNT_A\""
+sleep 2
+test_command "Verificare che il processo abbia chiuso solo quella sessione" "grep -q \"Sessione FUSE terminata: \$MULTI_MOUNT_A\" \"\$MULTI_CLIENT_LOG\" && kill -0 \$CLIENT_PID"
+test_command "Leggere ancora dal secondo mount" "[ \"\$(cat \"\$MULTI_MOUNT_B/b.txt\")\" = 'di bob' ]"
+
+# 4. SIGTERM smonta i mount rimasti e termina il processo
+test_command "Arrestare il processo con SIGTERM" "kill -TERM \$CLIENT_PID && wait_exited"
+test_command_fails "Verificare che il secondo mount sia stato smontato" "mount | grep -q \"\$MULTI_MOUNT_B\""
+
+# 5. Un mount non valido viene saltato, gli altri partono comunque
+write_config "multi_test/missing"
+start_client
+wait_mounted "$MULTI_MOUNT_A"
+test_command "Segnalare la root_prefix inesistente del secondo mount" "grep -q \"Errore di configurazione di \$MULTI_MOUNT_B\" \"\$MULTI_CLIENT_LOG\""
+test_command "Leggere dal primo mount" "[ \"\$(cat \"\$MULTI_MOUNT_A/a.txt\")\" = 'di alice' ]"
+test_command "Arrestare il processo" "kill -TERM \$CLIENT_PID && wait_exited"
+
+# 6. Una tabella [[mount]] senza mountpoint rende invalida la configurazione (exit code 2)
+printf 'server_url = "http://localhost:8080"\ncache_strategy = "ttl"\ncache_ttl_seconds = 60\ncache_lru_capacity = 1000\n\n[[mount]]\nroot_prefix = "multi_test/alice"\n' > "$MULTI_WORK_DIR/config.toml"
+test_command "Rifiutare un [[mount]] senza mountpoint" "(cd \"\$MULTI_WORK_DIR\" && timeout 10 \"\$CLIENT_BIN\" &> /dev/null); [ \$? -eq 2 ]"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
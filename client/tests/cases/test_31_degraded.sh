@@ -0,0 +1,186 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Server minimale il cui /healthz risponde 503 finché esiste il file $DEGRADED_WORK_DIR/healthz,
+# con il controllo fallito scritto dentro ("space" o "write"). Il client lo interroga ogni secondo.
+DEGRADED_PORT=8095
+DEGRADED_MOUNT_POINT="/tmp/remote_fs_degraded_mount"
+DEGRADED_WORK_DIR=$(mktemp -d)
+DEGRADED_CLIENT_LOG="/tmp/client_degraded.log"
+FAILED_TESTS=0
+
+cat > "$DEGRADED_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:$DEGRADED_PORT"
+cache_strategy = "ttl"
+cache_ttl_seconds = 1
+cache_lru_capacity = 1000
+verify_checksums = false
+health_poll_seconds = 1
+TOML
+
+cat > "$DEGRADED_WORK_DIR/fake_server.py" <<'PY'
+import json, os, sys
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+FILES = {"data.txt": b"contenuto iniziale\n"}
+HEALTHZ = os.path.join(os.path.dirname(os.path.abspath(__file__)), "healthz")
+
+def entries():
+    return [{"name": n, "kind": "file", "size": len(c), "mtime": 0, "perm": "644"} for n, c in FILES.items()]
+
+class Handler(BaseHTTPRequestHandler):
+    def send(self, status, body, content_type="application/json", with_body=True):
+        self.send_response(status)
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        if self.path.startswith("/files/"):
+            self.send_header("X-Kind", "file")
+            self.send_header("X-Mtime", "0")
+            self.send_header("X-Perm", "644")
+        self.end_headers()
+        if with_body:
+            self.wfile.write(body)
+
+    def do_GET(self):
+        path = self.path.split("?")[0]
+        if path == "/health":
+            self.send(200, b"OK", "text/plain")
+        elif path == "/healthz":
+            if os.path.exists(HEALTHZ):
+                check = open(HEALTHZ).read().strip()
+                self.send(503, json.dumps({"status": "unhealthy", "check": check, "reason": "simulato"}).encode())
+            else:
+                self.send(200, json.dumps({"status": "ok"}).encode())
+        elif path in ("/list", "/list/"):
+            self.send(200, json.dumps(entries()).encode())
+        elif path == "/list-stream":
+            self.send(200, "".join(json.dumps(e) + "\n" for e in entries()).encode(), "application/x-ndjson")
+        elif path.removeprefix("/files/") in FILES:
+            self.send(200, FILES[path.removeprefix("/files/")], "application/octet-stream")
+        else:
+            self.send(404, b"")
+
+    def do_HEAD(self):
+        if self.path.removeprefix("/files/") in FILES:
+            self.send(200, FILES[self.path.removeprefix("/files/")], "application/octet-stream", with_body=False)
+        else:
+            self.send(404, b"", with_body=False)
+
+    # Le scritture che arrivano al server riescono: il file caricato con PUT sostituisce il contenuto
+    def do_PUT(self):
+        body = self.rfile.read(int(self.headers.get("Content-Length", 0)))
+        name = self.path.split("?")[0].removeprefix("/files/")
+        if name in FILES:
+            FILES[name] = body
+        self.send(200, b"")
+
+    def do_POST(self):
+        self.rfile.read(int(self.headers.get("Content-Length", 0)))
+        self.send(200, b"")
+
+    def log_message(self, *args):
+        pass
+
+HTTPServer(("localhost", int(sys.argv[1])), Handler).serve_forever()
+PY
+
+python3 "$DEGRADED_WORK_DIR/fake_server.py" "$DEGRADED_PORT" &
+SERVER_PID=$!
+mkdir -p "$DEGRADED_MOUNT_POINT"
+(cd "$DEGRADED_WORK_DIR" && "$CLIENT_BIN" "$DEGRADED_MOUNT_POINT" &> "$DEGRADED_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_degraded() {
+  cd /
+  umount -l "$DEGRADED_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" "$SERVER_PID" 2>/dev/null || true
+  rm -rf "$DEGRADED_MOUNT_POINT" "$DEGRADED_WORK_DIR" "$DEGRADED_CLIENT_LOG"
+}
+trap cleanup_degraded EXIT
+
+timeout=20
+while ! mount | grep -q "$DEGRADED_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount verso il server minimale non pronto."
+    cat "$DEGRADED_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$DEGRADED_MOUNT_POINT"
+
+# 1. Disco del server pieno: le scritture falliscono subito con ENOSPC, le letture no
+echo space > "$DEGRADED_WORK_DIR/healthz"
+sleep 3
+test_command "Segnalare il server degradato nel log" "grep -q 'non accetta dati (simulato)' \"\$DEGRADED_CLIENT_LOG\""
+test_command "Rifiutare la scrittura con ENOSPC" "! echo 'nuovo' 2> \"\$DEGRADED_WORK_DIR/err\" >> data.txt && grep -q 'No space left on device' \"\$DEGRADED_WORK_DIR/err\""
+test_command_fails "Creare un file" "touch nuovo.txt"
+test_command_fails "Creare una directory" "mkdir nuova_dir"
+test_command "Leggere il file esistente" "[ \"\$(cat data.txt)\" = 'contenuto iniziale' ]"
+
+# 2. Directory dei dati in sola lettura: EROFS
+echo write > "$DEGRADED_WORK_DIR/healthz"
+sleep 3
+test_command "Rifiutare la creazione con EROFS" "! touch nuovo.txt 2> \"\$DEGRADED_WORK_DIR/err\" && grep -q 'Read-only file system' \"\$DEGRADED_WORK_DIR/err\""
+
+# 3. Server di nuovo sano: le scritture ripartono
+rm -f "$DEGRADED_WORK_DIR/healthz"
+sleep 3
+test_command "Segnalare il ritorno alla normalità" "grep -q 'accetta di nuovo le scritture' \"\$DEGRADED_CLIENT_LOG\""
+test_command "Scrivere di nuovo sul file" "echo 'aggiunto' >> data.txt"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,180 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Server minimale il cui /health (e /healthz) fallisce (500) finché esiste il file $WARMUP_WORK_DIR/down.
+# Registra i path chiesti con /stat-batch in stat_batch.log e le richieste HEAD in head.log.
+WARMUP_PORT=8096
+WARMUP_MOUNT_POINT="/tmp/remote_fs_warmup_mount"
+WARMUP_WORK_DIR=$(mktemp -d)
+WARMUP_CLIENT_LOG="/tmp/client_warmup.log"
+FAILED_TESTS=0
+
+cat > "$WARMUP_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:$WARMUP_PORT"
+cache_strategy = "ttl"
+cache_ttl_seconds = 60
+cache_lru_capacity = 1000
+verify_checksums = false
+health_poll_seconds = 1
+TOML
+
+cat > "$WARMUP_WORK_DIR/fake_server.py" <<'PY'
+import json, os, sys
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+FILES = {"a.txt": b"primo\n", "b.txt": b"secondo\n", "c.txt": b"terzo\n"}
+WORK_DIR = os.path.dirname(os.path.abspath(__file__))
+
+def entry(name):
+    return {"name": name, "kind": "file", "size": len(FILES[name]), "mtime": 0, "perm": "644"}
+
+def log(file, line):
+    with open(os.path.join(WORK_DIR, file), "a") as f:
+        f.write(line + "\n")
+
+class Handler(BaseHTTPRequestHandler):
+    def send(self, status, body, content_type="application/json", with_body=True, headers=None):
+        self.send_response(status)
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        for key, value in (headers or {}).items():
+            self.send_header(key, value)
+        self.end_headers()
+        if with_body:
+            self.wfile.write(body)
+
+    def do_GET(self):
+        path = self.path.split("?")[0]
+        if path in ("/health", "/healthz"):
+            self.send(500 if os.path.exists(os.path.join(WORK_DIR, "down")) else 200, b'{"status":"ok"}')
+        elif path in ("/list", "/list/"):
+            self.send(200, json.dumps([entry(n) for n in FILES]).encode())
+        elif path == "/list-stream":
+            self.send(200, "".join(json.dumps(entry(n)) + "\n" for n in FILES).encode(), "application/x-ndjson")
+        elif path.removeprefix("/files/") in FILES:
+            self.send(200, FILES[path.removeprefix("/files/")], "application/octet-stream")
+        else:
+            self.send(404, b"")
+
+    def do_HEAD(self):
+        name = self.path.removeprefix("/files/")
+        log("head.log", name)
+        if name in FILES:
+            headers = {"X-Kind": "file", "X-Mtime": "0", "X-Perm": "644"}
+            self.send(200, FILES[name], "application/octet-stream", with_body=False, headers=headers)
+        else:
+            self.send(404, b"", with_body=False)
+
+    def do_POST(self):
+        body = self.rfile.read(int(self.headers.get("Content-Length", 0)))
+        if self.path == "/stat-batch":
+            paths = json.loads(body)
+            for path in paths:
+                log("stat_batch.log", path)
+            self.send(200, json.dumps([entry(p) if p in FILES else None for p in paths]).encode())
+        else:
+            self.send(404, b"")
+
+    def log_message(self, *args):
+        pass
+
+HTTPServer(("localhost", int(sys.argv[1])), Handler).serve_forever()
+PY
+
+python3 "$WARMUP_WORK_DIR/fake_server.py" "$WARMUP_PORT" &
+SERVER_PID=$!
+mkdir -p "$WARMUP_MOUNT_POINT"
+(cd "$WARMUP_WORK_DIR" && "$CLIENT_BIN" "$WARMUP_MOUNT_POINT" &> "$WARMUP_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_warmup() {
+  cd /
+  umount -l "$WARMUP_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" "$SERVER_PID" 2>/dev/null || true
+  rm -rf "$WARMUP_MOUNT_POINT" "$WARMUP_WORK_DIR" "$WARMUP_CLIENT_LOG"
+}
+trap cleanup_warmup EXIT
+
+timeout=20
+while ! mount | grep -q "$WARMUP_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount verso il server minimale non pronto."
+    cat "$WARMUP_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$WARMUP_MOUNT_POINT"
+
+# 1. Uso di due file: diventano i path recenti
+stat a.txt b.txt > /dev/null
+
+# 2. Interruzione e ritorno del server: i path recenti vengono rimessi in cache con /stat-batch
+touch "$WARMUP_WORK_DIR/down"
+sleep 3
+test_command "Rilevare l'interruzione" "grep -q 'non risponde' \"\$WARMUP_CLIENT_LOG\""
+rm -f "$WARMUP_WORK_DIR/down" "$WARMUP_WORK_DIR/head.log"
+sleep 3
+test_command "Riscaldare la cache al ritorno del server" "grep -q 'attributi di 2 path recenti su 2 rimessi in cache' \"\$WARMUP_CLIENT_LOG\""
+test_command "Chiedere i path recenti con /stat-batch" "grep -qx a.txt \"\$WARMUP_WORK_DIR/stat_batch.log\" && grep -qx b.txt \"\$WARMUP_WORK_DIR/stat_batch.log\""
+test_command_fails "Escludere i path mai usati" "grep -qx c.txt \"\$WARMUP_WORK_DIR/stat_batch.log\""
+
+# 3. Gli attributi arrivano dalla cache, senza HEAD al server
+test_command "Leggere gli attributi dei path recenti" "stat a.txt b.txt > /dev/null"
+test_command_fails "Evitare le richieste HEAD per i path riscaldati" "grep -q txt \"\$WARMUP_WORK_DIR/head.log\""
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,109 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Il client viene avviato su mountpoint non validi (inesistente, un file, una directory
+# non vuota) e deve uscire con codice 2 e un messaggio chiaro; con --allow-nonempty
+# la directory non vuota viene montata. Configurazione di default (server su localhost:8080).
+GUARD_DIR=$(mktemp -d)
+GUARD_CLIENT_LOG="/tmp/client_guard.log"
+FAILED_TESTS=0
+CLIENT_PID=""
+
+mkdir -p "$GUARD_DIR/nonempty"
+echo "nascosto dal mount" > "$GUARD_DIR/nonempty/locale.txt"
+touch "$GUARD_DIR/file"
+
+cleanup_guard() {
+  umount -l "$GUARD_DIR/nonempty" 2>/dev/null || true
+  [ -n "$CLIENT_PID" ] && kill "$CLIENT_PID" 2>/dev/null || true
+  rm -rf "$GUARD_DIR" "$GUARD_CLIENT_LOG"
+}
+trap cleanup_guard EXIT
+
+# Avvia il client in primo piano su $1 (più eventuali opzioni) e ne restituisce il codice di uscita
+run_client() {
+  (cd /tmp && timeout 20 "$CLIENT_BIN" "$@" &> "$GUARD_CLIENT_LOG")
+}
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+
+# 1. Mountpoint non validi: il client esce subito con codice 2
+test_command "Rifiutare un mountpoint inesistente" "run_client \"\$GUARD_DIR/manca\"; [ \$? -eq 2 ] && grep -q 'il mountpoint non esiste: crealo con' \"\$GUARD_CLIENT_LOG\""
+test_command "Rifiutare un mountpoint che è un file" "run_client \"\$GUARD_DIR/file\"; [ \$? -eq 2 ] && grep -q 'il mountpoint non è una directory' \"\$GUARD_CLIENT_LOG\""
+test_command "Rifiutare un mountpoint non vuoto" "run_client \"\$GUARD_DIR/nonempty\"; [ \$? -eq 2 ] && grep -q 'usa --allow-nonempty' \"\$GUARD_CLIENT_LOG\""
+test_command_fails "Verificare che non sia stato montato nulla" "mount | grep -q \"\$GUARD_DIR\""
+
+# 2. Con --allow-nonempty la directory viene montata e il suo contenuto resta nascosto
+(cd /tmp && "$CLIENT_BIN" "$GUARD_DIR/nonempty" --allow-nonempty &> "$GUARD_CLIENT_LOG") &
+CLIENT_PID=$!
+timeout=20
+while ! mount | grep -q "$GUARD_DIR/nonempty"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount con --allow-nonempty non pronto."
+    cat "$GUARD_CLIENT_LOG"
+    exit 1
+  fi
+done
+test_command_fails "Nascondere i file locali sotto il mount" "[ -e \"\$GUARD_DIR/nonempty/locale.txt\" ]"
+
+# 3. Smontato, il contenuto locale torna visibile
+kill -TERM "$CLIENT_PID"
+wait "$CLIENT_PID" 2>/dev/null
+CLIENT_PID=""
+test_command "Ritrovare i file locali dopo lo smontaggio" "[ \"\$(cat \"\$GUARD_DIR/nonempty/locale.txt\")\" = 'nascosto dal mount' ]"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
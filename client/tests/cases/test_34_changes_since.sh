@@ -0,0 +1,192 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Server minimale che serve i file di $CHANGES_WORK_DIR/files, con le loro mtime reali.
+# Il suo /health (e /healthz) fallisce (500) finché esiste il file $CHANGES_WORK_DIR/down;
+# registra il parametro ts di /changes-since in changes.log e le richieste HEAD in head.log.
+CHANGES_PORT=8097
+CHANGES_MOUNT_POINT="/tmp/remote_fs_changes_mount"
+CHANGES_WORK_DIR=$(mktemp -d)
+CHANGES_CLIENT_LOG="/tmp/client_changes.log"
+FAILED_TESTS=0
+
+# Senza riscaldamento, così un path riletto dal server passa per forza da HEAD
+cat > "$CHANGES_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:$CHANGES_PORT"
+cache_strategy = "ttl"
+cache_ttl_seconds = 60
+cache_lru_capacity = 1000
+verify_checksums = false
+health_poll_seconds = 1
+warmup_recent_paths = 0
+TOML
+
+mkdir -p "$CHANGES_WORK_DIR/files"
+echo "primo" > "$CHANGES_WORK_DIR/files/a.txt"
+echo "secondo" > "$CHANGES_WORK_DIR/files/b.txt"
+# File vecchi: fuori dal margine di 10 secondi che il client sottrae all'istante della disconnessione
+touch -d "1 hour ago" "$CHANGES_WORK_DIR/files/a.txt" "$CHANGES_WORK_DIR/files/b.txt"
+
+cat > "$CHANGES_WORK_DIR/fake_server.py" <<'PY'
+import json, os, sys
+from urllib.parse import urlparse, parse_qs
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+WORK_DIR = os.path.dirname(os.path.abspath(__file__))
+FILES_DIR = os.path.join(WORK_DIR, "files")
+
+def entry(name):
+    st = os.stat(os.path.join(FILES_DIR, name))
+    return {"name": name, "kind": "file", "size": st.st_size, "mtime": int(st.st_mtime), "perm": "644"}
+
+def log(file, line):
+    with open(os.path.join(WORK_DIR, file), "a") as f:
+        f.write(line + "\n")
+
+class Handler(BaseHTTPRequestHandler):
+    def send(self, status, body, content_type="application/json", with_body=True, headers=None):
+        self.send_response(status)
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        for key, value in (headers or {}).items():
+            self.send_header(key, value)
+        self.end_headers()
+        if with_body:
+            self.wfile.write(body)
+
+    def do_GET(self):
+        url = urlparse(self.path)
+        path = url.path
+        names = sorted(os.listdir(FILES_DIR))
+        if path in ("/health", "/healthz"):
+            self.send(500 if os.path.exists(os.path.join(WORK_DIR, "down")) else 200, b'{"status":"ok"}')
+        elif path == "/changes-since":
+            ts = int(parse_qs(url.query)["ts"][0])
+            log("changes.log", str(ts))
+            results = [dict(entry(n), path=n) for n in names if entry(n)["mtime"] >= ts]
+            self.send(200, json.dumps({"results": results, "truncated": False}).encode())
+        elif path in ("/list", "/list/"):
+            self.send(200, json.dumps([entry(n) for n in names]).encode())
+        elif path == "/list-stream":
+            self.send(200, "".join(json.dumps(entry(n)) + "\n" for n in names).encode(), "application/x-ndjson")
+        elif path.removeprefix("/files/") in names:
+            with open(os.path.join(FILES_DIR, path.removeprefix("/files/")), "rb") as f:
+                self.send(200, f.read(), "application/octet-stream")
+        else:
+            self.send(404, b"")
+
+    def do_HEAD(self):
+        name = self.path.removeprefix("/files/")
+        log("head.log", name)
+        if name in os.listdir(FILES_DIR):
+            headers = {"X-Kind": "file", "X-Mtime": str(entry(name)["mtime"]), "X-Perm": "644"}
+            with open(os.path.join(FILES_DIR, name), "rb") as f:
+                self.send(200, f.read(), "application/octet-stream", with_body=False, headers=headers)
+        else:
+            self.send(404, b"", with_body=False)
+
+    def log_message(self, *args):
+        pass
+
+HTTPServer(("localhost", int(sys.argv[1])), Handler).serve_forever()
+PY
+
+python3 "$CHANGES_WORK_DIR/fake_server.py" "$CHANGES_PORT" &
+SERVER_PID=$!
+mkdir -p "$CHANGES_MOUNT_POINT"
+(cd "$CHANGES_WORK_DIR" && "$CLIENT_BIN" "$CHANGES_MOUNT_POINT" &> "$CHANGES_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_changes() {
+  cd /
+  umount -l "$CHANGES_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" "$SERVER_PID" 2>/dev/null || true
+  rm -rf "$CHANGES_MOUNT_POINT" "$CHANGES_WORK_DIR" "$CHANGES_CLIENT_LOG"
+}
+trap cleanup_changes EXIT
+
+timeout=20
+while ! mount | grep -q "$CHANGES_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount verso il server minimale non pronto."
+    cat "$CHANGES_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$CHANGES_MOUNT_POINT"
+
+# 1. Uso di due file: i loro attributi restano in cache per 60 secondi
+stat a.txt b.txt > /dev/null
+
+# 2. Durante l'interruzione un altro client modifica b.txt: nessuna notifica arriva
+touch "$CHANGES_WORK_DIR/down"
+sleep 3
+test_command "Rilevare l'interruzione" "grep -q 'non risponde' \"\$CHANGES_CLIENT_LOG\""
+echo "secondo, modificato" > "$CHANGES_WORK_DIR/files/b.txt"
+rm -f "$CHANGES_WORK_DIR/down" "$CHANGES_WORK_DIR/head.log"
+sleep 3
+
+# 3. Al ritorno il client chiede cosa è cambiato e invalida solo b.txt
+test_command "Chiedere le modifiche dall'interruzione" "[ \"\$(head -n1 \"\$CHANGES_WORK_DIR/changes.log\")\" -le \"\$(date +%s)\" ]"
+test_command "Invalidare solo i path modificati" "grep -q '1 path modificati' \"\$CHANGES_CLIENT_LOG\""
+test_command "Vedere la nuova dimensione di b.txt" "[ \"\$(stat -c %s b.txt)\" -eq 20 ]"
+test_command "Rileggere b.txt dal server" "grep -qx b.txt \"\$CHANGES_WORK_DIR/head.log\""
+test_command "Leggere gli attributi di a.txt" "stat a.txt > /dev/null"
+test_command_fails "Tenere in cache a.txt, non modificato" "grep -qx a.txt \"\$CHANGES_WORK_DIR/head.log\""
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
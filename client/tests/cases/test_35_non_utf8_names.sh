@@ -0,0 +1,103 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+cd "$MOUNT_POINT"
+FAILED_TESTS=0
+# Nome legale su Linux ma non UTF-8 (il byte 0xff non compare mai in UTF-8)
+BAD_NAME=$'bad\xff.txt'
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# Esegue l'operazione di Python `op` sui path dati (bytes) e stampa il nome dell'errno, o OK
+errno_of() {
+  python3 - "$@" <<'PY'
+import errno, os, sys
+op, *paths = sys.argv[1:]
+paths = [os.fsencode(p) for p in paths]
+try:
+    if op == "create":
+        os.close(os.open(paths[0], os.O_WRONLY | os.O_CREAT, 0o644))
+    elif op == "mkdir":
+        os.mkdir(paths[0])
+    elif op == "rename":
+        os.rename(paths[0], paths[1])
+    elif op == "unlink":
+        os.unlink(paths[0])
+    elif op == "stat":
+        os.stat(paths[0])
+    print("OK")
+except OSError as e:
+    print(errno.errorcode[e.errno])
+PY
+}
+
+# --- Esecuzione dei Test ---
+# Il server accetta solo path UTF-8: il client rifiuta questi nomi con EINVAL invece di andare in panic.
+
+# 1. Creazione e lookup
+test_command "Rifiutare la creazione di un file (EINVAL)" "[ \"\$(errno_of create \"\$BAD_NAME\")\" = EINVAL ]"
+test_command "Rifiutare la creazione di una directory (EINVAL)" "[ \"\$(errno_of mkdir \"\$BAD_NAME\")\" = EINVAL ]"
+test_command "Non trovare il nome con lookup (ENOENT)" "[ \"\$(errno_of stat \"\$BAD_NAME\")\" = ENOENT ]"
+
+# 2. Rinomina verso un nome non UTF-8
+test_command "Setup: Creare un file valido" "echo 'contenuto' > utf8_ok.txt"
+test_command "Rifiutare la rinomina (EINVAL)" "[ \"\$(errno_of rename utf8_ok.txt \"\$BAD_NAME\")\" = EINVAL ]"
+test_command "Verificare che il file sia intatto" "[ \"\$(cat utf8_ok.txt)\" = 'contenuto' ]"
+
+# 3. Il mount sopravvive
+test_command "Elencare la directory dopo gli errori" "ls > /dev/null"
+test_command "Verificare che il mount sia ancora attivo" "mount | grep -q \"\$MOUNT_POINT\""
+test_command "Pulizia: Rimuovere il file" "rm utf8_ok.txt"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,174 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Server minimale che registra metodo, path, User-Agent e X-Request-ID di ogni richiesta
+# in requests.log (separati da tab). Il download di broken.txt fallisce sempre con 500.
+TRACE_PORT=8098
+TRACE_MOUNT_POINT="/tmp/remote_fs_trace_mount"
+TRACE_WORK_DIR=$(mktemp -d)
+TRACE_CLIENT_LOG="/tmp/client_trace.log"
+FAILED_TESTS=0
+
+cat > "$TRACE_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:$TRACE_PORT"
+cache_strategy = "none"
+verify_checksums = false
+health_poll_seconds = 0
+user_agent = "test-suite"
+TOML
+
+cat > "$TRACE_WORK_DIR/fake_server.py" <<'PY'
+import json, os, sys
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+FILES = {"a.txt": b"primo\n", "broken.txt": b"mai scaricato\n"}
+WORK_DIR = os.path.dirname(os.path.abspath(__file__))
+
+def entry(name):
+    return {"name": name, "kind": "file", "size": len(FILES[name]), "mtime": 0, "perm": "644"}
+
+class Handler(BaseHTTPRequestHandler):
+    def send(self, status, body, content_type="application/json", with_body=True, headers=None):
+        self.send_response(status)
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        for key, value in (headers or {}).items():
+            self.send_header(key, value)
+        self.end_headers()
+        if with_body:
+            self.wfile.write(body)
+
+    def log_request_line(self):
+        line = "\t".join([self.command, self.path.split("?")[0], self.headers.get("User-Agent", "-"), self.headers.get("X-Request-ID", "-")])
+        with open(os.path.join(WORK_DIR, "requests.log"), "a") as f:
+            f.write(line + "\n")
+
+    def do_GET(self):
+        self.log_request_line()
+        path = self.path.split("?")[0]
+        name = path.removeprefix("/files/")
+        if path in ("/list", "/list/"):
+            self.send(200, json.dumps([entry(n) for n in FILES]).encode())
+        elif path == "/list-stream":
+            self.send(200, "".join(json.dumps(entry(n)) + "\n" for n in FILES).encode(), "application/x-ndjson")
+        elif name == "broken.txt":
+            self.send(500, b"errore interno")
+        elif name in FILES:
+            self.send(200, FILES[name], "application/octet-stream")
+        else:
+            self.send(404, b"")
+
+    def do_HEAD(self):
+        self.log_request_line()
+        name = self.path.removeprefix("/files/")
+        if name in FILES:
+            headers = {"X-Kind": "file", "X-Mtime": "0", "X-Perm": "644"}
+            self.send(200, FILES[name], "application/octet-stream", with_body=False, headers=headers)
+        else:
+            self.send(404, b"", with_body=False)
+
+    def log_message(self, *args):
+        pass
+
+HTTPServer(("localhost", int(sys.argv[1])), Handler).serve_forever()
+PY
+
+python3 "$TRACE_WORK_DIR/fake_server.py" "$TRACE_PORT" &
+SERVER_PID=$!
+mkdir -p "$TRACE_MOUNT_POINT"
+(cd "$TRACE_WORK_DIR" && "$CLIENT_BIN" "$TRACE_MOUNT_POINT" &> "$TRACE_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_trace() {
+  cd /
+  umount -l "$TRACE_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" "$SERVER_PID" 2>/dev/null || true
+  rm -rf "$TRACE_MOUNT_POINT" "$TRACE_WORK_DIR" "$TRACE_CLIENT_LOG"
+}
+trap cleanup_trace EXIT
+
+timeout=20
+while ! mount | grep -q "$TRACE_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount verso il server minimale non pronto."
+    cat "$TRACE_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$TRACE_MOUNT_POINT"
+REQUESTS="$TRACE_WORK_DIR/requests.log"
+
+# 1. User-Agent e X-Request-ID su ogni richiesta
+cat a.txt > /dev/null
+test_command "Inviare lo User-Agent configurato" "grep -q \$'\\ttest-suite remotefs-client/[0-9.]* (client-[0-9]*)\\t' \"\$REQUESTS\""
+test_command_fails "Non inviare richieste senza User-Agent del client" "cut -f3 \"\$REQUESTS\" | grep -v '^test-suite remotefs-client/'"
+test_command_fails "Non inviare richieste senza X-Request-ID" "cut -f4 \"\$REQUESTS\" | grep -vE '^[0-9a-f]{16}\$'"
+
+# 2. Operazioni diverse, id diversi
+: > "$REQUESTS"
+stat a.txt > /dev/null
+stat a.txt > /dev/null
+test_command "Usare un id nuovo per ogni operazione" "[ \"\$(cut -f4 \"\$REQUESTS\" | sort -u | wc -l)\" -ge 2 ]"
+
+# 3. Un errore del server: lo stesso id nei log del client e del server
+test_command_fails "Leggere un file che il server non sa servire" "cat broken.txt"
+BROKEN_ID=$(grep $'^GET\t/files/broken.txt' "$REQUESTS" | tail -n1 | cut -f4)
+test_command "Registrare l'id della richiesta fallita sul server" "[ -n \"\$BROKEN_ID\" ]"
+test_command "Riportare lo stesso id nel log del client" "grep -q \"\\[HTTP\\] Richiesta \$BROKEN_ID\" \"\$TRACE_CLIENT_LOG\""
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,189 @@
+#!/bin/bash
+set -o pipefail
+
+if [ -z "$CLIENT_BIN" ]; then
+    echo "Errore: la variabile d'ambiente CLIENT_BIN non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Server minimale che serve i file di data/ e non ha WebSocket: il client non viene
+# mai avvisato delle modifiche fatte direttamente su disco, che solo fsck può trovare.
+FSCK_PORT=8099
+FSCK_MOUNT_POINT="/tmp/remote_fs_fsck_mount"
+FSCK_WORK_DIR=$(mktemp -d)
+FSCK_DATA_DIR="$FSCK_WORK_DIR/data"
+FSCK_CLIENT_LOG="/tmp/client_fsck.log"
+FAILED_TESTS=0
+
+mkdir -p "$FSCK_DATA_DIR"
+printf 'primo\n' > "$FSCK_DATA_DIR/a.txt"
+printf 'da cancellare\n' > "$FSCK_DATA_DIR/gone.txt"
+
+cat > "$FSCK_WORK_DIR/config.toml" <<TOML
+server_url = "http://localhost:$FSCK_PORT"
+cache_ttl_seconds = 3600
+health_poll_seconds = 0
+TOML
+
+cat > "$FSCK_WORK_DIR/fake_server.py" <<'PY'
+import json, os, sys
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+DATA_DIR = os.path.join(os.path.dirname(os.path.abspath(__file__)), "data")
+
+def entry(path):
+    full = os.path.join(DATA_DIR, path)
+    if not path or not os.path.isfile(full):
+        return None
+    st = os.stat(full)
+    return {"name": os.path.basename(path), "kind": "file", "size": st.st_size, "mtime": int(st.st_mtime), "perm": "644"}
+
+class Handler(BaseHTTPRequestHandler):
+    def send(self, status, body, content_type="application/json", with_body=True, headers=None):
+        self.send_response(status)
+        self.send_header("Content-Type", content_type)
+        self.send_header("Content-Length", str(len(body)))
+        for key, value in (headers or {}).items():
+            self.send_header(key, value)
+        self.end_headers()
+        if with_body:
+            self.wfile.write(body)
+
+    def do_GET(self):
+        path = self.path.split("?")[0]
+        name = path.removeprefix("/files/")
+        entries = [entry(n) for n in sorted(os.listdir(DATA_DIR))]
+        if path in ("/list", "/list/"):
+            self.send(200, json.dumps(entries).encode())
+        elif path == "/list-stream":
+            self.send(200, "".join(json.dumps(e) + "\n" for e in entries).encode(), "application/x-ndjson")
+        elif entry(name):
+            with open(os.path.join(DATA_DIR, name), "rb") as f:
+                self.send(200, f.read(), "application/octet-stream")
+        else:
+            self.send(404, b"")
+
+    def do_HEAD(self):
+        e = entry(self.path.removeprefix("/files/"))
+        if e:
+            headers = {"X-Kind": "file", "X-Mtime": str(e["mtime"]), "X-Perm": "644"}
+            self.send(200, b"x" * e["size"], "application/octet-stream", with_body=False, headers=headers)
+        else:
+            self.send(404, b"", with_body=False)
+
+    def do_POST(self):
+        if self.path != "/stat-batch":
+            self.send(404, b"")
+            return
+        paths = json.loads(self.rfile.read(int(self.headers["Content-Length"])))
+        self.send(200, json.dumps([entry(p) for p in paths]).encode())
+
+    def log_message(self, *args):
+        pass
+
+HTTPServer(("localhost", int(sys.argv[1])), Handler).serve_forever()
+PY
+
+python3 "$FSCK_WORK_DIR/fake_server.py" "$FSCK_PORT" &
+SERVER_PID=$!
+mkdir -p "$FSCK_MOUNT_POINT"
+(cd "$FSCK_WORK_DIR" && "$CLIENT_BIN" "$FSCK_MOUNT_POINT" &> "$FSCK_CLIENT_LOG") &
+CLIENT_PID=$!
+cleanup_fsck() {
+  cd /
+  umount -l "$FSCK_MOUNT_POINT" 2>/dev/null || true
+  kill "$CLIENT_PID" "$SERVER_PID" 2>/dev/null || true
+  rm -rf "$FSCK_MOUNT_POINT" "$FSCK_WORK_DIR" "$FSCK_CLIENT_LOG"
+}
+trap cleanup_fsck EXIT
+
+timeout=20
+while ! mount | grep -q "$FSCK_MOUNT_POINT"; do
+  sleep 0.5
+  timeout=$((timeout - 1))
+  if [ "$timeout" -eq 0 ]; then
+    echo "Errore: mount verso il server minimale non pronto."
+    cat "$FSCK_CLIENT_LOG"
+    exit 1
+  fi
+done
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# --- Esecuzione dei Test ---
+cd "$FSCK_MOUNT_POINT"
+REPORT="$FSCK_WORK_DIR/report.txt"
+
+# 1. Cache appena riempite: nessuna discrepanza
+stat a.txt gone.txt > /dev/null
+test_command "Controllare le cache appena riempite" "\"\$CLIENT_BIN\" fsck \"\$FSCK_MOUNT_POINT\" > \"\$REPORT\""
+test_command "Riportare zero discrepanze" "grep -qx 'mismatches: 0' \"\$REPORT\" && grep -qx 'checked: 2' \"\$REPORT\""
+
+# 2. Modifiche sul server che il client non vede
+printf 'primo, ma più lungo\n' > "$FSCK_DATA_DIR/a.txt"
+rm "$FSCK_DATA_DIR/gone.txt"
+test_command "Vedere ancora la dimensione vecchia" "[ \"\$(stat -c %s a.txt)\" = '6' ]"
+test_command_fails "Uscire con errore se ci sono discrepanze" "\"\$CLIENT_BIN\" fsck \"\$FSCK_MOUNT_POINT\" > \"\$REPORT\""
+test_command "Riportare la dimensione diversa" "grep -q '^size a.txt (inode [0-9]*): cache 6, server [0-9]*' \"\$REPORT\""
+test_command "Riportare il file cancellato" "grep -q '^dangling gone.txt ' \"\$REPORT\""
+test_command "Non riparare senza --repair" "grep -qx 'repaired: 0' \"\$REPORT\" && [ \"\$(stat -c %s a.txt)\" = '6' ]"
+
+# 3. --repair invalida le voci non aggiornate
+test_command_fails "Riparare le discrepanze trovate" "\"\$CLIENT_BIN\" fsck --repair \"\$FSCK_MOUNT_POINT\" > \"\$REPORT\""
+test_command "Riportare le voci riparate" "! grep -qx 'repaired: 0' \"\$REPORT\""
+sleep 1 # le invalidazioni del kernel partono dopo la risposta alla write
+test_command "Vedere la dimensione nuova" "[ \"\$(stat -c %s a.txt)\" = \"\$(stat -c %s \"\$FSCK_DATA_DIR/a.txt\")\" ]"
+test_command "Leggere il contenuto nuovo" "[ \"\$(cat a.txt)\" = 'primo, ma più lungo' ]"
+test_command "Trovare le cache di nuovo allineate" "\"\$CLIENT_BIN\" fsck \"\$FSCK_MOUNT_POINT\" > \"\$REPORT\" && grep -qx 'mismatches: 0' \"\$REPORT\""
+
+# 4. Server irraggiungibile: il controllo non riesce
+kill "$SERVER_PID" 2>/dev/null
+wait "$SERVER_PID" 2>/dev/null
+test_command "Uscire con codice 2 se il server non risponde" "\"\$CLIENT_BIN\" fsck \"\$FSCK_MOUNT_POINT\" > /dev/null 2>&1; [ \$? -eq 2 ]"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -0,0 +1,116 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+if [ -z "$SERVER_DATA_DIR" ] || [ -z "$SERVER_MAX_PATH_DEPTH" ]; then
+    echo "Errore: le variabili d'ambiente SERVER_DATA_DIR e SERVER_MAX_PATH_DEPTH devono essere impostate."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+# Il server del mount principale è avviato con MAX_PATH_DEPTH=$SERVER_MAX_PATH_DEPTH.
+cd "$MOUNT_POINT"
+FAILED_TESTS=0
+
+# Path relativo di $1 livelli sotto la radice del mount (limits/d/d/...)
+deep_path() {
+  local path="limits"
+  for _ in $(seq 2 "$1"); do path="$path/d"; done
+  echo "$path"
+}
+AT_LIMIT=$(deep_path "$SERVER_MAX_PATH_DEPTH")
+OVER_LIMIT="$AT_LIMIT/d"
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+test_command_fails() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test (atteso fallimento): $description..."
+  local output; local error_output
+  output=$(eval "$command" 2> >(error_output=$(cat); cat >&2))
+  local exit_code=$?
+  if [ "$exit_code" -ne 0 ]; then
+    echo -e "\e[32m PASS (fallito come previsto)\e[0m"
+  else
+    echo -e "\e[31m FAIL (ha avuto successo)\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Il comando doveva fallire ma ha avuto successo (exit code 0)."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# Esegue l'operazione di Python `op` sui path dati e stampa il nome dell'errno, o OK
+errno_of() {
+  python3 - "$@" <<'PY'
+import errno, os, sys
+op, *paths = sys.argv[1:]
+paths = [os.fsencode(p) for p in paths]
+try:
+    if op == "create":
+        os.close(os.open(paths[0], os.O_WRONLY | os.O_CREAT, 0o644))
+    elif op == "mkdir":
+        os.mkdir(paths[0])
+    elif op == "rename":
+        os.rename(paths[0], paths[1])
+    elif op == "unlink":
+        os.unlink(paths[0])
+    elif op == "stat":
+        os.stat(paths[0])
+    print("OK")
+except OSError as e:
+    print(errno.errorcode[e.errno])
+PY
+}
+
+# --- Esecuzione dei Test ---
+
+# 1. Fino al limite si crea tutto
+test_command "Creare directory fino a $SERVER_MAX_PATH_DEPTH livelli" "mkdir -p \"\$AT_LIMIT\""
+test_command "Verificare che l'ultima esista sul server" "[ -d \"\$SERVER_DATA_DIR/\$AT_LIMIT\" ]"
+
+# 2. Oltre il limite: ENAMETOOLONG, non EIO
+test_command "Rifiutare una directory oltre il limite (ENAMETOOLONG)" "[ \"\$(errno_of mkdir \"\$OVER_LIMIT\")\" = ENAMETOOLONG ]"
+test_command "Rifiutare un file oltre il limite (ENAMETOOLONG)" "[ \"\$(errno_of create \"\$AT_LIMIT/file.txt\")\" = ENAMETOOLONG ]"
+test_command "Verificare che nulla sia stato creato sul server" "[ ! -e \"\$SERVER_DATA_DIR/\$OVER_LIMIT\" ] && [ ! -e \"\$SERVER_DATA_DIR/\$AT_LIMIT/file.txt\" ]"
+
+# 3. Una rinomina verso un path troppo profondo
+test_command "Setup: Creare un file poco profondo" "echo 'da spostare' > limits/move.txt"
+test_command "Rifiutare la rinomina oltre il limite (ENAMETOOLONG)" "[ \"\$(errno_of rename limits/move.txt \"\$AT_LIMIT/move.txt\")\" = ENAMETOOLONG ]"
+test_command "Verificare che il file sia rimasto al suo posto" "[ \"\$(cat limits/move.txt)\" = 'da spostare' ]"
+
+test_command "Pulizia: Rimuovere limits" "rm -rf limits"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
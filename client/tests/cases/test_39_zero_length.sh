@@ -0,0 +1,87 @@
+#!/bin/bash
+set -o pipefail
+
+# Assicurati che MOUNT_POINT sia impostato
+if [ -z "$MOUNT_POINT" ]; then
+    echo "Errore: la variabile d'ambiente MOUNT_POINT non è impostata."
+    exit 1
+fi
+
+if [ -z "$SERVER_DATA_DIR" ]; then
+    echo "Errore: la variabile d'ambiente SERVER_DATA_DIR non è impostata."
+    exit 1
+fi
+
+# --- Configurazione Iniziale ---
+cd "$MOUNT_POINT"
+FAILED_TESTS=0
+
+# --- Funzioni Helper Avanzate ---
+test_command() {
+  local description=$1
+  local command=$2
+  echo -n "  - Test: $description..."
+  local stderr_file=$(mktemp)
+  local output; local error_output
+  output=$(eval "$command" 2> "$stderr_file")
+  local exit_code=$?
+  error_output=$(cat "$stderr_file")
+  rm "$stderr_file"
+  if [ "$exit_code" -eq 0 ]; then
+    echo -e "\e[32m PASS\e[0m"
+  else
+    echo -e "\e[31m FAIL\e[0m"
+    echo "    ----------------------------------------------------"
+    echo "    REASON: Command failed with exit code $exit_code."
+    echo "    COMMAND: $command"
+    [ -n "$output" ] && echo "    STDOUT:" && echo "$output" | sed 's/^/    | /'
+    [ -n "$error_output" ] && echo "    STDERR:" && echo "$error_output" | sed 's/^/    | /'
+    echo "    ----------------------------------------------------"
+    FAILED_TESTS=$((FAILED_TESTS + 1))
+  fi
+}
+
+# Legge 0 byte da `file` all'offset `offset` (anche oltre la fine) e stampa quanti ne ha ricevuti
+read_zero() {
+  python3 - "$@" <<'PY'
+import os, sys
+path, offset = sys.argv[1], int(sys.argv[2])
+fd = os.open(path, os.O_RDONLY)
+print(len(os.pread(fd, 0, offset)))
+os.close(fd)
+PY
+}
+
+# Scrive 0 byte in `file` all'offset `offset` e stampa il valore restituito da pwrite
+write_zero() {
+  python3 - "$@" <<'PY'
+import os, sys
+path, offset = sys.argv[1], int(sys.argv[2])
+fd = os.open(path, os.O_WRONLY)
+print(os.pwrite(fd, b'', offset))
+os.close(fd)
+PY
+}
+
+# --- Esecuzione dei Test ---
+
+test_command "Setup: Creare un file di 10 byte" "printf '0123456789' > zero.txt"
+test_command "Setup: Impostare un mtime noto" "touch -m -d @1000000000 zero.txt"
+
+# 1. Letture di 0 byte: nessun dato, nessun errore, a qualunque offset
+test_command "Leggere 0 byte all'inizio" "[ \"\$(read_zero zero.txt 0)\" = '0' ]"
+test_command "Leggere 0 byte alla fine" "[ \"\$(read_zero zero.txt 10)\" = '0' ]"
+test_command "Leggere 0 byte oltre la fine" "[ \"\$(read_zero zero.txt 1000000)\" = '0' ]"
+
+# 2. Scritture di 0 byte: riuscite, senza modificare il file (né estenderlo all'offset)
+test_command "Scrivere 0 byte all'inizio" "[ \"\$(write_zero zero.txt 0)\" = '0' ]"
+test_command "Scrivere 0 byte oltre la fine" "[ \"\$(write_zero zero.txt 1000000)\" = '0' ]"
+sleep 1
+test_command "Verificare che la dimensione non sia cambiata" "[ \"\$(stat -c '%s' zero.txt)\" = '10' ] && [ \"\$(stat -c '%s' \"\$SERVER_DATA_DIR/zero.txt\")\" = '10' ]"
+test_command "Verificare che il contenuto non sia cambiato" "[ \"\$(cat zero.txt)\" = '0123456789' ]"
+test_command "Verificare che non ci sia stato alcun upload (mtime invariato)" "[ \"\$(stat -c '%Y' \"\$SERVER_DATA_DIR/zero.txt\")\" = '1000000000' ]"
+
+test_command "Pulizia: Rimuovere il file" "rm zero.txt"
+
+# --- Esito Finale ---
+exit $FAILED_TESTS
@@ -10,6 +10,12 @@ SERVER_PROJECT_DIR=$(readlink -f "$BASE_DIR/../../server")
 MOUNT_POINT="/tmp/remote_fs_test_mount"
 SERVER_LOG="/tmp/server.log"
 CLIENT_LOG="/tmp/client.log"
+# Dimensione di blocco non di default, verificata con `stat` dai test
+TRANSFER_BLOCK_SIZE=65536
+# Limite del server sulla dimensione dei file (sopra i 20MB del test sugli upload a blocchi)
+SERVER_MAX_FILE_SIZE=33554432
+# Profondità massima dei path creati dal server, raggiunta dal test sui limiti
+SERVER_MAX_PATH_DEPTH=32
 
 if [ -t 1 ]; then
   # Se è un terminale, definisci i codici colore
@@ -52,10 +58,10 @@ cargo build --manifest-path="$CLIENT_PROJECT_DIR/Cargo.toml" --quiet
 mkdir -p "$MOUNT_POINT"
 
 info "Avvio del server..."
-"$SERVER_PROJECT_DIR/target/debug/server" &> "$SERVER_LOG" &
+MAX_FILE_SIZE_BYTES="$SERVER_MAX_FILE_SIZE" MAX_PATH_DEPTH="$SERVER_MAX_PATH_DEPTH" "$SERVER_PROJECT_DIR/target/debug/server" &> "$SERVER_LOG" &
 
 info "Avvio del client FUSE..."
-"$CLIENT_PROJECT_DIR/target/debug/client" "$MOUNT_POINT" &> "$CLIENT_LOG" &
+"$CLIENT_PROJECT_DIR/target/debug/client" "$MOUNT_POINT" --transfer-block-size "$TRANSFER_BLOCK_SIZE" &> "$CLIENT_LOG" &
 
 info "Attesa che il mount point sia pronto..."
 timeout=10
@@ -75,6 +81,13 @@ echo "-------------------------------------------"
 FAILED_COUNT=0
 # Esporta la variabile MOUNT_POINT per renderla disponibile agli script di test
 export MOUNT_POINT
+export TRANSFER_BLOCK_SIZE
+export SERVER_MAX_FILE_SIZE
+export SERVER_MAX_PATH_DEPTH
+# Directory dati del server, usata per simulare modifiche fatte da un altro client
+export SERVER_DATA_DIR="$SERVER_PROJECT_DIR/data"
+# Binario del client, per i test che montano una seconda istanza con opzioni diverse
+export CLIENT_BIN="$CLIENT_PROJECT_DIR/target/debug/client"
 
 for test_file in "$BASE_DIR"/cases/test_*.sh; do
   info "Esecuzione di: $(basename "$test_file")"
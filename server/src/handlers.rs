@@ -1,29 +1,187 @@
 use axum::{
-    extract::{Path, State},
+    extract::{MatchedPath, Path, Query, Request, State},
     body::Body,
     http::{StatusCode, HeaderMap, header},
+    middleware::Next,
     response::{IntoResponse, Response},
     Json,
 };
+use metrics_exporter_prometheus::PrometheusHandle;
 use tokio::io::AsyncSeekExt;
 use tokio::io::AsyncReadExt;
 use std::io::SeekFrom;
-use std::time::{UNIX_EPOCH, Instant};
-use std::os::unix::fs::PermissionsExt;
+use std::time::{Duration, UNIX_EPOCH, Instant, SystemTime};
 use std::fs;
 use serde::{Deserialize, Serialize};
-use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use tokio_util::io::ReaderStream;
 use http_body_util::BodyExt;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::collections::HashMap;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, Notify};
+use std::net::SocketAddr;
+use sha2::{Digest, Sha256};
+use crate::journal::ChangeJournal;
+use crate::storage::{apply_mode, is_relative_path, EntryKind, EntryMetadata, EntryTimes, FileReader, StorageBackend, FALLOC_FL_KEEP_SIZE, FALLOC_FL_PUNCH_HOLE};
 
 #[derive(Clone)]
 pub struct AppState {
     pub tx: Arc<broadcast::Sender<String>>,
     pub recent_mods: Arc<Mutex<HashMap<String, (String, Instant)>>>,
+    /// Maximum size in bytes of an uploaded file (`0` = unlimited).
+    pub max_file_size: u64,
+    /// Renders the Prometheus metrics collected by the global recorder.
+    pub metrics: PrometheusHandle,
+    /// SHA-256 of the files hashed by `/checksum`, keyed by path (see `CachedChecksum`).
+    pub checksums: Arc<Mutex<HashMap<String, CachedChecksum>>>,
+    /// Permissions given to every file the server creates (`FILE_MODE`), instead of
+    /// those left by the process umask. `None` = the umask decides.
+    pub file_mode: Option<u32>,
+    /// Permissions given to every directory the server creates (`DIR_MODE`), like `file_mode`.
+    pub dir_mode: Option<u32>,
+    /// Clients connected to `/ws`, keyed by their `X-Client-ID` (see `WsClient`).
+    pub ws_clients: Arc<Mutex<HashMap<String, WsClient>>>,
+    /// Bearer token required by the `/admin` endpoints (`ADMIN_TOKEN`). `None` = disabled.
+    pub admin_token: Option<String>,
+    /// Where the files are stored (see `storage`): every handler reads and writes through it.
+    pub storage: Arc<dyn StorageBackend>,
+    /// Advisory locks taken with `POST /lock/<path>`, keyed by path (see `FileLock`).
+    /// Always locked after `ws_clients` when both are needed.
+    pub locks: Arc<Mutex<HashMap<String, FileLock>>>,
+    /// Free space in bytes below which `/healthz` reports the storage as unhealthy
+    /// (`HEALTHZ_MIN_FREE_BYTES`, `0` = not checked).
+    pub min_free_bytes: u64,
+    /// The changes seen by the watcher, recorded by `main.rs` and read by `/changes-since`.
+    pub journal: Arc<Mutex<ChangeJournal>>,
+    /// Maximum number of components of a path the server creates (`MAX_PATH_DEPTH`, `0` = unlimited).
+    pub max_path_depth: usize,
+    /// Maximum length in bytes of a name the server creates (`MAX_NAME_LENGTH`, `0` = unlimited).
+    pub max_name_length: usize,
+    /// Directory where `/healthz` writes its probe files (`UPLOAD_DIR` by default):
+    /// private to the server, outside the tree it serves.
+    pub healthz_dir: String,
+}
+
+/// A client connected to `/ws`, as listed by `GET /admin/clients`.
+pub struct WsClient {
+    /// Number of the connection, so that when a client reconnects under the same id
+    /// the end of its old connection does not remove the new one.
+    pub connection: u64,
+    pub addr: SocketAddr,
+    pub connected_at: SystemTime,
+    /// Woken by `POST /admin/clients/<id>/disconnect` to close the connection.
+    pub disconnect: Arc<Notify>,
+}
+
+/// An advisory lock on a path, held by a client connected to `/ws`.
+pub struct FileLock {
+    /// The `X-Client-ID` of the holder.
+    pub owner: String,
+    /// The holder's `/ws` connection (`WsClient::connection`): the lock is
+    /// released when it closes (see `release_client_locks`).
+    pub connection: u64,
+}
+
+/// A SHA-256 computed by `/checksum`, valid while the file keeps the same mtime and size.
+pub struct CachedChecksum {
+    mtime: SystemTime,
+    size: u64,
+    sha256: String,
+}
+
+impl AppState {
+    /// The state of a server over `storage`, configured like one started without
+    /// environment variables (see `main.rs`): the `DEFAULT_*` limits, no
+    /// `FILE_MODE`/`DIR_MODE`, no `ADMIN_TOKEN`. Fields can be overridden with
+    /// struct update syntax, e.g. `AppState { max_file_size: 0, ..AppState::new(storage) }`.
+    pub fn new(storage: Arc<dyn StorageBackend>) -> Self {
+        let (tx, _) = broadcast::channel(100);
+        AppState {
+            tx: Arc::new(tx),
+            recent_mods: Arc::new(Mutex::new(HashMap::new())),
+            max_file_size: crate::DEFAULT_MAX_FILE_SIZE_BYTES,
+            metrics: crate::metrics_handle(),
+            checksums: Arc::new(Mutex::new(HashMap::new())),
+            file_mode: None,
+            dir_mode: None,
+            ws_clients: Arc::new(Mutex::new(HashMap::new())),
+            admin_token: None,
+            storage,
+            locks: Arc::new(Mutex::new(HashMap::new())),
+            min_free_bytes: crate::DEFAULT_HEALTHZ_MIN_FREE_BYTES,
+            journal: Arc::new(Mutex::new(ChangeJournal::new(crate::DEFAULT_CHANGE_JOURNAL_CAPACITY, SystemTime::now()))),
+            max_path_depth: crate::DEFAULT_MAX_PATH_DEPTH,
+            max_name_length: crate::DEFAULT_MAX_NAME_LENGTH,
+            healthz_dir: UPLOAD_DIR.to_string(),
+        }
+    }
+
+    /// Whether a file of `size` bytes is over the configured `max_file_size`.
+    fn exceeds_max_file_size(&self, size: u64) -> bool {
+        self.max_file_size != 0 && size > self.max_file_size
+    }
+
+    /// Checks a path about to be created against `max_path_depth` and `max_name_length`.
+    ///
+    /// Called by every handler that creates an entry (upload, `mkdir`, `touch`,
+    /// renames, chunked uploads), before touching the storage: a client stuck in a
+    /// loop (e.g. a recursive move into itself) cannot nest directories without end.
+    /// Existing paths are never checked, so lowering the limits keeps them readable.
+    ///
+    /// # Returns
+    /// `Err(StatusCode::BAD_REQUEST)` if the path is too deep or one of its names too
+    /// long (the client reports `ENAMETOOLONG`).
+    fn check_path_limits(&self, path: &str) -> Result<(), StatusCode> {
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        if self.max_path_depth != 0 && components.len() > self.max_path_depth {
+            println!("[LIMITS] Rifiutato '{}': {} livelli, massimo {}", path, components.len(), self.max_path_depth);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        if self.max_name_length != 0
+            && let Some(name) = components.iter().find(|c| c.len() > self.max_name_length)
+        {
+            println!("[LIMITS] Rifiutato '{}': il nome '{}' supera {} byte", path, name, self.max_name_length);
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Ok(())
+    }
+}
+
+/// Checks a path sent in a JSON body or a query string, before any storage call.
+///
+/// The paths in URLs are routed by axum, but these reach the storage as sent: an
+/// absolute path or a `..` component would name a file outside `DATA_DIR`.
+///
+/// # Returns
+/// `Err(StatusCode::BAD_REQUEST)` if the path is absolute or has a `..` component.
+fn check_relative_path(path: &str) -> Result<(), StatusCode> {
+    if !is_relative_path(path) {
+        println!("[PATHS] Rifiutato '{}': fuori dalla directory dei dati", path);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(())
+}
+
+/// Joins a relative directory path and an entry name, without a leading `/`
+/// when the directory is the root of `DATA_DIR` (the empty path).
+fn join_path(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+/// Whether a relative path designates `DATA_DIR` itself (e.g. `""` or `"/"`),
+/// which destructive handlers must never touch.
+fn is_root_path(path: &str) -> bool {
+    path.trim_matches('/').is_empty()
+}
+
+/// The body length declared by the `Content-Length` header, if any.
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers.get(header::CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
 }
 
 #[derive(Serialize,Deserialize)]
@@ -31,16 +189,200 @@ pub struct RemoteEntry {
     name: String,
     kind: String,
     size: u64,
+    /// Blocchi da 512 byte realmente allocati su disco (`st_blocks`), minori di
+    /// `size / 512` per i file sparsi.
+    blocks: u64,
     mtime: i64,
+    /// Creazione del file (secondi Unix), assente se il filesystem del server non la registra.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    btime: Option<i64>,
     perm: String,
+    uid: u32,
+    gid: u32,
+    ino: u64,
+    /// Per i link simbolici (`kind == "symlink"`): il percorso a cui puntano, come scritto nel link.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    /// Per i link simbolici: `true` se il target non si risolve (non esiste, o i link formano un ciclo).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    dangling: bool,
 }
 
+/// Body of `PATCH /files/<path>`. Every field is optional; the ones present are applied together.
 #[derive(Deserialize)]
-pub struct UpdatePermissions {
-    perm: String,
+pub struct UpdateMetadata {
+    /// New octal permissions (`"755"`). `mode` is accepted as well.
+    #[serde(alias = "mode")]
+    perm: Option<String>,
+    owner: Option<Owner>,
+    /// New name of the entry, in the same directory.
+    rename_to: Option<String>,
+}
+
+/// New owner and/or group of an entry, in `UpdateMetadata`.
+#[derive(Deserialize)]
+pub struct Owner {
+    uid: Option<u32>,
+    gid: Option<u32>,
+}
+
+/// Query of `GET /list`: the optional page of entries to return.
+#[derive(Deserialize)]
+pub struct ListPage {
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+/// Query of `GET /files`: `metadata=true` adds the file's metadata headers to the content.
+#[derive(Deserialize)]
+pub struct FileQuery {
+    #[serde(default)]
+    metadata: bool,
+}
+
+/// Query of `POST /mkdir`: `parents=false` creates only the last component.
+#[derive(Deserialize)]
+pub struct MkdirQuery {
+    #[serde(default = "default_parents")]
+    parents: bool,
+}
+
+fn default_parents() -> bool {
+    true
+}
+
+/// Query of `POST /truncate`: the new size of the file in bytes.
+#[derive(Deserialize)]
+pub struct TruncateQuery {
+    size: u64,
+}
+
+/// Query of `POST /write`: where the body is written in the file.
+#[derive(Deserialize)]
+pub struct WriteQuery {
+    offset: u64,
+}
+
+/// Query of `POST /fallocate`: the byte range and the `fallocate(2)` mode flags.
+#[derive(Deserialize)]
+pub struct FallocateQuery {
+    offset: u64,
+    len: u64,
+    #[serde(default)]
+    mode: i32,
+}
+
+/// JSON body for `POST /utimes`: new timestamps as Unix seconds. Missing fields are left unchanged.
+#[derive(Deserialize)]
+pub struct UpdateTimes {
+    atime: Option<i64>,
+    mtime: Option<i64>,
+}
+
+/// JSON body for operations that involve two paths (`/rename`, `/exchange`).
+#[derive(Deserialize)]
+pub struct PathPair {
+    from: String,
+    to: String,
+}
+
+/// JSON body for `POST /upload/start`: the destination of the upload session.
+#[derive(Deserialize)]
+pub struct StartUpload {
+    path: String,
+}
+
+/// Query string of `PUT /upload/<id>`: where the chunk starts in the file.
+#[derive(Deserialize)]
+pub struct ChunkOffset {
+    offset: u64,
+}
+
+/// JSON response of `GET /checksum`: the SHA-256 of a file and the metadata it was computed for.
+#[derive(Serialize, Deserialize)]
+pub struct FileChecksum {
+    /// Hex-encoded SHA-256 of the content (the same value as `X-Content-SHA256`).
+    sha256: String,
+    size: u64,
+    /// Modification time as Unix seconds.
+    mtime: i64,
+}
+
+/// JSON response of the upload endpoints: the session id and the number of bytes committed so far.
+#[derive(Serialize, Deserialize)]
+pub struct UploadStatus {
+    id: String,
+    offset: u64,
+}
+
+/// A single descendant returned by `GET /tree`, with its path relative to `DATA_DIR`.
+#[derive(Serialize)]
+pub struct TreeEntry {
+    path: String,
+    kind: String,
+}
+
+/// The outcome of one path of `POST /delete-batch`. `error` is `None` on success,
+/// otherwise one of `"not_found"`, `"not_empty"`, `"permission_denied"` or `"io"`.
+#[derive(Serialize)]
+pub struct DeleteResult {
+    path: String,
+    error: Option<String>,
+}
+
+/// Query of `GET /search`.
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    /// Substring of the name, or a glob pattern if `glob` is true.
+    q: String,
+    /// Directory to search in, relative to `DATA_DIR` (the root if missing).
+    #[serde(default)]
+    path: String,
+    #[serde(default)]
+    glob: bool,
+    /// Maximum number of results, capped at `MAX_SEARCH_RESULTS`.
+    limit: Option<usize>,
+}
+
+/// A match of `GET /search`: the entry's metadata plus its path relative to `DATA_DIR`.
+#[derive(Serialize)]
+pub struct SearchResult {
+    path: String,
+    #[serde(flatten)]
+    entry: RemoteEntry,
+}
+
+/// Response of `GET /search`. `truncated` is true if the result or scan limit was hit.
+#[derive(Serialize)]
+pub struct SearchResponse {
+    results: Vec<SearchResult>,
+    truncated: bool,
+}
+
+/// Query of `GET /changes-since`.
+#[derive(Deserialize)]
+pub struct ChangesQuery {
+    /// Unix seconds: entries modified at or after this time are returned.
+    ts: i64,
+    /// Directory to look in, relative to `DATA_DIR` (the root if missing).
+    #[serde(default)]
+    path: String,
+    /// Maximum number of results, capped at `MAX_SEARCH_RESULTS`.
+    limit: Option<usize>,
 }
 
+/// Upper bound on the results of a single `GET /search`.
+const MAX_SEARCH_RESULTS: usize = 1000;
+/// Upper bound on the entries a single `GET /search` looks at, so a search over a
+/// huge tree cannot run away.
+const MAX_SEARCH_SCANNED: usize = 100_000;
+/// Upper bound on the paths of a single `POST /stat-batch`.
+const MAX_STAT_BATCH: usize = 1000;
+
 pub const DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data");
+/// Staging area for chunked uploads. It lives outside `DATA_DIR` so that partial
+/// files are neither listed nor reported by the watcher.
+pub const UPLOAD_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/uploads");
 
 // --- DEBUGGING HELPER ---
 fn record_change(state: &AppState, path: &str, headers: &HeaderMap) {
@@ -60,6 +402,286 @@ fn record_change(state: &AppState, path: &str, headers: &HeaderMap) {
     }
 }
 
+// --- AMMINISTRAZIONE ---
+
+/// A connected `/ws` client, as returned by `GET /admin/clients`.
+#[derive(Serialize)]
+pub struct ClientInfo {
+    /// The `X-Client-ID` sent in the handshake (`anonimo-<n>` if missing).
+    pub id: String,
+    /// Remote address of the connection.
+    pub addr: String,
+    /// When the WebSocket was opened (Unix seconds).
+    pub connected_at: i64,
+}
+
+/// Checks the `Authorization: Bearer <token>` header against `AppState::admin_token`.
+///
+/// # Returns
+/// * `Err(StatusCode::FORBIDDEN)` if no token is configured: the admin endpoints are disabled.
+/// * `Err(StatusCode::UNAUTHORIZED)` if the header is missing or carries another token.
+fn check_admin_token(state: &AppState, headers: &HeaderMap) -> Result<(), StatusCode> {
+    let Some(expected) = state.admin_token.as_deref() else {
+        return Err(StatusCode::FORBIDDEN);
+    };
+    let token = headers.get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if token == Some(expected) { Ok(()) } else { Err(StatusCode::UNAUTHORIZED) }
+}
+
+/// Handles `GET /admin/clients`.
+///
+/// Lists the clients connected to `/ws`, oldest connection first. Requires the
+/// admin token (see `check_admin_token`).
+pub async fn list_clients(State(state): State<AppState>, headers: HeaderMap) -> Result<Json<Vec<ClientInfo>>, StatusCode> {
+    check_admin_token(&state, &headers)?;
+    let clients = state.ws_clients.lock().unwrap();
+    let mut list: Vec<(u64, ClientInfo)> = clients.iter().map(|(id, client)| {
+        let connected_at = client.connected_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+        (client.connection, ClientInfo { id: id.clone(), addr: client.addr.to_string(), connected_at })
+    }).collect();
+    list.sort_by_key(|(connection, _)| *connection);
+    Ok(Json(list.into_iter().map(|(_, info)| info).collect()))
+}
+
+/// Handles `POST /admin/clients/<id>/disconnect`.
+///
+/// Closes the WebSocket of client `id` (e.g. a stuck client flooding changes).
+/// The client is free to reconnect. Requires the admin token (see `check_admin_token`).
+///
+/// # Returns
+/// * `StatusCode::OK` if the client was connected and is being disconnected.
+/// * `StatusCode::NOT_FOUND` if no client with that id is connected.
+pub async fn disconnect_client(State(state): State<AppState>, Path(id): Path<String>, headers: HeaderMap) -> StatusCode {
+    if let Err(status) = check_admin_token(&state, &headers) {
+        return status;
+    }
+    match state.ws_clients.lock().unwrap().get(&id) {
+        Some(client) => {
+            println!("[ADMIN] Disconnessione forzata del client '{}' ({})", id, client.addr);
+            client.disconnect.notify_one();
+            StatusCode::OK
+        }
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+// --- LOCK ADVISORY ---
+
+/// The `X-Client-ID` header of a request, if any.
+fn client_id(headers: &HeaderMap) -> Option<&str> {
+    headers.get("X-Client-ID").and_then(|v| v.to_str().ok()).filter(|id| !id.is_empty())
+}
+
+/// Handles `POST /lock/<path>`.
+///
+/// Takes the advisory lock on `path` for the client named by `X-Client-ID`. The
+/// lock belongs to the client's current `/ws` connection: a client must be
+/// connected to take one, and every lock it holds is released when that
+/// connection closes (see `release_client_locks`), so a crashed client cannot
+/// leave a file locked forever. Locks are advisory: no other endpoint checks them.
+///
+/// # Returns
+/// * `StatusCode::OK` if the lock was taken, or was already held by the same client.
+/// * `StatusCode::BAD_REQUEST` without `X-Client-ID`.
+/// * `StatusCode::PRECONDITION_REQUIRED` if the client is not connected to `/ws`.
+/// * `StatusCode::CONFLICT` if another client holds the lock.
+pub async fn acquire_lock(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap) -> StatusCode {
+    let Some(owner) = client_id(&headers) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let path = path.trim_matches('/').to_string();
+    // Il lock sui client resta preso fino all'inserimento: una connessione che si
+    // chiude nel frattempo non può lasciare un lock orfano
+    let clients = state.ws_clients.lock().unwrap();
+    let Some(client) = clients.get(owner) else {
+        return StatusCode::PRECONDITION_REQUIRED;
+    };
+    let mut locks = state.locks.lock().unwrap();
+    match locks.get(&path) {
+        Some(lock) if lock.owner != owner => StatusCode::CONFLICT,
+        _ => {
+            println!("[LOCK] '{}' preso dal client '{}'", path, owner);
+            locks.insert(path, FileLock { owner: owner.to_string(), connection: client.connection });
+            StatusCode::OK
+        }
+    }
+}
+
+/// Handles `DELETE /lock/<path>`.
+///
+/// Releases the advisory lock on `path` held by the client named by `X-Client-ID`.
+///
+/// # Returns
+/// * `StatusCode::OK` if the lock was released.
+/// * `StatusCode::BAD_REQUEST` without `X-Client-ID`.
+/// * `StatusCode::NOT_FOUND` if `path` is not locked.
+/// * `StatusCode::CONFLICT` if another client holds the lock.
+pub async fn release_lock(State(state): State<AppState>, Path(path): Path<String>, headers: HeaderMap) -> StatusCode {
+    let Some(owner) = client_id(&headers) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let path = path.trim_matches('/');
+    let mut locks = state.locks.lock().unwrap();
+    match locks.get(path) {
+        None => StatusCode::NOT_FOUND,
+        Some(lock) if lock.owner != owner => StatusCode::CONFLICT,
+        Some(_) => {
+            locks.remove(path);
+            println!("[LOCK] '{}' rilasciato dal client '{}'", path, owner);
+            StatusCode::OK
+        }
+    }
+}
+
+/// Releases every lock taken by `client_id` through its `/ws` connection number
+/// `connection`, when that connection closes.
+///
+/// Locks taken through a newer connection of the same client (it reconnected
+/// before the old one was torn down) are kept.
+///
+/// # Returns
+/// The number of locks released.
+pub fn release_client_locks(state: &AppState, client_id: &str, connection: u64) -> usize {
+    let mut locks = state.locks.lock().unwrap();
+    let before = locks.len();
+    locks.retain(|_, lock| lock.owner != client_id || lock.connection != connection);
+    before - locks.len()
+}
+
+// --- METRICHE ---
+
+/// Middleware that counts every request in `http_requests_total`, labelled with
+/// the method, the matched route (e.g. `/files/*path`, so paths do not explode
+/// the label set) and the class of the response status (`2xx`, `4xx`, ...).
+pub async fn track_requests(matched: Option<MatchedPath>, request: Request, next: Next) -> Response {
+    let route = matched.map(|m| m.as_str().to_string()).unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+    let response = next.run(request).await;
+    let status = format!("{}xx", response.status().as_u16() / 100);
+    metrics::counter!("http_requests_total", "method" => method, "route" => route, "status" => status).increment(1);
+    response
+}
+
+/// Handles `GET /metrics`.
+///
+/// Renders all the metrics in the Prometheus text format. Besides the counters
+/// updated by the handlers, the gauges sampled at scrape time are refreshed first:
+/// * `websocket_connections_active`: subscribers of the broadcast channel (one per `/ws` client).
+/// * `process_cpu_seconds_total`: user + system CPU time of the server process.
+/// * `data_dir_total_bytes` / `data_dir_available_bytes`: size and free space of the
+///   storage (the filesystem holding `DATA_DIR`), when the backend reports them.
+pub async fn render_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    metrics::gauge!("websocket_connections_active").set(state.tx.receiver_count() as f64);
+    if let Some(cpu_seconds) = process_cpu_seconds() {
+        metrics::gauge!("process_cpu_seconds_total").set(cpu_seconds);
+    }
+    if let Some((total, available)) = state.storage.space() {
+        metrics::gauge!("data_dir_total_bytes").set(total as f64);
+        metrics::gauge!("data_dir_available_bytes").set(available as f64);
+    }
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], state.metrics.render())
+}
+
+/// Prefix of the probe files written by `/healthz` in `AppState::healthz_dir`.
+pub const HEALTHZ_PROBE_PREFIX: &str = ".healthz-probe-";
+
+/// The body of a `/healthz` response.
+#[derive(Serialize)]
+pub struct HealthReport {
+    /// `ok` or `unhealthy`.
+    pub status: &'static str,
+    /// The failed check: `write` (the probe file) or `space` (the free space).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check: Option<&'static str>,
+    /// Why the check failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Handles `GET /healthz`, the deep health check.
+///
+/// `/health` only tells that the process answers, and stays cheap for load
+/// balancers. This one also verifies that the storage can take new data:
+/// 1. a probe file (`HEALTHZ_PROBE_PREFIX`) is written in `healthz_dir`, read
+///    back and deleted;
+/// 2. the free space, when the backend reports it, is at least `min_free_bytes`.
+///
+/// The probe stays out of the served tree: a check changes neither the listings
+/// nor the mtime of the root (and so the `304` of `/list`), and notifies no client.
+///
+/// # Returns
+/// * `200 OK` with `{"status": "ok"}`.
+/// * `503 Service Unavailable` with the failed `check` and its `reason`, e.g. a
+///   read-only or full data directory.
+pub async fn healthz(State(state): State<AppState>) -> (StatusCode, Json<HealthReport>) {
+    static NEXT_PROBE: AtomicU64 = AtomicU64::new(0);
+    let unhealthy = |check, reason| (StatusCode::SERVICE_UNAVAILABLE, Json(HealthReport { status: "unhealthy", check: Some(check), reason: Some(reason) }));
+
+    // Un nome diverso per ogni controllo: due richieste concorrenti non si cancellano la sonda a vicenda
+    let probe = format!("{}/{}{}", state.healthz_dir, HEALTHZ_PROBE_PREFIX, NEXT_PROBE.fetch_add(1, Ordering::Relaxed));
+    if let Err(e) = write_probe(&state.healthz_dir, &probe).await {
+        let _ = tokio::fs::remove_file(&probe).await;
+        eprintln!("[HEALTHZ] Scrittura in {} fallita: {}", state.healthz_dir, e);
+        return unhealthy("write", format!("scrittura in {} fallita: {}", state.healthz_dir, e));
+    }
+    if state.min_free_bytes > 0
+        && let Some((_, available)) = state.storage.space()
+        && available < state.min_free_bytes
+    {
+        eprintln!("[HEALTHZ] {} byte liberi, sotto il minimo di {}", available, state.min_free_bytes);
+        return unhealthy("space", format!("{} byte liberi, sotto il minimo di {}", available, state.min_free_bytes));
+    }
+    (StatusCode::OK, Json(HealthReport { status: "ok", check: None, reason: None }))
+}
+
+/// Writes a few bytes to the file `probe` in `dir` (created if missing), reads
+/// them back and deletes it.
+async fn write_probe(dir: &str, probe: &str) -> std::io::Result<()> {
+    const CONTENT: &[u8] = b"healthz";
+    tokio::fs::create_dir_all(dir).await?;
+    let mut file = tokio::fs::File::create(probe).await?;
+    file.write_all(CONTENT).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    let content = tokio::fs::read(probe).await?;
+    tokio::fs::remove_file(probe).await?;
+    if content != CONTENT {
+        return Err(std::io::Error::other("il contenuto riletto non corrisponde a quello scritto"));
+    }
+    Ok(())
+}
+
+/// User + system CPU time consumed so far by this process, in seconds.
+fn process_cpu_seconds() -> Option<f64> {
+    // SAFETY: `rusage` is plain data, and `getrusage` only writes into it.
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+    let seconds = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1_000_000.0;
+    Some(seconds(usage.ru_utime) + seconds(usage.ru_stime))
+}
+
+/// Computes the hex-encoded SHA-256 of `file`, then rewinds it to the start.
+///
+/// The file is read in chunks, so memory use stays constant for large files.
+async fn sha256_hex(file: &mut FileReader) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    file.seek(SeekFrom::Start(0)).await?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
 /// Handles `GET /files/<path>`.
 ///
 /// Reads a file from the server's data directory and streams its content
@@ -76,15 +698,27 @@ fn record_change(state: &AppState, path: &str, headers: &HeaderMap) {
 /// Handles `GET /files/<path>`.
 ///
 /// Supports HTTP Range Requests (RFC 7233) for chunked reading.
+/// Full (non-range) responses carry an `X-Content-SHA256` header with the
+/// hex-encoded SHA-256 of the content, so the client can detect corruption.
+///
+/// With `?metadata=true`, both full and range responses also carry the metadata
+/// headers of `HEAD /files` (see `with_metadata_headers`), so one request returns
+/// the content and the attributes. `X-Size` is the size of the whole file, also
+/// when `Content-Length` is the size of a range.
 pub async fn get_file(
+    State(state): State<AppState>,
     Path(path): Path<String>,
+    Query(query): Query<FileQuery>,
     headers: HeaderMap
 ) -> Result<impl IntoResponse, StatusCode> {
-    let file_path = format!("{}/{}", DATA_DIR, path);
-
-    let mut file = File::open(&file_path).await.map_err(|_| StatusCode::NOT_FOUND)?;
-    let metadata = file.metadata().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let file_size = metadata.len();
+    let mut file = state.storage.read(&path).map_err(|_| StatusCode::NOT_FOUND)?;
+    let metadata = state.storage.stat(&path).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let file_size = metadata.size;
+    let mut builder = Response::builder();
+    if query.metadata {
+        let name = path.rsplit('/').next().unwrap_or_default().to_string();
+        builder = with_metadata_headers(builder, &remote_entry(name, &metadata));
+    }
 
     // Check for Range header
     if let Some(range_header) = headers.get(header::RANGE).and_then(|h| h.to_str().ok()) {
@@ -96,7 +730,10 @@ pub async fn get_file(
                 let end_parse = parts[1].parse::<u64>();
 
                 if let (Ok(start), Ok(end)) = (start_parse, end_parse) {
-                    if start < file_size && end < file_size && start <= end {
+                    // A range ending past EOF is clamped to the last byte (RFC 7233, 2.1),
+                    // so block-aligned reads near the end of a file stay partial.
+                    let end = end.min(file_size.saturating_sub(1));
+                    if start < file_size && start <= end {
                         // 1. Seek to start
                         file.seek(SeekFrom::Start(start)).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -105,11 +742,12 @@ pub async fn get_file(
 
                         // 3. Limit the stream
                         let limited_file = file.take(content_length);
+                        metrics::counter!("file_bytes_read_total").increment(content_length);
                         let stream = ReaderStream::new(limited_file);
                         let body = Body::from_stream(stream);
 
                         // 4. Return 206 Partial Content
-                        return Ok(Response::builder()
+                        return Ok(builder
                             .status(StatusCode::PARTIAL_CONTENT)
                             .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, file_size))
                             .header(header::CONTENT_LENGTH, content_length.to_string())
@@ -123,14 +761,109 @@ pub async fn get_file(
     }
 
     // Fallback: Full file (200 OK) if no Range header or invalid range
+    let checksum = sha256_hex(&mut file).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     let stream = ReaderStream::new(file);
-    Ok(Response::builder()
+    metrics::counter!("file_bytes_read_total").increment(file_size);
+    Ok(builder
         .status(StatusCode::OK)
         .header(header::CONTENT_LENGTH, file_size.to_string())
         .header(header::ACCEPT_RANGES, "bytes")
+        .header("X-Content-SHA256", checksum)
         .body(Body::from_stream(stream))
         .unwrap())
 }
+/// Handles `HEAD /files/<path>`.
+///
+/// Answers metadata-only requests (e.g. a client's `getattr`) without opening
+/// the file, so neither the content nor its checksum is computed. Works for
+/// directories too. The metadata is sent as headers:
+/// * `Content-Length` and `Last-Modified`, as for a `GET`.
+/// * The headers of `with_metadata_headers`, with the same values as `/stat`.
+///
+/// # Returns
+/// * `200 OK` with the headers and an empty body.
+/// * `Err(StatusCode::NOT_FOUND)` if the path does not exist.
+pub async fn head_file(State(state): State<AppState>, Path(path): Path<String>) -> Result<Response, StatusCode> {
+    let metadata = state.storage.stat(&path).map_err(|_| StatusCode::NOT_FOUND)?;
+    let name = path.rsplit('/').next().unwrap_or_default().to_string();
+    let entry = remote_entry(name, &metadata);
+
+    Ok(with_metadata_headers(Response::builder(), &entry)
+        .status(StatusCode::OK)
+        .header(header::CONTENT_LENGTH, entry.size.to_string())
+        .header(header::LAST_MODIFIED, httpdate::fmt_http_date(metadata.modified))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .body(Body::empty())
+        .unwrap())
+}
+
+/// Adds the metadata of `entry` to a response, as sent by `HEAD /files` and
+/// `GET /files?metadata=true`: `X-Kind` (`file` or `directory`), `X-Size` (bytes),
+/// `X-Perm` (octal), `X-Mtime` (Unix seconds), `X-Blocks`, `X-Uid`, `X-Gid` and `X-Ino`,
+/// plus `X-Btime` (Unix seconds) if the creation time is known.
+fn with_metadata_headers(builder: axum::http::response::Builder, entry: &RemoteEntry) -> axum::http::response::Builder {
+    let builder = match entry.btime {
+        Some(btime) => builder.header("X-Btime", btime.to_string()),
+        None => builder,
+    };
+    builder
+        .header("X-Kind", entry.kind.as_str())
+        .header("X-Size", entry.size.to_string())
+        .header("X-Perm", entry.perm.as_str())
+        .header("X-Mtime", entry.mtime.to_string())
+        .header("X-Blocks", entry.blocks.to_string())
+        .header("X-Uid", entry.uid.to_string())
+        .header("X-Gid", entry.gid.to_string())
+        .header("X-Ino", entry.ino.to_string())
+}
+
+/// Handles `GET /checksum/<path>`.
+///
+/// Returns the SHA-256 of a file without transferring it, so a client can tell
+/// whether its cached copy is still current. Unlike the mtime alone, this also
+/// catches changes made within the same second.
+///
+/// The hash is cached in `AppState::checksums` and reused as long as the file has
+/// the same mtime (with full precision) and size, so repeated checks of an
+/// unchanged large file do not read it again. A hash is only cached if the file
+/// did not change while it was being read.
+///
+/// # Returns
+/// * `Ok(Json(FileChecksum))` with the hash, size and mtime.
+/// * `Err(StatusCode::NOT_FOUND)` if the file does not exist.
+/// * `Err(StatusCode::BAD_REQUEST)` if the path is a directory.
+pub async fn checksum(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+) -> Result<Json<FileChecksum>, StatusCode> {
+    let mut file = state.storage.read(&path).map_err(|_| StatusCode::NOT_FOUND)?;
+    let metadata = state.storage.stat(&path).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if metadata.is_dir() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let mtime = metadata.modified;
+    let size = metadata.size;
+    let response = |sha256: String| Json(FileChecksum {
+        sha256,
+        size,
+        mtime: mtime.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64,
+    });
+
+    if let Some(cached) = state.checksums.lock().unwrap().get(&path)
+        && cached.mtime == mtime
+        && cached.size == size
+    {
+        return Ok(response(cached.sha256.clone()));
+    }
+
+    let sha256 = sha256_hex(&mut file).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let unchanged = state.storage.stat(&path).is_ok_and(|m| m.modified == mtime && m.size == size);
+    if unchanged {
+        state.checksums.lock().unwrap().insert(path, CachedChecksum { mtime, size, sha256: sha256.clone() });
+    }
+    Ok(response(sha256))
+}
+
 /// Handles `PUT /files/<path>`.
 ///
 /// Receives a streaming request body from the client and writes the data
@@ -138,14 +871,26 @@ pub async fn get_file(
 /// This handler is capable of receiving large files without buffering them
 /// entirely in memory.
 ///
+/// The optional `X-Atime`/`X-Mtime` headers (Unix seconds) are applied to the
+/// file after writing, so a copied file can keep its original timestamps.
+/// A file that did not exist gets `AppState::file_mode`, if configured; an
+/// overwritten one keeps its permissions.
+///
+/// Files larger than `AppState::max_file_size` are refused: up front when the
+/// `Content-Length` is over the limit (the existing file is left untouched),
+/// otherwise as soon as the streamed body crosses it (the partial file is removed).
+///
 /// # Arguments
 /// * `Path(path)` - The relative path of the file to write.
+/// * `headers` - May carry `X-Atime`/`X-Mtime` and the client's `X-Client-ID`.
 /// * `body` - The streaming `Body` of the `PUT` request.
 ///
 /// # Returns
 /// * `StatusCode::OK` on success.
 /// * `StatusCode::INTERNAL_SERVER_ERROR` if creating or writing the file fails.
-/// * `StatusCode::BAD_REQUEST` if the request body stream is invalid.
+/// * `StatusCode::BAD_REQUEST` if the request body stream is invalid, or the path is
+///   over the limits (see `AppState::check_path_limits`).
+/// * `StatusCode::PAYLOAD_TOO_LARGE` if the file would exceed `max_file_size`.
 
 pub async fn put_file(
     State(state): State<AppState>,
@@ -153,93 +898,310 @@ pub async fn put_file(
     headers: HeaderMap, 
     mut body: Body
 ) -> StatusCode {
+    // Oversize uploads are refused before touching the existing file
+    if content_length(&headers).is_some_and(|len| state.exceeds_max_file_size(len)) {
+        return StatusCode::PAYLOAD_TOO_LARGE;
+    }
+    if let Err(status) = state.check_path_limits(&path) {
+        return status;
+    }
+
     record_change(&state, &path, &headers);
-    let file_path = format!("{}/{}", DATA_DIR, path);
-    let mut file = match File::create(&file_path).await {
+    let mut file = match state.storage.write(&path, state.file_mode) {
         Ok(f) => f,
         Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
     };
 
+    let mut written: u64 = 0;
     while let Some(result) = body.frame().await {
         let frame = match result {
             Ok(frame) => frame,
             Err(_) => return StatusCode::BAD_REQUEST,
         };
         if let Some(data) = frame.data_ref() {
+            // Bodies without `Content-Length` are counted while they arrive
+            written += data.len() as u64;
+            if state.exceeds_max_file_size(written) {
+                drop(file);
+                let _ = state.storage.delete(&path, false);
+                return StatusCode::PAYLOAD_TOO_LARGE;
+            }
             if file.write_all(data).await.is_err() {
                 return StatusCode::INTERNAL_SERVER_ERROR;
             }
         }
     }
+    metrics::counter!("file_bytes_written_total").increment(written);
+
+    // tokio's `File` completes the last write in the background: wait for it, so
+    // that a request following this response (e.g. `/stat`, `/utimes`) sees the final file
+    if file.flush().await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    drop(file);
+
+    // Timestamps to preserve (e.g. a copy that keeps the original mtime)
+    if let Some(times) = upload_times(&headers)
+        && state.storage.set_times(&path, times).is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
     StatusCode::OK
 }
-/// Handles `GET /list` and `GET /list/<path>`.
+/// Builds the `RemoteEntry` sent to clients from a file's name and metadata.
+///
+/// Metadata read with `StorageBackend::lstat` gives kind `"symlink"`, with the
+/// link's `target` and whether it is `dangling`.
+fn remote_entry(name: String, metadata: &EntryMetadata) -> RemoteEntry {
+    RemoteEntry {
+        name,
+        kind: metadata.kind.as_str().to_string(),
+        size: metadata.size,
+        blocks: metadata.blocks,
+        mtime: metadata.modified.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64,
+        btime: metadata.created.map(|created| created.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64),
+        perm: format!("{:o}", metadata.mode & 0o777),
+        uid: metadata.uid,
+        gid: metadata.gid,
+        ino: metadata.ino,
+        target: metadata.target.clone(),
+        dangling: metadata.dangling,
+    }
+}
+
+/// Builds the `RemoteEntry` of the entry `name` of directory `dir` for the
+/// listings (`/list`, `/list-stream`, `/search`).
+///
+/// Symlinks are never followed (`StorageBackend::lstat`), so a broken link or a
+/// cycle of links cannot fail or hang the listing.
+///
+/// # Returns
+/// `None` if the entry has disappeared or cannot be read.
+fn listed_entry(storage: &dyn StorageBackend, dir: &str, name: String) -> Option<RemoteEntry> {
+    let metadata = storage.lstat(&join_path(dir, &name)).ok()?;
+    Some(remote_entry(name, &metadata))
+}
+
+/// Handles `GET /list` and `GET /list/<path>`, optionally with `?offset=N&limit=M`.
 ///
 /// Lists the contents of a directory specified by the optional `path`.
 /// If `path` is `None` (from the `/list` route), it lists the root of `DATA_DIR`.
 ///
 /// It iterates the directory, reads metadata for each entry, and constructs
 /// a `RemoteEntry` struct containing name, kind, size, mtime, and permissions.
+/// Symlinks are listed as such, without following them (see `listed_entry`).
+///
+/// # Pagination
+/// With `offset` and/or `limit`, only the entries from position `offset`
+/// (0-based, in name order, see `StorageBackend::list`) and at most `limit` of them are returned, and
+/// the metadata is read for those only. Every response carries the number of
+/// entries of the whole directory in `X-Total-Count`; if entries remain after
+/// the page, `X-Next-Offset` is the `offset` of the next page.
+///
+/// # Conditional requests
+/// The response carries the directory's mtime in `Last-Modified` (see `stable_mtime`).
+/// A request with `If-Modified-Since` at or after it is answered `304 Not Modified`
+/// without reading the entries, so a client can keep its cached listing. Only
+/// creating, deleting or renaming entries changes the directory's mtime: the
+/// metadata of the entries (e.g. a file's size) may be stale in a cached listing.
 ///
 /// # Arguments
 /// * `path` - An `Option<Path<String>>` extracted from the URL.
+/// * `Query(page)` - The optional `offset` and `limit`.
+/// * `request_headers` - The optional `If-Modified-Since`.
 ///
 /// # Returns
-/// * `Ok(Json<Vec<RemoteEntry>>)` with the list of directory entries.
+/// * `Ok` with the `Json<Vec<RemoteEntry>>` list of directory entries.
+/// * `Ok` with `304 Not Modified` if the directory did not change since `If-Modified-Since`.
 /// * `Err(StatusCode::NOT_FOUND)` if the specified directory does not exist.
-pub async fn list_directory_contents(path: Option<Path<String>>) -> Result<Json<Vec<RemoteEntry>>, StatusCode> {
+pub async fn list_directory_contents(
+    State(state): State<AppState>,
+    path: Option<Path<String>>,
+    Query(page): Query<ListPage>,
+    request_headers: HeaderMap
+) -> Result<Response, StatusCode> {
     let relative_path = path.map_or("".to_string(), |Path(p)| p);
-    let full_path =  format!("{}/{}",DATA_DIR, relative_path);
+
+    let last_modified = match state.storage.stat(&relative_path) {
+        Ok(metadata) => stable_mtime(metadata.modified),
+        Err(_) => return Err(StatusCode::NOT_FOUND),
+    };
+    if let Some(mtime) = last_modified
+        && not_modified_since(&request_headers, mtime)
+    {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::LAST_MODIFIED, httpdate::fmt_http_date(mtime))]).into_response());
+    }
 
     let mut entries = Vec::new();
-    let read_dir = match fs::read_dir(&full_path) {
+    let read_dir = match state.storage.list(&relative_path) {
         Ok(rd) => rd,
         Err(_) => return Err(StatusCode::NOT_FOUND),
     };
 
-    for entry_result in read_dir {
-        if let Ok(entry) = entry_result {
-            if let Ok(metadata) = entry.metadata() {
-                let kind = if metadata.is_dir() { "directory".to_string() } else { "file".to_string() };
-                let mtime = metadata.modified().unwrap_or(UNIX_EPOCH).duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
-                let perm = format!("{:o}", metadata.permissions().mode() & 0o777);
-
-                entries.push(RemoteEntry {
-                    name: entry.file_name().to_string_lossy().to_string(),
-                    kind,
-                    size: metadata.len(),
-                    mtime,
-                    perm,
-                });
-            }
+    let offset = page.offset.unwrap_or(0);
+    let end = page.limit.map_or(usize::MAX, |limit| offset.saturating_add(limit));
+    let mut total = 0;
+    for (name, _) in read_dir {
+        // Entries outside the page are only counted, their metadata is not read
+        if (offset..end).contains(&total)
+            && let Some(remote) = listed_entry(state.storage.as_ref(), &relative_path, name)
+        {
+            entries.push(remote);
         }
+        total += 1;
     }
-    Ok(Json(entries))
+
+    let mut headers = HeaderMap::new();
+    headers.insert("X-Total-Count", total.into());
+    if end < total {
+        headers.insert("X-Next-Offset", end.into());
+    }
+    if let Some(mtime) = last_modified {
+        headers.insert(header::LAST_MODIFIED, httpdate::fmt_http_date(mtime).parse().unwrap());
+    }
+    Ok((headers, Json(entries)).into_response())
+}
+
+/// The mtime to send as `Last-Modified`, truncated to whole seconds like the HTTP date.
+///
+/// # Returns
+/// `None` while `modified` falls in the current second: a change later in the same
+/// second would leave the date unchanged, and a client revalidating with it would
+/// keep a stale copy. The entry is then sent without `Last-Modified`.
+fn stable_mtime(modified: SystemTime) -> Option<SystemTime> {
+    let seconds = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    (seconds < now).then(|| UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Whether the request's `If-Modified-Since` is at or after `mtime` (a missing or
+/// invalid date never matches).
+fn not_modified_since(headers: &HeaderMap, mtime: SystemTime) -> bool {
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok())
+        .is_some_and(|since| mtime <= since)
+}
+/// How many serialized entries `list_directory_stream` buffers ahead of the client.
+const LIST_STREAM_BUFFER: usize = 256;
+
+/// Handles `GET /list-stream` and `GET /list-stream/<path>`.
+///
+/// Streaming variant of `list_directory_contents` for very large directories:
+/// the entries are sent as newline-delimited JSON (`application/x-ndjson`), one
+/// `RemoteEntry` per line, in the same order as `list_directory_contents`, while
+/// the metadata of the entries is still being read. Only the names are held in
+/// memory on the server, and the client can use the first entries before the
+/// metadata of the last ones is read from disk.
+///
+/// The directory is read on a blocking thread that stops as soon as the client
+/// goes away.
+///
+/// # Returns
+/// * `Ok(Response)` with the streamed entries.
+/// * `Err(StatusCode::NOT_FOUND)` if the specified directory does not exist.
+pub async fn list_directory_stream(State(state): State<AppState>, path: Option<Path<String>>) -> Result<Response, StatusCode> {
+    let relative_path = path.map_or("".to_string(), |Path(p)| p);
+    let read_dir = state.storage.list(&relative_path).map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<String>(LIST_STREAM_BUFFER);
+    tokio::task::spawn_blocking(move || {
+        for (name, _) in read_dir {
+            let Some(entry) = listed_entry(state.storage.as_ref(), &relative_path, name) else { continue };
+            let Ok(mut line) = serde_json::to_string(&entry) else { continue };
+            line.push('\n');
+            // The receiver is gone: the client disconnected
+            if tx.blocking_send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|line| (Ok::<_, std::io::Error>(line), rx))
+    });
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .body(Body::from_stream(stream))
+        .unwrap())
 }
-/// Handles `POST /mkdir/<path>`.
+
+/// Handles `POST /mkdir/<path>?parents=<bool>`.
 ///
-/// Creates a new directory (and any necessary parent directories, like `mkdir -p`)
-/// at the specified path within `DATA_DIR`.
+/// Creates a new directory at the specified path within `DATA_DIR`.
+/// - With `parents=true` (the default), parent directories are created too and
+///   an existing directory is not an error, like `mkdir -p`.
+/// - With `parents=false`, only the last component is created, like `mkdir`:
+///   the parent must exist and the directory must not.
+///
+/// Every directory created (parents included, existing ones excluded) gets
+/// `AppState::dir_mode`, if configured.
 ///
 /// # Arguments
 /// * `Path(path)` - The relative path of the directory to create.
+/// * `Query(query)` - The optional `parents` flag.
 ///
 /// # Returns
 /// * `StatusCode::OK` on success.
+/// * `StatusCode::CONFLICT` if `parents=false` and the path already exists.
+/// * `StatusCode::NOT_FOUND` if `parents=false` and the parent does not exist.
+/// * `StatusCode::BAD_REQUEST` if the path is over the limits (see `AppState::check_path_limits`).
 /// * `StatusCode::INTERNAL_SERVER_ERROR` if directory creation fails.
 pub async fn mkdir(
     State(state): State<AppState>,
     Path(path): Path<String>,
+    Query(query): Query<MkdirQuery>,
     headers: HeaderMap
 ) -> StatusCode {
+    if let Err(status) = state.check_path_limits(&path) {
+        return status;
+    }
     record_change(&state, &path, &headers);
-    let dir_path =  format!("{}/{}",DATA_DIR, path);
-    match fs::create_dir_all(&dir_path) {
+    match state.storage.mkdir(&path, query.parents, state.dir_mode) {
         Ok(_) => StatusCode::OK,
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => StatusCode::CONFLICT,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
     }
 }
-/// Handles `DELETE /files/<path>`.
+
+/// Handles `POST /touch/<path>`.
+///
+/// Creates an empty file, failing if the path already exists (`O_CREAT | O_EXCL`).
+/// The FUSE client uses it in `create` instead of a `PUT` with an empty body:
+/// the content is only uploaded once, when the file is released.
+/// The new file gets `AppState::file_mode`, if configured.
+///
+/// # Arguments
+/// * `Path(path)` - The relative path of the file to create.
+///
+/// # Returns
+/// * `StatusCode::OK` on success.
+/// * `StatusCode::CONFLICT` if the path already exists (file or directory).
+/// * `StatusCode::NOT_FOUND` if the parent directory does not exist.
+/// * `StatusCode::BAD_REQUEST` if the path is over the limits (see `AppState::check_path_limits`).
+/// * `StatusCode::INTERNAL_SERVER_ERROR` if the file cannot be created.
+pub async fn touch(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    headers: HeaderMap
+) -> StatusCode {
+    if let Err(status) = state.check_path_limits(&path) {
+        return status;
+    }
+    record_change(&state, &path, &headers);
+    match state.storage.create(&path, state.file_mode) {
+        Ok(_) => StatusCode::OK,
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => StatusCode::CONFLICT,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Handles `DELETE /files/<path>`.
 ///
 /// Deletes a file or directory at the specified path.
 /// - If the path is a directory, it is removed recursively (`rm -r`).
@@ -251,67 +1213,832 @@ pub async fn mkdir(
 /// # Returns
 /// * `StatusCode::OK` on success.
 /// * `StatusCode::NOT_FOUND` if the path does not exist.
+/// * `StatusCode::FORBIDDEN` if the path is the root of the data directory.
 /// * `StatusCode::INTERNAL_SERVER_ERROR` if the deletion fails.
 pub async fn delete_file(
     State(state): State<AppState>,
     Path(path): Path<String>,
     headers: HeaderMap
 ) -> StatusCode {
+    if is_root_path(&path) {
+        return StatusCode::FORBIDDEN;
+    }
     record_change(&state, &path, &headers);
-    let file_path =  format!("{}/{}",DATA_DIR, path);
-    if let Ok(meta) = fs::metadata(&file_path) {
-        let res = if meta.is_dir() {
-            fs::remove_dir_all(&file_path)
-        } else {
-            fs::remove_file(&file_path)
+    match state.storage.delete(&path, true) {
+        Ok(_) => StatusCode::OK,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+/// Appends every descendant of `relative` to `out`, children before their parent directory.
+///
+/// Symlinks are never recursed through, so cycles of links cannot loop.
+fn collect_tree(storage: &dyn StorageBackend, relative: &str, out: &mut Vec<TreeEntry>) -> std::io::Result<()> {
+    for (name, kind) in storage.list(relative)? {
+        let child = join_path(relative, &name);
+        // `list` does not follow symlinks: a link to a directory is removed, not descended into
+        if kind == EntryKind::Directory {
+            collect_tree(storage, &child, out)?;
+        }
+        out.push(TreeEntry { path: child, kind: kind.as_str().to_string() });
+    }
+    Ok(())
+}
+
+/// Handles `GET /tree/<path>`.
+///
+/// Lists all the descendants of a directory in a single request, in post-order
+/// (every entry comes before its parent directory), so the list can be passed
+/// as-is to `POST /delete-batch`. The directory itself is not included.
+/// Symlinks are listed with kind `"symlink"` and not followed.
+///
+/// # Arguments
+/// * `Path(path)` - The relative path of the directory.
+///
+/// # Returns
+/// * `Ok(Json(Vec<TreeEntry>))` with the descendants on success.
+/// * `Err(StatusCode::NOT_FOUND)` if the directory does not exist or cannot be read.
+pub async fn tree(State(state): State<AppState>, Path(path): Path<String>) -> Result<Json<Vec<TreeEntry>>, StatusCode> {
+    let mut entries = Vec::new();
+    collect_tree(state.storage.as_ref(), &path, &mut entries).map_err(|_| StatusCode::NOT_FOUND)?;
+    Ok(Json(entries))
+}
+
+/// How a `GET /search` matches entry names.
+enum NameMatcher {
+    Substring(String),
+    Glob(glob::Pattern),
+}
+
+impl NameMatcher {
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            NameMatcher::Substring(s) => name.contains(s.as_str()),
+            NameMatcher::Glob(pattern) => pattern.matches(name),
+        }
+    }
+}
+
+/// Walks the subtree under `relative` breadth-first and collects the entries that
+/// `select` returns, stopping after `limit` results or `MAX_SEARCH_SCANNED` entries.
+///
+/// `select` gets the directory and the name of every entry, and returns its
+/// `RemoteEntry` if it must be reported. Symlinks are reported but never
+/// followed. Unreadable directories are skipped.
+fn walk_tree(
+    storage: &dyn StorageBackend,
+    relative: &str,
+    limit: usize,
+    mut select: impl FnMut(&str, String) -> Option<RemoteEntry>,
+) -> std::io::Result<SearchResponse> {
+    let mut results = Vec::new();
+    let mut scanned = 0;
+    let mut pending = std::collections::VecDeque::from([relative.trim_matches('/').to_string()]);
+    let mut first = true;
+
+    while let Some(dir) = pending.pop_front() {
+        let read_dir = match storage.list(&dir) {
+            Ok(rd) => rd,
+            // Only the starting directory must exist
+            Err(e) if first => return Err(e),
+            Err(_) => continue,
         };
+        first = false;
 
-        match res {
-            Ok(_) => StatusCode::OK,
-            Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        for (name, kind) in read_dir {
+            scanned += 1;
+            if scanned > MAX_SEARCH_SCANNED || results.len() >= limit {
+                return Ok(SearchResponse { results, truncated: true });
+            }
+            let child = join_path(&dir, &name);
+            if let Some(remote) = select(&dir, name) {
+                results.push(SearchResult { path: child.clone(), entry: remote });
+            }
+            if kind == EntryKind::Directory {
+                pending.push_back(child);
+            }
         }
+    }
+    Ok(SearchResponse { results, truncated: false })
+}
+
+/// Handles `GET /search?q=<text>&path=<dir>&glob=<bool>&limit=<n>`.
+///
+/// Finds files and directories by name in a whole subtree, so clients do not
+/// have to walk it with one `/list` per directory. `q` is matched against the
+/// entry's name: as a substring by default, or as a glob pattern (`*.txt`,
+/// `report-??.pdf`) when `glob=true`.
+///
+/// The scan is bounded by `limit` (at most `MAX_SEARCH_RESULTS`) and by
+/// `MAX_SEARCH_SCANNED` entries; `truncated` tells the client if it was cut short.
+///
+/// # Returns
+/// * `Ok(Json(SearchResponse))` with the matches, each with its path relative to `DATA_DIR`.
+/// * `Err(StatusCode::BAD_REQUEST)` if `q` is empty or not a valid glob pattern, or
+///   `path` has a `..` component.
+/// * `Err(StatusCode::NOT_FOUND)` if `path` does not exist or is not a directory.
+pub async fn search(State(state): State<AppState>, Query(query): Query<SearchQuery>) -> Result<Json<SearchResponse>, StatusCode> {
+    check_relative_path(query.path.trim_matches('/'))?;
+    if query.q.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let matcher = if query.glob {
+        NameMatcher::Glob(glob::Pattern::new(&query.q).map_err(|_| StatusCode::BAD_REQUEST)?)
     } else {
-        StatusCode::NOT_FOUND
+        NameMatcher::Substring(query.q)
+    };
+    let limit = query.limit.unwrap_or(MAX_SEARCH_RESULTS).min(MAX_SEARCH_RESULTS);
+
+    // The walk is blocking I/O over a possibly large tree
+    tokio::task::spawn_blocking(move || {
+        let storage = state.storage.as_ref();
+        walk_tree(storage, &query.path, limit, |dir, name| {
+            if matcher.matches(&name) { listed_entry(storage, dir, name) } else { None }
+        })
+    })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(Json)
+        .map_err(|_| StatusCode::NOT_FOUND)
+}
+
+/// Handles `GET /changes-since?ts=<unix>&path=<dir>&limit=<n>`.
+///
+/// Lists the entries of a subtree changed at or after `ts` (Unix seconds), so a
+/// client coming back after a disconnection can drop only those from its caches:
+/// the WebSocket notifications sent in the meantime are lost.
+///
+/// # Sources
+/// * The change journal (`AppState::journal`), if it covers `ts`: the paths the
+///   watcher reported since then, in time proportional to the number of changes.
+///   A path that no longer exists is reported through its parent directory.
+/// * Otherwise (`ts` before the start of the watcher or the oldest entry kept,
+///   or `CHANGE_JOURNAL_CAPACITY=0`) a walk of the subtree, listing the entries
+///   with mtime at or after `ts`, the directory itself first. A deleted entry is
+///   not listed, but the mtime of its parent directory changed, so the parent is.
+///   The walk has the bounds of `/search` (`MAX_SEARCH_SCANNED` entries): its
+///   cost grows with the size of the subtree, not with the number of changes.
+///
+/// At most `limit` results (and `MAX_SEARCH_RESULTS`); `truncated` tells the
+/// client that the list is incomplete.
+///
+/// # Returns
+/// * `Ok(Json(SearchResponse))` with the changed entries, each with its path
+///   relative to `DATA_DIR` (same shape as `/search`).
+/// * `Err(StatusCode::BAD_REQUEST)` if `path` has a `..` component.
+/// * `Err(StatusCode::NOT_FOUND)` if `path` does not exist or is not a directory.
+pub async fn changes_since(State(state): State<AppState>, Query(query): Query<ChangesQuery>) -> Result<Json<SearchResponse>, StatusCode> {
+    let limit = query.limit.unwrap_or(MAX_SEARCH_RESULTS).min(MAX_SEARCH_RESULTS);
+    let since = query.ts;
+    let root = query.path.trim_matches('/').to_string();
+    check_relative_path(&root)?;
+    let walk_root = root.clone();
+
+    let threshold = UNIX_EPOCH + Duration::from_secs(since.max(0) as u64);
+    let journaled = state.journal.lock().unwrap().changes_since(threshold, &root);
+
+    let response = tokio::task::spawn_blocking(move || {
+        let storage = state.storage.as_ref();
+        if let Some(paths) = journaled {
+            return journaled_changes(storage, &walk_root, paths, limit);
+        }
+        let changed = |metadata: &EntryMetadata| metadata.modified >= threshold;
+        let root = walk_root.as_str();
+        let mut response = walk_tree(storage, root, limit, |dir, name| {
+            let metadata = storage.lstat(&join_path(dir, &name)).ok()?;
+            changed(&metadata).then(|| remote_entry(name, &metadata))
+        })?;
+        if let Ok(metadata) = storage.stat(root)
+            && changed(&metadata)
+        {
+            let name = root.rsplit('/').next().unwrap_or_default().to_string();
+            if response.results.len() >= limit {
+                response.results.pop();
+                response.truncated = true;
+            }
+            response.results.insert(0, SearchResult { path: root.to_string(), entry: remote_entry(name, &metadata) });
+        }
+        Ok::<_, std::io::Error>(response)
+    })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    println!("[CHANGES] {} entry modificate da {} sotto '{}'{}", response.results.len(), since, root, if response.truncated { " (troncato)" } else { "" });
+    Ok(Json(response))
+}
+
+/// Builds the `/changes-since` response from the paths found in the change journal.
+///
+/// Each path is reported with its current metadata. A path that no longer exists
+/// (removed, or renamed away) is replaced by its parent directory, as long as
+/// that is inside `root`, so the clients refresh the listing that contained it.
+/// Fails with `NotFound` if `root` is not a directory.
+fn journaled_changes(storage: &dyn StorageBackend, root: &str, paths: Vec<String>, limit: usize) -> std::io::Result<SearchResponse> {
+    if storage.stat(root)?.kind != EntryKind::Directory {
+        return Err(std::io::ErrorKind::NotFound.into());
     }
+    let mut results: Vec<SearchResult> = Vec::new();
+    let mut truncated = false;
+    for path in paths {
+        let mut path = path;
+        let metadata = loop {
+            match storage.lstat(&path) {
+                Ok(metadata) => break Some(metadata),
+                Err(_) if path != root => path = path.rsplit_once('/').map_or(String::new(), |(parent, _)| parent.to_string()),
+                Err(_) => break None,
+            }
+        };
+        let Some(metadata) = metadata else { continue };
+        if results.iter().any(|r| r.path == path) {
+            continue;
+        }
+        if results.len() >= limit {
+            truncated = true;
+            break;
+        }
+        let name = path.rsplit('/').next().unwrap_or_default().to_string();
+        results.push(SearchResult { path, entry: remote_entry(name, &metadata) });
+    }
+    Ok(SearchResponse { results, truncated })
+}
+
+/// Handles `POST /delete-batch`.
+///
+/// Deletes many paths in a single request, in the given order. Directories are
+/// removed with `rmdir(2)`, so they must already be empty (i.e. listed after
+/// their contents, as `GET /tree` does). A failure does not stop the batch.
+/// The root of the data directory is never deleted (`permission_denied`).
+///
+/// # Arguments
+/// * `Json(paths)` - A JSON array of relative paths.
+///
+/// # Returns
+/// * `Ok(Json(Vec<DeleteResult>))` with one result per path, in the same order.
+/// * `Err(StatusCode::BAD_REQUEST)` if a path is absolute or has a `..` component:
+///   nothing is deleted.
+pub async fn delete_batch(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(paths): Json<Vec<String>>
+) -> Result<Json<Vec<DeleteResult>>, StatusCode> {
+    // The root, in any spelling (`/` too), is refused per path below
+    paths.iter().filter(|path| !is_root_path(path)).try_for_each(|path| check_relative_path(path))?;
+    let results = paths.into_iter().map(|path| {
+        if is_root_path(&path) {
+            return DeleteResult { path, error: Some("permission_denied".to_string()) };
+        }
+        record_change(&state, &path, &headers);
+        let error = state.storage.delete(&path, false).err().map(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => "not_found",
+            std::io::ErrorKind::DirectoryNotEmpty => "not_empty",
+            std::io::ErrorKind::PermissionDenied => "permission_denied",
+            _ => "io",
+        }.to_string());
+        DeleteResult { path, error }
+    }).collect();
+    Ok(Json(results))
 }
 /// Handles `PATCH /files/<path>`.
 ///
-/// Updates the file permissions (mode) of a file or directory.
-/// This is used by the FUSE client to implement `chmod`.
+/// Updates the metadata of a file or directory; the fields of the body select
+/// what changes, and can be combined in one request:
+/// * `perm` (or `mode`): the octal permissions, as used by the FUSE client for `chmod`.
+/// * `owner`: `{"uid": 1000, "gid": 1000}`, either id optional (`chown`).
+/// * `rename_to`: a new name in the same directory. Unlike `POST /rename`, an
+///   existing entry with that name is never replaced.
+///
+/// The body is validated first, then the changes are applied in that order
+/// (the rename last): a failure stops at the first change that fails.
 ///
 /// # Arguments
 /// * `Path(path)` - The relative path of the item to modify.
-/// * `Json(payload)` - A JSON body `{"perm": "755"}` with the new octal permissions.
+/// * `Json(payload)` - A JSON body like `{"perm": "755"}` or `{"mode": "640", "owner": {"gid": 100}, "rename_to": "new.txt"}`.
 ///
 /// # Returns
 /// * `StatusCode::OK` on success.
-/// * `StatusCode::BAD_REQUEST` if the octal string in the payload is invalid.
+/// * `StatusCode::BAD_REQUEST` if the body has no field, the octal string is invalid,
+///   or `rename_to` is not a plain name (or the path is the root) or is too long.
 /// * `StatusCode::NOT_FOUND` if the path does not exist.
-/// * `StatusCode::INTERNAL_SERVER_ERROR` if setting permissions fails.
-
+/// * `StatusCode::FORBIDDEN` if the server may not change the owner.
+/// * `StatusCode::CONFLICT` if an entry named `rename_to` already exists.
+/// * `StatusCode::INTERNAL_SERVER_ERROR` if a change fails otherwise.
 pub async fn patch_file(
     State(state): State<AppState>,
     Path(path): Path<String>, 
     headers: HeaderMap,
-    Json(payload): Json<UpdatePermissions>
+    Json(payload): Json<UpdateMetadata>
 ) -> StatusCode {
+    if payload.perm.is_none() && payload.owner.is_none() && payload.rename_to.is_none() {
+        return StatusCode::BAD_REQUEST;
+    }
+    let mode = match payload.perm.as_deref().map(|perm| u32::from_str_radix(perm, 8)) {
+        Some(Ok(m)) => Some(m),
+        Some(Err(_)) => return StatusCode::BAD_REQUEST,
+        None => None,
+    };
+    let new_path = match payload.rename_to.as_deref() {
+        Some(name) if is_root_path(&path) || name.is_empty() || name == "." || name == ".." || name.contains('/') => {
+            return StatusCode::BAD_REQUEST;
+        }
+        Some(name) => Some(match path.trim_end_matches('/').rsplit_once('/') {
+            Some((parent, _)) => format!("{}/{}", parent, name),
+            None => name.to_string(),
+        }),
+        None => None,
+    };
+    if let Some(new_path) = &new_path
+        && let Err(status) = state.check_path_limits(new_path)
+    {
+        return status;
+    }
+
     record_change(&state, &path, &headers);
-    let file_path = format!("{}/{}", DATA_DIR, path);
-    let mode = match u32::from_str_radix(&payload.perm, 8) {
-        Ok(m) => m,
-        Err(_) => return StatusCode::BAD_REQUEST,
+    let status = |e: std::io::Error| match e.kind() {
+        std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        std::io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
     };
+    if let Some(mode) = mode
+        && let Err(e) = state.storage.set_permissions(&path, mode)
+    {
+        return status(e);
+    }
+    if let Some(owner) = &payload.owner
+        && let Err(e) = state.storage.set_owner(&path, owner.uid, owner.gid)
+    {
+        return status(e);
+    }
+    if let Some(new_path) = new_path {
+        if let Err(e) = state.storage.lstat(&path) {
+            return status(e);
+        }
+        if state.storage.lstat(&new_path).is_ok() {
+            return StatusCode::CONFLICT;
+        }
+        record_change(&state, &new_path, &headers);
+        if let Err(e) = state.storage.rename(&path, &new_path) {
+            return status(e);
+        }
+    }
+    StatusCode::OK
+}
+/// Builds the `EntryTimes` to apply from optional Unix timestamps (in seconds).
+/// A missing timestamp is left unchanged.
+fn file_times(atime: Option<i64>, mtime: Option<i64>) -> EntryTimes {
+    let to_system_time = |secs: i64| UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64);
+    EntryTimes { accessed: atime.map(to_system_time), modified: mtime.map(to_system_time) }
+}
 
-    match fs::metadata(&file_path) {
-        Ok(metadata) => {
-            let mut perms = metadata.permissions();
-            perms.set_mode(mode);
-            if fs::set_permissions(&file_path, perms).is_ok() {
-                StatusCode::OK
-            } else {
-                StatusCode::INTERNAL_SERVER_ERROR
+/// Reads the optional `X-Atime`/`X-Mtime` headers (Unix seconds) sent with an upload.
+///
+/// # Returns
+/// `Some(EntryTimes)` if at least one valid header is present, `None` otherwise.
+fn upload_times(headers: &HeaderMap) -> Option<EntryTimes> {
+    let parse = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).and_then(|v| v.parse::<i64>().ok());
+    let (atime, mtime) = (parse("X-Atime"), parse("X-Mtime"));
+    if atime.is_none() && mtime.is_none() {
+        return None;
+    }
+    Some(file_times(atime, mtime))
+}
+
+/// Handles `POST /utimes/<path>`.
+///
+/// Sets the access and/or modification time of a file or directory.
+/// This is used by the FUSE client to implement `utimensat` (e.g. `touch -d`).
+///
+/// # Arguments
+/// * `Path(path)` - The relative path of the item to modify.
+/// * `Json(payload)` - A JSON body `{"atime": 1700000000, "mtime": 1700000000}` (both optional).
+///
+/// # Returns
+/// * `StatusCode::OK` on success.
+/// * `StatusCode::NOT_FOUND` if the path does not exist.
+/// * `StatusCode::INTERNAL_SERVER_ERROR` if setting the times fails.
+pub async fn utimes(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    headers: HeaderMap,
+    Json(payload): Json<UpdateTimes>
+) -> StatusCode {
+    record_change(&state, &path, &headers);
+    match state.storage.set_times(&path, file_times(payload.atime, payload.mtime)) {
+        Ok(_) => StatusCode::OK,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Handles `POST /truncate/<path>?size=<n>`.
+///
+/// Resizes a file in place with `set_len`: a shorter size drops the tail, a
+/// larger one extends the file with zeros. No file content is transferred,
+/// which is what the FUSE client uses for `truncate`/`ftruncate`.
+///
+/// # Arguments
+/// * `Path(path)` - The relative path of the file to resize.
+/// * `Query(query)` - The new size in bytes.
+///
+/// # Returns
+/// * `StatusCode::OK` on success.
+/// * `StatusCode::NOT_FOUND` if the file does not exist.
+/// * `StatusCode::BAD_REQUEST` if the path is a directory.
+/// * `StatusCode::PAYLOAD_TOO_LARGE` if `size` exceeds `max_file_size`.
+/// * `StatusCode::INTERNAL_SERVER_ERROR` if resizing the file fails.
+pub async fn truncate_file(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    Query(query): Query<TruncateQuery>,
+    headers: HeaderMap
+) -> StatusCode {
+    if state.exceeds_max_file_size(query.size) {
+        return StatusCode::PAYLOAD_TOO_LARGE;
+    }
+    match state.storage.stat(&path) {
+        Ok(metadata) if metadata.is_dir() => return StatusCode::BAD_REQUEST,
+        Ok(_) => {}
+        Err(_) => return StatusCode::NOT_FOUND,
+    }
+
+    record_change(&state, &path, &headers);
+    match state.storage.truncate(&path, query.size) {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Handles `POST /write/<path>?offset=<n>`.
+///
+/// Writes the request body into an existing file at `offset`, leaving the rest
+/// of the file as it is: a write past the end extends the file (the gap reads as
+/// zeros). Used by the FUSE client in `write_through` mode, one request per `write`.
+///
+/// # Arguments
+/// * `Path(path)` - The relative path of the file to write into.
+/// * `Query(query)` - The byte offset of the first byte of the body.
+/// * `headers` - May carry the client's `X-Client-ID`.
+/// * `body` - The bytes to write.
+///
+/// # Returns
+/// * `StatusCode::OK` on success.
+/// * `StatusCode::NOT_FOUND` if the file does not exist.
+/// * `StatusCode::BAD_REQUEST` if the path is a directory or the body stream is invalid.
+/// * `StatusCode::PAYLOAD_TOO_LARGE` if the file would exceed `max_file_size`. Checked up
+///   front with `Content-Length`; a body without it is cut when it crosses the limit,
+///   and the bytes written until then stay.
+/// * `StatusCode::INTERNAL_SERVER_ERROR` if writing the file fails.
+pub async fn write_at(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    Query(query): Query<WriteQuery>,
+    headers: HeaderMap,
+    mut body: Body
+) -> StatusCode {
+    if content_length(&headers).is_some_and(|len| state.exceeds_max_file_size(query.offset.saturating_add(len))) {
+        return StatusCode::PAYLOAD_TOO_LARGE;
+    }
+    match state.storage.stat(&path) {
+        Ok(metadata) if metadata.is_dir() => return StatusCode::BAD_REQUEST,
+        Ok(_) => {}
+        Err(_) => return StatusCode::NOT_FOUND,
+    }
+
+    record_change(&state, &path, &headers);
+    let mut file = match state.storage.write_at(&path, query.offset) {
+        Ok(f) => f,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    let mut written: u64 = 0;
+    while let Some(result) = body.frame().await {
+        let frame = match result {
+            Ok(frame) => frame,
+            Err(_) => return StatusCode::BAD_REQUEST,
+        };
+        if let Some(data) = frame.data_ref() {
+            written += data.len() as u64;
+            if state.exceeds_max_file_size(query.offset + written) {
+                return StatusCode::PAYLOAD_TOO_LARGE;
+            }
+            if file.write_all(data).await.is_err() {
+                return StatusCode::INTERNAL_SERVER_ERROR;
+            }
+        }
+    }
+    metrics::counter!("file_bytes_written_total").increment(written);
+
+    // As in `put_file`: the response must not precede the last write
+    match file.flush().await {
+        Ok(()) => StatusCode::OK,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Handles `POST /fallocate/<path>?offset=<n>&len=<n>&mode=<flags>`.
+///
+/// Applies `fallocate(2)` to a file on the server. The supported modes are:
+/// * `0` - preallocates the range, extending the file if it ends past EOF;
+/// * `FALLOC_FL_KEEP_SIZE` - preallocates without changing the size;
+/// * `FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE` - deallocates the range,
+///   which then reads back as zeros.
+///
+/// # Returns
+/// * `StatusCode::OK` on success.
+/// * `StatusCode::BAD_REQUEST` for an unsupported mode, an empty range or a directory.
+/// * `StatusCode::NOT_FOUND` if the file does not exist.
+/// * `StatusCode::PAYLOAD_TOO_LARGE` if the file would grow past `max_file_size`.
+/// * `StatusCode::NOT_IMPLEMENTED` if the server's filesystem does not support the mode.
+/// * `StatusCode::INSUFFICIENT_STORAGE` if there is not enough space on the server.
+/// * `StatusCode::INTERNAL_SERVER_ERROR` for any other failure.
+pub async fn fallocate(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    Query(query): Query<FallocateQuery>,
+    headers: HeaderMap
+) -> StatusCode {
+    let supported = [0, FALLOC_FL_KEEP_SIZE, FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE];
+    if !supported.contains(&query.mode) || query.len == 0 {
+        return StatusCode::BAD_REQUEST;
+    }
+    let Some(end) = query.offset.checked_add(query.len) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    if query.mode & FALLOC_FL_KEEP_SIZE == 0 && state.exceeds_max_file_size(end) {
+        return StatusCode::PAYLOAD_TOO_LARGE;
+    }
+    match state.storage.stat(&path) {
+        Ok(metadata) if metadata.is_dir() => return StatusCode::BAD_REQUEST,
+        Ok(_) => {}
+        Err(_) => return StatusCode::NOT_FOUND,
+    }
+
+    record_change(&state, &path, &headers);
+    match state.storage.fallocate(&path, query.mode, query.offset, query.len) {
+        Ok(_) => StatusCode::OK,
+        Err(e) if e.kind() == std::io::ErrorKind::Unsupported => StatusCode::NOT_IMPLEMENTED,
+        Err(e) if e.kind() == std::io::ErrorKind::StorageFull => StatusCode::INSUFFICIENT_STORAGE,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Handles `GET /stat/<path>`, and `GET /stat` for the data directory itself.
+///
+/// Returns the metadata of a single file or directory, in the same format
+/// as one element of the `/list` response. The `name` field is the last
+/// component of `path` (empty for the root).
+///
+/// # Returns
+/// * `Ok(Json<RemoteEntry>)` with the entry's metadata.
+/// * `Err(StatusCode::NOT_FOUND)` if the path does not exist.
+pub async fn stat_file(State(state): State<AppState>, path: Option<Path<String>>) -> Result<Json<RemoteEntry>, StatusCode> {
+    let path = path.map_or(String::new(), |Path(p)| p);
+    let metadata = state.storage.stat(&path).map_err(|_| StatusCode::NOT_FOUND)?;
+    let name = path.rsplit('/').next().unwrap_or_default().to_string();
+    Ok(Json(remote_entry(name, &metadata)))
+}
+
+/// Handles `POST /stat-batch`.
+///
+/// Returns the metadata of many paths in a single request, so a client can
+/// fill its attribute cache for a whole directory at once instead of calling
+/// `/stat` once per entry.
+///
+/// # Arguments
+/// * `Json(paths)` - A JSON array of relative paths (at most `MAX_STAT_BATCH`).
+///
+/// # Returns
+/// * `Ok(Json(Vec<Option<RemoteEntry>>))` with one element per path, in the same
+///   order: the entry as in `/stat`, or `null` if the path does not exist.
+/// * `Err(StatusCode::PAYLOAD_TOO_LARGE)` if more than `MAX_STAT_BATCH` paths are sent.
+/// * `Err(StatusCode::BAD_REQUEST)` if a path is absolute or has a `..` component.
+pub async fn stat_batch(State(state): State<AppState>, Json(paths): Json<Vec<String>>) -> Result<Json<Vec<Option<RemoteEntry>>>, StatusCode> {
+    if paths.len() > MAX_STAT_BATCH {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    paths.iter().try_for_each(|path| check_relative_path(path))?;
+    let entries = paths.iter().map(|path| {
+        let metadata = state.storage.stat(path).ok()?;
+        let name = path.rsplit('/').next().unwrap_or_default().to_string();
+        Some(remote_entry(name, &metadata))
+    }).collect();
+    Ok(Json(entries))
+}
+
+/// Handles `POST /rename`.
+///
+/// Moves a file or directory with a single `rename(2)` call, which is atomic
+/// and overwrites an existing destination file. This is used by the FUSE
+/// client as a fast path for renames within the same directory.
+///
+/// # Arguments
+/// * `Json(payload)` - A JSON body `{"from": "a", "to": "b"}` with the source and destination paths.
+///
+/// # Returns
+/// * `StatusCode::OK` on success.
+/// * `StatusCode::NOT_FOUND` if the source does not exist.
+/// * `StatusCode::BAD_REQUEST` if a path is absolute or has a `..` component, or the
+///   destination is over the limits (see `AppState::check_path_limits`).
+/// * `StatusCode::INTERNAL_SERVER_ERROR` if the rename fails (e.g. non-empty destination directory).
+pub async fn rename(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<PathPair>
+) -> StatusCode {
+    if let Err(status) = check_relative_path(&payload.from)
+        .and_then(|_| check_relative_path(&payload.to))
+        .and_then(|_| state.check_path_limits(&payload.to))
+    {
+        return status;
+    }
+    record_change(&state, &payload.from, &headers);
+    record_change(&state, &payload.to, &headers);
+    if state.storage.lstat(&payload.from).is_err() {
+        return StatusCode::NOT_FOUND;
+    }
+
+    match state.storage.rename(&payload.from, &payload.to) {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Handles `POST /exchange`.
+///
+/// Atomically swaps two existing files or directories. This is used by the
+/// FUSE client to implement `rename` with the `RENAME_EXCHANGE` flag.
+///
+/// # Arguments
+/// * `Json(payload)` - A JSON body `{"from": "a", "to": "b"}` with the two relative paths.
+///
+/// # Returns
+/// * `StatusCode::OK` on success.
+/// * `StatusCode::BAD_REQUEST` if a path is absolute or has a `..` component.
+/// * `StatusCode::NOT_FOUND` if either path does not exist.
+/// * `StatusCode::INTERNAL_SERVER_ERROR` if the swap fails.
+pub async fn exchange(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<PathPair>
+) -> StatusCode {
+    if let Err(status) = check_relative_path(&payload.from).and_then(|_| check_relative_path(&payload.to)) {
+        return status;
+    }
+    record_change(&state, &payload.from, &headers);
+    record_change(&state, &payload.to, &headers);
+    if state.storage.lstat(&payload.from).is_err() || state.storage.lstat(&payload.to).is_err() {
+        return StatusCode::NOT_FOUND;
+    }
+
+    match state.storage.exchange(&payload.from, &payload.to) {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Returns the paths of the partial file and of the destination record of upload `id`.
+///
+/// Returns `None` if `id` is not a valid session id, so it can never escape `UPLOAD_DIR`.
+fn upload_files(id: &str) -> Option<(String, String)> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some((format!("{}/{}.part", UPLOAD_DIR, id), format!("{}/{}.path", UPLOAD_DIR, id)))
+}
+
+/// Handles `POST /upload/start`.
+///
+/// Opens a chunked upload session for a file. The data is staged in `UPLOAD_DIR`
+/// and only moved to its destination by `POST /upload/<id>/complete`. Sessions
+/// are stored on disk, so they survive a server restart.
+///
+/// # Arguments
+/// * `Json(payload)` - A JSON body `{"path": "dir/file"}` with the destination path.
+///
+/// # Returns
+/// * `Ok(Json(UploadStatus))` with the new session id and offset `0`.
+/// * `Err(StatusCode::BAD_REQUEST)` if the path is absolute or has a `..` component,
+///   or is over the limits (see `AppState::check_path_limits`).
+/// * `Err(StatusCode::INTERNAL_SERVER_ERROR)` if the session files cannot be created.
+pub async fn start_upload(State(state): State<AppState>, Json(payload): Json<StartUpload>) -> Result<Json<UploadStatus>, StatusCode> {
+    check_relative_path(&payload.path)?;
+    state.check_path_limits(&payload.path)?;
+    static NEXT_UPLOAD: AtomicU64 = AtomicU64::new(0);
+    let nanos = std::time::SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let id = format!("{:x}{:04x}", nanos, NEXT_UPLOAD.fetch_add(1, Ordering::Relaxed) & 0xffff);
+    let (part_path, dest_path) = upload_files(&id).ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    fs::create_dir_all(UPLOAD_DIR).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    fs::write(&part_path, b"").map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    fs::write(&dest_path, &payload.path).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(UploadStatus { id, offset: 0 }))
+}
+
+/// Handles `GET /upload/<id>`.
+///
+/// Reports how many bytes of the upload have been committed, so an interrupted
+/// client knows where to resume.
+///
+/// # Returns
+/// * `Ok(Json(UploadStatus))` with the committed offset.
+/// * `Err(StatusCode::NOT_FOUND)` if the session does not exist.
+pub async fn upload_status(Path(id): Path<String>) -> Result<Json<UploadStatus>, StatusCode> {
+    let (part_path, _) = upload_files(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let offset = fs::metadata(&part_path).map_err(|_| StatusCode::NOT_FOUND)?.len();
+    Ok(Json(UploadStatus { id, offset }))
+}
+
+/// Handles `PUT /upload/<id>?offset=<n>`.
+///
+/// Appends the request body to the upload. Chunks must be sent in order:
+/// `offset` has to match the number of bytes already committed.
+///
+/// # Returns
+/// * `Ok(Json(UploadStatus))` with the new committed offset.
+/// * `Err(StatusCode::NOT_FOUND)` if the session does not exist.
+/// * `Err(StatusCode::CONFLICT)` if `offset` does not match the committed size;
+///   the client should query `GET /upload/<id>` and resume from there.
+/// * `Err(StatusCode::BAD_REQUEST)` if the request body stream is invalid.
+/// * `Err(StatusCode::PAYLOAD_TOO_LARGE)` if the upload would exceed `max_file_size`;
+///   nothing of this chunk is committed.
+pub async fn upload_chunk(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<ChunkOffset>,
+    headers: HeaderMap,
+    mut body: Body
+) -> Result<Json<UploadStatus>, StatusCode> {
+    let (part_path, _) = upload_files(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let mut file = tokio::fs::OpenOptions::new()
+        .append(true)
+        .open(&part_path)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let committed = file.metadata().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?.len();
+    if query.offset != committed {
+        return Err(StatusCode::CONFLICT);
+    }
+    if content_length(&headers).is_some_and(|len| state.exceeds_max_file_size(committed + len)) {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    let mut written = committed;
+    while let Some(result) = body.frame().await {
+        let frame = result.map_err(|_| StatusCode::BAD_REQUEST)?;
+        if let Some(data) = frame.data_ref() {
+            // Bytes past the limit are never committed: the chunk is rolled back
+            written += data.len() as u64;
+            if state.exceeds_max_file_size(written) {
+                let _ = file.set_len(committed).await;
+                return Err(StatusCode::PAYLOAD_TOO_LARGE);
             }
+            file.write_all(data).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         }
-        Err(_) => StatusCode::NOT_FOUND,
     }
-}
\ No newline at end of file
+    file.flush().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let offset = file.metadata().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?.len();
+    Ok(Json(UploadStatus { id, offset }))
+}
+
+/// Handles `POST /upload/<id>/complete`.
+///
+/// Moves the assembled file to its destination (overwriting any existing file)
+/// and closes the session. Like `put_file`, it honours the optional
+/// `X-Atime`/`X-Mtime` headers. The file is a new one even if it replaces
+/// another, so it always gets `AppState::file_mode`, if configured.
+///
+/// # Returns
+/// * `StatusCode::OK` on success.
+/// * `StatusCode::NOT_FOUND` if the session does not exist.
+/// * `StatusCode::INTERNAL_SERVER_ERROR` if the file cannot be moved into place.
+pub async fn complete_upload(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap
+) -> StatusCode {
+    let Some((part_path, dest_path)) = upload_files(&id) else {
+        return StatusCode::NOT_FOUND;
+    };
+    let Ok(path) = fs::read_to_string(&dest_path) else {
+        return StatusCode::NOT_FOUND;
+    };
+
+    record_change(&state, &path, &headers);
+    if let Some(times) = upload_times(&headers) {
+        let applied = fs::File::options().write(true).open(&part_path).and_then(|f| f.set_times(times.to_file_times()));
+        if applied.is_err() {
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+    if apply_mode(&part_path, state.file_mode).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+    if state.storage.import(&part_path, &path).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+    let _ = fs::remove_file(&dest_path);
+    StatusCode::OK
+}
@@ -0,0 +1,84 @@
+//! The change journal: the paths the watcher saw change, most recent last.
+//!
+//! `main.rs` records every watcher event in the `ChangeJournal` held in
+//! `AppState::journal`, so `GET /changes-since` can answer from it in time
+//! proportional to the number of changes instead of walking the whole tree.
+//! The journal lives in memory only: after a restart it starts empty, and the
+//! older requests fall back to the tree walk.
+
+use std::collections::{HashSet, VecDeque};
+use std::time::SystemTime;
+
+/// What the watcher saw happen to a path.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChangeKind {
+    Create,
+    Modify,
+    Remove,
+    /// Both the source and the destination of a rename are recorded with this kind.
+    Rename,
+}
+
+/// One watcher event.
+#[derive(Debug)]
+pub struct JournalEntry {
+    /// Relative to `DATA_DIR`.
+    pub path: String,
+    /// When the watcher reported it.
+    pub time: SystemTime,
+    pub kind: ChangeKind,
+}
+
+/// A bounded ring buffer of the latest watcher events.
+///
+/// The journal is complete after `complete_after`: every change after that
+/// instant is in it. That is the start of the watcher at first, then the time
+/// of the last entry dropped to make room for a new one.
+pub struct ChangeJournal {
+    entries: VecDeque<JournalEntry>,
+    /// Maximum number of entries (`CHANGE_JOURNAL_CAPACITY`, `0` = disabled).
+    capacity: usize,
+    complete_after: SystemTime,
+}
+
+impl ChangeJournal {
+    /// Creates an empty journal of at most `capacity` entries, complete after
+    /// `started` (when the watcher that fills it started).
+    pub fn new(capacity: usize, started: SystemTime) -> Self {
+        ChangeJournal { entries: VecDeque::new(), capacity, complete_after: started }
+    }
+
+    /// Records that `path` changed now, dropping the oldest entry if the journal is full.
+    pub fn record(&mut self, path: String, kind: ChangeKind) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity
+            && let Some(dropped) = self.entries.pop_front()
+        {
+            self.complete_after = dropped.time;
+        }
+        self.entries.push_back(JournalEntry { path, time: SystemTime::now(), kind });
+    }
+
+    /// The distinct paths under `prefix` ("" for all) changed at or after `since`,
+    /// in order of their first change.
+    ///
+    /// # Returns
+    /// * `Some(paths)` if the journal covers `since`.
+    /// * `None` if `since` is not after `complete_after` (changes may be missing,
+    ///   the caller must look at the tree instead) or the journal is disabled.
+    pub fn changes_since(&self, since: SystemTime, prefix: &str) -> Option<Vec<String>> {
+        if self.capacity == 0 || since <= self.complete_after {
+            return None;
+        }
+        let start = self.entries.partition_point(|entry| entry.time < since);
+        let mut seen = HashSet::new();
+        let paths = self.entries.range(start..)
+            .filter(|entry| prefix.is_empty() || entry.path == prefix || entry.path.starts_with(&format!("{}/", prefix)))
+            .filter(|entry| seen.insert(entry.path.as_str()))
+            .map(|entry| entry.path.clone())
+            .collect();
+        Some(paths)
+    }
+}
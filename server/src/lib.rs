@@ -0,0 +1,249 @@
+//! The remote filesystem server, as a library.
+//!
+//! `main.rs` runs it on `DATA_DIR` with the watcher; the tests build the same
+//! `Router` with `app` over an `InMemoryBackend` and serve it on a free port.
+//! `spawn` does that in a single call, so that the client's tests can run a
+//! server in their own process too, over a temporary directory.
+
+// Declares the module containing all HTTP request handlers.
+pub mod handlers;
+// Declares the journal of the changes seen by the watcher, read by `/changes-since`.
+pub mod journal;
+// Declares the storage backends the handlers read and write the files through.
+pub mod storage;
+
+use axum::{
+    extract::{ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade}, ConnectInfo, State},
+    http::HeaderMap,
+    response::IntoResponse,
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use futures_util::{sink::SinkExt, stream::StreamExt};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock, atomic::{AtomicU64, Ordering}};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, Notify};
+use handlers::*;
+use tower_http::trace::TraceLayer;
+
+/// Interval between WebSocket `Ping` frames sent to each client.
+const WS_PING_INTERVAL: Duration = Duration::from_secs(15);
+/// A client that sends no frame (not even a `Pong`) for this long is disconnected.
+const WS_PONG_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// Default maximum size of an uploaded file (1 GiB), see `MAX_FILE_SIZE_BYTES`.
+pub const DEFAULT_MAX_FILE_SIZE_BYTES: u64 = 1024 * 1024 * 1024;
+/// Default free space below which `/healthz` fails (64 MiB), see `HEALTHZ_MIN_FREE_BYTES`.
+pub const DEFAULT_HEALTHZ_MIN_FREE_BYTES: u64 = 64 * 1024 * 1024;
+/// Default number of watcher events kept for `/changes-since`, see `CHANGE_JOURNAL_CAPACITY`.
+pub const DEFAULT_CHANGE_JOURNAL_CAPACITY: usize = 10_000;
+/// Default maximum number of components of a new path, see `MAX_PATH_DEPTH`.
+pub const DEFAULT_MAX_PATH_DEPTH: usize = 256;
+/// Default maximum length of a new name (`NAME_MAX` on Linux), see `MAX_NAME_LENGTH`.
+pub const DEFAULT_MAX_NAME_LENGTH: usize = 255;
+
+/// The handle of the global recorder of the `metrics` crate, rendered by `GET /metrics`.
+///
+/// The recorder is installed by the first call; every later call (e.g. another
+/// server started in the same process by a test) shares it.
+pub fn metrics_handle() -> PrometheusHandle {
+    static METRICS: OnceLock<PrometheusHandle> = OnceLock::new();
+    METRICS
+        .get_or_init(|| PrometheusBuilder::new().install_recorder().expect("Impossibile installare il recorder delle metriche"))
+        .clone()
+}
+
+/// Serves the routes of `app` over `state` on `listener`, until the process ends.
+///
+/// Every connection carries its remote address, read by `/ws` and `/admin/clients`.
+pub async fn serve(listener: TcpListener, state: AppState) -> std::io::Result<()> {
+    axum::serve(listener, app(state).into_make_service_with_connect_info::<SocketAddr>()).await
+}
+
+/// Starts a server over `state` in the current process, on a free port of `127.0.0.1`,
+/// and returns its address.
+///
+/// Meant for the tests: the server runs as a task of the current Tokio runtime,
+/// without the watcher (no `CHANGE` is broadcast, the journal stays empty).
+pub async fn spawn(state: AppState) -> std::io::Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        if let Err(e) = serve(listener, state).await {
+            eprintln!("[SERVER] Server di test terminato: {}", e);
+        }
+    });
+    Ok(addr)
+}
+
+/// Defines the application's routes over `state`.
+///
+/// `/ws` and `/admin/clients` read the remote address of each connection: serve
+/// the router with `into_make_service_with_connect_info::<SocketAddr>()`.
+pub fn app(state: AppState) -> Router {
+    Router::new()
+        // A simple health check endpoint, cheap enough for load balancers.
+        .route("/health", get(|| async { "OK" }))
+        // Deep health check: the data directory takes writes and has free space.
+        .route("/healthz", get(healthz))
+        // Prometheus metrics: requests per route and status class, bytes transferred, WebSocket clients.
+        .route("/metrics", get(render_metrics))
+        .route("/ws", get(websocket_handler))
+        // Administration (Bearer `ADMIN_TOKEN`): connected WebSocket clients, and forced disconnection.
+        .route("/admin/clients", get(list_clients))
+        .route("/admin/clients/:id/disconnect", post(disconnect_client))
+        // Advisory locks, held while the client's WebSocket stays connected.
+        .route("/lock/*path", post(acquire_lock).delete(release_lock))
+        // Routes for listing directory contents.
+        // Both `/list` (for root) and `/list/*path` (for subdirs)
+        // are handled by the same `list_directory_contents` handler.
+        .route("/list", get(list_directory_contents))
+        .route("/list/*path", get(list_directory_contents))
+        // Same listing as newline-delimited JSON, streamed while the directory is read.
+        .route("/list-stream", get(list_directory_stream))
+        .route("/list-stream/*path", get(list_directory_stream))
+        // Route for creating a new directory.
+        .route("/mkdir/*path", post(mkdir))
+        // Creates an empty file, failing if it already exists (used by the client's `create`).
+        .route("/touch/*path", post(touch))
+        // Routes for file operations (Read, Write, Delete, Chmod).
+        // All file-based operations are grouped under the `/files/` path.
+        // `HEAD` answers with the metadata only, without reading the file.
+        .route("/files/*path", get(get_file).head(head_file).put(put_file).delete(delete_file).patch(patch_file))
+        // SHA-256 of a file, to validate a cached copy without downloading it.
+        .route("/checksum/*path", get(checksum))
+        // Metadata of a single entry (`/stat` for the root), and timestamp updates (utimensat).
+        .route("/stat", get(stat_file))
+        .route("/stat/*path", get(stat_file))
+        .route("/stat-batch", post(stat_batch))
+        .route("/utimes/*path", post(utimes))
+        // Resize a file in place (truncate/ftruncate), without transferring its content.
+        .route("/truncate/*path", post(truncate_file))
+        // Write a block at an offset of an existing file (the client's write-through mode).
+        .route("/write/*path", post(write_at))
+        // Preallocate or punch holes in a file (fallocate).
+        .route("/fallocate/*path", post(fallocate))
+        // Server-side moves: plain rename, and atomic swap of two entries (renameat2 RENAME_EXCHANGE).
+        .route("/rename", post(rename))
+        .route("/exchange", post(exchange))
+        // Bulk operations: list a whole subtree, and delete many paths in one request.
+        .route("/tree/*path", get(tree))
+        .route("/delete-batch", post(delete_batch))
+        // Search by name (substring or glob) in a whole subtree.
+        .route("/search", get(search))
+        .route("/changes-since", get(changes_since))
+        // Resumable chunked uploads: open a session, append chunks at an offset, then move the file into place.
+        .route("/upload/start", post(start_upload))
+        .route("/upload/:id", get(upload_status).put(upload_chunk))
+        .route("/upload/:id/complete", post(complete_upload))
+        // Count every request in the metrics, by route and status class.
+        .layer(middleware::from_fn(track_requests))
+        // Apply a logging layer to trace all HTTP requests.
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        .with_state(state)
+}
+
+/// The tracing span of a request, logged by `TraceLayer` with every line about it.
+///
+/// Besides the method and the URI it carries the `X-Request-ID` sent by the client
+/// for each FUSE operation and its `User-Agent` (client version and id), so a
+/// request found in the client's logs can be found here, and vice versa.
+fn request_span(request: &axum::extract::Request) -> tracing::Span {
+    let header = |name: &str| request.headers().get(name).and_then(|v| v.to_str().ok()).unwrap_or("-");
+    tracing::debug_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id = header("X-Request-ID"),
+        user_agent = header("User-Agent"),
+    )
+}
+
+async fn websocket_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let client_id = headers.get("X-Client-ID").and_then(|v| v.to_str().ok()).map(str::to_string);
+    ws.on_upgrade(move |socket| websocket(socket, state, addr, client_id))
+}
+
+async fn websocket(stream: WebSocket, state: AppState, addr: SocketAddr, client_id: Option<String>) {
+    static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(0);
+    let (mut sender, mut receiver) = stream.split();
+    let mut rx = state.tx.subscribe();
+
+    // --- REGISTRO DEI CLIENT (GET /admin/clients) ---
+    let connection = NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed);
+    let client_id = client_id.unwrap_or_else(|| format!("anonimo-{}", connection));
+    let disconnect = Arc::new(Notify::new());
+    state.ws_clients.lock().unwrap().insert(client_id.clone(), WsClient {
+        connection,
+        addr,
+        connected_at: SystemTime::now(),
+        disconnect: disconnect.clone(),
+    });
+    println!("[WEBSOCKET] Client '{}' connesso da {}.", client_id, addr);
+
+    // --- KEEPALIVE ---
+    // Un Ping periodico verso il client: se non arriva nessun frame (nemmeno il Pong)
+    // entro WS_PONG_TIMEOUT, la connessione è considerata morta e viene chiusa.
+    let mut ping_interval = tokio::time::interval(WS_PING_INTERVAL);
+    let mut last_seen = Instant::now();
+
+    loop {
+        tokio::select! {
+            event = rx.recv() => match event {
+                Ok(msg) => {
+                    if sender.send(Message::Text(msg)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    // Le notifiche perse non si possono recuperare da qui: chiudendo la
+                    // connessione il client si riconnette e rilegge le modifiche con /changes-since
+                    println!("[WEBSOCKET] Client '{}' lento, {} notifiche perse: chiudo la connessione.", client_id, skipped);
+                    let frame = CloseFrame { code: close_code::AGAIN, reason: "notifications lost, resync".into() };
+                    let _ = sender.send(Message::Close(Some(frame))).await;
+                    break;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            incoming = receiver.next() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Pong, Ping (a cui axum risponde da solo) o altro: il client è vivo
+                Some(Ok(_)) => last_seen = Instant::now(),
+            },
+            _ = ping_interval.tick() => {
+                if last_seen.elapsed() > WS_PONG_TIMEOUT {
+                    println!("[WEBSOCKET] Nessun pong da {:?}, chiudo la connessione.", last_seen.elapsed());
+                    break;
+                }
+                if sender.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+            _ = disconnect.notified() => {
+                let _ = sender.send(Message::Close(None)).await;
+                break;
+            }
+        }
+    }
+
+    // Solo se nel frattempo il client non si è riconnesso con lo stesso id
+    let mut clients = state.ws_clients.lock().unwrap();
+    if clients.get(&client_id).is_some_and(|c| c.connection == connection) {
+        clients.remove(&client_id);
+    }
+    // I lock presi con questa connessione muoiono con lei (`ws_clients` è ancora bloccato, vedi `acquire_lock`)
+    let released = release_client_locks(&state, &client_id, connection);
+    if released > 0 {
+        println!("[LOCK] Rilasciati {} lock del client '{}'.", released, client_id);
+    }
+    println!("[WEBSOCKET] Client '{}' disconnesso.", client_id);
+}
\ No newline at end of file
@@ -1,30 +1,38 @@
 //! The main entry point for the remote filesystem server.
 //!
 //! This binary initializes the Axum web server, sets up logging/tracing,
-//! and defines all the API routes required by the FUSE client.
+//! and serves the API routes required by the FUSE client (defined by `server::app`,
+//! served by `server::serve`).
 //! All route logic is forwarded to functions in the `handlers` module.
 
-// Declares the module containing all HTTP request handlers.
-
-mod handlers;
-
-use axum::{
-    extract::{ws::{Message, WebSocket, WebSocketUpgrade}, State},
-    response::IntoResponse,
-    routing::{get, put, post, delete,patch},
-    Router,
-};
-use futures_util::{sink::SinkExt, stream::StreamExt};
-use notify::{RecursiveMode, Watcher};
+use notify::{event::{ModifyKind, RenameMode}, EventKind, RecursiveMode, Watcher};
 use std::{collections::HashMap, sync::{Arc, Mutex}};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 use std::net::SocketAddr;
 use std::fs;
-use std::time::Duration;
-use handlers::*; 
-use tower_http::trace::TraceLayer;
+use std::time::{Duration, Instant, SystemTime};
+use server::handlers::*;
+use server::journal::{ChangeJournal, ChangeKind};
+use server::storage;
+use server::{DEFAULT_CHANGE_JOURNAL_CAPACITY, DEFAULT_HEALTHZ_MIN_FREE_BYTES, DEFAULT_MAX_FILE_SIZE_BYTES, DEFAULT_MAX_NAME_LENGTH, DEFAULT_MAX_PATH_DEPTH};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+/// Default debounce window for watcher events, overridable with `WATCHER_DEBOUNCE_MS`.
+const DEFAULT_DEBOUNCE_MS: u64 = 100;
+/// Reads an octal permission mode (e.g. `640` or `0640`) from the environment variable `name`.
+///
+/// An unset variable means no mode; an invalid one is reported and ignored.
+fn octal_env(name: &str) -> Option<u32> {
+    let value = std::env::var(name).ok()?;
+    match u32::from_str_radix(value.trim(), 8) {
+        Ok(mode) if mode <= 0o7777 => Some(mode),
+        _ => {
+            eprintln!("[SERVER] {} non valido ({:?}): atteso un permesso ottale come 0640, ignorato", name, value);
+            None
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
     // Ensure the data directory exists.
@@ -32,6 +40,9 @@ async fn main() {
     if let Err(e) = fs::create_dir_all(manifest_dir.to_owned() + "/data"){
         println!("Warning: Could not create data directory: {}", e);
     }
+    if let Err(e) = fs::create_dir_all(UPLOAD_DIR) {
+        println!("Warning: Could not create uploads directory: {}", e);
+    }
     // Initialize the logging and tracing subscriber.
     // Uses `RUST_LOG` env var or defaults to "server=debug,tower_http=debug".
     tracing_subscriber::registry()
@@ -44,46 +55,91 @@ async fn main() {
          // --- LOGICA DEL WATCHER E WEBSOCKET ---
     let (tx, _) = broadcast::channel(100);
     let recent_mods = Arc::new(Mutex::new(HashMap::new()));
+
+    // Uploads over this size are refused with `413` (`0` = unlimited)
+    let max_file_size = std::env::var("MAX_FILE_SIZE_BYTES").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_MAX_FILE_SIZE_BYTES);
+    println!("[SERVER] Dimensione massima dei file: {} byte", max_file_size);
+
+    // `/healthz` fails below this free space (`0` = not checked)
+    let min_free_bytes = std::env::var("HEALTHZ_MIN_FREE_BYTES").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_HEALTHZ_MIN_FREE_BYTES);
+
+    // Watcher events kept for `/changes-since` (`0` = always walk the tree)
+    let journal_capacity = std::env::var("CHANGE_JOURNAL_CAPACITY").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_CHANGE_JOURNAL_CAPACITY);
+    println!("[SERVER] Journal delle modifiche: {} eventi", journal_capacity);
+    // Complete from now: the watcher is started below
+    let journal = Arc::new(Mutex::new(ChangeJournal::new(journal_capacity, SystemTime::now())));
+
+    // New paths deeper or with longer names are refused with `400` (`0` = unlimited)
+    let max_path_depth = std::env::var("MAX_PATH_DEPTH").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_MAX_PATH_DEPTH);
+    let max_name_length = std::env::var("MAX_NAME_LENGTH").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_MAX_NAME_LENGTH);
+    println!("[SERVER] Limiti dei path: {} livelli, nomi di {} byte", max_path_depth, max_name_length);
+
+    // Permissions of the files/directories created by the server, instead of the umask
+    let file_mode = octal_env("FILE_MODE");
+    let dir_mode = octal_env("DIR_MODE");
+    println!("[SERVER] Permessi dei nuovi file: {:?}, delle nuove directory: {:?}", file_mode.map(|m| format!("{:o}", m)), dir_mode.map(|m| format!("{:o}", m)));
    
+    // Files are stored on the local disk, under `DATA_DIR`
     let app_state = AppState { 
         tx: Arc::new(tx),
         recent_mods: recent_mods.clone(),
+        max_file_size,
+        file_mode,
+        dir_mode,
+        // The `/admin` endpoints are disabled without a token
+        admin_token: std::env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+        min_free_bytes,
+        journal: journal.clone(),
+        max_path_depth,
+        max_name_length,
+        ..AppState::new(Arc::new(storage::LocalFsBackend::new(DATA_DIR)))
     };
 
-    let watcher_tx = app_state.tx.clone();
     let watcher_mods = recent_mods.clone();
 
+    // Events from the watcher go through a debounce task before being broadcast,
+    // so a burst of events on the same path (e.g. a large `cp`) becomes one `CHANGE`.
+    let debounce_window = Duration::from_millis(
+        std::env::var("WATCHER_DEBOUNCE_MS").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_DEBOUNCE_MS),
+    );
+    println!("[WATCHER] Finestra di debounce: {:?}", debounce_window);
+    let (event_tx, event_rx) = mpsc::unbounded_channel::<(String, String)>();
+    tokio::spawn(debounce_events(event_rx, app_state.tx.clone(), debounce_window));
+
     tokio::spawn(async move {
         let mut watcher = match notify::recommended_watcher(move |res: Result<notify::Event, notify::Error>| {
             if let Ok(event) = res {
+                // Rinomina con origine e destinazione note: un unico evento RENAME,
+                // così i client possono spostare gli inode invece di perderli.
+                if let (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) = (&event.kind, event.paths.as_slice())
+                    && let (Ok(from), Ok(to)) = (from.strip_prefix(DATA_DIR), to.strip_prefix(DATA_DIR))
+                {
+                    let from_str = from.to_string_lossy().to_string();
+                    let to_str = to.to_string_lossy().to_string();
+                    let source_tag = lookup_source_tag(&watcher_mods, &to_str);
+                    {
+                        let mut journal = journal.lock().unwrap();
+                        journal.record(from_str.clone(), ChangeKind::Rename);
+                        journal.record(to_str.clone(), ChangeKind::Rename);
+                    }
+
+                    let msg = format!("RENAME:{}|TO:{}{}", from_str, to_str, source_tag);
+                    println!("[WATCHER] Rilevata rinomina: {}", msg);
+                    let _ = event_tx.send((format!("{}|TO:{}", from_str, to_str), msg));
+                    return;
+                }
+
                 for path in event.paths {
                     if let Ok(relative_path) = path.strip_prefix(DATA_DIR) {
                         let path_str = relative_path.to_string_lossy().to_string();
-                        
-                        // --- LOGICA DI FIRMA CON DEBUG ---
-                         let mut source_tag = String::new();
-                        {
-                            let mut mods = watcher_mods.lock().unwrap();
-                            
-                            println!("[DEBUG WATCHER] Cerco chiave '{}' nella mappa...", path_str);
-                            
-                            if let Some((client_id, time)) = mods.get(&path_str) {
-                                if time.elapsed() < Duration::from_millis(500) {
-                                    source_tag = format!("|BY:{}", client_id);
-                                    println!("[DEBUG WATCHER] TROVATO! Modifica di {}", client_id);
-                                } else {
-                                    println!("[DEBUG WATCHER] Trovato ma SCADUTO (>500ms)");
-                                }
-                            } else {
-                                println!("[DEBUG WATCHER] Chiave '{}' NON trovata. Chiavi presenti: {:?}", path_str, mods.keys());
-                            }
-                            
-                            mods.retain(|_, (_, t)| t.elapsed() < Duration::from_secs(5));
+                        let source_tag = lookup_source_tag(&watcher_mods, &path_str);
+                        if let Some(kind) = change_kind(&event.kind) {
+                            journal.lock().unwrap().record(path_str.clone(), kind);
                         }
-                        
+
                         let msg = format!("CHANGE:{}{}", path_str, source_tag);
                         println!("[WATCHER] Rilevato cambiamento: {}", msg);
-                        let _ = watcher_tx.send(msg);
+                        let _ = event_tx.send((path_str, msg));
                     }
                 }
             }
@@ -103,59 +159,90 @@ async fn main() {
         println!("[WATCHER] Watcher del filesystem avviato sulla directory: {}", DATA_DIR);
         std::future::pending::<()>().await;
     });
-    // Define the application's routes.
-    let app = Router::new()
-    // A simple health check endpoint.
-        .route("/health", get(|| async { "OK" }))
-        .route("/ws", get(websocket_handler))
-        // Routes for listing directory contents.
-        // Both `/list` (for root) and `/list/*path` (for subdirs)
-        // are handled by the same `list_directory_contents` handler.
-        .route("/list", get(list_directory_contents))
-        .route("/list/*path", get(list_directory_contents))
-         // Route for creating a new directory.
-        .route("/mkdir/*path", post(mkdir))
-        // Routes for file operations (Read, Write, Delete, Chmod).
-        // All file-based operations are grouped under the `/files/` path.
-        .route("/files/*path", get(get_file).put(put_file).delete(delete_file).patch(patch_file))
-        // Apply a logging layer to trace all HTTP requests.
-        .layer(TraceLayer::new_for_http())
-        .with_state(app_state);
-
     let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
     tracing::debug!("listening on {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    server::serve(listener, app_state).await.unwrap();
 }
 
-async fn websocket_handler(
-    ws: WebSocketUpgrade,
-    State(state): State<AppState>,
-) -> impl IntoResponse {
-    ws.on_upgrade(|socket| websocket(socket, state))
+/// The kind of change recorded in the journal for a watcher event, or `None`
+/// for the events that change nothing (e.g. a file opened or read).
+fn change_kind(kind: &EventKind) -> Option<ChangeKind> {
+    match kind {
+        EventKind::Create(_) => Some(ChangeKind::Create),
+        EventKind::Remove(_) => Some(ChangeKind::Remove),
+        // Una sola metà della rinomina (l'altra è fuori da DATA_DIR o non è stata abbinata)
+        EventKind::Modify(ModifyKind::Name(_)) => Some(ChangeKind::Rename),
+        EventKind::Modify(_) | EventKind::Any | EventKind::Other => Some(ChangeKind::Modify),
+        EventKind::Access(_) => None,
+    }
 }
 
-async fn websocket(stream: WebSocket, state: AppState) {
-    let (mut sender, mut receiver) = stream.split();
-    let mut rx = state.tx.subscribe();
+/// Returns the `|BY:<client-id>` tag for `path_str` if a client modified it
+/// within the last 500ms, or an empty string otherwise.
+///
+/// Also prunes entries older than 5 seconds from `mods`.
+fn lookup_source_tag(mods: &Mutex<HashMap<String, (String, Instant)>>, path_str: &str) -> String {
+    // --- LOGICA DI FIRMA CON DEBUG ---
+    let mut source_tag = String::new();
+    let mut mods = mods.lock().unwrap();
 
-    let mut send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
-            if sender.send(Message::Text(msg)).await.is_err() {
-                break;
-            }
+    println!("[DEBUG WATCHER] Cerco chiave '{}' nella mappa...", path_str);
+
+    if let Some((client_id, time)) = mods.get(path_str) {
+        if time.elapsed() < Duration::from_millis(500) {
+            source_tag = format!("|BY:{}", client_id);
+            println!("[DEBUG WATCHER] TROVATO! Modifica di {}", client_id);
+        } else {
+            println!("[DEBUG WATCHER] Trovato ma SCADUTO (>500ms)");
         }
-    });
+    } else {
+        println!("[DEBUG WATCHER] Chiave '{}' NON trovata. Chiavi presenti: {:?}", path_str, mods.keys());
+    }
+
+    mods.retain(|_, (_, t)| t.elapsed() < Duration::from_secs(5));
+    source_tag
+}
 
-    let mut recv_task = tokio::spawn(async move {
-        while let Some(Ok(Message::Close(_))) = receiver.next().await {
-            break;
+/// Coalesces watcher events before broadcasting them to the WebSocket clients.
+///
+/// The first event on an idle channel opens a window of `window`; every event
+/// received until it closes is merged per key (the path, or the `from|TO:to` pair
+/// for renames), keeping the latest message (and thus the latest `|BY:` tag).
+/// One message per key is then broadcast, in order of first arrival, so that
+/// chained renames (`a -> b`, `b -> c`) reach the clients in sequence.
+/// A zero `window` disables debouncing.
+async fn debounce_events(
+    mut rx: mpsc::UnboundedReceiver<(String, String)>,
+    tx: Arc<broadcast::Sender<String>>,
+    window: Duration,
+) {
+    while let Some((path, msg)) = rx.recv().await {
+        // Messages in order of first arrival, and the position of each key among them,
+        // so that merging an event costs O(1) even during a storm of thousands of paths
+        let mut pending: Vec<String> = vec![msg];
+        let mut positions: HashMap<String, usize> = HashMap::from([(path, 0)]);
+
+        let deadline = tokio::time::sleep(window);
+        tokio::pin!(deadline);
+        loop {
+            tokio::select! {
+                _ = &mut deadline => break,
+                event = rx.recv() => match event {
+                    Some((path, msg)) => match positions.get(&path) {
+                        Some(&index) => pending[index] = msg,
+                        None => {
+                            positions.insert(path, pending.len());
+                            pending.push(msg);
+                        }
+                    },
+                    None => break,
+                },
+            }
         }
-    });
 
-    tokio::select! {
-        _ = (&mut send_task) => recv_task.abort(),
-        _ = (&mut recv_task) => send_task.abort(),
-    };
-    println!("[WEBSOCKET] Client disconnesso.");
-}
\ No newline at end of file
+        for msg in pending {
+            let _ = tx.send(msg);
+        }
+    }
+}
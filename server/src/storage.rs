@@ -0,0 +1,821 @@
+//! Where the server keeps the files.
+//!
+//! The handlers never touch the disk directly: they go through the
+//! `StorageBackend` held in `AppState::storage`. `LocalFsBackend` stores the
+//! files in a local directory (`DATA_DIR`); `InMemoryBackend` keeps them in
+//! memory, for the tests. Other backends (e.g. S3) only need to implement the trait.
+//!
+//! Every path is relative to the root of the storage, as received in the URLs.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Seek, SeekFrom};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite};
+
+/// `fallocate(2)` mode flags accepted by `StorageBackend::fallocate`.
+pub const FALLOC_FL_KEEP_SIZE: i32 = 0x01;
+pub const FALLOC_FL_PUNCH_HOLE: i32 = 0x02;
+
+/// The type of an entry, as reported to clients in `kind`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+}
+
+impl EntryKind {
+    /// The value of `kind` in the API (`"file"`, `"directory"` or `"symlink"`).
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntryKind::File => "file",
+            EntryKind::Directory => "directory",
+            EntryKind::Symlink => "symlink",
+        }
+    }
+}
+
+/// The metadata of an entry, independent of where it is stored.
+pub struct EntryMetadata {
+    pub kind: EntryKind,
+    pub size: u64,
+    /// 512-byte blocks actually allocated (`st_blocks`).
+    pub blocks: u64,
+    pub modified: SystemTime,
+    /// Creation (birth) time, where the filesystem records it (`statx` `btime`).
+    pub created: Option<SystemTime>,
+    /// Permission bits (`st_mode & 0o7777`).
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub ino: u64,
+    /// For symlinks read with `lstat`: the path they point to.
+    pub target: Option<String>,
+    /// For symlinks read with `lstat`: whether the target does not resolve.
+    pub dangling: bool,
+}
+
+impl EntryMetadata {
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Directory
+    }
+}
+
+impl From<&fs::Metadata> for EntryMetadata {
+    fn from(metadata: &fs::Metadata) -> Self {
+        let kind = if metadata.is_dir() {
+            EntryKind::Directory
+        } else if metadata.is_symlink() {
+            EntryKind::Symlink
+        } else {
+            EntryKind::File
+        };
+        EntryMetadata {
+            kind,
+            size: metadata.len(),
+            blocks: metadata.blocks(),
+            modified: metadata.modified().unwrap_or(UNIX_EPOCH),
+            created: metadata.created().ok(),
+            mode: metadata.permissions().mode() & 0o7777,
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            ino: metadata.ino(),
+            target: None,
+            dangling: false,
+        }
+    }
+}
+
+/// Access and modification times to set on an entry. `None` leaves that time unchanged.
+#[derive(Clone, Copy, Default)]
+pub struct EntryTimes {
+    pub accessed: Option<SystemTime>,
+    pub modified: Option<SystemTime>,
+}
+
+impl EntryTimes {
+    /// The same times, for `fs::File::set_times`.
+    pub fn to_file_times(self) -> fs::FileTimes {
+        let mut times = fs::FileTimes::new();
+        if let Some(accessed) = self.accessed {
+            times = times.set_accessed(accessed);
+        }
+        if let Some(modified) = self.modified {
+            times = times.set_modified(modified);
+        }
+        times
+    }
+}
+
+/// Content of a file opened for reading: seekable, for range requests.
+pub trait ReadSeek: AsyncRead + AsyncSeek + Send {}
+impl<T: AsyncRead + AsyncSeek + Send> ReadSeek for T {}
+
+pub type FileReader = Pin<Box<dyn ReadSeek>>;
+pub type FileWriter = Pin<Box<dyn AsyncWrite + Send>>;
+
+/// The entries of a directory, as `(name, kind)`, produced while it is read.
+pub type DirEntries = Box<dyn Iterator<Item = (String, EntryKind)> + Send>;
+
+/// Storage of the files served by the server.
+///
+/// The methods are blocking, like the `std::fs` calls they replace: the
+/// handlers that may run long (listings, search) call them on a blocking
+/// thread. A missing path is reported as `io::ErrorKind::NotFound`, an
+/// existing one as `io::ErrorKind::AlreadyExists`, so the handlers can map
+/// the errors to status codes the same way for every backend.
+pub trait StorageBackend: Send + Sync {
+    /// Metadata of `path`, following symlinks.
+    fn stat(&self, path: &str) -> io::Result<EntryMetadata>;
+    /// Metadata of `path` itself, without following symlinks. A symlink has
+    /// kind `Symlink`, its `target`, and `dangling` if the target does not resolve.
+    fn lstat(&self, path: &str) -> io::Result<EntryMetadata>;
+    /// The entries of the directory `path`, sorted by name (byte order), so that two
+    /// listings or pages of an unchanged directory see the same order. Symlinks are not followed.
+    fn list(&self, path: &str) -> io::Result<DirEntries>;
+    /// Opens the file `path` for reading.
+    fn read(&self, path: &str) -> io::Result<FileReader>;
+    /// Creates `path`, or empties it if it exists, and opens it for writing.
+    /// A file that did not exist gets `mode`, if given.
+    fn write(&self, path: &str, mode: Option<u32>) -> io::Result<FileWriter>;
+    /// Opens the existing file `path` for writing from `offset`, keeping the rest
+    /// of its content. Writing past the end extends it (the gap reads as zeros).
+    fn write_at(&self, path: &str, offset: u64) -> io::Result<FileWriter>;
+    /// Creates the empty file `path`, failing if it already exists. It gets `mode`, if given.
+    fn create(&self, path: &str, mode: Option<u32>) -> io::Result<()>;
+    /// Creates the directory `path` and, with `parents`, the missing parents
+    /// (an existing directory is then not an error). Every directory created gets `mode`, if given.
+    fn mkdir(&self, path: &str, parents: bool, mode: Option<u32>) -> io::Result<()>;
+    /// Deletes `path`. A directory must be empty unless `recursive`. Symlinks
+    /// are deleted, never followed.
+    fn delete(&self, path: &str, recursive: bool) -> io::Result<()>;
+    /// Moves `from` to `to` atomically, replacing an existing file at `to`.
+    fn rename(&self, from: &str, to: &str) -> io::Result<()>;
+    /// Swaps two existing entries atomically.
+    fn exchange(&self, a: &str, b: &str) -> io::Result<()>;
+    /// Sets the permission bits of `path`.
+    fn set_permissions(&self, path: &str, mode: u32) -> io::Result<()>;
+    /// Sets the owner and/or group of `path`. `None` leaves that id unchanged.
+    fn set_owner(&self, path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()>;
+    /// Sets the access and/or modification time of `path`.
+    fn set_times(&self, path: &str, times: EntryTimes) -> io::Result<()>;
+    /// Resizes the file `path`, dropping the tail or extending it with zeros.
+    fn truncate(&self, path: &str, size: u64) -> io::Result<()>;
+    /// Applies `fallocate(2)` with `mode` to a range of the file `path`.
+    /// An unsupported mode fails with `io::ErrorKind::Unsupported`.
+    fn fallocate(&self, path: &str, mode: i32, offset: u64, len: u64) -> io::Result<()>;
+    /// Moves the local file `staged` (an assembled chunked upload) to `path`,
+    /// replacing an existing file.
+    fn import(&self, staged: &str, path: &str) -> io::Result<()>;
+    /// Total and available bytes of the space holding the files, if it is bounded.
+    fn space(&self) -> Option<(u64, u64)>;
+}
+
+/// Stores the files in a directory of the local filesystem.
+pub struct LocalFsBackend {
+    root: String,
+}
+
+impl LocalFsBackend {
+    /// A backend over the directory `root`, which must exist.
+    pub fn new(root: impl Into<String>) -> Self {
+        LocalFsBackend { root: root.into() }
+    }
+
+    /// The path on disk of the relative `path`.
+    ///
+    /// An absolute path or a `..` component could reach outside `root`: refused
+    /// with `InvalidInput`, whatever the handler that asked (see `is_relative_path`).
+    fn full_path(&self, path: &str) -> io::Result<String> {
+        if !is_relative_path(path) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("path fuori dalla directory dei dati: {}", path)));
+        }
+        Ok(format!("{}/{}", self.root, path))
+    }
+}
+
+/// Whether `path` stays inside the directory it is relative to: it is not
+/// absolute and has no `..` component.
+pub fn is_relative_path(path: &str) -> bool {
+    !path.starts_with('/') && !path.split('/').any(|component| component == "..")
+}
+
+/// Sets the permissions of `path` to `mode`, if one is configured (see
+/// `AppState::file_mode`). `set_permissions` is not subject to the umask.
+pub fn apply_mode(path: &str, mode: Option<u32>) -> io::Result<()> {
+    match mode {
+        Some(mode) => fs::set_permissions(path, fs::Permissions::from_mode(mode)),
+        None => Ok(()),
+    }
+}
+
+impl StorageBackend for LocalFsBackend {
+    fn stat(&self, path: &str) -> io::Result<EntryMetadata> {
+        fs::metadata(self.full_path(path)?).map(|m| EntryMetadata::from(&m))
+    }
+
+    /// The target of a link is resolved with a single `metadata` call: the
+    /// kernel bounds the resolution and returns `ELOOP` on a cycle.
+    fn lstat(&self, path: &str) -> io::Result<EntryMetadata> {
+        let full_path = self.full_path(path)?;
+        let metadata = fs::symlink_metadata(&full_path)?;
+        let mut entry = EntryMetadata::from(&metadata);
+        if metadata.is_symlink() {
+            entry.target = fs::read_link(&full_path).ok().map(|target| target.to_string_lossy().to_string());
+            entry.dangling = fs::metadata(&full_path).is_err();
+        }
+        Ok(entry)
+    }
+
+    /// `read_dir` returns the entries in an order that depends on the filesystem
+    /// and may change between calls: they are collected (names only, the metadata
+    /// is read later by the handlers) and sorted.
+    fn list(&self, path: &str) -> io::Result<DirEntries> {
+        let read_dir = fs::read_dir(self.full_path(path)?)?;
+        let mut entries: Vec<(String, EntryKind)> = read_dir.flatten().map(|entry| {
+            // `file_type` does not follow symlinks
+            let file_type = entry.file_type().ok();
+            let kind = if file_type.is_some_and(|t| t.is_dir()) {
+                EntryKind::Directory
+            } else if file_type.is_some_and(|t| t.is_symlink()) {
+                EntryKind::Symlink
+            } else {
+                EntryKind::File
+            };
+            (entry.file_name().to_string_lossy().to_string(), kind)
+        }).collect();
+        entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn read(&self, path: &str) -> io::Result<FileReader> {
+        let file = fs::File::open(self.full_path(path)?)?;
+        Ok(Box::pin(tokio::fs::File::from_std(file)))
+    }
+
+    fn write(&self, path: &str, mode: Option<u32>) -> io::Result<FileWriter> {
+        let full_path = self.full_path(path)?;
+        let created = !std::path::Path::new(&full_path).exists();
+        let file = fs::File::create(&full_path)?;
+        if created {
+            apply_mode(&full_path, mode)?;
+        }
+        Ok(Box::pin(tokio::fs::File::from_std(file)))
+    }
+
+    fn write_at(&self, path: &str, offset: u64) -> io::Result<FileWriter> {
+        let mut file = fs::File::options().write(true).open(self.full_path(path)?)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(Box::pin(tokio::fs::File::from_std(file)))
+    }
+
+    fn create(&self, path: &str, mode: Option<u32>) -> io::Result<()> {
+        let full_path = self.full_path(path)?;
+        fs::File::options().write(true).create_new(true).open(&full_path)?;
+        apply_mode(&full_path, mode)
+    }
+
+    fn mkdir(&self, path: &str, parents: bool, mode: Option<u32>) -> io::Result<()> {
+        let full_path = self.full_path(path)?;
+        // The directories this call creates, to apply `mode` to
+        let mut missing = std::path::Path::new(path)
+            .ancestors()
+            .map(|ancestor| self.full_path(&ancestor.to_string_lossy()))
+            .collect::<io::Result<Vec<String>>>()?;
+        missing.retain(|dir| !std::path::Path::new(dir).exists());
+        if parents {
+            fs::create_dir_all(&full_path)?;
+        } else {
+            fs::create_dir(&full_path)?;
+        }
+        missing.iter().try_for_each(|dir| apply_mode(dir, mode))
+    }
+
+    fn delete(&self, path: &str, recursive: bool) -> io::Result<()> {
+        let full_path = self.full_path(path)?;
+        match fs::symlink_metadata(&full_path)? {
+            meta if meta.is_dir() && recursive => fs::remove_dir_all(&full_path),
+            meta if meta.is_dir() => fs::remove_dir(&full_path),
+            _ => fs::remove_file(&full_path),
+        }
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        fs::rename(self.full_path(from)?, self.full_path(to)?)
+    }
+
+    fn exchange(&self, a: &str, b: &str) -> io::Result<()> {
+        exchange_paths(&self.full_path(a)?, &self.full_path(b)?)
+    }
+
+    fn set_permissions(&self, path: &str, mode: u32) -> io::Result<()> {
+        fs::set_permissions(self.full_path(path)?, fs::Permissions::from_mode(mode))
+    }
+
+    /// Without privileges, only the group can be changed, to one of the server's user.
+    fn set_owner(&self, path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+        std::os::unix::fs::chown(self.full_path(path)?, uid, gid)
+    }
+
+    fn set_times(&self, path: &str, times: EntryTimes) -> io::Result<()> {
+        fs::File::open(self.full_path(path)?)?.set_times(times.to_file_times())
+    }
+
+    fn truncate(&self, path: &str, size: u64) -> io::Result<()> {
+        fs::File::options().write(true).open(self.full_path(path)?)?.set_len(size)
+    }
+
+    fn fallocate(&self, path: &str, mode: i32, offset: u64, len: u64) -> io::Result<()> {
+        let file = fs::File::options().write(true).open(self.full_path(path)?)?;
+        fallocate_file(&file, mode, offset, len)
+    }
+
+    fn import(&self, staged: &str, path: &str) -> io::Result<()> {
+        fs::rename(staged, self.full_path(path)?)
+    }
+
+    /// The size and free space of the filesystem holding `root`.
+    fn space(&self) -> Option<(u64, u64)> {
+        let path = std::ffi::CString::new(self.root.as_str()).ok()?;
+        // SAFETY: `path` is a valid, NUL-terminated `CString`, and `statvfs` is plain data.
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return None;
+        }
+        let fragment = stat.f_frsize as u64;
+        Some((stat.f_blocks as u64 * fragment, stat.f_bavail as u64 * fragment))
+    }
+}
+
+/// Calls `fallocate(2)` on `file`.
+#[cfg(target_os = "linux")]
+fn fallocate_file(file: &fs::File, mode: i32, offset: u64, len: u64) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    // SAFETY: the descriptor belongs to `file`, which stays open for the whole call.
+    let res = unsafe { libc::fallocate(file.as_raw_fd(), mode, offset as libc::off_t, len as libc::off_t) };
+    if res == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+}
+
+/// Emulates `fallocate(2)` where it is not available.
+///
+/// Preallocation only extends the file (with `set_len`, so no space is actually
+/// reserved); punching holes is not supported.
+#[cfg(not(target_os = "linux"))]
+fn fallocate_file(file: &fs::File, mode: i32, offset: u64, len: u64) -> io::Result<()> {
+    match mode {
+        0 if offset + len > file.metadata()?.len() => file.set_len(offset + len),
+        0 | FALLOC_FL_KEEP_SIZE => Ok(()),
+        _ => Err(io::ErrorKind::Unsupported.into()),
+    }
+}
+
+/// Swaps two paths with a single `renameat2(RENAME_EXCHANGE)` call.
+#[cfg(target_os = "linux")]
+fn exchange_paths(a: &str, b: &str) -> io::Result<()> {
+    let a = std::ffi::CString::new(a)?;
+    let b = std::ffi::CString::new(b)?;
+    // SAFETY: both pointers come from valid, NUL-terminated `CString`s that outlive the call.
+    let res = unsafe {
+        libc::renameat2(libc::AT_FDCWD, a.as_ptr(), libc::AT_FDCWD, b.as_ptr(), libc::RENAME_EXCHANGE)
+    };
+    if res == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+}
+
+/// Swaps two paths through a temporary name.
+///
+/// `renameat2` is Linux-only: elsewhere the swap is done with three renames,
+/// which is not atomic if the server crashes halfway through.
+#[cfg(not(target_os = "linux"))]
+fn exchange_paths(a: &str, b: &str) -> io::Result<()> {
+    let tmp = format!("{}.exchange-tmp", a);
+    fs::rename(a, &tmp)?;
+    fs::rename(b, a)?;
+    fs::rename(&tmp, b)
+}
+
+/// An entry of `InMemoryBackend`: a file with its content, or a directory.
+struct MemoryNode {
+    /// `None` for a directory.
+    content: Option<Vec<u8>>,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    modified: SystemTime,
+    created: SystemTime,
+    ino: u64,
+}
+
+type MemoryNodes = Arc<Mutex<BTreeMap<String, MemoryNode>>>;
+
+/// Keeps the files in memory, keyed by their normalized path (`""` is the root).
+///
+/// Meant for the tests: nothing touches `DATA_DIR`, and every instance starts
+/// from an empty root. It has no symlinks, and no sparse files (`blocks` is
+/// always the size rounded up to 512 bytes).
+pub struct InMemoryBackend {
+    nodes: MemoryNodes,
+    next_ino: AtomicU64,
+}
+
+impl Default for InMemoryBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemoryBackend {
+    /// An empty backend, with only the root directory.
+    pub fn new() -> Self {
+        let backend = InMemoryBackend { nodes: Arc::new(Mutex::new(BTreeMap::new())), next_ino: AtomicU64::new(1) };
+        let root = backend.node(None, Some(0o755));
+        backend.nodes.lock().unwrap().insert(String::new(), root);
+        backend
+    }
+
+    /// Stores the file `path` with `content`, creating the missing parents.
+    /// Used to seed the fixtures of a test.
+    pub fn insert_file(&self, path: &str, content: impl Into<Vec<u8>>) -> io::Result<()> {
+        let path = normalize(path);
+        if let Some((parent, _)) = path.rsplit_once('/') {
+            self.mkdir(parent, true, None)?;
+        }
+        let node = self.node(Some(content.into()), None);
+        self.nodes.lock().unwrap().insert(path, node);
+        Ok(())
+    }
+
+    /// A new entry with the next inode number; without `mode`, the permissions
+    /// the umask `022` would leave.
+    fn node(&self, content: Option<Vec<u8>>, mode: Option<u32>) -> MemoryNode {
+        let default_mode = if content.is_some() { 0o644 } else { 0o755 };
+        MemoryNode {
+            content,
+            mode: mode.unwrap_or(default_mode),
+            // The files belong to whoever runs the server, as on disk
+            // SAFETY: `getuid`/`getgid` cannot fail.
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            modified: SystemTime::now(),
+            created: SystemTime::now(),
+            ino: self.next_ino.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+/// `path` without empty and `.` components, so that `"a//b/"` and `"a/b"` are the same key.
+fn normalize(path: &str) -> String {
+    path.split('/').filter(|c| !c.is_empty() && *c != ".").collect::<Vec<_>>().join("/")
+}
+
+/// The parent of a normalized path (the root is its own parent).
+fn parent_of(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+/// Whether `path` is `dir` or one of its descendants.
+fn is_within(path: &str, dir: &str) -> bool {
+    dir.is_empty() || path == dir || path.strip_prefix(dir).is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn get<'a>(nodes: &'a BTreeMap<String, MemoryNode>, path: &str) -> io::Result<&'a MemoryNode> {
+    nodes.get(path).ok_or_else(|| io::ErrorKind::NotFound.into())
+}
+
+fn get_file<'a>(nodes: &'a mut BTreeMap<String, MemoryNode>, path: &str) -> io::Result<&'a mut MemoryNode> {
+    match nodes.get_mut(path) {
+        Some(node) if node.content.is_some() => Ok(node),
+        Some(_) => Err(io::ErrorKind::IsADirectory.into()),
+        None => Err(io::ErrorKind::NotFound.into()),
+    }
+}
+
+/// Checks that the parent of `path` exists and is a directory, as needed to create `path`.
+fn check_parent(nodes: &BTreeMap<String, MemoryNode>, path: &str) -> io::Result<()> {
+    match get(nodes, parent_of(path))?.content {
+        Some(_) => Err(io::ErrorKind::NotADirectory.into()),
+        None => Ok(()),
+    }
+}
+
+/// Updates the mtime of the parent of `path`, whose entries changed.
+fn touch_parent(nodes: &mut BTreeMap<String, MemoryNode>, path: &str) {
+    if let Some(parent) = nodes.get_mut(parent_of(path)) {
+        parent.modified = SystemTime::now();
+    }
+}
+
+/// Removes `path` and all its descendants, returning them keyed by their path relative to `path`.
+fn take_subtree(nodes: &mut BTreeMap<String, MemoryNode>, path: &str) -> Vec<(String, MemoryNode)> {
+    let keys: Vec<String> = nodes.keys().filter(|key| is_within(key, path)).cloned().collect();
+    keys.into_iter()
+        .map(|key| {
+            let node = nodes.remove(&key).unwrap();
+            (key[path.len()..].to_string(), node)
+        })
+        .collect()
+}
+
+/// Inserts a subtree taken with `take_subtree` under `path`.
+fn put_subtree(nodes: &mut BTreeMap<String, MemoryNode>, path: &str, subtree: Vec<(String, MemoryNode)>) {
+    for (suffix, node) in subtree {
+        nodes.insert(format!("{}{}", path, suffix), node);
+    }
+}
+
+fn has_children(nodes: &BTreeMap<String, MemoryNode>, dir: &str) -> bool {
+    nodes.keys().any(|key| key != dir && is_within(key, dir))
+}
+
+impl From<&MemoryNode> for EntryMetadata {
+    fn from(node: &MemoryNode) -> Self {
+        let size = node.content.as_ref().map_or(0, |content| content.len() as u64);
+        EntryMetadata {
+            kind: if node.content.is_some() { EntryKind::File } else { EntryKind::Directory },
+            size,
+            blocks: size.div_ceil(512),
+            modified: node.modified,
+            created: Some(node.created),
+            mode: node.mode,
+            uid: node.uid,
+            gid: node.gid,
+            ino: node.ino,
+            target: None,
+            dangling: false,
+        }
+    }
+}
+
+/// Writes into a file of `InMemoryBackend`, from `offset` onwards.
+///
+/// Every write lands directly in the stored content, so there is nothing to flush.
+struct MemoryWriter {
+    nodes: MemoryNodes,
+    path: String,
+    offset: usize,
+}
+
+impl AsyncWrite for MemoryWriter {
+    fn poll_write(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let mut nodes = this.nodes.lock().unwrap();
+        // The file may have been deleted or replaced by a directory in the meantime
+        let node = match get_file(&mut nodes, &this.path) {
+            Ok(node) => node,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        let content = node.content.as_mut().unwrap();
+        let end = this.offset + buf.len();
+        if content.len() < end {
+            content.resize(end, 0);
+        }
+        content[this.offset..end].copy_from_slice(buf);
+        node.modified = SystemTime::now();
+        this.offset = end;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl StorageBackend for InMemoryBackend {
+    fn stat(&self, path: &str) -> io::Result<EntryMetadata> {
+        get(&self.nodes.lock().unwrap(), &normalize(path)).map(EntryMetadata::from)
+    }
+
+    fn lstat(&self, path: &str) -> io::Result<EntryMetadata> {
+        self.stat(path)
+    }
+
+    fn list(&self, path: &str) -> io::Result<DirEntries> {
+        let path = normalize(path);
+        let nodes = self.nodes.lock().unwrap();
+        if get(&nodes, &path)?.content.is_some() {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+        // The keys are sorted, and the children of `path` share its prefix: they come out by name
+        let entries: Vec<(String, EntryKind)> = nodes
+            .iter()
+            .filter(|(key, _)| !key.is_empty() && **key != path && parent_of(key) == path)
+            .map(|(key, node)| {
+                let name = key.rsplit('/').next().unwrap_or(key).to_string();
+                (name, if node.content.is_some() { EntryKind::File } else { EntryKind::Directory })
+            })
+            .collect();
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    /// The reader works on a copy of the content taken when the file is opened.
+    fn read(&self, path: &str) -> io::Result<FileReader> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let content = get_file(&mut nodes, &normalize(path))?.content.clone().unwrap();
+        Ok(Box::pin(io::Cursor::new(content)))
+    }
+
+    fn write(&self, path: &str, mode: Option<u32>) -> io::Result<FileWriter> {
+        let path = normalize(path);
+        let mut nodes = self.nodes.lock().unwrap();
+        match nodes.get_mut(&path) {
+            Some(node) => match node.content.as_mut() {
+                Some(content) => {
+                    content.clear();
+                    node.modified = SystemTime::now();
+                }
+                None => return Err(io::ErrorKind::IsADirectory.into()),
+            },
+            None => {
+                check_parent(&nodes, &path)?;
+                nodes.insert(path.clone(), self.node(Some(Vec::new()), mode));
+                touch_parent(&mut nodes, &path);
+            }
+        }
+        Ok(Box::pin(MemoryWriter { nodes: self.nodes.clone(), path, offset: 0 }))
+    }
+
+    fn write_at(&self, path: &str, offset: u64) -> io::Result<FileWriter> {
+        let path = normalize(path);
+        get_file(&mut self.nodes.lock().unwrap(), &path)?;
+        Ok(Box::pin(MemoryWriter { nodes: self.nodes.clone(), path, offset: offset as usize }))
+    }
+
+    fn create(&self, path: &str, mode: Option<u32>) -> io::Result<()> {
+        let path = normalize(path);
+        let mut nodes = self.nodes.lock().unwrap();
+        if nodes.contains_key(&path) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        check_parent(&nodes, &path)?;
+        nodes.insert(path.clone(), self.node(Some(Vec::new()), mode));
+        touch_parent(&mut nodes, &path);
+        Ok(())
+    }
+
+    fn mkdir(&self, path: &str, parents: bool, mode: Option<u32>) -> io::Result<()> {
+        let path = normalize(path);
+        let mut nodes = self.nodes.lock().unwrap();
+        if !parents {
+            if nodes.contains_key(&path) {
+                return Err(io::ErrorKind::AlreadyExists.into());
+            }
+            check_parent(&nodes, &path)?;
+        }
+        // Like `create_dir_all`: every missing ancestor, from the top
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        for depth in 1..=components.len() {
+            let dir = components[..depth].join("/");
+            match nodes.get(&dir) {
+                Some(node) if node.content.is_some() => return Err(io::ErrorKind::AlreadyExists.into()),
+                Some(_) => {}
+                None => {
+                    nodes.insert(dir.clone(), self.node(None, mode));
+                    touch_parent(&mut nodes, &dir);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn delete(&self, path: &str, recursive: bool) -> io::Result<()> {
+        let path = normalize(path);
+        if path.is_empty() {
+            return Err(io::ErrorKind::PermissionDenied.into());
+        }
+        let mut nodes = self.nodes.lock().unwrap();
+        if get(&nodes, &path)?.content.is_none() && !recursive && has_children(&nodes, &path) {
+            return Err(io::ErrorKind::DirectoryNotEmpty.into());
+        }
+        take_subtree(&mut nodes, &path);
+        touch_parent(&mut nodes, &path);
+        Ok(())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let (from, to) = (normalize(from), normalize(to));
+        let mut nodes = self.nodes.lock().unwrap();
+        let source_is_dir = get(&nodes, &from)?.content.is_none();
+        check_parent(&nodes, &to)?;
+        if from == to {
+            return Ok(());
+        }
+        // As in rename(2): a directory cannot move inside itself, and replaces only an empty directory
+        if is_within(&to, &from) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        if let Some(target) = nodes.get(&to) {
+            match (source_is_dir, target.content.is_none()) {
+                (true, true) if has_children(&nodes, &to) => return Err(io::ErrorKind::DirectoryNotEmpty.into()),
+                (true, false) => return Err(io::ErrorKind::NotADirectory.into()),
+                (false, true) => return Err(io::ErrorKind::IsADirectory.into()),
+                _ => {}
+            }
+            nodes.remove(&to);
+        }
+        let subtree = take_subtree(&mut nodes, &from);
+        put_subtree(&mut nodes, &to, subtree);
+        touch_parent(&mut nodes, &from);
+        touch_parent(&mut nodes, &to);
+        Ok(())
+    }
+
+    fn exchange(&self, a: &str, b: &str) -> io::Result<()> {
+        let (a, b) = (normalize(a), normalize(b));
+        let mut nodes = self.nodes.lock().unwrap();
+        get(&nodes, &a)?;
+        get(&nodes, &b)?;
+        if is_within(&a, &b) || is_within(&b, &a) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let subtree_a = take_subtree(&mut nodes, &a);
+        let subtree_b = take_subtree(&mut nodes, &b);
+        put_subtree(&mut nodes, &b, subtree_a);
+        put_subtree(&mut nodes, &a, subtree_b);
+        Ok(())
+    }
+
+    fn set_permissions(&self, path: &str, mode: u32) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let node = nodes.get_mut(&normalize(path)).ok_or(io::ErrorKind::NotFound)?;
+        node.mode = mode & 0o7777;
+        Ok(())
+    }
+
+    /// Any ids are accepted: nothing checks them against real users.
+    fn set_owner(&self, path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let node = nodes.get_mut(&normalize(path)).ok_or(io::ErrorKind::NotFound)?;
+        node.uid = uid.unwrap_or(node.uid);
+        node.gid = gid.unwrap_or(node.gid);
+        Ok(())
+    }
+
+    /// Only the modification time is kept: entries have no access time.
+    fn set_times(&self, path: &str, times: EntryTimes) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let node = nodes.get_mut(&normalize(path)).ok_or(io::ErrorKind::NotFound)?;
+        if let Some(modified) = times.modified {
+            node.modified = modified;
+        }
+        Ok(())
+    }
+
+    fn truncate(&self, path: &str, size: u64) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let node = get_file(&mut nodes, &normalize(path))?;
+        node.content.as_mut().unwrap().resize(size as usize, 0);
+        node.modified = SystemTime::now();
+        Ok(())
+    }
+
+    /// Nothing is actually reserved: preallocation only extends the file, and
+    /// punching a hole writes zeros over the range.
+    fn fallocate(&self, path: &str, mode: i32, offset: u64, len: u64) -> io::Result<()> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let content = get_file(&mut nodes, &normalize(path))?.content.as_mut().unwrap();
+        let end = (offset + len) as usize;
+        match mode {
+            0 if end > content.len() => content.resize(end, 0),
+            0 | FALLOC_FL_KEEP_SIZE => {}
+            m if m == FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE => {
+                let end = end.min(content.len());
+                let start = (offset as usize).min(end);
+                content[start..end].fill(0);
+            }
+            _ => return Err(io::ErrorKind::Unsupported.into()),
+        }
+        Ok(())
+    }
+
+    /// The staged file is read into memory, with its permissions and mtime, then deleted.
+    fn import(&self, staged: &str, path: &str) -> io::Result<()> {
+        let path = normalize(path);
+        let content = fs::read(staged)?;
+        let metadata = fs::metadata(staged)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        check_parent(&nodes, &path)?;
+        if nodes.get(&path).is_some_and(|node| node.content.is_none()) {
+            return Err(io::ErrorKind::IsADirectory.into());
+        }
+        let mut node = self.node(Some(content), Some(metadata.permissions().mode() & 0o7777));
+        node.modified = metadata.modified().unwrap_or(node.modified);
+        nodes.insert(path.clone(), node);
+        touch_parent(&mut nodes, &path);
+        drop(nodes);
+        fs::remove_file(staged)
+    }
+
+    /// Memory has no fixed size to report.
+    fn space(&self) -> Option<(u64, u64)> {
+        None
+    }
+}
@@ -1,12 +1,55 @@
 #[cfg(test)]
 mod endpoints_tests  {
     use reqwest::{Client, StatusCode};
+    use server::handlers::AppState;
+    use server::journal::{ChangeJournal, ChangeKind};
+    use server::storage::{InMemoryBackend, LocalFsBackend, StorageBackend};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, UNIX_EPOCH};
 
-    const BASE_URL: &str = "http://127.0.0.1:8080";
+    /// An `AppState` over `storage`, configured like a server started without
+    /// environment variables (see `AppState::new`), except for the free space
+    /// checked by `/healthz`, which a test machine may lack.
+    fn test_state(storage: Arc<dyn StorageBackend>) -> AppState {
+        AppState { min_free_bytes: 0, ..AppState::new(storage) }
+    }
+
+    /// Serves the routes of the server over `state` on a free port, and returns its base URL.
+    async fn serve(state: AppState) -> String {
+        let addr = server::spawn(state).await.expect("Failed to start the server");
+        format!("http://{}", addr)
+    }
+
+    /// Starts a server of its own for a test, over an `InMemoryBackend` holding
+    /// the fixtures: `test_file.txt` ("Hello, world!") and `test_dir/nested_file.txt`.
+    async fn spawn_server() -> String {
+        let storage = InMemoryBackend::new();
+        storage.insert_file("test_file.txt", "Hello, world!").unwrap();
+        storage.insert_file("test_dir/nested_file.txt", "nested").unwrap();
+        serve(test_state(Arc::new(storage))).await
+    }
+
+    /// Starts a server over a new, empty directory on the local disk, for the
+    /// tests that need a real filesystem (symlinks, sparse files, disk space).
+    ///
+    /// # Returns
+    /// The base URL of the server and the directory, to be removed by the test.
+    async fn spawn_local_server() -> (String, String) {
+        static NEXT_DIR: AtomicU64 = AtomicU64::new(0);
+        let dir = std::env::temp_dir()
+            .join(format!("remote-fs-endpoints-{}-{}", std::process::id(), NEXT_DIR.fetch_add(1, Ordering::Relaxed)))
+            .to_string_lossy()
+            .to_string();
+        std::fs::create_dir_all(&dir).expect("Failed to create the data directory");
+        let base_url = serve(test_state(Arc::new(LocalFsBackend::new(dir.clone())))).await;
+        (base_url, dir)
+    }
 
     #[tokio::test]
     async fn test_health_endpoint() {
-        let response = reqwest::get(format!("{}/health", BASE_URL))
+        let base_url = spawn_server().await;
+        let response = reqwest::get(format!("{}/health", base_url))
             .await
             .expect("Failed to send request");
         assert_eq!(response.status(), StatusCode::OK);
@@ -16,27 +59,34 @@ mod endpoints_tests  {
 
     #[tokio::test]
     async fn test_list_root_directory() {
-        let response = reqwest::get(format!("{}/list/", BASE_URL))
+        let base_url = spawn_server().await;
+        let response = reqwest::get(format!("{}/list", base_url))
             .await
             .expect("Failed to send request");
         assert_eq!(response.status(), StatusCode::OK);
-        let body: Vec<String> = response.json().await.expect("Failed to parse response body");
-        println!("Root directory contents: {:?}", body);
+        let body: Vec<serde_json::Value> = response.json().await.expect("Failed to parse response body");
+        let mut names: Vec<&str> = body.iter().map(|e| e["name"].as_str().unwrap()).collect();
+        names.sort();
+        assert_eq!(names, ["test_dir", "test_file.txt"]);
     }
 
     #[tokio::test]
     async fn test_list_nested_directory() {
-        let response = reqwest::get(format!("{}/list/test_dir", BASE_URL))
+        let base_url = spawn_server().await;
+        let response = reqwest::get(format!("{}/list/test_dir", base_url))
             .await
             .expect("Failed to send request");
         assert_eq!(response.status(), StatusCode::OK);
-        let body: Vec<String> = response.json().await.expect("Failed to parse response body");
-        println!("Nested directory contents: {:?}", body);
+        let body: Vec<serde_json::Value> = response.json().await.expect("Failed to parse response body");
+        assert_eq!(body.len(), 1);
+        assert_eq!(body[0]["name"], "nested_file.txt");
+        assert_eq!(body[0]["kind"], "file");
     }
 
     #[tokio::test]
     async fn test_read_file() {
-        let response = reqwest::get(format!("{}/files/test_file.txt", BASE_URL))
+        let base_url = spawn_server().await;
+        let response = reqwest::get(format!("{}/files/test_file.txt", base_url))
             .await
             .expect("Failed to send request");
         assert_eq!(response.status(), StatusCode::OK);
@@ -46,9 +96,10 @@ mod endpoints_tests  {
 
     #[tokio::test]
     async fn test_write_file() {
+        let base_url = spawn_server().await;
         let client = Client::new();
         let response = client
-            .put(format!("{}/files/new_file.txt", BASE_URL))
+            .put(format!("{}/files/new_file.txt", base_url))
             .body("New file content")
             .send()
             .await
@@ -58,11 +109,12 @@ mod endpoints_tests  {
 
     #[tokio::test]
     async fn test_overwrite_file() {
+        let base_url = spawn_server().await;
         let client = Client::new();
 
         // Create a file
         let create_response = client
-            .put(format!("{}/files/overwrite_test.txt", BASE_URL))
+            .put(format!("{}/files/overwrite_test.txt", base_url))
             .body("Initial content")
             .send()
             .await
@@ -71,7 +123,7 @@ mod endpoints_tests  {
 
         // Overwrite the file
         let overwrite_response = client
-            .put(format!("{}/files/overwrite_test.txt", BASE_URL))
+            .put(format!("{}/files/overwrite_test.txt", base_url))
             .body("Overwritten content")
             .send()
             .await
@@ -80,7 +132,7 @@ mod endpoints_tests  {
 
         // Read the file
         let read_response = client
-            .get(format!("{}/files/overwrite_test.txt", BASE_URL))
+            .get(format!("{}/files/overwrite_test.txt", base_url))
             .send()
             .await
             .expect("Failed to send request");
@@ -91,9 +143,10 @@ mod endpoints_tests  {
 
     #[tokio::test]
     async fn test_create_directory() {
+        let base_url = spawn_server().await;
         let client = Client::new();
         let response = client
-            .post(format!("{}/mkdir/new_directory", BASE_URL))
+            .post(format!("{}/mkdir/new_directory", base_url))
             .send()
             .await
             .expect("Failed to send request");
@@ -102,9 +155,10 @@ mod endpoints_tests  {
 
     #[tokio::test]
     async fn test_delete_file() {
+        let base_url = spawn_server().await;
         let client = Client::new();
         let response = client
-            .delete(format!("{}/files/new_file.txt", BASE_URL))
+            .delete(format!("{}/files/test_file.txt", base_url))
             .send()
             .await
             .expect("Failed to send request");
@@ -113,11 +167,12 @@ mod endpoints_tests  {
 
     #[tokio::test]
     async fn test_delete_directory() {
+        let base_url = spawn_server().await;
         let client = Client::new();
 
         // Create a directory
         let create_response = client
-            .post(format!("{}/mkdir/test_delete_dir", BASE_URL))
+            .post(format!("{}/mkdir/test_delete_dir", base_url))
             .send()
             .await
             .expect("Failed to send request");
@@ -125,7 +180,7 @@ mod endpoints_tests  {
 
         // Delete the directory
         let delete_response = client
-            .delete(format!("{}/files/test_delete_dir", BASE_URL))
+            .delete(format!("{}/files/test_delete_dir", base_url))
             .send()
             .await
             .expect("Failed to send request");
@@ -134,11 +189,12 @@ mod endpoints_tests  {
 
     #[tokio::test]
     async fn test_invalid_path() {
+        let base_url = spawn_server().await;
         let client = Client::new();
 
         // Attempt to read a non-existent file
         let response = client
-            .get(format!("{}/files/non_existent_file.txt", BASE_URL))
+            .get(format!("{}/files/non_existent_file.txt", base_url))
             .send()
             .await
             .expect("Failed to send request");
@@ -146,7 +202,7 @@ mod endpoints_tests  {
 
         // Attempt to delete a non-existent file
         let delete_response = client
-            .delete(format!("{}/files/non_existent_file.txt", BASE_URL))
+            .delete(format!("{}/files/non_existent_file.txt", base_url))
             .send()
             .await
             .expect("Failed to send request");
@@ -155,11 +211,12 @@ mod endpoints_tests  {
 
     #[tokio::test]
     async fn test_list_empty_directory() {
+        let base_url = spawn_server().await;
         let client = Client::new();
 
         // Create an empty directory
         let create_response = client
-            .post(format!("{}/mkdir/empty_dir", BASE_URL))
+            .post(format!("{}/mkdir/empty_dir", base_url))
             .send()
             .await
             .expect("Failed to send request");
@@ -167,7 +224,7 @@ mod endpoints_tests  {
 
         // List the empty directory
         let list_response = client
-            .get(format!("{}/list/empty_dir", BASE_URL))
+            .get(format!("{}/list/empty_dir", base_url))
             .send()
             .await
             .expect("Failed to send request");
@@ -175,4 +232,1935 @@ mod endpoints_tests  {
         let body: Vec<String> = list_response.json().await.expect("Failed to parse response body");
         assert!(body.is_empty());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_range_past_eof_is_clamped() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+
+        // Create a 10-byte file
+        let create_response = client
+            .put(format!("{}/files/range_test.txt", base_url))
+            .body("0123456789")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(create_response.status(), StatusCode::OK);
+
+        // Request a block that extends past the end of the file
+        let range_response = client
+            .get(format!("{}/files/range_test.txt", base_url))
+            .header("Range", "bytes=5-4095")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(range_response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            range_response.headers()["content-range"].to_str().unwrap(),
+            "bytes 5-9/10"
+        );
+        let body = range_response.text().await.expect("Failed to read response body");
+        assert_eq!(body, "56789");
+    }
+
+    #[tokio::test]
+    async fn test_stat_file() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let create_response = client
+            .put(format!("{}/files/stat_test.txt", base_url))
+            .body("12345")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(create_response.status(), StatusCode::OK);
+
+        let stat_response = client
+            .get(format!("{}/stat/stat_test.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(stat_response.status(), StatusCode::OK);
+        let entry: serde_json::Value = stat_response.json().await.expect("Failed to parse response body");
+        assert_eq!(entry["name"], "stat_test.txt");
+        assert_eq!(entry["kind"], "file");
+        assert_eq!(entry["size"], 5);
+
+        let missing_response = client
+            .get(format!("{}/stat/non_existent_file.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(missing_response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_exchange_files() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        for (name, content) in [("exchange_a.txt", "A"), ("exchange_b.txt", "B")] {
+            let response = client
+                .put(format!("{}/files/{}", base_url, name))
+                .body(content)
+                .send()
+                .await
+                .expect("Failed to send request");
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let exchange_response = client
+            .post(format!("{}/exchange", base_url))
+            .json(&serde_json::json!({ "from": "exchange_a.txt", "to": "exchange_b.txt" }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(exchange_response.status(), StatusCode::OK);
+
+        let body_a = reqwest::get(format!("{}/files/exchange_a.txt", base_url))
+            .await
+            .expect("Failed to send request")
+            .text()
+            .await
+            .expect("Failed to read response body");
+        assert_eq!(body_a, "B");
+
+        // Exchanging with a missing path must fail without touching the other one
+        let missing_response = client
+            .post(format!("{}/exchange", base_url))
+            .json(&serde_json::json!({ "from": "exchange_a.txt", "to": "non_existent_file.txt" }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(missing_response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_rename_file() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let create_response = client
+            .put(format!("{}/files/rename_src.txt", base_url))
+            .body("rename me")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(create_response.status(), StatusCode::OK);
+
+        let rename_response = client
+            .post(format!("{}/rename", base_url))
+            .json(&serde_json::json!({ "from": "rename_src.txt", "to": "rename_dst.txt" }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(rename_response.status(), StatusCode::OK);
+
+        let old_response = client
+            .get(format!("{}/files/rename_src.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(old_response.status(), StatusCode::NOT_FOUND);
+
+        let body = client
+            .get(format!("{}/files/rename_dst.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .text()
+            .await
+            .expect("Failed to read response body");
+        assert_eq!(body, "rename me");
+
+        // Renaming a missing source is a 404
+        let missing_response = client
+            .post(format!("{}/rename", base_url))
+            .json(&serde_json::json!({ "from": "rename_src.txt", "to": "rename_dst.txt" }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(missing_response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_utimes() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let create_response = client
+            .put(format!("{}/files/utimes_test.txt", base_url))
+            .body("times")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(create_response.status(), StatusCode::OK);
+
+        let utimes_response = client
+            .post(format!("{}/utimes/utimes_test.txt", base_url))
+            .json(&serde_json::json!({ "atime": 1000000000, "mtime": 1000000000 }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(utimes_response.status(), StatusCode::OK);
+
+        let entry: serde_json::Value = client
+            .get(format!("{}/stat/utimes_test.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        assert_eq!(entry["mtime"], 1000000000);
+
+        let missing_response = client
+            .post(format!("{}/utimes/non_existent_file.txt", base_url))
+            .json(&serde_json::json!({ "mtime": 1000000000 }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(missing_response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_read_file_checksum() {
+        let base_url = spawn_server().await;
+        use sha2::{Digest, Sha256};
+
+        let client = Client::new();
+        let content = "checksum me";
+        let create_response = client
+            .put(format!("{}/files/checksum_test.txt", base_url))
+            .body(content)
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(create_response.status(), StatusCode::OK);
+
+        let response = client
+            .get(format!("{}/files/checksum_test.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let header = response
+            .headers()
+            .get("X-Content-SHA256")
+            .expect("Missing X-Content-SHA256 header")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = response.bytes().await.expect("Failed to read response body");
+        assert_eq!(body, content.as_bytes());
+        assert_eq!(header, format!("{:x}", Sha256::digest(&body)));
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let start: serde_json::Value = client
+            .post(format!("{}/upload/start", base_url))
+            .json(&serde_json::json!({ "path": "chunked_test.txt" }))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        let id = start["id"].as_str().expect("Missing upload id").to_string();
+        assert_eq!(start["offset"], 0);
+
+        let first: serde_json::Value = client
+            .put(format!("{}/upload/{}?offset=0", base_url, id))
+            .body("Hello, ")
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        assert_eq!(first["offset"], 7);
+
+        // A chunk at the wrong offset is rejected, and the committed offset is unchanged
+        let conflict_response = client
+            .put(format!("{}/upload/{}?offset=3", base_url, id))
+            .body("xxx")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(conflict_response.status(), StatusCode::CONFLICT);
+
+        let status: serde_json::Value = client
+            .get(format!("{}/upload/{}", base_url, id))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        assert_eq!(status["offset"], 7);
+
+        let second_response = client
+            .put(format!("{}/upload/{}?offset=7", base_url, id))
+            .body("chunks!")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(second_response.status(), StatusCode::OK);
+
+        let complete_response = client
+            .post(format!("{}/upload/{}/complete", base_url, id))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(complete_response.status(), StatusCode::OK);
+
+        let body = client
+            .get(format!("{}/files/chunked_test.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .text()
+            .await
+            .expect("Failed to read response body");
+        assert_eq!(body, "Hello, chunks!");
+
+        // The session is gone once completed
+        let missing_response = client
+            .get(format!("{}/upload/{}", base_url, id))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(missing_response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_tree_and_delete_batch() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let mkdir_response = client
+            .post(format!("{}/mkdir/batch_dir/sub", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(mkdir_response.status(), StatusCode::OK);
+        for file in ["batch_dir/a.txt", "batch_dir/sub/b.txt"] {
+            let response = client
+                .put(format!("{}/files/{}", base_url, file))
+                .body("batch")
+                .send()
+                .await
+                .expect("Failed to send request");
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let tree: Vec<serde_json::Value> = client
+            .get(format!("{}/tree/batch_dir", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        let mut paths: Vec<String> = tree.iter().map(|e| e["path"].as_str().unwrap().to_string()).collect();
+        assert_eq!(paths.len(), 3);
+        // Post-order: the file inside `sub` is listed before `sub` itself
+        let sub_file = paths.iter().position(|p| p == "batch_dir/sub/b.txt").expect("Missing nested file");
+        let sub_dir = paths.iter().position(|p| p == "batch_dir/sub").expect("Missing subdirectory");
+        assert!(sub_file < sub_dir);
+
+        paths.push("batch_dir".to_string());
+        paths.push("batch_dir/missing.txt".to_string());
+        let results: Vec<serde_json::Value> = client
+            .post(format!("{}/delete-batch", base_url))
+            .json(&paths)
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        assert_eq!(results.len(), paths.len());
+        assert!(results[..4].iter().all(|r| r["error"].is_null()));
+        assert_eq!(results[4]["error"], "not_found");
+
+        let gone_response = client
+            .get(format!("{}/list/batch_dir", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(gone_response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_put_file_keeps_times() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let put_response = client
+            .put(format!("{}/files/put_times_test.txt", base_url))
+            .header("X-Atime", "1200000000")
+            .header("X-Mtime", "1100000000")
+            .body("old times")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(put_response.status(), StatusCode::OK);
+
+        let entry: serde_json::Value = client
+            .get(format!("{}/stat/put_times_test.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        assert_eq!(entry["mtime"], 1100000000);
+        assert_eq!(entry["size"], 9);
+    }
+
+    #[tokio::test]
+    async fn test_stat_reports_owner() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let put_response = client
+            .put(format!("{}/files/owner_test.txt", base_url))
+            .body("owned")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(put_response.status(), StatusCode::OK);
+
+        let entry: serde_json::Value = client
+            .get(format!("{}/stat/owner_test.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        // The server runs as the same user as the tests
+        assert_eq!(entry["uid"], unsafe { libc::getuid() });
+        assert_eq!(entry["gid"], unsafe { libc::getgid() });
+    }
+
+    #[tokio::test]
+    async fn test_stat_inode_survives_rename() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let put_response = client
+            .put(format!("{}/files/inode_src.txt", base_url))
+            .body("same file")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(put_response.status(), StatusCode::OK);
+
+        let before: serde_json::Value = client
+            .get(format!("{}/stat/inode_src.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        assert!(before["ino"].as_u64().is_some());
+
+        let rename_response = client
+            .post(format!("{}/rename", base_url))
+            .json(&serde_json::json!({ "from": "inode_src.txt", "to": "inode_dst.txt" }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(rename_response.status(), StatusCode::OK);
+
+        let after: serde_json::Value = client
+            .get(format!("{}/stat/inode_dst.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        assert_eq!(after["ino"], before["ino"]);
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        for dir in ["search_test/a", "search_test/b"] {
+            let mkdir_response = client
+                .post(format!("{}/mkdir/{}", base_url, dir))
+                .send()
+                .await
+                .expect("Failed to send request");
+            assert_eq!(mkdir_response.status(), StatusCode::OK);
+        }
+        for path in ["search_test/a/report.txt", "search_test/b/notes.md", "search_test/b/old_report.txt"] {
+            let put_response = client
+                .put(format!("{}/files/{}", base_url, path))
+                .body("x")
+                .send()
+                .await
+                .expect("Failed to send request");
+            assert_eq!(put_response.status(), StatusCode::OK);
+        }
+
+        let search = |query: &'static str| {
+            let client = client.clone();
+            let base_url = base_url.clone();
+            async move {
+                client
+                    .get(format!("{}/search?path=search_test&{}", base_url, query))
+                    .send()
+                    .await
+                    .expect("Failed to send request")
+            }
+        };
+        let paths = |body: serde_json::Value| {
+            let mut paths: Vec<String> = body["results"].as_array().unwrap().iter()
+                .map(|r| r["path"].as_str().unwrap().to_string())
+                .collect();
+            paths.sort();
+            paths
+        };
+
+        // Substring mode
+        let body: serde_json::Value = search("q=report").await.json().await.expect("Failed to parse response body");
+        assert_eq!(paths(body), ["search_test/a/report.txt", "search_test/b/old_report.txt"]);
+
+        // Glob mode, with the entry's metadata alongside the path
+        let body: serde_json::Value = search("q=*.md&glob=true").await.json().await.expect("Failed to parse response body");
+        assert_eq!(body["results"][0]["kind"], "file");
+        assert_eq!(body["truncated"], false);
+        assert_eq!(paths(body), ["search_test/b/notes.md"]);
+
+        // Result cap
+        let body: serde_json::Value = search("q=*&glob=true&limit=1").await.json().await.expect("Failed to parse response body");
+        assert_eq!(body["results"].as_array().unwrap().len(), 1);
+        assert_eq!(body["truncated"], true);
+
+        assert_eq!(search("q=[&glob=true").await.status(), StatusCode::BAD_REQUEST);
+        let missing = client
+            .get(format!("{}/search?path=search_test_missing&q=x", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_head_file() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let put_response = client
+            .put(format!("{}/files/head_test.txt", base_url))
+            .body("twelve bytes")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(put_response.status(), StatusCode::OK);
+
+        let response = client
+            .head(format!("{}/files/head_test.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers().clone();
+        assert_eq!(headers["content-length"], "12");
+        assert_eq!(headers["x-kind"], "file");
+        assert_eq!(headers["x-perm"], "644");
+        assert!(headers.contains_key("last-modified"));
+        // Metadata only: no checksum is computed
+        assert!(!headers.contains_key("x-content-sha256"));
+        assert!(response.bytes().await.unwrap().is_empty());
+
+        let missing = client
+            .head(format!("{}/files/head_missing.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_mkdir_without_parents() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let mkdir = |path: &'static str| {
+            let client = client.clone();
+            let base_url = base_url.clone();
+            async move {
+                client
+                    .post(format!("{}/mkdir/{}?parents=false", base_url, path))
+                    .send()
+                    .await
+                    .expect("Failed to send request")
+                    .status()
+            }
+        };
+        assert_eq!(mkdir("mkdir_single").await, StatusCode::OK);
+        assert_eq!(mkdir("mkdir_single").await, StatusCode::CONFLICT);
+        assert_eq!(mkdir("mkdir_single/missing/child").await, StatusCode::NOT_FOUND);
+
+        // The default keeps the `mkdir -p` behaviour
+        let recursive = client
+            .post(format!("{}/mkdir/mkdir_single/a/b", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(recursive.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_list_stream() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let mkdir_response = client
+            .post(format!("{}/mkdir/list_stream_test", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(mkdir_response.status(), StatusCode::OK);
+        for i in 0..300 {
+            let put_response = client
+                .put(format!("{}/files/list_stream_test/file_{}.txt", base_url, i))
+                .body("x")
+                .send()
+                .await
+                .expect("Failed to send request");
+            assert_eq!(put_response.status(), StatusCode::OK);
+        }
+
+        let response = client
+            .get(format!("{}/list-stream/list_stream_test", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+
+        // One JSON entry per line, the same as `/list`
+        let body = response.text().await.expect("Failed to read response body");
+        let mut names: Vec<String> = body.lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).expect("Invalid NDJSON line"))
+            .map(|entry| entry["name"].as_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), 300);
+
+        let missing = client
+            .get(format!("{}/list-stream/list_stream_missing", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_list_pagination() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let mkdir_response = client
+            .post(format!("{}/mkdir/list_page_test", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(mkdir_response.status(), StatusCode::OK);
+        for i in 0..5 {
+            let put_response = client
+                .put(format!("{}/files/list_page_test/file_{}.txt", base_url, i))
+                .body("x")
+                .send()
+                .await
+                .expect("Failed to send request");
+            assert_eq!(put_response.status(), StatusCode::OK);
+        }
+
+        // Walk the pages following X-Next-Offset
+        let mut names = Vec::new();
+        let mut offset = Some("0".to_string());
+        while let Some(current) = offset {
+            let response = client
+                .get(format!("{}/list/list_page_test?offset={}&limit=2", base_url, current))
+                .send()
+                .await
+                .expect("Failed to send request");
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["x-total-count"], "5");
+            offset = response.headers().get("x-next-offset").map(|v| v.to_str().unwrap().to_string());
+            let page: Vec<serde_json::Value> = response.json().await.expect("Failed to parse response body");
+            assert!(page.len() <= 2);
+            names.extend(page.into_iter().map(|e| e["name"].as_str().unwrap().to_string()));
+        }
+        names.sort();
+        assert_eq!(names, ["file_0.txt", "file_1.txt", "file_2.txt", "file_3.txt", "file_4.txt"]);
+
+        // Without parameters the whole directory is returned
+        let all: Vec<serde_json::Value> = client
+            .get(format!("{}/list/list_page_test", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        assert_eq!(all.len(), 5);
+    }
+
+    /// Sends only the headers of a `PUT /files/<path>` declaring `content_length` bytes,
+    /// with `Expect: 100-continue`, and returns the first status line of the reply.
+    ///
+    /// `100 Continue` means the server accepted the size and is waiting for the body.
+    async fn put_headers_status(base_url: &str, path: &str, content_length: u64) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let addr = base_url.trim_start_matches("http://");
+        let mut stream = tokio::net::TcpStream::connect(addr).await.expect("Failed to connect");
+        let request = format!(
+            "PUT /files/{} HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Length: {}\r\nExpect: 100-continue\r\n\r\n",
+            path, content_length
+        );
+        stream.write_all(request.as_bytes()).await.expect("Failed to send request");
+
+        let mut reply = Vec::new();
+        let mut byte = [0u8; 1];
+        while !reply.ends_with(b"\r\n") {
+            stream.read_exact(&mut byte).await.expect("Failed to read response");
+            reply.push(byte[0]);
+        }
+        String::from_utf8(reply).unwrap().trim_end().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_put_file_size_limit() {
+        // As if the server was started with MAX_FILE_SIZE_BYTES=1024
+        let limit: u64 = 1024;
+        let mut state = test_state(Arc::new(InMemoryBackend::new()));
+        state.max_file_size = limit;
+        let base_url = serve(state).await;
+
+        // Exactly at the limit: the server asks for the body
+        let status = put_headers_status(&base_url, "size_limit_test.bin", limit).await;
+        assert_eq!(status, "HTTP/1.1 100 Continue");
+
+        // One byte over: refused before any data is sent
+        let status = put_headers_status(&base_url, "size_limit_over_test.bin", limit + 1).await;
+        assert!(status.starts_with("HTTP/1.1 413"), "unexpected status: {}", status);
+
+        let client = Client::new();
+        let response = client
+            .get(format!("{}/files/size_limit_over_test.bin", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let _ = client.delete(format!("{}/files/size_limit_test.bin", base_url)).send().await;
+    }
+
+    #[tokio::test]
+    async fn test_truncate_file() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let put_response = client
+            .put(format!("{}/files/truncate_test.txt", base_url))
+            .body("0123456789")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(put_response.status(), StatusCode::OK);
+
+        // Shrink: the tail is dropped
+        let response = client
+            .post(format!("{}/truncate/truncate_test.txt?size=4", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = client.get(format!("{}/files/truncate_test.txt", base_url)).send().await.unwrap().bytes().await.unwrap();
+        assert_eq!(&body[..], b"0123");
+
+        // Grow: the new bytes are zeros
+        let response = client
+            .post(format!("{}/truncate/truncate_test.txt?size=8", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = client.get(format!("{}/files/truncate_test.txt", base_url)).send().await.unwrap().bytes().await.unwrap();
+        assert_eq!(&body[..], b"0123\0\0\0\0");
+
+        // Missing file
+        let response = client
+            .post(format!("{}/truncate/truncate_missing.txt?size=1", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let _ = client.delete(format!("{}/files/truncate_test.txt", base_url)).send().await;
+    }
+
+    #[tokio::test]
+    async fn test_fallocate() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let put_response = client
+            .put(format!("{}/files/fallocate_test.txt", base_url))
+            .body("0123456789")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(put_response.status(), StatusCode::OK);
+        let fallocate = |query: &str| {
+            client.post(format!("{}/fallocate/fallocate_test.txt?{}", base_url, query)).send()
+        };
+        let size = || async {
+            let response = client.head(format!("{}/files/fallocate_test.txt", base_url)).send().await.unwrap();
+            response.headers()["content-length"].to_str().unwrap().to_string()
+        };
+
+        // Mode 0 past EOF extends the file
+        let response = fallocate("offset=0&len=20").await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(size().await, "20");
+
+        // KEEP_SIZE preallocates without changing the size
+        let response = fallocate("offset=0&len=100&mode=1").await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(size().await, "20");
+
+        // PUNCH_HOLE | KEEP_SIZE zeroes the range
+        let response = fallocate("offset=2&len=4&mode=3").await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = client.get(format!("{}/files/fallocate_test.txt", base_url)).send().await.unwrap().bytes().await.unwrap();
+        assert_eq!(&body[..10], [&b"01"[..], &[0; 4], b"6789"].concat());
+
+        // PUNCH_HOLE without KEEP_SIZE is invalid, as in fallocate(2)
+        let response = fallocate("offset=0&len=1&mode=2").await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let _ = client.delete(format!("{}/files/fallocate_test.txt", base_url)).send().await;
+    }
+
+    #[tokio::test]
+    async fn test_stat_batch() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let put_response = client
+            .put(format!("{}/files/stat_batch_test.txt", base_url))
+            .body("batch")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(put_response.status(), StatusCode::OK);
+
+        let response = client
+            .post(format!("{}/stat-batch", base_url))
+            .json(&["stat_batch_test.txt", "stat_batch_missing.txt"])
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let entries: Vec<serde_json::Value> = response.json().await.expect("Failed to parse response body");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["name"], "stat_batch_test.txt");
+        assert_eq!(entries[0]["size"], 5);
+        assert!(entries[1].is_null());
+
+        // Too many paths in one request
+        let paths: Vec<String> = (0..1001).map(|i| format!("f{}", i)).collect();
+        let response = client
+            .post(format!("{}/stat-batch", base_url))
+            .json(&paths)
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let _ = client.delete(format!("{}/files/stat_batch_test.txt", base_url)).send().await;
+    }
+
+    #[tokio::test]
+    async fn test_delete_nested_from_root_keeps_root() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let mkdir_response = client
+            .post(format!("{}/mkdir/root_delete_test/a/b", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(mkdir_response.status(), StatusCode::OK);
+        for path in ["root_delete_test/a/b/file.txt", "root_delete_keep.txt"] {
+            let put_response = client
+                .put(format!("{}/files/{}", base_url, path))
+                .body("x")
+                .send()
+                .await
+                .expect("Failed to send request");
+            assert_eq!(put_response.status(), StatusCode::OK);
+        }
+
+        let tree: Vec<serde_json::Value> = client
+            .get(format!("{}/tree/root_delete_test", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        let mut paths: Vec<String> = tree.iter().map(|e| e["path"].as_str().unwrap().to_string()).collect();
+        assert!(paths.iter().all(|p| p.starts_with("root_delete_test/")), "unexpected paths: {:?}", paths);
+
+        // The root itself, in any spelling, is refused; everything else goes
+        paths.extend(["root_delete_test".to_string(), "".to_string(), "/".to_string()]);
+        let results: Vec<serde_json::Value> = client
+            .post(format!("{}/delete-batch", base_url))
+            .json(&paths)
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        let (removed, root) = results.split_at(results.len() - 2);
+        assert!(removed.iter().all(|r| r["error"].is_null()), "unexpected results: {:?}", removed);
+        assert!(root.iter().all(|r| r["error"] == "permission_denied"));
+
+        // The nested directory is gone, the rest of the root is untouched
+        let gone = client.head(format!("{}/files/root_delete_test", base_url)).send().await.unwrap();
+        assert_eq!(gone.status(), StatusCode::NOT_FOUND);
+        let kept = client.head(format!("{}/files/root_delete_keep.txt", base_url)).send().await.unwrap();
+        assert_eq!(kept.status(), StatusCode::OK);
+        let root_listing = client.get(format!("{}/list", base_url)).send().await.unwrap();
+        assert_eq!(root_listing.status(), StatusCode::OK);
+
+        let _ = client.delete(format!("{}/files/root_delete_keep.txt", base_url)).send().await;
+    }
+
+    #[tokio::test]
+    async fn test_blocks_of_sparse_file() {
+        // Holes need a real filesystem
+        let (base_url, data_dir) = spawn_local_server().await;
+        let client = Client::new();
+        let put_response = client
+            .put(format!("{}/files/sparse_blocks.bin", base_url))
+            .body("x")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(put_response.status(), StatusCode::OK);
+
+        // Growing with /truncate leaves a hole: 8 MiB logical, (almost) nothing allocated
+        let size: u64 = 8 * 1024 * 1024;
+        let response = client
+            .post(format!("{}/truncate/sparse_blocks.bin?size={}", base_url, size))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let stat: serde_json::Value = client
+            .get(format!("{}/stat/sparse_blocks.bin", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        assert_eq!(stat["size"].as_u64(), Some(size));
+        let blocks = stat["blocks"].as_u64().expect("blocks missing from /stat");
+        assert!(blocks < size / 512, "sparse file reports {} blocks", blocks);
+
+        // HEAD carries the same value
+        let head = client
+            .head(format!("{}/files/sparse_blocks.bin", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(head.headers()["X-Blocks"].to_str().unwrap(), blocks.to_string());
+
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[tokio::test]
+    async fn test_metrics() {
+        // The disk space gauges are only reported for a local directory
+        let (base_url, data_dir) = spawn_local_server().await;
+        let client = Client::new();
+        let health = client.get(format!("{}/health", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(health.status(), StatusCode::OK);
+        let missing = client.get(format!("{}/files/metrics_missing.txt", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+        let put_response = client
+            .put(format!("{}/files/metrics_test.txt", base_url))
+            .body("metrics")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(put_response.status(), StatusCode::OK);
+
+        let response = client.get(format!("{}/metrics", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.text().await.expect("Failed to read response body");
+
+        // Requests are labelled with the matched route, not the concrete path
+        let health_line = body
+            .lines()
+            .find(|l| l.starts_with("http_requests_total{") && l.contains("route=\"/health\"") && l.contains("status=\"2xx\""));
+        assert!(health_line.is_some(), "no /health counter in:\n{}", body);
+        assert!(body.lines().any(|l| l.contains("route=\"/files/*path\"") && l.contains("status=\"4xx\"")));
+        assert!(!body.contains("metrics_missing.txt"));
+
+        for metric in ["file_bytes_written_total", "websocket_connections_active", "process_cpu_seconds_total", "data_dir_available_bytes"] {
+            assert!(body.lines().any(|l| l.starts_with(metric)), "{} missing", metric);
+        }
+
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[tokio::test]
+    async fn test_touch() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let _ = client.delete(format!("{}/files/touch_test.txt", base_url)).send().await;
+
+        let response = client.post(format!("{}/touch/touch_test.txt", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let content = client.get(format!("{}/files/touch_test.txt", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(content.status(), StatusCode::OK);
+        assert!(content.bytes().await.unwrap().is_empty());
+
+        // Like O_EXCL: an existing file is not touched
+        let put_response = client.put(format!("{}/files/touch_test.txt", base_url)).body("dati").send().await.expect("Failed to send request");
+        assert_eq!(put_response.status(), StatusCode::OK);
+        let again = client.post(format!("{}/touch/touch_test.txt", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(again.status(), StatusCode::CONFLICT);
+        let content = client.get(format!("{}/files/touch_test.txt", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(content.text().await.unwrap(), "dati");
+
+        let missing_parent = client.post(format!("{}/touch/touch_missing_dir/a.txt", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(missing_parent.status(), StatusCode::NOT_FOUND);
+
+        let _ = client.delete(format!("{}/files/touch_test.txt", base_url)).send().await;
+    }
+
+    #[tokio::test]
+    async fn test_read_file_with_metadata() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let create_response = client
+            .put(format!("{}/files/metadata_test.txt", base_url))
+            .body("content and metadata")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(create_response.status(), StatusCode::OK);
+
+        // Without the query parameter only the content headers are sent
+        let response = client
+            .get(format!("{}/files/metadata_test.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert!(response.headers().get("X-Size").is_none());
+
+        // Full response: content plus metadata
+        let response = client
+            .get(format!("{}/files/metadata_test.txt?metadata=true", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers().clone();
+        assert_eq!(headers["X-Kind"], "file");
+        assert_eq!(headers["X-Size"], "20");
+        assert_eq!(headers["X-Perm"], "644");
+        assert!(headers["X-Mtime"].to_str().unwrap().parse::<i64>().unwrap() > 0);
+        assert_eq!(response.text().await.unwrap(), "content and metadata");
+
+        // Range response: X-Size is still the size of the whole file
+        let response = client
+            .get(format!("{}/files/metadata_test.txt?metadata=true", base_url))
+            .header("Range", "bytes=0-6")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()["X-Size"], "20");
+        assert_eq!(response.text().await.unwrap(), "content");
+    }
+
+    #[tokio::test]
+    async fn test_checksum() {
+        let base_url = spawn_server().await;
+        use sha2::{Digest, Sha256};
+
+        let client = Client::new();
+        let checksum = || async {
+            let response = client
+                .get(format!("{}/checksum/checksum_endpoint.txt", base_url))
+                .send()
+                .await
+                .expect("Failed to send request");
+            assert_eq!(response.status(), StatusCode::OK);
+            let body: serde_json::Value = response.json().await.expect("Invalid JSON");
+            body
+        };
+
+        let create_response = client
+            .put(format!("{}/files/checksum_endpoint.txt", base_url))
+            .body("first version")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(create_response.status(), StatusCode::OK);
+
+        let first = checksum().await;
+        assert_eq!(first["sha256"], format!("{:x}", Sha256::digest(b"first version")));
+        assert_eq!(first["size"], 13);
+        // Served again (from the server's cache) with the same value
+        assert_eq!(checksum().await, first);
+
+        // Same size, new content: the hash changes
+        let update_response = client
+            .put(format!("{}/files/checksum_endpoint.txt", base_url))
+            .body("other version")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(update_response.status(), StatusCode::OK);
+        assert_eq!(checksum().await["sha256"], format!("{:x}", Sha256::digest(b"other version")));
+
+        let missing = client
+            .get(format!("{}/checksum/checksum_missing.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_write_at_offset() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let create_response = client
+            .put(format!("{}/files/write_at_test.txt", base_url))
+            .body("0123456789")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(create_response.status(), StatusCode::OK);
+
+        // Inside the file: only those bytes change
+        let response = client
+            .post(format!("{}/write/write_at_test.txt?offset=2", base_url))
+            .body("ab")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Past the end: the file grows
+        let response = client
+            .post(format!("{}/write/write_at_test.txt?offset=10", base_url))
+            .body("XY")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let content = client
+            .get(format!("{}/files/write_at_test.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(content, "01ab456789XY");
+
+        let missing = client
+            .post(format!("{}/write/write_at_missing.txt?offset=0", base_url))
+            .body("x")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_stat_directory_mtime_advances() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let root: serde_json::Value = client
+            .get(format!("{}/stat", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        assert_eq!(root["kind"], "directory");
+
+        let mkdir_response = client
+            .post(format!("{}/mkdir/stat_mtime_dir", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert!(mkdir_response.status().is_success());
+        let stat_dir = || async {
+            let entry: serde_json::Value = client
+                .get(format!("{}/stat/stat_mtime_dir", base_url))
+                .send()
+                .await
+                .expect("Failed to send request")
+                .json()
+                .await
+                .expect("Failed to parse response body");
+            entry["mtime"].as_i64().unwrap()
+        };
+        let before = stat_dir().await;
+
+        // mtime has a resolution of one second
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let put_response = client
+            .put(format!("{}/files/stat_mtime_dir/child.txt", base_url))
+            .body("child")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(put_response.status(), StatusCode::OK);
+        assert!(stat_dir().await > before);
+
+        client.delete(format!("{}/files/stat_mtime_dir/child.txt", base_url)).send().await.unwrap();
+        client.delete(format!("{}/files/stat_mtime_dir", base_url)).send().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_admin_clients_require_token() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        // Without `ADMIN_TOKEN` the endpoints are disabled (403)
+        let list_response = client
+            .get(format!("{}/admin/clients", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(list_response.status(), StatusCode::FORBIDDEN);
+
+        // With it, a missing or wrong token is refused (401)
+        let mut state = test_state(Arc::new(InMemoryBackend::new()));
+        state.admin_token = Some("admin-secret".to_string());
+        let base_url = serve(state).await;
+        let list_response = client
+            .get(format!("{}/admin/clients", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(list_response.status(), StatusCode::UNAUTHORIZED);
+
+        let disconnect_response = client
+            .post(format!("{}/admin/clients/some-client/disconnect", base_url))
+            .header("Authorization", "Bearer wrong-token")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(disconnect_response.status(), StatusCode::UNAUTHORIZED);
+
+        let list_response = client
+            .get(format!("{}/admin/clients", base_url))
+            .header("Authorization", "Bearer admin-secret")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(list_response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_binary_content_round_trip() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let content: Vec<u8> = (0..=255u8).collect();
+        let put_response = client
+            .put(format!("{}/files/binary_test.bin", base_url))
+            .body(content.clone())
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(put_response.status(), StatusCode::OK);
+
+        let body = client
+            .get(format!("{}/files/binary_test.bin", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .bytes()
+            .await
+            .expect("Failed to read response body");
+        assert_eq!(body.as_ref(), content.as_slice());
+
+        // A range in the non-UTF-8 half
+        let range = client
+            .get(format!("{}/files/binary_test.bin", base_url))
+            .header("Range", "bytes=128-255")
+            .send()
+            .await
+            .expect("Failed to send request")
+            .bytes()
+            .await
+            .expect("Failed to read response body");
+        assert_eq!(range.as_ref(), &content[128..]);
+
+        client.delete(format!("{}/files/binary_test.bin", base_url)).send().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_with_broken_and_looping_symlinks() {
+        // I link vengono creati direttamente nella directory dati del server di prova
+        let (base_url, data_dir) = spawn_local_server().await;
+        let client = reqwest::Client::new();
+        let dir = "symlink_test_dir";
+        client.post(format!("{}/mkdir/{}", base_url, dir)).send().await.unwrap();
+        client.put(format!("{}/files/{}/real.txt", base_url, dir)).body("reale").send().await.unwrap();
+
+        let base = format!("{}/{}", data_dir, dir);
+        std::os::unix::fs::symlink("non_esiste.txt", format!("{}/broken", base)).unwrap();
+        std::os::unix::fs::symlink("loop_b", format!("{}/loop_a", base)).unwrap();
+        std::os::unix::fs::symlink("loop_a", format!("{}/loop_b", base)).unwrap();
+        std::os::unix::fs::symlink(".", format!("{}/self_dir", base)).unwrap();
+
+        let res = client.get(format!("{}/list/{}", base_url, dir)).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let entries: Vec<serde_json::Value> = res.json().await.unwrap();
+        assert_eq!(entries.len(), 5);
+        let find = |name: &str| entries.iter().find(|e| e["name"] == name).unwrap().clone();
+        assert_eq!(find("real.txt")["kind"], "file");
+        assert!(find("real.txt").get("target").is_none());
+        assert_eq!(find("broken")["kind"], "symlink");
+        assert_eq!(find("broken")["target"], "non_esiste.txt");
+        assert_eq!(find("broken")["dangling"], true);
+        assert_eq!(find("loop_a")["dangling"], true);
+        assert_eq!(find("self_dir")["kind"], "symlink");
+        assert!(find("self_dir").get("dangling").is_none());
+
+        // Anche il listing in streaming termina
+        let res = client.get(format!("{}/list-stream/{}", base_url, dir)).send().await.unwrap();
+        assert_eq!(res.text().await.unwrap().lines().count(), 5);
+
+        // /tree non scende nel link alla directory stessa
+        let res = client.get(format!("{}/tree/{}", base_url, dir)).send().await.unwrap();
+        let tree: Vec<serde_json::Value> = res.json().await.unwrap();
+        assert_eq!(tree.len(), 5);
+        assert!(tree.iter().any(|e| e["path"] == format!("{}/self_dir", dir) && e["kind"] == "symlink"));
+
+        client.delete(format!("{}/files/{}", base_url, dir)).send().await.unwrap();
+        let _ = std::fs::remove_dir_all(data_dir);
+    }
+
+    #[tokio::test]
+    async fn test_list_if_modified_since() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        client.post(format!("{}/mkdir/conditional_dir", base_url)).send().await.unwrap();
+        client.put(format!("{}/files/conditional_dir/a.txt", base_url)).body("a").send().await.unwrap();
+
+        // Modified in the current second: no date to revalidate with yet
+        let response = client.get(format!("{}/list/conditional_dir", base_url)).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get("last-modified").is_none());
+
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let response = client.get(format!("{}/list/conditional_dir", base_url)).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let last_modified = response.headers()["last-modified"].to_str().unwrap().to_string();
+        assert_eq!(response.json::<Vec<serde_json::Value>>().await.unwrap().len(), 1);
+
+        // Unchanged directory: 304, without the entries
+        let list = || client.get(format!("{}/list/conditional_dir", base_url)).header("If-Modified-Since", &last_modified).send();
+        let response = list().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(response.bytes().await.unwrap().is_empty());
+
+        // A new entry changes the directory's mtime: the full listing again
+        client.put(format!("{}/files/conditional_dir/b.txt", base_url)).body("b").send().await.unwrap();
+        let response = list().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.json::<Vec<serde_json::Value>>().await.unwrap().len(), 2);
+
+        // Writing into an existing file does not touch the directory
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        let response = client.get(format!("{}/list/conditional_dir", base_url)).send().await.unwrap();
+        let last_modified = response.headers()["last-modified"].to_str().unwrap().to_string();
+        client.put(format!("{}/files/conditional_dir/a.txt", base_url)).body("changed").send().await.unwrap();
+        let response = client.get(format!("{}/list/conditional_dir", base_url)).header("If-Modified-Since", &last_modified).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    /// Opens a WebSocket to `/ws` of the server at `base_url` as `client_id`, with a
+    /// bare handshake over TCP: dropping the returned stream closes the connection.
+    async fn open_ws(base_url: &str, client_id: &str) -> tokio::net::TcpStream {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let addr = base_url.trim_start_matches("http://");
+        let mut stream = tokio::net::TcpStream::connect(addr).await.expect("Failed to connect");
+        let handshake = format!(
+            "GET /ws HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\nX-Client-ID: {}\r\n\r\n",
+            addr, client_id
+        );
+        stream.write_all(handshake.as_bytes()).await.expect("Failed to send the handshake");
+        let mut response = Vec::new();
+        while !response.ends_with(b"\r\n\r\n") {
+            let mut byte = [0u8; 1];
+            stream.read_exact(&mut byte).await.expect("Failed to read the handshake response");
+            response.push(byte[0]);
+        }
+        assert!(String::from_utf8_lossy(&response).starts_with("HTTP/1.1 101"));
+        stream
+    }
+
+    /// Sends `POST /lock/<path>` as `client_id` until it answers something other
+    /// than `expected_not` (the WebSocket is registered, or torn down, asynchronously).
+    async fn lock_until(client: &Client, base_url: &str, path: &str, client_id: &str, expected_not: StatusCode) -> StatusCode {
+        for _ in 0..100 {
+            let status = client
+                .post(format!("{}/lock/{}", base_url, path))
+                .header("X-Client-ID", client_id)
+                .send()
+                .await
+                .expect("Failed to send request")
+                .status();
+            if status != expected_not {
+                return status;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        expected_not
+    }
+
+    #[tokio::test]
+    async fn test_lock_requires_websocket() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let response = client
+            .post(format!("{}/lock/test_file.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // A client without a WebSocket could never have its locks released
+        let response = client
+            .post(format!("{}/lock/test_file.txt", base_url))
+            .header("X-Client-ID", "not-connected")
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+    }
+
+    #[tokio::test]
+    async fn test_lock_released_when_websocket_drops() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let holder = open_ws(&base_url, "lock-holder").await;
+        let _waiter = open_ws(&base_url, "lock-waiter").await;
+
+        assert_eq!(lock_until(&client, &base_url, "test_file.txt", "lock-holder", StatusCode::PRECONDITION_REQUIRED).await, StatusCode::OK);
+        assert_eq!(lock_until(&client, &base_url, "test_file.txt", "lock-waiter", StatusCode::PRECONDITION_REQUIRED).await, StatusCode::CONFLICT);
+        // Taking it again is a no-op for the holder
+        assert_eq!(lock_until(&client, &base_url, "test_file.txt", "lock-holder", StatusCode::PRECONDITION_REQUIRED).await, StatusCode::OK);
+
+        // The holder crashes: its connection drops, and the lock with it
+        drop(holder);
+        assert_eq!(lock_until(&client, &base_url, "test_file.txt", "lock-waiter", StatusCode::CONFLICT).await, StatusCode::OK);
+
+        let release = |client_id: &'static str| {
+            let client = client.clone();
+            let base_url = base_url.clone();
+            async move {
+                client
+                    .delete(format!("{}/lock/test_file.txt", base_url))
+                    .header("X-Client-ID", client_id)
+                    .send()
+                    .await
+                    .expect("Failed to send request")
+                    .status()
+            }
+        };
+        assert_eq!(release("lock-holder").await, StatusCode::CONFLICT);
+        assert_eq!(release("lock-waiter").await, StatusCode::OK);
+        assert_eq!(release("lock-waiter").await, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_websocket_closed() {
+        use tokio::io::AsyncReadExt;
+        let state = test_state(Arc::new(InMemoryBackend::new()));
+        let tx = state.tx.clone();
+        let base_url = serve(state).await;
+        let mut ws = open_ws(&base_url, "slow-client").await;
+
+        // The connection subscribes to the notifications after the handshake
+        for _ in 0..100 {
+            if tx.receiver_count() > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(tx.receiver_count(), 1);
+
+        // More notifications than the channel holds, sent before the connection can
+        // forward any of them (the test runtime has a single thread): it lags behind
+        for i in 0..150 {
+            tx.send(format!("MODIFIED:file_{}.txt", i)).unwrap();
+        }
+
+        // The server closes the connection, with a Close frame 1013 (Try Again Later)
+        let mut frames = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), ws.read_to_end(&mut frames))
+            .await
+            .expect("The lagging connection was not closed")
+            .expect("Failed to read from the WebSocket");
+        assert_eq!(frames[0], 0x88, "expected a Close frame, got {:?}", frames);
+        assert_eq!(&frames[2..4], &1013u16.to_be_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_patch_file_metadata() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let stat = |path: &str| {
+            let request = client.get(format!("{}/stat/{}", base_url, path)).send();
+            async move { request.await.expect("Failed to send request").json::<serde_json::Value>().await.expect("Failed to parse response body") }
+        };
+
+        // The original `perm`-only body is still accepted
+        let perm_response = client
+            .patch(format!("{}/files/test_file.txt", base_url))
+            .json(&serde_json::json!({ "perm": "600" }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(perm_response.status(), StatusCode::OK);
+        assert_eq!(stat("test_file.txt").await["perm"], "600");
+
+        // Mode, owner and rename in one request
+        let combined_response = client
+            .patch(format!("{}/files/test_dir/nested_file.txt", base_url))
+            .json(&serde_json::json!({ "mode": "640", "owner": { "uid": 1234, "gid": 5678 }, "rename_to": "renamed.txt" }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(combined_response.status(), StatusCode::OK);
+        let entry = stat("test_dir/renamed.txt").await;
+        assert_eq!(entry["perm"], "640");
+        assert_eq!(entry["uid"], 1234);
+        assert_eq!(entry["gid"], 5678);
+        let old_response = client
+            .get(format!("{}/stat/test_dir/nested_file.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(old_response.status(), StatusCode::NOT_FOUND);
+
+        // An existing entry is never replaced
+        let conflict_response = client
+            .patch(format!("{}/files/test_dir", base_url))
+            .json(&serde_json::json!({ "rename_to": "test_file.txt" }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(conflict_response.status(), StatusCode::CONFLICT);
+
+        // Names with a `/`, and bodies without any field, are refused
+        for body in [serde_json::json!({ "rename_to": "../escaped.txt" }), serde_json::json!({})] {
+            let bad_response = client
+                .patch(format!("{}/files/test_file.txt", base_url))
+                .json(&body)
+                .send()
+                .await
+                .expect("Failed to send request");
+            assert_eq!(bad_response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        let missing_response = client
+            .patch(format!("{}/files/non_existent_file.txt", base_url))
+            .json(&serde_json::json!({ "rename_to": "other.txt" }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(missing_response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_list_order_is_stable() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let mkdir_response = client
+            .post(format!("{}/mkdir/list_order_test", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(mkdir_response.status(), StatusCode::OK);
+        // Created out of order, with a directory among the files
+        for name in ["m.txt", "b.txt", "z.txt", "a.txt", "k.txt"] {
+            let put_response = client
+                .put(format!("{}/files/list_order_test/{}", base_url, name))
+                .body("x")
+                .send()
+                .await
+                .expect("Failed to send request");
+            assert_eq!(put_response.status(), StatusCode::OK);
+        }
+        client.post(format!("{}/mkdir/list_order_test/d", base_url)).send().await.expect("Failed to send request");
+
+        let list_names = |url: String| {
+            let client = client.clone();
+            async move {
+                let entries: Vec<serde_json::Value> = client.get(url).send().await.expect("Failed to send request").json().await.expect("Failed to parse response body");
+                entries.into_iter().map(|e| e["name"].as_str().unwrap().to_string()).collect::<Vec<_>>()
+            }
+        };
+        let expected = ["a.txt", "b.txt", "d", "k.txt", "m.txt", "z.txt"];
+
+        // Two listings of the same directory return the same order, sorted by name
+        let first = list_names(format!("{}/list/list_order_test", base_url)).await;
+        let second = list_names(format!("{}/list/list_order_test", base_url)).await;
+        assert_eq!(first, expected);
+        assert_eq!(first, second);
+
+        // The pages, put together, follow the same order
+        let mut paged = Vec::new();
+        for offset in [0, 2, 4] {
+            paged.extend(list_names(format!("{}/list/list_order_test?offset={}&limit=2", base_url, offset)).await);
+        }
+        assert_eq!(paged, expected);
+
+        // And so does the streaming listing
+        let body = client
+            .get(format!("{}/list-stream/list_order_test", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .text()
+            .await
+            .expect("Failed to read response body");
+        let streamed: Vec<String> = body
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(streamed, expected);
+    }
+
+    #[tokio::test]
+    async fn test_healthz() {
+        let client = Client::new();
+
+        // A writable probe directory: healthy, and the probe file is gone
+        let dir = std::env::temp_dir().join(format!("remote-fs-healthz-{}", std::process::id()));
+        let data_dir = dir.join("data");
+        let probe_dir = dir.join("probes");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let state = AppState {
+            healthz_dir: probe_dir.to_string_lossy().to_string(),
+            ..test_state(Arc::new(LocalFsBackend::new(data_dir.to_string_lossy().to_string())))
+        };
+        let base_url = serve(state).await;
+        let data_mtime = std::fs::metadata(&data_dir).unwrap().modified().unwrap();
+        let response = client.get(format!("{}/healthz", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let report: serde_json::Value = response.json().await.expect("Failed to parse response body");
+        assert_eq!(report["status"], "ok");
+        assert_eq!(std::fs::read_dir(&probe_dir).unwrap().count(), 0);
+
+        // The probe never touches the served tree: nothing listed, same root mtime
+        assert_eq!(std::fs::read_dir(&data_dir).unwrap().count(), 0);
+        assert_eq!(std::fs::metadata(&data_dir).unwrap().modified().unwrap(), data_mtime);
+        let listing: serde_json::Value = client.get(format!("{}/list", base_url)).send().await.unwrap().json().await.unwrap();
+        assert_eq!(listing, serde_json::json!([]));
+
+        // A probe directory that cannot take writes (here, a regular file is in its way)
+        std::fs::remove_dir_all(&probe_dir).unwrap();
+        std::fs::write(&probe_dir, "not a directory").unwrap();
+        let response = client.get(format!("{}/healthz", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let report: serde_json::Value = response.json().await.expect("Failed to parse response body");
+        assert_eq!(report["status"], "unhealthy");
+        assert_eq!(report["check"], "write");
+        assert!(report["reason"].as_str().is_some_and(|reason| !reason.is_empty()));
+        // The cheap check does not look at the storage
+        let response = client.get(format!("{}/health", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Less free space than required
+        std::fs::remove_file(&probe_dir).unwrap();
+        let state = AppState {
+            min_free_bytes: u64::MAX,
+            healthz_dir: probe_dir.to_string_lossy().to_string(),
+            ..test_state(Arc::new(LocalFsBackend::new(data_dir.to_string_lossy().to_string())))
+        };
+        let base_url = serve(state).await;
+        let response = client.get(format!("{}/healthz", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let report: serde_json::Value = response.json().await.expect("Failed to parse response body");
+        assert_eq!(report["check"], "space");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_btime() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let put_response = client.put(format!("{}/files/born.txt", base_url)).body("x").send().await.expect("Failed to send request");
+        assert_eq!(put_response.status(), StatusCode::OK);
+
+        // Changing the mtime leaves the creation time alone
+        let utimes_response = client
+            .post(format!("{}/utimes/born.txt", base_url))
+            .json(&serde_json::json!({ "mtime": 1_000_000_000 }))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(utimes_response.status(), StatusCode::OK);
+        let entry: serde_json::Value = client
+            .get(format!("{}/stat/born.txt", base_url))
+            .send()
+            .await
+            .expect("Failed to send request")
+            .json()
+            .await
+            .expect("Failed to parse response body");
+        assert_eq!(entry["mtime"], 1_000_000_000);
+        let btime = entry["btime"].as_i64().expect("btime missing");
+        assert!(btime > 1_000_000_000);
+
+        // Sent by HEAD as well
+        let head_response = client.head(format!("{}/files/born.txt", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(head_response.headers()["X-Btime"], btime.to_string().as_str());
+    }
+
+    #[tokio::test]
+    async fn test_changes_since() {
+        let base_url = spawn_server().await;
+        let client = Client::new();
+        let mkdir_response = client
+            .post(format!("{}/mkdir/changes_test/old", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(mkdir_response.status(), StatusCode::OK);
+        for path in ["changes_test/old/a.txt", "changes_test/new.txt"] {
+            let put_response = client
+                .put(format!("{}/files/{}", base_url, path))
+                .body("x")
+                .send()
+                .await
+                .expect("Failed to send request");
+            assert_eq!(put_response.status(), StatusCode::OK);
+        }
+        // Everything is old except new.txt (directories last, so their mtime sticks)
+        for (path, mtime) in [
+            ("changes_test/old/a.txt", 1_000_000),
+            ("changes_test/new.txt", 1_500_000),
+            ("changes_test/old", 1_000_000),
+            ("changes_test", 1_000_000),
+        ] {
+            let utimes_response = client
+                .post(format!("{}/utimes/{}", base_url, path))
+                .json(&serde_json::json!({ "mtime": mtime }))
+                .send()
+                .await
+                .expect("Failed to send request");
+            assert_eq!(utimes_response.status(), StatusCode::OK);
+        }
+
+        let changes = |query: &'static str| {
+            let client = client.clone();
+            let base_url = base_url.clone();
+            async move {
+                client
+                    .get(format!("{}/changes-since?{}", base_url, query))
+                    .send()
+                    .await
+                    .expect("Failed to send request")
+            }
+        };
+        let paths = |body: serde_json::Value| {
+            body["results"].as_array().unwrap().iter()
+                .map(|r| r["path"].as_str().unwrap().to_string())
+                .collect::<Vec<String>>()
+        };
+
+        // Only the entry modified after ts
+        let response = changes("path=changes_test&ts=1200000").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(paths(body.clone()), vec!["changes_test/new.txt"]);
+        assert_eq!(body["truncated"], false);
+        assert_eq!(body["results"][0]["mtime"], 1_500_000);
+
+        // The starting directory comes first when it changed too
+        let response = changes("path=changes_test&ts=1000000").await;
+        let mut found = paths(response.json().await.unwrap());
+        assert_eq!(found[0], "changes_test");
+        found.sort();
+        assert_eq!(found, vec!["changes_test", "changes_test/new.txt", "changes_test/old", "changes_test/old/a.txt"]);
+
+        // Bounded by limit
+        let response = changes("path=changes_test&ts=0&limit=1").await;
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["truncated"], true);
+
+        // Missing directory
+        let response = changes("path=changes_test/missing&ts=0").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_changes_since_journal() {
+        let storage = InMemoryBackend::new();
+        storage.insert_file("journal_test/a.txt", "a").unwrap();
+        storage.insert_file("journal_test/b.txt", "b").unwrap();
+        storage.insert_file("other/c.txt", "c").unwrap();
+        // Complete after 1_100_000: asking for older changes walks the tree
+        let journal = Arc::new(Mutex::new(ChangeJournal::new(3, UNIX_EPOCH + Duration::from_secs(1_100_000))));
+        {
+            let mut journal = journal.lock().unwrap();
+            journal.record("journal_test/a.txt".to_string(), ChangeKind::Modify);
+            journal.record("journal_test/gone.txt".to_string(), ChangeKind::Remove);
+            journal.record("other/c.txt".to_string(), ChangeKind::Create);
+        }
+        let state = AppState { journal: journal.clone(), ..test_state(Arc::new(storage)) };
+        let base_url = serve(state).await;
+        let client = Client::new();
+
+        let changes = |query: &'static str| {
+            let client = client.clone();
+            let base_url = base_url.clone();
+            async move {
+                let response = client
+                    .get(format!("{}/changes-since?path=journal_test&{}", base_url, query))
+                    .send()
+                    .await
+                    .expect("Failed to send request");
+                assert_eq!(response.status(), StatusCode::OK);
+                let body: serde_json::Value = response.json().await.unwrap();
+                body["results"].as_array().unwrap().iter()
+                    .map(|r| r["path"].as_str().unwrap().to_string())
+                    .collect::<Vec<String>>()
+            }
+        };
+
+        // From the journal: only the recorded paths, the removed one through its parent
+        assert_eq!(changes("ts=1200000").await, vec!["journal_test/a.txt", "journal_test"]);
+
+        // Before the journal is complete: the walk finds b.txt too
+        assert!(changes("ts=1000000").await.contains(&"journal_test/b.txt".to_string()));
+
+        // Once full, the oldest entries are dropped and the journal no longer covers ts
+        journal.lock().unwrap().record("journal_test/a.txt".to_string(), ChangeKind::Modify);
+        assert!(changes("ts=1200000").await.contains(&"journal_test/b.txt".to_string()));
+
+        // Missing directory
+        let response = client
+            .get(format!("{}/changes-since?path=journal_test/missing&ts=1200000", base_url))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_path_limits() {
+        // As if the server was started with MAX_PATH_DEPTH=3 and MAX_NAME_LENGTH=8
+        let storage = InMemoryBackend::new();
+        storage.insert_file("limits/a/b/deep.txt", "esistente").unwrap();
+        let mut state = test_state(Arc::new(storage));
+        state.max_path_depth = 3;
+        state.max_name_length = 8;
+        let base_url = serve(state).await;
+        let client = Client::new();
+
+        // At the limits: accepted
+        let response = client.post(format!("{}/mkdir/limits/b/c", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = client.put(format!("{}/files/limits/12345678", base_url)).body("ok").send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // One level or one byte over: refused, nothing created
+        let response = client.post(format!("{}/mkdir/limits/b/c/d?parents=true", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = client.post(format!("{}/touch/limits/123456789", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = client.put(format!("{}/files/limits/a/b/c.txt", base_url)).body("no").send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = client
+            .post(format!("{}/rename", base_url))
+            .json(&serde_json::json!({"from": "limits/12345678", "to": "limits/b/c/12345678"}))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = client
+            .patch(format!("{}/files/limits/12345678", base_url))
+            .json(&serde_json::json!({"rename_to": "nome_lungo.txt"}))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = client
+            .post(format!("{}/upload/start", base_url))
+            .json(&serde_json::json!({"path": "limits/nome_lungo.txt"}))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = client.get(format!("{}/stat/limits/b/c/d", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = client.get(format!("{}/stat/limits/12345678", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Existing paths over the limits stay readable
+        let response = client.get(format!("{}/files/limits/a/b/deep.txt", base_url)).send().await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_paths_outside_data_dir_rejected() {
+        let (base_url, data_dir) = spawn_local_server().await;
+        let outside_name = format!("{}-outside.txt", std::path::Path::new(&data_dir).file_name().unwrap().to_string_lossy());
+        let outside = std::path::Path::new(&data_dir).parent().unwrap().join(&outside_name);
+        std::fs::write(&outside, "secret").expect("Failed to write the file outside the data directory");
+        std::fs::write(format!("{}/inside.txt", data_dir), "inside").expect("Failed to write the file");
+        let escaping = format!("../{}", outside_name);
+        let client = Client::new();
+
+        let posts = [
+            ("delete-batch", serde_json::json!([escaping])),
+            ("stat-batch", serde_json::json!(["inside.txt", escaping])),
+            ("rename", serde_json::json!({"from": escaping, "to": "stolen.txt"})),
+            ("rename", serde_json::json!({"from": "inside.txt", "to": "/tmp/moved.txt"})),
+            ("exchange", serde_json::json!({"from": "inside.txt", "to": escaping})),
+            ("upload/start", serde_json::json!({"path": escaping})),
+        ];
+        for (endpoint, body) in posts {
+            let response = client.post(format!("{}/{}", base_url, endpoint)).json(&body).send().await.expect("Failed to send request");
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "/{} {}", endpoint, body);
+        }
+        for query in ["search?q=outside&path=..", "changes-since?ts=0&path=../"] {
+            let response = client.get(format!("{}/{}", base_url, query)).send().await.expect("Failed to send request");
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "/{}", query);
+        }
+
+        // A `..` percent-encoded in the URL is decoded by axum, then refused by the storage
+        let response = client.get(format!("{}/files/%2e%2e/{}", base_url, outside_name)).send().await.expect("Failed to send request");
+        assert_ne!(response.status(), StatusCode::OK);
+
+        assert_eq!(std::fs::read_to_string(&outside).unwrap(), "secret");
+        assert_eq!(std::fs::read_to_string(format!("{}/inside.txt", data_dir)).unwrap(), "inside");
+        assert!(!std::path::Path::new(&data_dir).join("stolen.txt").exists());
+        std::fs::remove_file(&outside).unwrap();
+    }
+}